@@ -0,0 +1,137 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Instant;
+use questdb::ingress::Buffer;
+use rand::Rng;
+
+use crate::units;
+
+/// Seconds of sent rows on each side of an addition that its throughput is compared over
+const WINDOW_SECS: usize = 10;
+
+/// Type of a column added with --add-column-at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddedType {
+    F64,
+    I64,
+    Bool,
+    Str,
+}
+
+impl AddedType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AddedType::F64 => "DOUBLE",
+            AddedType::I64 => "LONG",
+            AddedType::Bool => "BOOLEAN",
+            AddedType::Str => "STRING",
+        }
+    }
+}
+
+/// A column of --add-column-at: from `secs` into the run, every row has it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddColumnAt {
+    pub secs: f64,
+    pub name: String,
+    pub kind: AddedType,
+}
+
+impl FromStr for AddColumnAt {
+    type Err = String;
+
+    /// "5m:wind_shear:f64"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        let (at, name, kind) = match parts[..] {
+            [at, name, kind] => (at, name, kind),
+            _ => return Err(format!("invalid column '{}', expected TIME:NAME:TYPE such as 5m:wind_shear:f64", s)),
+        };
+        let kind = match kind {
+            "f64" => AddedType::F64,
+            "i64" => AddedType::I64,
+            "bool" => AddedType::Bool,
+            "str" => AddedType::Str,
+            _ => return Err(format!("invalid type '{}' of column '{}', expected one of: f64, i64, bool, str", kind, name)),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid column name '{}', use letters, digits and underscores", name));
+        }
+        Ok(AddColumnAt { secs: units::secs_f64(at)?, name: name.to_string(), kind })
+    }
+}
+
+/// The columns of --add-column-at, that the rows start having partway through the run so
+/// the server adds them to the table under load, and the rows sent in each second of the
+/// run, to compare the throughput before and after each addition.
+#[derive(Debug)]
+pub struct AddedColumns {
+    columns: Vec<AddColumnAt>,
+    started: Instant,
+    /// Rows the server accepted, by second of the run
+    sent: Mutex<Vec<u64>>,
+}
+
+impl AddedColumns {
+    pub fn new(mut columns: Vec<AddColumnAt>) -> Self {
+        columns.sort_by(|a, b| a.secs.total_cmp(&b.secs));
+        AddedColumns { columns, started: Instant::now(), sent: Mutex::new(vec![]) }
+    }
+
+    /// Writes the columns added so far, with random values
+    pub fn write(&self, buffer: &mut Buffer) -> questdb::Result<()> {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let mut rng = rand::thread_rng();
+        for column in self.columns.iter().take_while(|column| column.secs <= elapsed) {
+            let name = column.name.as_str();
+            match column.kind {
+                AddedType::F64 => buffer.column_f64(name, rng.gen_range(-10.0..10.0))?,
+                AddedType::I64 => buffer.column_i64(name, rng.gen_range(0..1000))?,
+                AddedType::Bool => buffer.column_bool(name, rng.gen_bool(0.5))?,
+                AddedType::Str => buffer.column_str(name, ["low", "moderate", "severe"][rng.gen_range(0..3)])?,
+            };
+        }
+        Ok(())
+    }
+
+    /// Counts rows the server accepted
+    pub fn record_sent(&self, rows: usize) {
+        let second = self.started.elapsed().as_secs() as usize;
+        let mut sent = self.sent.lock().unwrap();
+        if sent.len() <= second {
+            sent.resize(second + 1, 0);
+        }
+        sent[second] += rows as u64;
+    }
+
+    /// Rows per second sent over the whole seconds of the run in `from..to`
+    fn rate(sent: &[u64], from: usize, to: usize) -> Option<f64> {
+        let to = to.min(sent.len());
+        (to > from).then(|| sent[from..to].iter().sum::<u64>() as f64 / (to - from) as f64)
+    }
+
+    pub fn print_summary(&self) {
+        let sent = self.sent.lock().unwrap();
+        // The last second is cut short by the end of the run
+        let complete = sent.len().saturating_sub(1);
+        for column in &self.columns {
+            let at = column.secs.ceil() as usize;
+            if at >= complete {
+                println!("Column {} ({}) was not added, the run ended before {:.0}s", column.name, column.kind.as_str(), column.secs);
+                continue;
+            }
+            let before = Self::rate(&sent[..complete], at.saturating_sub(WINDOW_SECS), at);
+            let after = Self::rate(&sent[..complete], at, at + WINDOW_SECS).unwrap_or(0.0);
+            match before {
+                Some(before) if before > 0.0 => println!("Column {} ({}) added at {:.0}s: {:.1} rows/s sent in the {}s before, {:.1} in the {}s after, {:+.1}%",
+                                         column.name, column.kind.as_str(), column.secs, before, at.min(WINDOW_SECS), after,
+                                         WINDOW_SECS.min(complete - at),
+                                         (after / before - 1.0) * 100.0),
+                Some(_) => println!("Column {} ({}) added at {:.0}s: no rows sent in the {}s before, {:.1} rows/s in the {}s after",
+                                    column.name, column.kind.as_str(), column.secs, at.min(WINDOW_SECS), after, WINDOW_SECS.min(complete - at)),
+                None => println!("Column {} ({}) added from the start: {:.1} rows/s sent in the first {}s",
+                                 column.name, column.kind.as_str(), after, WINDOW_SECS.min(complete)),
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::RunContext;
+use crate::error_summary::thousands;
+
+/// Batch sizes tried by --auto-batch, smallest first
+const CANDIDATES: &[u64] = &[100, 250, 500, 1000, 2500, 5000, 10_000, 25_000, 50_000, 100_000];
+
+/// Flushes a trial should see at least, for its timing to mean something
+const MIN_FLUSHES_PER_TRIAL: f64 = 3.0;
+
+/// How much more rows per second a trial with more senders has to reach to be chosen over
+/// one with fewer, which open fewer connections on the server
+const MORE_SENDERS_GAIN: f64 = 1.05;
+
+/// Rows the server accepted and time spent flushing them during one trial.
+#[derive(Debug, Clone, Copy)]
+struct Trial {
+    batch_size: u64,
+    senders: usize,
+    rows: u64,
+    secs: f64,
+    flush_secs: f64,
+}
+
+impl Trial {
+    fn rate(&self) -> f64 {
+        self.rows as f64 / self.secs.max(f64::MIN_POSITIVE)
+    }
+
+    /// Rows accepted per second spent inside flushes, what a larger batch buys
+    fn flush_rate(&self) -> f64 {
+        self.rows as f64 / self.flush_secs.max(f64::MIN_POSITIVE)
+    }
+}
+
+/// Tries batch sizes one after the other during --warmup-secs and keeps the one the server
+/// took the most rows per second of flush time with, for --auto-batch. Only sizes that fill
+/// up a few times within a trial at the configured rate are tried. With more than one of
+/// --senders, the rest of the warm-up then flushes the chosen size through 1, 2, 4 and up
+/// to all of them, and keeps the fewest that reach about the most rows per second. With
+/// --sync each thread has a connection of its own, so only the batch size is tuned.
+pub struct AutoBatch {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+fn run_trial(ctx: &RunContext, batch_size: u64, senders: usize, trial: Duration, stop: &AtomicBool) -> Option<Trial> {
+    ctx.auto_flush.set_rows(batch_size);
+    ctx.sink.use_connections(senders);
+    let (rows, nanos) = (ctx.rows_sent.load(Ordering::SeqCst), ctx.sent_nanos.load(Ordering::SeqCst));
+    let started = Instant::now();
+    while started.elapsed() < trial {
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Some(Trial {
+        batch_size,
+        senders,
+        rows: ctx.rows_sent.load(Ordering::SeqCst) - rows,
+        secs: started.elapsed().as_secs_f64(),
+        flush_secs: (ctx.sent_nanos.load(Ordering::SeqCst) - nanos) as f64 / 1e9,
+    })
+}
+
+impl AutoBatch {
+    pub fn start(ctx: Arc<RunContext>, tasks: usize) -> Result<Self, String> {
+        let warmup = Duration::from_secs(ctx.opt.warmup_secs);
+        // Rows each task writes per second, which a batch has to fill up every time
+        let task_rate = ctx.opt.plane_count as f64 * 1000.0 / ctx.opt.tick_millis() as f64 / tasks.max(1) as f64;
+        let connections = ctx.sink.connections();
+        let mut senders: Vec<usize> = (0..).map(|power| 1 << power).take_while(|count| *count < connections).collect();
+        if connections > 1 && !ctx.opt.sync {
+            senders.push(connections);
+        } else {
+            senders.clear();
+        }
+        let mut candidates = CANDIDATES.to_vec();
+        let fits = |candidates: &[u64]| {
+            let trial = warmup.as_secs_f64() / (candidates.len() + senders.len()) as f64;
+            candidates.iter().copied().filter(|size| *size as f64 * MIN_FLUSHES_PER_TRIAL <= task_rate * trial).count()
+        };
+        while candidates.len() > 1 && fits(&candidates) < candidates.len() {
+            candidates.pop();
+        }
+        if fits(&candidates) == 0 {
+            return Err(format!("--warmup-secs {} is too short to try any batch size at {:.0} rows/s per task", ctx.opt.warmup_secs, task_rate));
+        }
+        let trial = warmup / (candidates.len() + senders.len()) as u32;
+        println!("Auto batch: trying batch sizes {} for {:.1}s each",
+                 candidates.iter().map(|size| thousands(*size)).collect::<Vec<_>>().join(", "), trial.as_secs_f64());
+        if !senders.is_empty() {
+            println!("Auto batch: then trying {} senders with the chosen size",
+                     senders.iter().map(usize::to_string).collect::<Vec<_>>().join(", "));
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = thread::Builder::new().name("auto-batch".to_string()).spawn(move || {
+            let mut trials = vec![];
+            for batch_size in candidates {
+                match run_trial(&ctx, batch_size, connections, trial, &stopped) {
+                    Some(trial) => {
+                        println!("Auto batch: {} rows per batch, {:.0} rows/s, {:.0} rows/s of flush time",
+                                 thousands(trial.batch_size), trial.rate(), trial.flush_rate());
+                        trials.push(trial);
+                    }
+                    None => break,
+                }
+            }
+            let best = trials.iter().filter(|trial| trial.rows > 0).max_by(|a, b| a.flush_rate().total_cmp(&b.flush_rate()));
+            let batch_size = match best {
+                Some(best) => {
+                    println!("Auto batch: chose {} rows per batch, pin it with --batch-size {}", thousands(best.batch_size), best.batch_size);
+                    best.batch_size
+                }
+                None => {
+                    eprintln!("Auto batch: no flush measured, keeping --batch-size {}", ctx.opt.batch_size);
+                    ctx.opt.batch_size as u64
+                }
+            };
+            ctx.auto_flush.set_rows(batch_size);
+            if best.is_none() || senders.is_empty() {
+                return;
+            }
+
+            let mut chosen: Option<Trial> = None;
+            for count in senders {
+                match run_trial(&ctx, batch_size, count, trial, &stopped) {
+                    Some(trial) => {
+                        println!("Auto batch: {} senders, {:.0} rows/s", trial.senders, trial.rate());
+                        if chosen.is_none_or(|chosen| trial.rate() > chosen.rate() * MORE_SENDERS_GAIN) {
+                            chosen = Some(trial);
+                        }
+                    }
+                    None => break,
+                }
+            }
+            match chosen.filter(|chosen| chosen.rows > 0) {
+                Some(chosen) => {
+                    ctx.sink.use_connections(chosen.senders);
+                    println!("Auto batch: chose {} senders, pin it with --senders {}", chosen.senders, chosen.senders);
+                }
+                None => ctx.sink.use_connections(connections),
+            }
+        }).map_err(|e| e.to_string())?;
+        Ok(AutoBatch { stop, thread })
+    }
+
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error_summary::thousands;
+
+/// Rows a generator added to its buffer since it last flushed.
+#[derive(Debug)]
+pub struct Batch {
+    rows: u64,
+    started: Instant,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Batch { rows: 0, started: Instant::now() }
+    }
+
+    /// When the previous batch was flushed, or the generator started
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+}
+
+/// When the generators flush their buffer. By default every --batch-size rows; with
+/// --auto-flush-rows or --auto-flush-interval-ms the way the other QuestDB clients
+/// auto-flush: once the buffer holds that many rows, or when a row is added that long
+/// after the previous flush. The Rust client leaves flushing to the caller, so the
+/// simulator does it. The flushes are counted by what triggered them, to compare
+/// settings by their batch sizes next to the rates and memory they give.
+#[derive(Debug)]
+pub struct AutoFlush {
+    /// Rows per flush, 0 when off; changed while running by --auto-batch
+    rows: AtomicU64,
+    interval: Option<Duration>,
+    by_rows: AtomicU64,
+    by_interval: AtomicU64,
+    /// Last rows of the run and buffers over the memory limits
+    forced: AtomicU64,
+    rows_flushed: AtomicU64,
+}
+
+impl AutoFlush {
+    pub fn new(batch_size: usize, auto_flush_rows: Option<u64>, auto_flush_interval_ms: Option<u64>) -> Self {
+        let rows = match (auto_flush_rows, auto_flush_interval_ms) {
+            (None, None) => Some(batch_size as u64),
+            (rows, _) => rows,
+        };
+        AutoFlush {
+            rows: AtomicU64::new(rows.unwrap_or(0)),
+            interval: auto_flush_interval_ms.map(Duration::from_millis),
+            by_rows: AtomicU64::new(0),
+            by_interval: AtomicU64::new(0),
+            forced: AtomicU64::new(0),
+            rows_flushed: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a row added to the batch and tells whether to flush now; `forced` flushes
+    /// whatever the settings. A new batch starts when it says so.
+    pub fn due(&self, batch: &mut Batch, forced: bool) -> bool {
+        batch.rows += 1;
+        let rows = self.rows.load(Ordering::Relaxed);
+        let trigger = if rows > 0 && batch.rows >= rows {
+            &self.by_rows
+        } else if self.interval.is_some_and(|interval| batch.started.elapsed() >= interval) {
+            &self.by_interval
+        } else if forced {
+            &self.forced
+        } else {
+            return false;
+        };
+        trigger.fetch_add(1, Ordering::Relaxed);
+        self.rows_flushed.fetch_add(batch.rows, Ordering::Relaxed);
+        *batch = Batch::new();
+        true
+    }
+
+    pub fn set_rows(&self, rows: u64) {
+        self.rows.store(rows, Ordering::Relaxed);
+    }
+
+    /// The settings, as the other clients write them in the connection string
+    pub fn settings(&self) -> String {
+        let rows = match self.rows.load(Ordering::Relaxed) {
+            0 => "off".to_string(),
+            rows => rows.to_string(),
+        };
+        let interval = self.interval.map(|interval| interval.as_millis().to_string()).unwrap_or_else(|| "off".to_string());
+        format!("auto_flush_rows={};auto_flush_interval={}", rows, interval)
+    }
+
+    /// Flushes triggered by the row count, the interval and forced, and the rows they sent
+    pub fn counts(&self) -> (u64, u64, u64, u64) {
+        (self.by_rows.load(Ordering::Relaxed), self.by_interval.load(Ordering::Relaxed),
+         self.forced.load(Ordering::Relaxed), self.rows_flushed.load(Ordering::Relaxed))
+    }
+
+    pub fn print_summary(&self, peak_bytes: u64) {
+        let (by_rows, by_interval, forced, rows) = self.counts();
+        let flushes = by_rows + by_interval + forced;
+        println!("Flushes with {}: {} ({} by rows, {} by interval, {} forced), {:.1} rows each; buffers peaked at {:.1} KiB in total",
+                 self.settings(), thousands(flushes), thousands(by_rows), thousands(by_interval), thousands(forced),
+                 rows as f64 / flushes.max(1) as f64, peak_bytes as f64 / 1024.0);
+    }
+}
@@ -0,0 +1,141 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use futures::executor::block_on;
+
+use crate::{Plane, RunContext, rows_per_tick};
+use crate::blocking;
+use crate::dropout::Signal;
+use crate::error_summary::thousands;
+use crate::seed;
+use crate::task::{Engine, TaskBuffer};
+
+/// Timestamps for --backfill-hours: the simulated clock starts that far in the past and
+/// runs --backfill-speed times faster than the wall clock until it catches up with it,
+/// then keeps to the wall clock. Planes fly on the simulated clock, so a leg takes as
+/// long in the data as it would in real time.
+#[derive(Debug, Clone)]
+pub struct BackfillClock {
+    started_at: i64,
+    start: i64,
+    speed: f64,
+}
+
+impl BackfillClock {
+    pub fn new(hours: f64, speed: f64) -> Self {
+        let now = Utc::now().timestamp_nanos_opt().unwrap();
+        BackfillClock { started_at: now, start: now - (hours * 3600e9) as i64, speed }
+    }
+
+    /// Where the simulated clock starts
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    /// When the simulated clock reaches the wall clock, in real seconds from the start
+    pub fn catch_up_secs(&self) -> f64 {
+        (self.started_at - self.start) as f64 / 1e9 / (self.speed - 1.0).max(f64::MIN_POSITIVE)
+    }
+
+    pub fn now(&self) -> i64 {
+        let now = Utc::now().timestamp_nanos_opt().unwrap();
+        let simulated = self.start + ((now - self.started_at) as f64 * self.speed) as i64;
+        simulated.min(now)
+    }
+}
+
+fn format_time(timestamp: i64) -> String {
+    DateTime::from_timestamp_nanos(timestamp).format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Writes the rows of every plane from `from` to `to` on its own connection, stepping the
+/// planes' clock a tick at a time without waiting between ticks. Returns the rows written.
+fn backfill_slice(ctx: &RunContext, name: &str, planes: &[(String, String)], slice: usize, from: i64, to: i64) -> u64 {
+    let opt = &ctx.opt;
+    let mut connection = match blocking::Connection::open(ctx) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("{} cannot connect: {}", name, e);
+            return 0;
+        }
+    };
+    let tick_nanos = opt.tick_millis() as i64 * 1_000_000;
+    let mut planes: Vec<Plane> = planes.iter()
+        .map(|(plane_id, icao24)| {
+            let seed = seed::slice_seed(seed::plane_seed(ctx.seed, plane_id), slice);
+            Plane::starting_at(ctx, plane_id.clone(), icao24.clone(), from, seed)
+        })
+        .collect();
+    let mut buffer = TaskBuffer::new(ctx);
+    let mut allowance = ctx.quota.allowance(rows_per_tick(&planes));
+
+    let mut now = from;
+    'ticks: while now < to {
+        now += tick_nanos;
+        for i in 0..planes.len() {
+            let plane = &mut planes[i];
+            if plane.stopped {
+                continue;
+            }
+            let Some(signal) = plane.advance_at(ctx, now) else { continue };
+            // Rows out of coverage would be late anyway, with --dropout-backfill they are written in place
+            if !plane.is_hot() || !plane.has_readings(ctx) || (signal == Signal::Dark && !opt.dropout_backfill) {
+                continue;
+            }
+            if !allowance.take() {
+                break 'ticks;
+            }
+            let last_row = allowance.is_exhausted();
+            block_on(buffer.write(ctx, &mut Engine::Blocking(&mut connection), name, planes.as_mut_slice(), i, last_row));
+            if last_row {
+                break 'ticks;
+            }
+        }
+        if planes.iter().all(|plane| plane.stopped) {
+            // The rows it will not write go to the other slices right away
+            allowance.release();
+            break;
+        }
+    }
+    drop(allowance);
+    block_on(buffer.finish(ctx, &mut Engine::Blocking(&mut connection), name, planes.as_mut_slice()));
+    buffer.rows_written()
+}
+
+/// Backfills the last --backfill-hours with --backfill-workers threads, each taking an
+/// equal slice of the time range for all the planes, so a long history is written as
+/// fast as the cores and the server allow. The planes of each slice start afresh at its
+/// beginning with a seed of their own, so a slice does not replay the callsigns, airports
+/// and noise of another, and a flight breaks off where its slice ends.
+pub fn run_parallel(ctx: &RunContext, planes: &[(String, String)], hours: f64, workers: usize) {
+    let to = Utc::now().timestamp_nanos_opt().unwrap();
+    let from = to - (hours * 3600e9) as i64;
+    let slice = (to - from) / workers as i64;
+    println!("Backfilling from {} to {} on {} workers", format_time(from), format_time(to), workers);
+    let started = Instant::now();
+    let rows: u64 = thread::scope(|scope| {
+        let threads: Vec<_> = (0..workers).map(|worker| {
+            let (start, end) = (from + worker as i64 * slice, if worker + 1 == workers { to } else { from + (worker as i64 + 1) * slice });
+            let (name, delay) = (format!("backfill {}", worker), ctx.opt.connect_delay(worker, workers));
+            thread::Builder::new().name(name.clone()).spawn_scoped(scope, move || {
+                thread::sleep(delay);
+                backfill_slice(ctx, &name, planes, worker, start, end)
+            })
+        }).collect();
+        threads.into_iter().map(|thread| match thread.map(|handle| handle.join()) {
+            Ok(Ok(rows)) => rows,
+            Ok(Err(_)) => {
+                eprintln!("A backfill thread panicked");
+                0
+            }
+            Err(e) => {
+                eprintln!("Cannot start a backfill thread: {}", e);
+                0
+            }
+        }).sum()
+    });
+    let secs = started.elapsed().as_secs_f64();
+    println!("Backfilled {} rows in {:.1}s, {:.0} rows/s", thousands(rows), secs, rows as f64 / secs.max(f64::MIN_POSITIVE));
+    ctx.rate.reset();
+    ctx.phases.fill_for(Duration::from_secs(ctx.opt.warmup_secs));
+}
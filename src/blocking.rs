@@ -0,0 +1,231 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use futures::executor::block_on;
+use questdb::ingress::{Buffer, Sender};
+
+use crate::{MissedTick, Plane, RunContext, record_flush, rows_per_tick};
+use crate::dropout::Signal;
+use crate::rate_limit::RateLimit;
+use crate::sink;
+use crate::stats::PlaneStats;
+use crate::task::{Engine, TaskBuffer};
+use crate::type_flip::FlipType;
+
+/// Runs the generator groups on plain threads, each with its own blocking sender, and
+/// returns the statistics of all planes once they are done.
+pub fn run(ctx: Arc<RunContext>, groups: Vec<(String, Vec<(String, String)>)>) -> Vec<PlaneStats> {
+    let count = groups.len();
+    let threads: Vec<_> = groups.into_iter().enumerate().map(|(index, (name, planes))| {
+        let (ctx, delay) = (ctx.clone(), ctx.opt.connect_delay(index, count));
+        thread::Builder::new().name(name.clone()).spawn(move || generate_data(&ctx, &name, planes, delay))
+    }).collect();
+    threads.into_iter().flat_map(|thread| match thread.map(|handle| handle.join()) {
+        Ok(Ok(stats)) => stats,
+        Ok(Err(_)) => {
+            eprintln!("A generator thread panicked");
+            vec![]
+        }
+        Err(e) => {
+            eprintln!("Cannot start a generator thread: {}", e);
+            vec![]
+        }
+    }).collect()
+}
+
+/// A thread's own connection, with its --max-rate-per-conn, and its own connection to
+/// the --mirror-connection-string.
+pub struct Connection {
+    sender: Sender,
+    limit: Option<RateLimit>,
+    mirror: Option<Sender>,
+}
+
+impl Connection {
+    pub fn open(ctx: &RunContext) -> questdb::Result<Self> {
+        let connect = |conf: &str| sink::connect(conf, ctx.opt.protocol_version);
+        let mirror = ctx.opt.mirror_connection_string.as_deref().map(connect).transpose()?;
+        Ok(Connection { sender: connect(&ctx.opt.connection_string)?, limit: ctx.rate_limits.for_connection(), mirror })
+    }
+}
+
+/// Sends a copy of the buffer to the mirror, opening its connection again first when it
+/// had to be closed. Tells whether the mirror accepted the rows.
+fn flush_mirror(ctx: &RunContext, mirror: &mut Sender, buffer: &Buffer, name: &str) -> bool {
+    if mirror.must_close() {
+        match sink::connect(ctx.opt.mirror_connection_string.as_deref().unwrap_or_default(), ctx.opt.protocol_version) {
+            Ok(new_mirror) => *mirror = new_mirror,
+            Err(e) => {
+                ctx.errors.record("mirror flush failures", &format!("{} cannot reconnect to the mirror yet", name), e.msg());
+                return false;
+            }
+        }
+    }
+    match mirror.flush_and_keep(buffer) {
+        Ok(()) => true,
+        Err(e) => {
+            ctx.errors.record("mirror flush failures", &format!("{} could not flush to the mirror", name), e.msg());
+            false
+        }
+    }
+}
+
+/// Flushes the buffer on the thread's own connection, once the rate limits allow. Tells
+/// whether the server accepted the rows.
+pub fn flush(ctx: &RunContext, connection: &mut Connection, buffer: &mut Buffer, name: &str, planes: &mut [Plane], flip: Option<FlipType>) -> bool {
+    let started = Instant::now();
+    thread::sleep(ctx.rate_limits.reserve_total(buffer.row_count()));
+    thread::sleep(ctx.rate_limits.reserve_conn(connection.limit.as_ref(), buffer.row_count()));
+    // The mirror gets its copy first, the flush to the primary empties the buffer
+    let mirrored = connection.mirror.as_mut().map(|mirror| flush_mirror(ctx, mirror, buffer, name));
+    let sender = &mut connection.sender;
+    let (rows, bytes) = (buffer.row_count(), buffer.len());
+    let cold = planes.iter().any(|plane| plane.cold_buffered > 0);
+    let flush_started = Instant::now();
+    thread::sleep(ctx.network.delay(bytes));
+    let result = sender.flush(buffer);
+    let accepted = result.is_ok();
+    if let (Some(mirror), Some(mirrored)) = (&ctx.mirror, mirrored) {
+        mirror.record(accepted, mirrored, rows);
+    }
+    if accepted {
+        ctx.record_sent(rows, bytes, cold, flush_started.elapsed());
+    }
+    record_flush(ctx, result, buffer, rows, name, planes, flip);
+    ctx.record_flush(started.elapsed());
+    if sender.must_close() {
+        match sink::connect(&ctx.opt.connection_string, ctx.opt.protocol_version) {
+            Ok(new_sender) => *sender = new_sender,
+            Err(e) => ctx.errors.record("reconnect failures", &format!("{} cannot reconnect yet", name), e.msg()),
+        }
+    }
+    accepted
+}
+
+fn flush_backlog(ctx: &RunContext, connection: &mut Connection, plane: &mut Plane, name: &str) {
+    let mut backlog = std::mem::replace(&mut plane.backlog, ctx.pool.empty());
+    let buffered = std::mem::replace(&mut plane.buffered, backlog.row_count() as u64);
+    flush(ctx, connection, &mut backlog, name, std::slice::from_mut(plane), None);
+    plane.buffered = buffered;
+    plane.backlog = backlog;
+}
+
+/// Same loop as the async generator, sleeping the thread between ticks. Without the
+/// health watcher, rows stay buffered and are retried on the next flush when the
+/// server is unavailable. The thread connects and starts ticking after `delay`. The
+/// steps of a row it shares with the other loops never wait on the async runtime, so
+/// `block_on` runs them on the thread.
+fn generate_data(ctx: &RunContext, name: &str, planes: Vec<(String, String)>, delay: Duration) -> Vec<PlaneStats> {
+    let opt = &ctx.opt;
+    thread::sleep(delay);
+    let mut planes: Vec<Plane> = planes.into_iter().map(|(plane_id, icao24)| Plane::new(ctx, plane_id, icao24)).collect();
+    let mut connection = match Connection::open(ctx) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("{} cannot connect: {}", name, e);
+            return planes.into_iter().map(|plane| plane.stats).collect();
+        }
+    };
+    let mut next_tick = Instant::now();
+    let mut buffer = TaskBuffer::new(ctx);
+    let mut allowance = ctx.quota.allowance(rows_per_tick(&planes));
+
+    'ticks: loop {
+        let now = Instant::now();
+        if next_tick > now {
+            thread::sleep(next_tick - now);
+        }
+        let tick = Duration::from_millis(ctx.tick_millis());
+        next_tick = match opt.on_missed_tick {
+            MissedTick::Burst => next_tick + tick,
+            MissedTick::Skip => {
+                let now = Instant::now();
+                while next_tick <= now {
+                    next_tick += tick;
+                }
+                next_tick
+            }
+            MissedTick::Delay => Instant::now() + tick,
+        };
+        ctx.publish(&planes);
+
+        for i in 0..planes.len() {
+            if planes[i].stopped {
+                continue;
+            }
+            if ctx.is_killed(&planes[i]) {
+                planes[i].stopped = true;
+                continue;
+            }
+            if ctx.is_paused() {
+                ctx.rate.record_skipped();
+                continue;
+            }
+
+            let plane = &mut planes[i];
+            let Some(signal) = plane.advance(ctx) else {
+                ctx.rate.record_skipped();
+                continue;
+            };
+            if !plane.is_hot() {
+                // A cold plane can go many ticks without a row, it stops with the others
+                if allowance.is_exhausted() {
+                    break 'ticks;
+                }
+                continue;
+            }
+            if !plane.has_readings(ctx) {
+                ctx.rate.record_skipped();
+                continue;
+            }
+            if signal == Signal::Dark && !opt.dropout_backfill {
+                ctx.rate.record_skipped();
+                continue;
+            }
+            if signal != Signal::Dark && !plane.backlog.is_empty() {
+                flush_backlog(ctx, &mut connection, plane, name);
+            }
+            if !allowance.take() {
+                break 'ticks;
+            }
+            let last_row = allowance.is_exhausted();
+            if last_row {
+                ctx.phases.drain();
+            }
+
+            if signal == Signal::Dark {
+                plane.record_dark_row(ctx);
+                if last_row {
+                    break 'ticks;
+                }
+                continue;
+            }
+            block_on(buffer.write(ctx, &mut Engine::Blocking(&mut connection), name, planes.as_mut_slice(), i, last_row));
+
+            if last_row {
+                break 'ticks;
+            }
+        }
+
+        if planes.iter().all(|plane| plane.stopped) {
+            // The rows it will not write go to the other tasks right away
+            allowance.release();
+            break;
+        }
+    }
+    // Once all rows are handed out, what is left is flushing them
+    if ctx.quota.remaining() == 0 {
+        ctx.phases.drain();
+    }
+    // Rows a stopped task did not get to write go back to the others
+    drop(allowance);
+
+    block_on(buffer.finish(ctx, &mut Engine::Blocking(&mut connection), name, planes.as_mut_slice()));
+
+    for plane in planes.iter_mut().filter(|plane| !plane.backlog.is_empty()) {
+        flush_backlog(ctx, &mut connection, plane, name);
+    }
+    ctx.publish(&planes);
+
+    planes.into_iter().map(|plane| plane.finish(ctx)).collect()
+}
@@ -0,0 +1,99 @@
+use std::future::Future;
+use std::io::{self, Write};
+use std::sync::Arc;
+use object_store::{ObjectStore, ObjectStoreExt, WriteMultipart, path::Path};
+
+/// Parts of a file that may be uploading at once before the sink waits for them
+const PARTS_IN_FLIGHT: usize = 8;
+/// Size of the parts, unless --sink-part-size says
+pub const DEFAULT_PART_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The bucket the `csv` sink uploads its files to when --sink-path is a URL, `s3://`, `gs://`
+/// or `az://`, with the names of the files under the prefix of the URL. The credentials and
+/// region come from the environment, as the SDKs of each cloud read them.
+pub struct Bucket {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    url: String,
+    part_size: usize,
+}
+
+impl Bucket {
+    pub fn open(url: &str, part_size: u64) -> Result<Self, String> {
+        let parsed = url::Url::parse(url).map_err(|e| format!("invalid --sink-path URL '{}': {}", url, e))?;
+        let (store, prefix) = object_store::parse_url_opts(&parsed, std::env::vars())
+            .map_err(|e| format!("cannot open {}: {}", url, e))?;
+        Ok(Bucket { store: Arc::from(store), prefix, url: url.trim_end_matches('/').to_string(), part_size: part_size as usize })
+    }
+
+    /// Where a file of the sink goes, for the messages
+    pub fn location(&self, name: &str) -> String {
+        format!("{}/{}", self.url, name)
+    }
+
+    /// Starts the multipart upload of a file
+    pub fn create(&self, name: &str) -> io::Result<Upload> {
+        let upload = block_on(self.store.put_multipart(&self.prefix.clone().join(name))).map_err(io::Error::other)?;
+        Ok(Upload { multipart: WriteMultipart::new_with_chunk_size(upload, self.part_size), unchecked: 0, part_size: self.part_size })
+    }
+}
+
+/// A file being uploaded, a part of --sink-part-size at a time while the sink writes it
+pub struct Upload {
+    multipart: WriteMultipart,
+    /// Bytes written since the parts in flight were last checked
+    unchecked: usize,
+    part_size: usize,
+}
+
+impl Upload {
+    /// Uploads the last part and completes the file, which only then shows in the bucket
+    pub fn finish(self) -> io::Result<()> {
+        block_on(self.multipart.finish()).map(|_| ()).map_err(io::Error::other)
+    }
+}
+
+impl Write for Upload {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Holds the sink back while the uploads fall behind, and reports a part that failed
+        if self.unchecked >= self.part_size {
+            block_on(self.multipart.wait_for_capacity(PARTS_IN_FLIGHT)).map_err(io::Error::other)?;
+            self.unchecked = 0;
+        }
+        self.multipart.write(buf);
+        self.unchecked += buf.len();
+        Ok(buf.len())
+    }
+
+    /// The parts go up as they fill, there is nothing to push before
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Waits for a request to the bucket from the synchronous writes of the sink, on the
+/// runtime of the run
+fn block_on<F: Future>(future: F) -> F::Output {
+    let handle = tokio::runtime::Handle::current();
+    tokio::task::block_in_place(|| handle.block_on(future))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_file_shows_in_the_bucket_once_its_parts_are_uploaded() {
+        let bucket = Bucket::open("memory:///exports/flights/", 8).unwrap();
+        assert_eq!(bucket.location("t.csv"), "memory:///exports/flights/t.csv");
+        let mut upload = bucket.create("t.csv").unwrap();
+        for n in 0..10 {
+            writeln!(upload, "A,{}", n).unwrap();
+        }
+        let location = Path::from("exports/flights/t.csv");
+        assert!(bucket.store.head(&location).await.is_err());
+        upload.finish().unwrap();
+        let bytes = bucket.store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(std::str::from_utf8(&bytes).unwrap(), (0..10).map(|n| format!("A,{}\n", n)).collect::<String>());
+    }
+}
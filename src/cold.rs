@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use rand::Rng;
+
+use crate::error_summary::thousands;
+
+const DAY_NANOS: f64 = 86_400e9;
+
+#[derive(Debug, Default)]
+struct FlushTimes {
+    batches: AtomicU64,
+    rows: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl FlushTimes {
+    fn record(&self, rows: usize, elapsed: Duration) {
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.rows.fetch_add(rows as u64, Ordering::Relaxed);
+        self.nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Rows per second of flush time and average flush latency in ms, if any batch was flushed
+    fn summary(&self) -> Option<(f64, f64)> {
+        let batches = self.batches.load(Ordering::Relaxed);
+        let secs = self.nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        if batches == 0 || secs == 0.0 {
+            return None;
+        }
+        Some((self.rows.load(Ordering::Relaxed) as f64 / secs, secs * 1000.0 / batches as f64))
+    }
+}
+
+/// Rows sent with timestamps days in the past with --cold-writes-pct, which land in old
+/// partitions and make the server merge them out of order. Flushes are timed separately
+/// for batches with and without such rows, to show what the merges cost.
+#[derive(Debug)]
+pub struct ColdWrites {
+    probability: f64,
+    max_days: f64,
+    rows: AtomicU64,
+    cold: FlushTimes,
+    warm: FlushTimes,
+}
+
+impl ColdWrites {
+    pub fn new(pct: f64, max_days: f64) -> Self {
+        ColdWrites { probability: pct / 100.0, max_days, rows: AtomicU64::new(0), cold: FlushTimes::default(), warm: FlushTimes::default() }
+    }
+
+    /// How far back to move the next row, in nanoseconds, if it is a cold one: between
+    /// one day and --cold-writes-days.
+    pub fn offset(&self) -> Option<i64> {
+        let mut rng = rand::thread_rng();
+        if !rng.gen_bool(self.probability) {
+            return None;
+        }
+        Some((rng.gen_range(1.0..=self.max_days) * DAY_NANOS) as i64)
+    }
+
+    pub fn record_row(&self) {
+        self.rows.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_flush(&self, cold: bool, rows: usize, elapsed: Duration) {
+        match cold {
+            true => self.cold.record(rows, elapsed),
+            false => self.warm.record(rows, elapsed),
+        }
+    }
+
+    pub fn print_summary(&self, rows_generated: u64) {
+        let rows = self.rows.load(Ordering::Relaxed);
+        println!("Cold writes: {} rows ({:.1}%) up to {} days in the past",
+                 thousands(rows), rows as f64 * 100.0 / rows_generated.max(1) as f64, self.max_days);
+        let describe = |times: &FlushTimes| match times.summary() {
+            Some((rate, latency)) => format!("{} batches at {:.0} rows/s of flush time, {:.1} ms each",
+                                             thousands(times.batches.load(Ordering::Relaxed)), rate, latency),
+            None => "none".to_string(),
+        };
+        println!("  batches with cold rows: {}", describe(&self.cold));
+        println!("  batches without: {}", describe(&self.warm));
+        if let (Some((cold, _)), Some((warm, _))) = (self.cold.summary(), self.warm.summary()) {
+            println!("  batches with cold rows flushed {:.1}% {} than the others",
+                     ((cold - warm) / warm * 100.0).abs(), if cold < warm { "slower" } else { "faster" });
+        }
+    }
+}
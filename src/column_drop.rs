@@ -0,0 +1,92 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::config::{self, Columns};
+use crate::error_summary::thousands;
+use crate::units;
+
+/// A window of --drop-column-at: from `secs` into the run and for `for_secs`, the rows
+/// leave the column out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropColumnAt {
+    pub secs: f64,
+    pub column: String,
+    pub for_secs: f64,
+}
+
+impl FromStr for DropColumnAt {
+    type Err = String;
+
+    /// "5m:altitude:2m"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        let (at, column, length) = match parts[..] {
+            [at, column, length] => (at, column, length),
+            _ => return Err(format!("invalid window '{}', expected TIME:COLUMN:DURATION such as 5m:altitude:2m", s)),
+        };
+        if !config::is_value_column(column) {
+            return Err(format!("cannot drop '{}', only the sensor columns can be dropped, such as altitude or fuel_kg", column));
+        }
+        Ok(DropColumnAt { secs: units::secs_f64(at)?, column: column.to_string(), for_secs: units::secs_f64(length)? })
+    }
+}
+
+/// The rows written without the column of a window, and the timestamps of the first and
+/// the last of them.
+#[derive(Debug, Default)]
+struct NullWindow {
+    rows: u64,
+    first: Option<i64>,
+    last: i64,
+}
+
+/// The windows of --drop-column-at, where the rows leave a column out so it is null in the
+/// table, to check how queries and dashboards cope with it. They follow the clock of the
+/// rows, the wall clock unless backfilling, so the null windows are where the timestamps
+/// say.
+#[derive(Debug)]
+pub struct ColumnDrops {
+    windows: Vec<DropColumnAt>,
+    /// Timestamp the windows count from
+    start: i64,
+    nulls: Mutex<Vec<NullWindow>>,
+}
+
+impl ColumnDrops {
+    pub fn new(windows: Vec<DropColumnAt>, start: i64) -> Self {
+        let nulls = windows.iter().map(|_| NullWindow::default()).collect();
+        ColumnDrops { windows, start, nulls: Mutex::new(nulls) }
+    }
+
+    /// The columns a row at `timestamp` leaves out, counting the row in their windows
+    pub fn dropped(&self, timestamp: i64) -> Vec<&str> {
+        let since = (timestamp - self.start) as f64 / 1e9;
+        let mut nulls = self.nulls.lock().unwrap();
+        self.windows.iter().zip(nulls.iter_mut())
+            .filter(|(window, _)| (window.secs..window.secs + window.for_secs).contains(&since))
+            .map(|(window, nulls)| {
+                nulls.rows += 1;
+                nulls.first.get_or_insert(timestamp);
+                nulls.last = nulls.last.max(timestamp);
+                window.column.as_str()
+            })
+            .collect()
+    }
+
+    pub fn print_summary(&self, columns: &Columns, table_name: &str) {
+        let date = |at: i64| chrono::DateTime::from_timestamp_nanos(at).format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string();
+        let nulls = self.nulls.lock().unwrap();
+        for (window, nulls) in self.windows.iter().zip(nulls.iter()) {
+            let name = columns.name(&window.column);
+            match nulls.first {
+                Some(first) => {
+                    println!("Column {} dropped from {:.0}s for {:.0}s: {} rows without it, from {} to {}",
+                             name, window.secs, window.for_secs, thousands(nulls.rows), date(first), date(nulls.last));
+                    println!("  check with: SELECT count() FROM {} WHERE {} IS NULL AND timestamp BETWEEN '{}' AND '{}'",
+                             table_name, name, date(first), date(nulls.last));
+                }
+                None => println!("Column {} dropped from {:.0}s for {:.0}s: the run ended before", name, window.secs, window.for_secs),
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+use questdb::ingress::Buffer;
+
+use crate::config::Columns;
+use crate::plane::{FlightPhase, PlaneData};
+
+const INSTRUCTIONS_EN_ROUTE: &[&str] = &["maintain", "direct", "contact"];
+
+/// ATC clearances issued to a plane, written to a companion table once every `ratio`
+/// flight data rows. They share the plane_id and callsign of the flight data and follow
+/// its phase, so an ASOF JOIN shows each reading next to the clearance in force.
+#[derive(Debug, Clone)]
+pub struct Clearances {
+    ratio: u64,
+    /// Flight data rows left until the next clearance
+    rows_until_next: u64,
+}
+
+impl Clearances {
+    pub fn new(ratio: u64) -> Self {
+        // Staggered, so the planes do not all get a clearance on the same tick
+        Clearances { ratio, rows_until_next: rand::thread_rng().gen_range(0..ratio) }
+    }
+
+    /// Counts a flight data row and tells whether a clearance goes with it.
+    pub fn due(&mut self) -> bool {
+        if self.rows_until_next == 0 {
+            self.rows_until_next = self.ratio - 1;
+            true
+        } else {
+            self.rows_until_next -= 1;
+            false
+        }
+    }
+}
+
+pub fn write(plane: &PlaneData, buffer: &mut Buffer, table_name: &str, columns: &Columns) -> questdb::Result<()> {
+    let mut rng = rand::thread_rng();
+    // Flight levels are multiples of 1000 ft
+    let level = (plane.altitude / 1000.0).round() as i64 * 1000;
+    let (instruction, assigned_altitude) = match plane.phase {
+        FlightPhase::Climb => ("climb", level + 2000),
+        FlightPhase::Descent => ("descend", (level - 2000).max(0)),
+        FlightPhase::Cruise => (*INSTRUCTIONS_EN_ROUTE.choose(&mut rng).unwrap(), level),
+    };
+    // Sectors are 10 by 10 degree cells
+    let sector = format!("{}{:02}{}{:03}",
+                         if plane.latitude < 0.0 { 'S' } else { 'N' }, (plane.latitude.abs() / 10.0) as u32 * 10,
+                         if plane.longitude < 0.0 { 'W' } else { 'E' }, (plane.longitude.abs() / 10.0) as u32 * 10);
+    // VHF airband channels, 25 kHz apart
+    let frequency = 118.0 + rng.gen_range(0..760) as f64 * 0.025;
+
+    buffer.table(table_name)?
+        .symbol(columns.name("plane_id"), &plane.plane_id)?
+        .symbol(columns.name("callsign"), &plane.callsign)?
+        .symbol("instruction", instruction)?
+        .symbol("sector", sector)?;
+    if let Some((name, label)) = columns.run_label() {
+        buffer.symbol(name, label)?;
+    }
+    buffer.column_i64("assigned_altitude", assigned_altitude)?
+        .column_i64("assigned_heading", ((plane.track / 5.0).round() as i64 * 5).rem_euclid(360))?
+        .column_f64("frequency_mhz", (frequency * 1000.0).round() / 1000.0)?;
+    plane.at(buffer)
+}
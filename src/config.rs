@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use questdb::ingress::ColumnName;
+use serde::Deserialize;
+
+use crate::noise::{Noise, NoiseDistribution};
+use crate::secrets;
+
+/// Optional settings that are too detailed for command line flags, read from
+/// the TOML file passed with `--config`, with the `${NAME}` of its strings replaced by the
+/// environment.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Per-column overrides, keyed by the default column name
+    pub columns: HashMap<String, ColumnConfig>,
+    /// Columns sampled at their own rate, keyed by group name
+    pub sensor_groups: HashMap<String, SensorGroupConfig>,
+    /// Groups of airlines tagged with a `fleet` column, or sent to a table of their own,
+    /// keyed by fleet name
+    pub fleets: HashMap<String, FleetConfig>,
+    /// Total rates that follow the day and the week, keyed by the name --rate-schedule takes
+    pub rate_schedules: HashMap<String, RateScheduleConfig>,
+    /// Where the rows go, unless --sink says otherwise
+    pub sink: SinkConfig,
+    /// The environment variables the strings were expanded with, and their values
+    #[serde(skip)]
+    expanded: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnConfig {
+    /// Name of the column in the table
+    pub name: Option<String>,
+    /// Unit to convert the value to before sending
+    pub unit: Option<String>,
+    /// Distribution of the noise added to every reading: uniform, gaussian, lognormal or pareto
+    pub noise: Option<String>,
+    /// Size of the noise, in the unit the column is sent in. Defaults to 1
+    pub noise_scale: Option<f64>,
+    /// How far the readings drift over an hour of a flight leg, in the unit the column is sent in
+    pub drift_per_hour: Option<f64>,
+    /// Percentage of the readings sent as outliers, instead of --outliers-pct
+    pub outlier_pct: Option<f64>,
+    /// How many standard deviations away outliers are, instead of --outlier-sigmas
+    pub outlier_sigmas: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorGroupConfig {
+    /// Default names of the columns in the group
+    pub columns: Vec<String>,
+    /// Readings per second. Columns outside any group are sent on every row
+    pub rate_hz: f64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FleetConfig {
+    /// Airline codes, the first three letters of the callsign
+    pub airlines: Vec<String>,
+    /// Table for the rows of the fleet, instead of --table-name
+    pub table: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateScheduleConfig {
+    /// Windows of the week with a rate of their own, the first one a time is in wins
+    pub windows: Vec<RateWindowConfig>,
+    /// Rows per second across all the planes outside the windows, e.g. "10k". Defaults to
+    /// --total-rate or --rate-per-plane
+    pub otherwise: Option<String>,
+    /// Hours the time of the windows is ahead of UTC. Defaults to 0
+    pub utc_offset: Option<f64>,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateWindowConfig {
+    /// Days of the window, e.g. "mon-fri", "sat,sun", "weekdays" or "weekends". Defaults to every day
+    pub days: Option<String>,
+    /// Hours of the window, from the first to before the second, e.g. "9-17" or "08:30-18:00"
+    pub hours: Option<String>,
+    /// Rows per second across all the planes, e.g. "100k"
+    pub total_rate: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SinkConfig {
+    /// Name of the sink, as --sink takes it
+    pub kind: Option<String>,
+    /// Directory or bucket URL of the file sinks, as --sink-path
+    pub path: Option<PathBuf>,
+    /// none, gzip, zstd or lz4, as --sink-compression
+    pub compression: Option<String>,
+    /// Size of CSV a file ends at, e.g. "1GB", as --sink-rotate-size
+    pub rotate_size: Option<String>,
+    /// Window of time the files split the rows by, e.g. "1h", as --sink-rotate-every
+    pub rotate_every: Option<String>,
+    /// Size of the parts of the uploads to a bucket, e.g. "64MB", as --sink-part-size
+    pub part_size: Option<String>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read config file {}: {}", path.display(), e))?;
+        let mut value: toml::Value = toml::from_str(&text).map_err(|e| format!("invalid config file {}: {}", path.display(), e))?;
+        let replaced = secrets::expand_toml(&mut value).map_err(|e| format!("config file {}: {}", path.display(), e))?;
+        let config: Config = value.try_into()
+            .map_err(|e| format!("invalid config file {}: {}", path.display(), secrets::redact(&e.to_string(), &replaced)))?;
+        Ok(Config { expanded: replaced, ..config })
+    }
+
+    /// A message about the config, with the values that came from the environment given
+    /// back as their `${NAME}`
+    pub fn redact(&self, message: String) -> String {
+        match self.expanded.is_empty() {
+            true => message,
+            false => secrets::redact(&message, &self.expanded),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quantity {
+    Symbol,
+    Speed,
+    VerticalSpeed,
+    Altitude,
+    Angle,
+    Temperature,
+    Mass,
+    /// An array of amplitudes in g
+    Spectrum,
+}
+
+impl Quantity {
+    /// Whether the column has a single reading, that noise and outliers apply to.
+    fn is_scalar(self) -> bool {
+        !matches!(self, Quantity::Symbol | Quantity::Spectrum)
+    }
+}
+
+/// Every column the simulator can emit, with the quantity it measures. Values
+/// are generated in knots, feet, feet per minute, degrees, °C, kg and g.
+const COLUMNS: &[(&str, Quantity)] = &[
+    ("plane_id", Quantity::Symbol),
+    ("icao24", Quantity::Symbol),
+    ("callsign", Quantity::Symbol),
+    ("phase", Quantity::Symbol),
+    ("run_label", Quantity::Symbol),
+    ("fleet", Quantity::Symbol),
+    ("formation", Quantity::Symbol),
+    ("quality", Quantity::Symbol),
+    ("airspeed", Quantity::Speed),
+    ("altitude", Quantity::Altitude),
+    ("pitch", Quantity::Angle),
+    ("roll", Quantity::Angle),
+    ("yaw", Quantity::Angle),
+    ("aoa", Quantity::Angle),
+    ("oat", Quantity::Temperature),
+    ("fuel_kg", Quantity::Mass),
+    ("gross_weight_kg", Quantity::Mass),
+    ("latitude", Quantity::Angle),
+    ("longitude", Quantity::Angle),
+    ("ground_speed", Quantity::Speed),
+    ("track", Quantity::Angle),
+    ("vertical_speed", Quantity::VerticalSpeed),
+    ("vibration_spectrum", Quantity::Spectrum),
+];
+
+/// Whether a column has the readings of a sensor, rather than being a symbol.
+pub fn is_value_column(column: &str) -> bool {
+    COLUMNS.iter().any(|(name, quantity)| *name == column && *quantity != Quantity::Symbol)
+}
+
+/// Scale and offset to go from the generated unit to the requested one.
+fn conversion(quantity: Quantity, unit: &str) -> Option<(f64, f64)> {
+    match (quantity, unit) {
+        (Quantity::Speed, "kt") | (Quantity::Speed, "knots") => Some((1.0, 0.0)),
+        (Quantity::Speed, "m/s") => Some((0.514444, 0.0)),
+        (Quantity::Speed, "km/h") => Some((1.852, 0.0)),
+        (Quantity::Speed, "mph") => Some((1.150779, 0.0)),
+        (Quantity::VerticalSpeed, "ft/min") => Some((1.0, 0.0)),
+        (Quantity::VerticalSpeed, "m/s") => Some((0.00508, 0.0)),
+        (Quantity::VerticalSpeed, "m/min") => Some((0.3048, 0.0)),
+        (Quantity::Altitude, "ft") => Some((1.0, 0.0)),
+        (Quantity::Altitude, "m") => Some((0.3048, 0.0)),
+        (Quantity::Angle, "deg") => Some((1.0, 0.0)),
+        (Quantity::Angle, "rad") => Some((std::f64::consts::PI / 180.0, 0.0)),
+        (Quantity::Temperature, "C") => Some((1.0, 0.0)),
+        (Quantity::Temperature, "K") => Some((1.0, 273.15)),
+        (Quantity::Temperature, "F") => Some((1.8, 32.0)),
+        (Quantity::Mass, "kg") => Some((1.0, 0.0)),
+        (Quantity::Mass, "lb") => Some((2.204623, 0.0)),
+        (Quantity::Spectrum, "g") => Some((1.0, 0.0)),
+        _ => None,
+    }
+}
+
+fn units(quantity: Quantity) -> &'static str {
+    match quantity {
+        Quantity::Symbol => "none, symbols have no unit",
+        Quantity::Speed => "kt, m/s, km/h, mph",
+        Quantity::VerticalSpeed => "ft/min, m/s, m/min",
+        Quantity::Altitude => "ft, m",
+        Quantity::Angle => "deg, rad",
+        Quantity::Temperature => "C, K, F",
+        Quantity::Mass => "kg, lb",
+        Quantity::Spectrum => "g",
+    }
+}
+
+/// Noise of a column from its config, if it has any.
+fn noise(name: &str, quantity: Quantity, config: &ColumnConfig) -> Result<Option<Noise>, String> {
+    if config.noise.is_none() && config.noise_scale.is_none() && config.drift_per_hour.is_none() {
+        return Ok(None);
+    }
+    match quantity {
+        Quantity::Symbol => return Err(format!("symbol column '{}' cannot have noise or drift", name)),
+        Quantity::Spectrum => return Err(format!("array column '{}' cannot have noise or drift", name)),
+        _ => {}
+    }
+    let distribution = match &config.noise {
+        Some(noise) => noise.parse::<NoiseDistribution>().map_err(|e| format!("{} for column '{}'", e, name))?,
+        None if config.noise_scale.is_some() => return Err(format!("column '{}' has a noise_scale but no noise", name)),
+        None => NoiseDistribution::Uniform,
+    };
+    let scale = match (&config.noise, config.noise_scale) {
+        (None, _) => 0.0,
+        (Some(_), Some(scale)) if scale < 0.0 || !scale.is_finite() => {
+            return Err(format!("noise_scale of column '{}' must be 0 or more, got {}", name, scale));
+        }
+        (Some(_), scale) => scale.unwrap_or(1.0),
+    };
+    let drift_per_hour = config.drift_per_hour.unwrap_or(0.0);
+    if !drift_per_hour.is_finite() {
+        return Err(format!("drift_per_hour of column '{}' must be a number, got {}", name, drift_per_hour));
+    }
+    Ok(Some(Noise::new(distribution, scale, drift_per_hour)))
+}
+
+#[derive(Debug, Clone)]
+struct Fleet {
+    name: String,
+    table: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Column {
+    name: String,
+    scale: f64,
+    offset: f64,
+    /// Sensor group, for columns that are not sent on every row
+    group: Option<usize>,
+    noise: Option<Noise>,
+    outlier_pct: Option<f64>,
+    outlier_sigmas: Option<f64>,
+}
+
+/// Column names and unit conversions resolved from the config.
+#[derive(Debug, Clone)]
+pub struct Columns {
+    columns: HashMap<&'static str, Column>,
+    /// Rate of each sensor group, in Hz
+    group_rates: Vec<f64>,
+    run_label: Option<String>,
+    /// --outliers-pct and --outlier-sigmas, for the columns that do not set their own
+    outliers: (f64, f64),
+    fleets: Vec<Fleet>,
+    /// Index in `fleets` for each airline code
+    airlines: HashMap<String, usize>,
+}
+
+impl Columns {
+    pub fn new(config: &Config) -> Result<Self, String> {
+        Columns::build(config).map_err(|e| config.redact(e))
+    }
+
+    fn build(config: &Config) -> Result<Self, String> {
+        if let Some(unknown) = config.columns.keys().find(|k| !COLUMNS.iter().any(|(name, _)| name == k)) {
+            return Err(format!("unknown column '{}' in config", unknown));
+        }
+        let mut columns = HashMap::new();
+        for (name, quantity) in COLUMNS.iter() {
+            let mut column = Column { name: name.to_string(), scale: 1.0, offset: 0.0, group: None, noise: None, outlier_pct: None, outlier_sigmas: None };
+            if let Some(column_config) = config.columns.get(*name) {
+                if let Some(renamed) = &column_config.name {
+                    column.name = renamed.clone();
+                }
+                if let Some(unit) = &column_config.unit {
+                    let (scale, offset) = conversion(*quantity, unit).ok_or_else(|| {
+                        format!("invalid unit '{}' for column '{}', expected one of: {}", unit, name, units(*quantity))
+                    })?;
+                    column.scale = scale;
+                    column.offset = offset;
+                }
+                column.noise = noise(name, *quantity, column_config)?;
+                if column_config.outlier_pct.is_some() || column_config.outlier_sigmas.is_some() {
+                    if !quantity.is_scalar() {
+                        return Err(format!("{} column '{}' cannot have outliers", if *quantity == Quantity::Symbol { "symbol" } else { "array" }, name));
+                    }
+                    if let Some(pct) = column_config.outlier_pct.filter(|pct| !(0.0..=100.0).contains(pct)) {
+                        return Err(format!("outlier_pct of column '{}' must be between 0 and 100, got {}", name, pct));
+                    }
+                    if let Some(sigmas) = column_config.outlier_sigmas.filter(|sigmas| *sigmas <= 0.0 || sigmas.is_nan()) {
+                        return Err(format!("outlier_sigmas of column '{}' must be greater than 0, got {}", name, sigmas));
+                    }
+                }
+                column.outlier_pct = column_config.outlier_pct;
+                column.outlier_sigmas = column_config.outlier_sigmas;
+            }
+            columns.insert(*name, column);
+        }
+        // A name the server cannot take, or that two columns share, would fail every row
+        let mut named: HashMap<&str, &str> = HashMap::new();
+        for (name, _) in COLUMNS.iter() {
+            let final_name = columns[name].name.as_str();
+            ColumnName::new(final_name).map_err(|e| format!("invalid name '{}' for column '{}': {}", final_name, name, e.msg()))?;
+            if final_name == "timestamp" {
+                return Err(format!("column '{}' cannot be named 'timestamp', the designated timestamp of the table", name));
+            }
+            if let Some(other) = named.insert(final_name, name) {
+                return Err(format!("columns '{}' and '{}' are both named '{}'", other, name, final_name));
+            }
+        }
+
+        // Sorted so group numbers do not depend on the map order
+        let mut groups: Vec<_> = config.sensor_groups.iter().collect();
+        groups.sort_by_key(|(name, _)| name.as_str());
+        if groups.len() > 64 {
+            return Err(format!("at most 64 sensor groups are supported, got {}", groups.len()));
+        }
+        let mut group_rates = vec![];
+        for (index, (group, group_config)) in groups.into_iter().enumerate() {
+            if group_config.rate_hz <= 0.0 || group_config.rate_hz.is_nan() {
+                return Err(format!("sensor group '{}' needs a rate_hz greater than 0", group));
+            }
+            for name in group_config.columns.iter() {
+                let quantity = COLUMNS.iter().find(|(column, _)| column == name).map(|(_, quantity)| *quantity);
+                let column = match quantity {
+                    Some(Quantity::Symbol) => return Err(format!("symbol column '{}' cannot be in sensor group '{}', symbols are sent on every row", name, group)),
+                    Some(Quantity::Spectrum) => return Err(format!("array column '{}' cannot be in sensor group '{}', it is sent on every row", name, group)),
+                    Some(_) => columns.get_mut(name.as_str()).unwrap(),
+                    None => return Err(format!("unknown column '{}' in sensor group '{}'", name, group)),
+                };
+                if column.group.is_some() {
+                    return Err(format!("column '{}' is in more than one sensor group", name));
+                }
+                column.group = Some(index);
+            }
+            group_rates.push(group_config.rate_hz);
+        }
+
+        let mut fleet_configs: Vec<_> = config.fleets.iter().collect();
+        fleet_configs.sort_by_key(|(name, _)| name.as_str());
+        let (mut fleets, mut airlines) = (vec![], HashMap::new());
+        for (index, (fleet, fleet_config)) in fleet_configs.into_iter().enumerate() {
+            if fleet_config.airlines.is_empty() {
+                return Err(format!("fleet '{}' needs at least one airline", fleet));
+            }
+            if fleet_config.table.as_deref().map(|table| table.trim().is_empty()).unwrap_or(false) {
+                return Err(format!("fleet '{}' has an empty table name", fleet));
+            }
+            for airline in fleet_config.airlines.iter() {
+                match airlines.insert(airline.to_uppercase(), index) {
+                    Some(other) if other != index => {
+                        let other: &Fleet = &fleets[other];
+                        return Err(format!("airline '{}' is in both fleet '{}' and fleet '{}'", airline, other.name, fleet));
+                    }
+                    _ => {}
+                }
+            }
+            fleets.push(Fleet { name: fleet.clone(), table: fleet_config.table.clone() });
+        }
+        Ok(Columns { columns, group_rates, run_label: None, outliers: (0.0, 0.0), fleets, airlines })
+    }
+
+    /// Tags every row with a `run_label` symbol, to tell runs apart in a shared table.
+    pub fn with_run_label(mut self, run_label: Option<String>) -> Self {
+        self.run_label = run_label;
+        self
+    }
+
+    /// The columns of a changed config, keeping the run label and the --outliers-pct
+    /// defaults of these ones.
+    pub fn reloaded(&self, config: &Config) -> Result<Self, String> {
+        Ok(Columns { run_label: self.run_label.clone(), outliers: self.outliers, ..Columns::new(config)? })
+    }
+
+    /// Sends `pct` percent of the readings of every value column `sigmas` standard deviations
+    /// away, for columns that do not set their own.
+    pub fn with_outliers(mut self, pct: f64, sigmas: f64) -> Self {
+        self.outliers = (pct, sigmas);
+        self
+    }
+
+    /// Percentage of the readings of a column sent as outliers, and how many standard
+    /// deviations away.
+    pub fn outliers(&self, column: &str) -> (f64, f64) {
+        let column = &self.columns[column];
+        (column.outlier_pct.unwrap_or(self.outliers.0), column.outlier_sigmas.unwrap_or(self.outliers.1))
+    }
+
+    /// Whether any value column gets outliers.
+    pub fn has_outliers(&self) -> bool {
+        COLUMNS.iter().any(|(name, quantity)| quantity.is_scalar() && self.outliers(name).0 > 0.0)
+    }
+
+    /// Column name and value of the run label, if there is one.
+    pub fn run_label(&self) -> Option<(&str, &str)> {
+        self.run_label.as_deref().map(|label| (self.name("run_label"), label))
+    }
+
+    fn fleet_of(&self, callsign: &str) -> Option<&Fleet> {
+        callsign.get(..3).and_then(|airline| self.airlines.get(airline)).map(|index| &self.fleets[*index])
+    }
+
+    /// Column name and fleet of the airline flying `callsign`, for the `fleet` column.
+    pub fn fleet(&self, callsign: &str) -> Option<(&str, &str)> {
+        self.fleet_of(callsign).map(|fleet| (self.name("fleet"), fleet.name.as_str()))
+    }
+
+    /// Table for the rows of `callsign`: the one of its fleet, if it has one, or `default`.
+    pub fn table_for<'a>(&'a self, callsign: &str, default: &'a str) -> &'a str {
+        self.fleet_of(callsign).and_then(|fleet| fleet.table.as_deref()).unwrap_or(default)
+    }
+
+    pub fn has_fleets(&self) -> bool {
+        !self.fleets.is_empty()
+    }
+
+    /// Tables of the fleets that have one of their own.
+    pub fn fleet_tables(&self) -> impl Iterator<Item = &str> {
+        self.fleets.iter().filter_map(|fleet| fleet.table.as_deref())
+    }
+
+    pub fn group_rates(&self) -> &[f64] {
+        &self.group_rates
+    }
+
+    /// Whether a column is sent on a row where the groups in the `due` bitmask have a reading.
+    pub fn is_due(&self, column: &str, due: u64) -> bool {
+        match self.columns[column].group {
+            Some(group) => due & (1 << group) != 0,
+            None => true,
+        }
+    }
+
+    /// Whether a row where the groups in `due` have a reading has any value column.
+    pub fn any_due(&self, due: u64) -> bool {
+        COLUMNS.iter().any(|(name, quantity)| quantity.is_scalar() && self.is_due(name, due))
+    }
+
+    /// Name of a column in the table, given its default name.
+    pub fn name(&self, column: &str) -> &str {
+        &self.columns[column].name
+    }
+
+    /// Converts a generated value to the configured unit of the column.
+    pub fn value(&self, column: &str, value: f64) -> f64 {
+        let column = &self.columns[column];
+        value * column.scale + column.offset
+    }
+
+    /// Converts a generated value like `value`, then adds the noise and drift of the column,
+    /// `leg_hours` into the flight leg.
+    pub fn reading(&self, column: &str, value: f64, leg_hours: f64) -> f64 {
+        let converted = self.value(column, value);
+        match &self.columns[column].noise {
+            Some(noise) => noise.apply(converted, leg_hours),
+            None => converted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(text: &str) -> Result<Columns, String> {
+        Columns::new(&toml::from_str(text).unwrap())
+    }
+
+    #[test]
+    fn renamed_columns_need_names_of_their_own() {
+        assert!(columns("[columns.altitude]\nname = \"alt\"\n").is_ok());
+        assert_eq!(columns("[columns.altitude]\nname = \"airspeed\"\n").err().unwrap(), "columns 'airspeed' and 'altitude' are both named 'airspeed'");
+        assert_eq!(columns("[columns.oat]\nname = \"callsign\"\n").err().unwrap(), "columns 'callsign' and 'oat' are both named 'callsign'");
+        assert!(columns("[columns.oat]\nname = \"\"\n").err().unwrap().starts_with("invalid name '' for column 'oat'"));
+        assert!(columns("[columns.oat]\nname = \"oat.c\"\n").err().unwrap().starts_with("invalid name 'oat.c' for column 'oat'"));
+        assert!(columns("[columns.oat]\nname = \"timestamp\"\n").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(feature = "profile")]
+use std::fs::File;
+use std::path::PathBuf;
+
+/// Samples a second, off the 100 Hz of timers so a periodic task is not always caught or missed
+#[cfg(feature = "profile")]
+const FREQUENCY: i32 = 99;
+
+/// The pprof-rs sampling of --profile, from the start of the generation to its end. The
+/// samples land wherever a thread is, the generation loop, the flushes, the runtime, and the
+/// stacks are only resolved once the run is over, so it costs little while it runs.
+#[cfg_attr(not(feature = "profile"), allow(dead_code))]
+pub struct CpuProfile {
+    path: PathBuf,
+    #[cfg(feature = "profile")]
+    guard: pprof::ProfilerGuard<'static>,
+}
+
+impl CpuProfile {
+    #[cfg(feature = "profile")]
+    pub fn start(path: PathBuf) -> Result<Self, String> {
+        // Unwinding through these in a signal handler can deadlock
+        let guard = pprof::ProfilerGuardBuilder::default().frequency(FREQUENCY)
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"]).build()
+            .map_err(|e| e.to_string())?;
+        Ok(CpuProfile { path, guard })
+    }
+
+    #[cfg(not(feature = "profile"))]
+    pub fn start(_path: PathBuf) -> Result<Self, String> {
+        unreachable!("--profile is refused without the profile feature");
+    }
+
+    /// Writes the samples to the --profile file: a flamegraph when it ends in `.svg`, else a
+    /// pprof protobuf, for `go tool pprof` or `pprof -http`.
+    #[cfg(feature = "profile")]
+    pub fn write(self) -> Result<(), String> {
+        use pprof::protos::Message;
+        use std::io::Write;
+
+        let failed = |e: &dyn std::fmt::Display| format!("{}: {}", self.path.display(), e);
+        let report = self.guard.report().build().map_err(|e| failed(&e))?;
+        let mut file = File::create(&self.path).map_err(|e| failed(&e))?;
+        if self.path.extension().is_some_and(|extension| extension == "svg") {
+            report.flamegraph(file).map_err(|e| failed(&e))?;
+        } else {
+            let profile = report.pprof().map_err(|e| failed(&e))?;
+            file.write_all(&profile.encode_to_vec()).map_err(|e| failed(&e))?;
+        }
+        println!("CPU profile written to {}", self.path.display());
+        Ok(())
+    }
+
+    #[cfg(not(feature = "profile"))]
+    pub fn write(self) -> Result<(), String> {
+        unreachable!("--profile is refused without the profile feature");
+    }
+}
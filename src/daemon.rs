@@ -0,0 +1,187 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::Opt;
+
+/// Set on the processes started by `--daemon` to tell them their role.
+const ROLE_ENV: &str = "AIRPLANES_DAEMON_ROLE";
+
+/// Which process of a daemonized run this is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    /// Started by the user
+    Foreground,
+    /// Detached process that writes the pid file and the logs
+    Supervisor,
+    /// The simulator itself, with its output going to the supervisor
+    Simulator,
+}
+
+pub fn role() -> Role {
+    match std::env::var(ROLE_ENV).as_deref() {
+        Ok("supervisor") => Role::Supervisor,
+        Ok("simulator") => Role::Simulator,
+        _ => Role::Foreground,
+    }
+}
+
+/// Same executable and arguments, in the given role.
+fn this_process(role: &str) -> io::Result<Command> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command.args(std::env::args_os().skip(1)).env(ROLE_ENV, role);
+    Ok(command)
+}
+
+#[cfg(unix)]
+fn detached(command: &mut Command) -> &mut Command {
+    use std::os::unix::process::CommandExt;
+    // Out of the terminal's process group, so closing it or Ctrl-C do not reach us
+    command.process_group(0)
+}
+
+#[cfg(windows)]
+fn detached(command: &mut Command) -> &mut Command {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn detached(command: &mut Command) -> &mut Command {
+    command
+}
+
+/// Starts the supervisor in the background and exits.
+pub fn detach(opt: &Opt) -> ! {
+    if opt.pid_file.exists() {
+        eprintln!("Pid file {} exists, is the simulator already running? Remove the file if it is stale", opt.pid_file.display());
+        std::process::exit(1);
+    }
+    let started = this_process("supervisor").and_then(|mut command| {
+        detached(&mut command).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()
+    });
+    match started {
+        Ok(_) => {
+            println!("Running in the background, pid in {} and logs in {}", opt.pid_file.display(), opt.log_file.display());
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Cannot start in the background: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs the simulator as a child process, writes its pid to the pid file and its output
+/// to the rotated log file, and cleans up when it exits. Stopping the pid in the pid
+/// file stops the whole daemon.
+pub fn supervise(opt: &Opt) -> ! {
+    let log = match RotatingLog::open(opt.log_file.clone(), opt.log_max_bytes, opt.log_keep) {
+        Ok(log) => Arc::new(Mutex::new(log)),
+        Err(_) => std::process::exit(1),
+    };
+    let log_line = |line: &str| {
+        let _ = log.lock().unwrap().write_line(line);
+    };
+    let mut child = match this_process("simulator").and_then(|mut command| {
+        command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
+    }) {
+        Ok(child) => child,
+        Err(e) => {
+            log_line(&format!("Cannot start the simulator: {}", e));
+            std::process::exit(1);
+        }
+    };
+    let pid_file = OpenOptions::new().write(true).create_new(true).open(&opt.pid_file)
+        .and_then(|mut file| writeln!(file, "{}", child.id()));
+    if let Err(e) = pid_file {
+        log_line(&format!("Cannot write pid file {}: {}", opt.pid_file.display(), e));
+        let _ = child.kill();
+        std::process::exit(1);
+    }
+
+    let readers: Vec<_> = vec![child.stdout.take().map(|out| Box::new(out) as Box<dyn Read + Send>),
+                               child.stderr.take().map(|err| Box::new(err) as Box<dyn Read + Send>)]
+        .into_iter().flatten().map(|output| {
+            let log = log.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(output).lines().map_while(|line| line.ok()) {
+                    let _ = log.lock().unwrap().write_line(&line);
+                }
+            })
+        }).collect();
+    let status = child.wait();
+    for reader in readers {
+        let _ = reader.join();
+    }
+    let _ = fs::remove_file(&opt.pid_file);
+    let code = match status {
+        Ok(status) => {
+            log_line(&format!("Simulator exited with {}", status));
+            status.code().unwrap_or(1)
+        }
+        Err(e) => {
+            log_line(&format!("Lost track of the simulator: {}", e));
+            1
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Log file that is rotated to `<file>.1`, `<file>.2`... once it reaches `max_bytes`,
+/// keeping at most `keep` old files.
+struct RotatingLog {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: u32,
+    file: File,
+    written: u64,
+}
+
+impl RotatingLog {
+    fn open(path: PathBuf, max_bytes: u64, keep: u32) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(RotatingLog { path, max_bytes, keep, file, written })
+    }
+
+    fn rotated(&self, n: u32) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep > 0 {
+            for n in (1..self.keep).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let line = format!("{} {}\n", chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"), line);
+        if self.max_bytes > 0 && self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+}
+
+fn create(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).write(true).truncate(true).open(path)
+}
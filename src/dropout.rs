@@ -0,0 +1,61 @@
+use rand_distr::{Distribution, Exp};
+
+/// What a plane's coverage looks like at a given tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Covered,
+    /// Out of coverage, no rows reach the server
+    Dark,
+    /// First tick back in coverage after a gap
+    Resumed,
+}
+
+/// Random coverage gaps of one plane. Time between gaps and gap lengths are exponentially
+/// distributed, like aircraft flying in and out of receiver range.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    between_gaps: Exp<f64>,
+    gap_length: Exp<f64>,
+    next_gap: i64,
+    gap_ends: Option<i64>,
+    last_gap_ended: Option<i64>,
+}
+
+/// How long before and after a gap the signal is weak
+const MARGINAL_NANOS: i64 = 5_000_000_000;
+
+fn sample_nanos(distribution: &Exp<f64>) -> i64 {
+    (distribution.sample(&mut rand::thread_rng()) * 1e9) as i64
+}
+
+impl Coverage {
+    pub fn new(every_secs: f64, length_secs: f64, now: i64) -> Self {
+        let between_gaps = Exp::new(1.0 / every_secs).unwrap();
+        let gap_length = Exp::new(1.0 / length_secs).unwrap();
+        let next_gap = now + sample_nanos(&between_gaps);
+        Coverage { between_gaps, gap_length, next_gap, gap_ends: None, last_gap_ended: None }
+    }
+
+    pub fn check(&mut self, now: i64) -> Signal {
+        if let Some(gap_ends) = self.gap_ends {
+            if now < gap_ends {
+                return Signal::Dark;
+            }
+            self.gap_ends = None;
+            self.last_gap_ended = Some(now);
+            self.next_gap = now + sample_nanos(&self.between_gaps);
+            return Signal::Resumed;
+        }
+        if now >= self.next_gap {
+            self.gap_ends = Some(now + sample_nanos(&self.gap_length));
+            return Signal::Dark;
+        }
+        Signal::Covered
+    }
+
+    /// Whether `now`, in coverage, is close enough to a gap for the signal to be weak.
+    pub fn is_marginal(&self, now: i64) -> bool {
+        self.gap_ends.is_none()
+            && (self.next_gap - now <= MARGINAL_NANOS || self.last_gap_ended.map(|ended| now - ended < MARGINAL_NANOS).unwrap_or(false))
+    }
+}
@@ -0,0 +1,32 @@
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Why a simulation could not be set up or run. The parts of the run keep their own
+/// error messages, this sorts them by where they come from so `main` can tell a
+/// misconfiguration from a server that is not there.
+#[derive(Debug, Error)]
+pub enum SimError {
+    /// An ILP or HTTP call to QuestDB
+    #[error("{}", .0.msg())]
+    Questdb(#[from] questdb::Error),
+    /// A file the run reads or writes
+    #[error("Cannot {what} {}: {source}", .path.display())]
+    Io { what: &'static str, path: PathBuf, source: io::Error },
+    /// The --config file or the plane ids it asks for
+    #[error("{0}")]
+    Config(String),
+    /// Options that do not make sense together, one message each
+    #[error("{}", .0.iter().map(|error| format!("Error: {}", error)).collect::<Vec<_>>().join("\n"))]
+    Validation(Vec<String>),
+    /// A part of the run that could not start, such as the preflight or a background task
+    #[error("{0}")]
+    Setup(String),
+}
+
+impl SimError {
+    pub fn io(what: &'static str, path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> SimError {
+        let path = path.into();
+        move |source| SimError::Io { what, path, source }
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Aggregates repeated errors so a failing server does not flood stderr: the first error
+/// of each kind is printed in full, the following ones are counted and printed as one
+/// line per message every `--error-summary-secs`.
+#[derive(Debug)]
+pub struct ErrorSummary {
+    every: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    window_started: Instant,
+    /// Errors since the last summary, by kind and message
+    window: BTreeMap<(&'static str, String), Count>,
+    totals: BTreeMap<(&'static str, String), Count>,
+}
+
+#[derive(Debug, Clone)]
+struct Count {
+    count: u64,
+    /// Message as first seen, on one line. The key has its numbers masked
+    message: String,
+}
+
+impl ErrorSummary {
+    pub fn new(every: Duration) -> Self {
+        ErrorSummary {
+            every,
+            state: Mutex::new(State { window_started: Instant::now(), window: BTreeMap::new(), totals: BTreeMap::new() }),
+        }
+    }
+
+    /// Counts an error of a kind such as "flush failures". `context` says where it happened
+    /// and is only printed for the first one, or for every one when summaries are disabled.
+    pub fn record(&self, kind: &'static str, context: &str, message: &str) {
+        let line = message.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+        let key = (kind, mask_numbers(&line));
+        let mut state = self.state.lock().unwrap();
+        let total = state.totals.entry(key.clone()).or_insert_with(|| Count { count: 0, message: line.clone() });
+        total.count += 1;
+        if total.count == 1 || self.every.is_zero() {
+            eprintln!("{}: {}", context, message);
+        } else {
+            state.window.entry(key).or_insert(Count { count: 0, message: line }).count += 1;
+        }
+        if state.window_started.elapsed() >= self.every {
+            print_window(&mut state, self.every);
+        }
+    }
+
+    /// Prints what is left of the current window, then the totals of the run.
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = state.window_started.elapsed();
+        print_window(&mut state, elapsed);
+        for ((kind, _), total) in state.totals.iter() {
+            eprintln!("{} {} in total: {}", thousands(total.count), kind, total.message);
+        }
+    }
+}
+
+fn print_window(state: &mut State, elapsed: Duration) {
+    for ((kind, _), window) in state.window.iter() {
+        eprintln!("{} {} in last {:.0}s: {}", thousands(window.count), kind, elapsed.as_secs_f64(), window.message);
+    }
+    state.window.clear();
+    state.window_started = Instant::now();
+}
+
+/// Replaces every number with `#`, as line numbers and the like would otherwise make
+/// every message unique
+fn mask_numbers(message: &str) -> String {
+    let mut masked = String::with_capacity(message.len());
+    for c in message.chars() {
+        if !c.is_ascii_digit() {
+            masked.push(c);
+        } else if !masked.ends_with('#') {
+            masked.push('#');
+        }
+    }
+    masked
+}
+
+/// Formats a count with a comma every three digits, e.g. 1,243
+pub fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
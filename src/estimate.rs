@@ -0,0 +1,93 @@
+use questdb::ingress::{Buffer, ProtocolVersion};
+
+use crate::Opt;
+use crate::config::Columns;
+use crate::error_summary::thousands;
+use crate::plane::{PlaneData, Variations};
+use crate::sensors::SensorSchedule;
+use crate::spectrum::Spectrum;
+
+/// Rows generated to measure the ILP bytes per row
+const SAMPLE_ROWS: u64 = 1000;
+
+/// ILP bytes per row of one plane over `SAMPLE_ROWS` ticks, with the sensor groups that
+/// have a reading at each tick.
+fn bytes_per_row(opt: &Opt, columns: &Columns) -> Result<f64, String> {
+    let tick_nanos = opt.tick_millis() as i64 * 1_000_000;
+    let started_at = chrono::Utc::now().timestamp_nanos_opt().unwrap();
+    let mut plane = PlaneData::new("estimate".to_string(), "000000".to_string(), opt.flight_leg_secs, opt.turbulence, started_at, rand::random());
+    let mut sensors = SensorSchedule::new(columns.group_rates(), started_at);
+    // Arrays need version 2, that servers taking them offer
+    let version = opt.protocol_version.unwrap_or(match opt.spectrum_bins {
+        Some(_) => ProtocolVersion::V2,
+        None => ProtocolVersion::V1,
+    });
+    plane.spectrum = opt.spectrum_bins.filter(|_| version != ProtocolVersion::V1).map(|bins| Spectrum::new(bins, 0));
+    let mut buffer = Buffer::new(version);
+    for tick in 1..=SAMPLE_ROWS as i64 {
+        plane.update(started_at + tick * tick_nanos);
+        let due = sensors.due(plane.timestamp);
+        plane.write_sensors(&mut buffer, &opt.table_name, columns, opt.schema, due, Variations::default()).map_err(|e| e.to_string())?;
+    }
+    Ok(buffer.len() as f64 / SAMPLE_ROWS as f64)
+}
+
+fn duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{}s", m, s),
+        (h, m, s) => format!("{}h{}m{}s", h, m, s),
+    }
+}
+
+pub fn size(bytes: f64) -> String {
+    let units = ["KiB", "MiB", "GiB", "TiB"];
+    let (mut value, mut unit) = (bytes / 1024.0, 0);
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
+/// Prints what a run with these options would take and send, for --estimate, without
+/// connecting to the server. Rates are the ones the ticks allow, a busy machine or
+/// server may not reach them.
+pub fn print(opt: &Opt, columns: &Columns) {
+    let plane_rate = 1000.0 / opt.tick_millis() as f64;
+    let rate = plane_rate * opt.plane_count as f64;
+    let by_rows = opt.total_rows_arg.map(|rows| rows as f64 / rate);
+    let secs = match (by_rows, opt.duration) {
+        (Some(by_rows), Some(duration)) => by_rows.min(duration),
+        (by_rows, duration) => by_rows.or(duration).unwrap_or(0.0),
+    };
+    let rows = match by_rows {
+        Some(by_rows) if by_rows <= secs => opt.total_rows as f64,
+        _ => (secs * rate).floor(),
+    };
+    println!("{} planes at {:.1} rows/s each, {:.1} rows/s in total", opt.plane_count, plane_rate, rate);
+    let stopped_by = if by_rows.is_some_and(|by_rows| by_rows <= secs) { "--total-rows" } else { "--duration" };
+    println!("{} rows in {}, when {} is reached", thousands(rows as u64), duration(secs), stopped_by);
+    if opt.warmup_secs > 0 {
+        println!("The first {} are warm-up and not measured", duration(opt.warmup_secs as f64));
+    }
+    if opt.mimic_table.is_some() {
+        println!("Row sizes depend on the schema of --mimic-table, not estimated");
+        return;
+    }
+    match bytes_per_row(opt, columns) {
+        Ok(per_row) => {
+            println!("{:.1} bytes per row over ILP, {} in total, {}/s", per_row, size(per_row * rows), size(per_row * rate));
+            let batch_rows = opt.auto_flush_rows.unwrap_or(opt.batch_size as u64).min(rows.max(1.0) as u64) as f64;
+            if opt.auto_flush_interval_ms.is_none() {
+                println!("About {} flushes of {} rows, {} each", thousands((rows / batch_rows).ceil() as u64),
+                         thousands(batch_rows as u64), size(per_row * batch_rows));
+            }
+        }
+        Err(e) => eprintln!("Cannot estimate the row size: {}", e),
+    }
+    if opt.companion_table.is_some() || opt.status_table.is_some() {
+        println!("Rows of --companion-table and --status-table come on top and are not estimated");
+    }
+}
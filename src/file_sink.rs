@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::{Chars, FromStr};
+use std::sync::Mutex;
+use flate2::write::GzEncoder;
+use futures::future::BoxFuture;
+use lz4_flex::frame::FrameEncoder;
+use questdb::{Error, ErrorCode, Result, ingress::Buffer};
+use tokio::time::sleep;
+
+#[cfg(feature = "object-store")]
+use crate::bucket::{Bucket, Upload};
+use crate::network::Network;
+use crate::rate_limit::{RateLimit, RateLimits};
+use crate::sink::{Sink, SinkStats};
+
+/// A row read back from the ILP text of a buffer: its table, its symbols and columns as
+/// text in the order they were written, and its designated timestamp, none when the server
+/// would have stamped it.
+#[derive(Debug, PartialEq)]
+struct Row {
+    table: String,
+    values: Vec<(String, String)>,
+    timestamp: Option<i64>,
+}
+
+/// How the files of the `csv` sink are compressed, with --sink-compression
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            "lz4" => Ok(Compression::Lz4),
+            _ => Err(format!("invalid compression '{}', expected none, gzip, zstd or lz4", s)),
+        }
+    }
+}
+
+impl Compression {
+    fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+            Compression::Lz4 => ".lz4",
+        }
+    }
+}
+
+/// When the `csv` sink moves on to the next file of a table: once a file holds
+/// --sink-rotate-size bytes of CSV, and for each --sink-rotate-every window of the
+/// timestamps of the rows.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rotation {
+    pub size: Option<u64>,
+    pub every_secs: Option<u64>,
+}
+
+/// Where the files of the `csv` sink go: a directory, or a bucket with the object-store feature
+pub enum Target {
+    Dir(PathBuf),
+    #[cfg(feature = "object-store")]
+    Bucket(Bucket),
+}
+
+impl Target {
+    /// Where a file goes, for the messages
+    fn location(&self, name: &str) -> String {
+        match self {
+            Target::Dir(dir) => dir.join(name).display().to_string(),
+            #[cfg(feature = "object-store")]
+            Target::Bucket(bucket) => bucket.location(name),
+        }
+    }
+
+    fn create(&self, name: &str) -> io::Result<Box<dyn Destination>> {
+        Ok(match self {
+            Target::Dir(dir) => Box::new(BufWriter::new(File::create(dir.join(name))?)),
+            #[cfg(feature = "object-store")]
+            Target::Bucket(bucket) => Box::new(bucket.create(name)?),
+        })
+    }
+}
+
+/// The bytes of a file, once compressed, on their way to the target
+trait Destination: Write + Send {
+    /// Writes what is left, after which the file is complete
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl Destination for BufWriter<File> {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(feature = "object-store")]
+impl Destination for Upload {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Upload::finish(*self)
+    }
+}
+
+/// A file being written, through its compression
+enum Output {
+    Plain(Box<dyn Destination>),
+    Gzip(GzEncoder<Box<dyn Destination>>),
+    Zstd(zstd::Encoder<'static, Box<dyn Destination>>),
+    Lz4(FrameEncoder<Box<dyn Destination>>),
+}
+
+impl Output {
+    fn create(target: &Target, name: &str, compression: Compression) -> io::Result<Self> {
+        let file = target.create(name)?;
+        Ok(match compression {
+            Compression::None => Output::Plain(file),
+            Compression::Gzip => Output::Gzip(GzEncoder::new(file, flate2::Compression::default())),
+            Compression::Zstd => Output::Zstd(zstd::Encoder::new(file, 0)?),
+            Compression::Lz4 => Output::Lz4(FrameEncoder::new(file)),
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Output::Plain(out) => out,
+            Output::Gzip(out) => out,
+            Output::Zstd(out) => out,
+            Output::Lz4(out) => out,
+        }
+    }
+
+    /// Ends the compressed stream and writes what is left to the file
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Plain(out) => out.finish(),
+            Output::Gzip(out) => out.finish()?.finish(),
+            Output::Zstd(out) => out.finish()?.finish(),
+            Output::Lz4(out) => out.finish().map_err(io::Error::other)?.finish(),
+        }
+    }
+}
+
+/// The file a table is written to, with the columns of its header
+struct TableFile {
+    /// Where it goes, for the messages
+    path: String,
+    columns: Vec<String>,
+    /// Start of the --sink-rotate-every window of its rows, in nanoseconds
+    window: Option<i64>,
+    /// Bytes of CSV written to it, before compression
+    written: u64,
+    out: Output,
+}
+
+#[derive(Default)]
+struct Files {
+    open: HashMap<String, TableFile>,
+    /// Files opened so far for each table and window, to number the next one
+    parts: HashMap<(String, Option<i64>), usize>,
+}
+
+/// The `csv` sink: a CSV file for each table in the --sink-path directory, `<table>.csv`,
+/// with a header of the symbols and columns of its first row and the designated timestamp
+/// last. A row with a column the header does not have starts the next file of the table,
+/// `<table>.1.csv` and so on, with all the columns so far; the columns a row leaves out are
+/// empty. With --sink-rotate-size a file also ends once it holds that much CSV, and with
+/// --sink-rotate-every the rows of each window of time go to files of their own, with the
+/// start of the window in their name, `<table>.20240101T000000Z.csv`; a row back in a window
+/// already left starts another file of it. --sink-compression adds `.gz`, `.zst` or `.lz4`.
+/// Files of an earlier run with the same names are replaced. When --sink-path is a bucket,
+/// each file is a multipart upload, complete once the file ends.
+pub struct FileSink {
+    target: Target,
+    compression: Compression,
+    rotation: Rotation,
+    /// --max-rate-per-conn, as if the files were a single connection
+    limit: Option<RateLimit>,
+    files: Mutex<Files>,
+    stats: Mutex<SinkStats>,
+}
+
+impl FileSink {
+    pub fn new(target: Target, compression: Compression, rotation: Rotation, limits: &RateLimits) -> Self {
+        FileSink {
+            target,
+            compression,
+            rotation,
+            limit: limits.for_connection(),
+            files: Mutex::new(Files::default()),
+            stats: Mutex::new(SinkStats::default()),
+        }
+    }
+
+    fn name(&self, table: &str, window: Option<i64>, part: usize) -> String {
+        let mut name = table.to_string();
+        if let Some(window) = window {
+            name += &chrono::DateTime::from_timestamp_nanos(window).format(".%Y%m%dT%H%M%SZ").to_string();
+        }
+        if part > 0 {
+            name += &format!(".{}", part);
+        }
+        format!("{}.csv{}", name, self.compression.extension())
+    }
+
+    /// Start of the --sink-rotate-every window of a row
+    fn window(&self, timestamp: Option<i64>) -> Option<i64> {
+        self.rotation.every_secs.map(|every| {
+            let at = timestamp.unwrap_or_else(|| chrono::Utc::now().timestamp_nanos_opt().unwrap());
+            at - at.rem_euclid(every as i64 * 1_000_000_000)
+        })
+    }
+
+    /// Whether a row goes to the file open for its table
+    fn fits(&self, file: &TableFile, row: &Row, window: Option<i64>) -> bool {
+        file.window == window
+            && self.rotation.size.is_none_or(|size| file.written < size)
+            && row.values.iter().all(|(name, _)| file.columns.contains(name))
+    }
+
+    /// Ends the file open for the table of `row`, if any, and opens the next one, with the
+    /// columns so far and those of the row.
+    fn rotate(&self, files: &mut Files, row: &Row, window: Option<i64>) -> Result<()> {
+        let mut columns = vec![];
+        if let Some(TableFile { path, columns: kept, out, .. }) = files.open.remove(&row.table) {
+            columns = kept;
+            out.finish().map_err(|e| io_error(&path, e))?;
+        }
+        for (name, _) in &row.values {
+            if !columns.contains(name) {
+                columns.push(name.clone());
+            }
+        }
+        let part = files.parts.entry((row.table.clone(), window)).or_insert(0);
+        let name = self.name(&row.table, window, *part);
+        let path = self.target.location(&name);
+        *part += 1;
+        let header: Vec<String> = columns.iter().map(|column| quote(column)).chain(std::iter::once("timestamp".to_string())).collect();
+        let header = header.join(",") + "\n";
+        let mut out = Output::create(&self.target, &name, self.compression).map_err(|e| io_error(&path, e))?;
+        out.writer().write_all(header.as_bytes()).map_err(|e| io_error(&path, e))?;
+        files.open.insert(row.table.clone(), TableFile { path, columns, window, written: header.len() as u64, out });
+        Ok(())
+    }
+
+    /// Writes the rows of the buffer, returning the bytes of CSV written.
+    fn write_rows(&self, buffer: &Buffer) -> Result<u64> {
+        let text = std::str::from_utf8(buffer.as_bytes()).map_err(|e| invalid(format!("the buffer is not ILP text: {}", e)))?;
+        let rows = rows(text).map_err(invalid)?;
+        let mut files = self.files.lock().unwrap();
+        let mut bytes = 0;
+        for row in rows {
+            let window = self.window(row.timestamp);
+            if !files.open.get(&row.table).is_some_and(|file| self.fits(file, &row, window)) {
+                self.rotate(&mut files, &row, window)?;
+            }
+            let file = files.open.get_mut(&row.table).unwrap();
+            let mut line: Vec<String> = file.columns.iter()
+                .map(|column| row.values.iter().find(|(name, _)| name == column).map(|(_, value)| quote(value)).unwrap_or_default())
+                .collect();
+            line.push(row.timestamp.map(timestamp).unwrap_or_default());
+            let line = line.join(",") + "\n";
+            file.out.writer().write_all(line.as_bytes()).map_err(|e| io_error(&file.path, e))?;
+            file.written += line.len() as u64;
+            bytes += line.len() as u64;
+        }
+        Ok(bytes)
+    }
+
+    async fn write(&self, buffer: &mut Buffer, limits: &RateLimits, network: &Network) -> Result<()> {
+        sleep(limits.reserve_conn(self.limit.as_ref(), buffer.row_count())).await;
+        sleep(network.delay(buffer.len())).await;
+        let rows = buffer.row_count() as u64;
+        let result = self.write_rows(buffer);
+        let mut stats = self.stats.lock().unwrap();
+        match result {
+            Ok(bytes) => {
+                stats.batches += 1;
+                stats.rows += rows;
+                stats.bytes += bytes;
+                buffer.clear();
+                Ok(())
+            }
+            Err(e) => {
+                stats.failed_batches += 1;
+                Err(e)
+            }
+        }
+    }
+
+    fn flush_files(&self) -> Result<()> {
+        for file in self.files.lock().unwrap().open.values_mut() {
+            file.out.writer().flush().map_err(|e| io_error(&file.path, e))?;
+        }
+        Ok(())
+    }
+
+    /// Ends every file, once the run has written its last batch
+    fn finish(&self) {
+        let open = std::mem::take(&mut self.files.lock().unwrap().open);
+        for file in open.into_values() {
+            if let Err(e) = file.out.finish() {
+                eprintln!("{}", io_error(&file.path, e).msg());
+            }
+        }
+    }
+}
+
+impl Sink for FileSink {
+    fn write_batch<'a>(&'a self, buffer: &'a mut Buffer, limits: &'a RateLimits, network: &'a Network) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.write(buffer, limits, network))
+    }
+
+    fn flush(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move { self.flush_files() })
+    }
+
+    fn close(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move { self.finish() })
+    }
+
+    fn stats(&self) -> SinkStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+/// A batch the sink cannot write, which trying again would not change
+fn invalid(message: String) -> Error {
+    Error::new(ErrorCode::ServerFlushError, message)
+}
+
+fn io_error(path: &str, e: io::Error) -> Error {
+    invalid(format!("cannot write {}: {}", path, e))
+}
+
+/// A field of a CSV line, quoted when it has a comma, a quote or a line break
+fn quote(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+fn timestamp(nanos: i64) -> String {
+    chrono::DateTime::from_timestamp_nanos(nanos).format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string()
+}
+
+/// The rows of ILP text, protocol version 1
+fn rows(text: &str) -> std::result::Result<Vec<Row>, String> {
+    let mut chars = text.chars().peekable();
+    let mut rows = vec![];
+    while chars.peek().is_some() {
+        let (table, mut end) = token(&mut chars, &[',', ' '], false);
+        let mut values = vec![];
+        while end == Some(',') {
+            let (name, _) = token(&mut chars, &['='], false);
+            let (value, next) = token(&mut chars, &[',', ' '], false);
+            values.push((name, value));
+            end = next;
+        }
+        if end != Some(' ') {
+            return Err(format!("a row of table '{}' has no columns", table));
+        }
+        loop {
+            let (name, _) = token(&mut chars, &['='], false);
+            let (value, next) = token(&mut chars, &[',', ' ', '\n'], true);
+            let value = field(&value).ok_or_else(|| format!("invalid value '{}' of column '{}' in table '{}'", value, name, table))?;
+            values.push((name, value));
+            end = next;
+            if end != Some(',') {
+                break;
+            }
+        }
+        let timestamp = match end {
+            Some(' ') => {
+                let (at, _) = token(&mut chars, &['\n'], false);
+                Some(at.parse().map_err(|_| format!("invalid timestamp '{}' in table '{}'", at, table))?)
+            }
+            _ => None,
+        };
+        rows.push(Row { table, values, timestamp });
+    }
+    Ok(rows)
+}
+
+/// Reads up to the first of `ends` that is not escaped, nor between quotes when `quoted`,
+/// and returns what it read without the escapes, and the end it stopped at.
+fn token(chars: &mut Peekable<Chars>, ends: &[char], quoted: bool) -> (String, Option<char>) {
+    let (mut token, mut in_quotes) = (String::new(), false);
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => token.extend(chars.next()),
+            '"' if quoted => {
+                in_quotes = !in_quotes;
+                token.push(c);
+            }
+            c if !in_quotes && ends.contains(&c) => return (token, Some(c)),
+            c => token.push(c),
+        }
+    }
+    (token, None)
+}
+
+/// The text of an ILP column value: strings without their quotes, integers without their
+/// `i`, timestamps as dates
+fn field(value: &str) -> Option<String> {
+    if let Some(text) = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) {
+        return Some(text.to_string());
+    }
+    match value {
+        "t" | "T" | "true" | "True" | "TRUE" => return Some("true".to_string()),
+        "f" | "F" | "false" | "False" | "FALSE" => return Some("false".to_string()),
+        _ => {}
+    }
+    if let Some(integer) = value.strip_suffix('i') {
+        return integer.parse::<i64>().ok().map(|_| integer.to_string());
+    }
+    if let Some(micros) = value.strip_suffix('t') {
+        return micros.parse::<i64>().ok().map(|micros| timestamp(micros.saturating_mul(1000)));
+    }
+    value.parse::<f64>().ok().map(|_| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use questdb::ingress::{ProtocolVersion, TimestampMicros, TimestampNanos};
+
+    #[test]
+    fn ilp_rows_are_read_back() {
+        let mut buffer = Buffer::new(ProtocolVersion::V1);
+        buffer.table("flights").unwrap().symbol("plane_id", "AA 00,\"x\"").unwrap()
+            .column_f64("altitude", 31000.5).unwrap().column_i64("legs", 3).unwrap()
+            .column_str("note", "a \"quoted\",\nline").unwrap().column_bool("late", true).unwrap()
+            .column_ts("landed", TimestampMicros::new(1_000_000)).unwrap()
+            .at(TimestampNanos::new(1_500_000_000)).unwrap();
+        buffer.table("legs").unwrap().column_f64("distance", 12.0).unwrap().at_now().unwrap();
+        let rows = rows(std::str::from_utf8(buffer.as_bytes()).unwrap()).unwrap();
+        let values = |pairs: &[(&str, &str)]| pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        assert_eq!(rows, [
+            Row {
+                table: "flights".to_string(),
+                values: values(&[("plane_id", "AA 00,\"x\""), ("altitude", "31000.5"), ("legs", "3"), ("note", "a \"quoted\",\nline"),
+                                 ("late", "true"), ("landed", "1970-01-01T00:00:01.000000Z")]),
+                timestamp: Some(1_500_000_000),
+            },
+            Row { table: "legs".to_string(), values: values(&[("distance", "12.0")]), timestamp: None },
+        ]);
+        assert_eq!(quote(&rows[0].values[3].1), "\"a \"\"quoted\"\",\nline\"");
+    }
+
+    #[test]
+    fn files_rotate_by_window_and_size_in_every_compression() {
+        let dir = std::env::temp_dir().join(format!("airplanes-file-sink-{}", std::process::id()));
+        for (compression, decode) in [
+            (Compression::Gzip, (|file| Box::new(flate2::read::GzDecoder::new(file))) as fn(File) -> Box<dyn io::Read>),
+            (Compression::Zstd, |file| Box::new(zstd::Decoder::new(file).unwrap())),
+            (Compression::Lz4, |file| Box::new(lz4_flex::frame::FrameDecoder::new(file))),
+        ] {
+            std::fs::create_dir_all(&dir).unwrap();
+            let rotation = Rotation { size: Some(60), every_secs: Some(3600) };
+            let sink = FileSink::new(Target::Dir(dir.clone()), compression, rotation, &RateLimits::new(None, None));
+            let mut buffer = Buffer::new(ProtocolVersion::V1);
+            // Three rows in the first hour, the third past the size, and one in the next
+            for (n, at) in [0i64, 60, 120, 3600].iter().enumerate() {
+                buffer.table("t").unwrap().symbol("id", "A").unwrap().column_i64("n", n as i64).unwrap()
+                    .at(TimestampNanos::new(at * 1_000_000_000)).unwrap();
+            }
+            sink.write_rows(&buffer).unwrap();
+            sink.finish();
+            let read = |name: &str| {
+                let mut text = String::new();
+                decode(File::open(dir.join(format!("{}{}", name, compression.extension()))).unwrap()).read_to_string(&mut text).unwrap();
+                text
+            };
+            assert_eq!(read("t.19700101T000000Z.csv"),
+                       "id,n,timestamp\nA,0,1970-01-01T00:00:00.000000Z\nA,1,1970-01-01T00:01:00.000000Z\n");
+            assert_eq!(read("t.19700101T000000Z.1.csv"), "id,n,timestamp\nA,2,1970-01-01T00:02:00.000000Z\n");
+            assert_eq!(read("t.19700101T010000Z.csv"), "id,n,timestamp\nA,3,1970-01-01T01:00:00.000000Z\n");
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
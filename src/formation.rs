@@ -0,0 +1,61 @@
+use std::f64::consts::PI;
+
+/// Turbulence events shared by groups of planes, for --formations. Every plane belongs to
+/// one of the formations, and all the planes of a formation go through the same events at
+/// the same time, giving series that are correlated across planes. The events are a pure
+/// function of the formation and the timestamp, so planes on different tasks agree on them
+/// without talking to each other, and a backfill gets the same events as a live run.
+#[derive(Debug, Clone)]
+pub struct Formations {
+    count: u32,
+    /// Each formation has one event in every window of this length, at a random offset
+    every_nanos: i64,
+    event_nanos: i64,
+    intensity: f64,
+}
+
+/// SplitMix64, to draw the events of a window from the formation and window numbers
+pub fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+impl Formations {
+    pub fn new(count: u32, every_secs: f64, event_secs: f64, intensity: f64) -> Self {
+        Formations {
+            count: count.max(1),
+            every_nanos: (every_secs * 1e9) as i64,
+            event_nanos: (event_secs * 1e9) as i64,
+            intensity,
+        }
+    }
+
+    /// Formation of a plane, spread evenly over the plane ids
+    pub fn of(&self, plane_id: &str) -> u32 {
+        let hash = plane_id.bytes().fold(0u64, |hash, byte| mix(hash ^ byte as u64));
+        (hash % self.count as u64) as u32
+    }
+
+    pub fn label(formation: u32) -> String {
+        format!("F{:02}", formation)
+    }
+
+    /// How hard the formation is shaken at `timestamp`, between -intensity and intensity:
+    /// 0 outside of its events, and within one an oscillation of a few seconds that
+    /// swells and dies down.
+    pub fn shock(&self, formation: u32, timestamp: i64) -> f64 {
+        let window = timestamp.div_euclid(self.every_nanos);
+        let seed = mix(((formation as u64) << 40) ^ window as u64);
+        let slack = (self.every_nanos - self.event_nanos).max(0) as u64;
+        let start = window * self.every_nanos + (seed % (slack + 1)) as i64;
+        let into = timestamp - start;
+        if into < 0 || into >= self.event_nanos {
+            return 0.0;
+        }
+        let period_secs = 1.5 + ((seed >> 32) % 3000) as f64 / 1000.0;
+        let envelope = (PI * into as f64 / self.event_nanos as f64).sin().powi(2);
+        self.intensity * envelope * (2.0 * PI * into as f64 / 1e9 / period_secs).sin()
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use serde_json::{json, Value};
+use clap::Parser;
+
+use crate::config::{Config, Columns};
+
+/// Grafana data source the panels query QuestDB through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DatasourceType {
+    /// The QuestDB plugin
+    QuestDb,
+    /// The built-in PostgreSQL data source over the PG wire protocol
+    Postgres,
+}
+
+impl DatasourceType {
+    pub fn plugin_id(&self) -> &'static str {
+        match self {
+            DatasourceType::QuestDb => "questdb-questdb-datasource",
+            DatasourceType::Postgres => "grafana-postgresql-datasource",
+        }
+    }
+}
+
+impl FromStr for DatasourceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "questdb" => Ok(DatasourceType::QuestDb),
+            "postgres" => Ok(DatasourceType::Postgres),
+            _ => Err(format!("invalid datasource type '{}', expected questdb or postgres", s)),
+        }
+    }
+}
+
+/// Options of `airplanes grafana-dashboard`.
+#[derive(Parser, Debug)]
+pub struct DashboardOpt {
+    /// Table the simulator writes to
+    #[arg(long)]
+    table_name: String,
+
+    /// UID of the Grafana data source pointing to QuestDB
+    #[arg(long)]
+    datasource_uid: String,
+
+    /// `questdb` for the QuestDB plugin, `postgres` for the PostgreSQL data source
+    #[arg(long, default_value = "questdb")]
+    datasource_type: DatasourceType,
+
+    #[arg(long, default_value = "Airplanes simulator")]
+    title: String,
+
+    /// Config file used for the runs, so renamed columns are queried by their new name
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Write the dashboard to this file instead of the standard output
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+/// Writes a dashboard with airspeed, altitude, a map of the planes and ingestion rates
+/// per fleet, ready to import or provision.
+pub fn run(opt: &DashboardOpt) -> Result<(), String> {
+    let config = match &opt.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let columns = Columns::new(&config)?;
+    let text = serde_json::to_string_pretty(&dashboard(opt, &columns)).map_err(|e| e.to_string())?;
+    match &opt.output {
+        Some(path) => {
+            std::fs::write(path, text + "\n").map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+            eprintln!("Dashboard written to {}", path.display());
+        }
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn dashboard(opt: &DashboardOpt, columns: &Columns) -> Value {
+    let table = quote_identifier(&opt.table_name);
+    let column = |name: &str| quote_identifier(columns.name(name));
+    let time_range = "$__timeFilter(timestamp)";
+    let airspeed = format!(
+        "SELECT timestamp, avg({c}) avg_airspeed, min({c}) min_airspeed, max({c}) max_airspeed FROM {t} WHERE {f} SAMPLE BY $__interval",
+        c = column("airspeed"), t = table, f = time_range);
+    let altitude = format!(
+        "SELECT timestamp, avg({c}) avg_altitude, max({c}) max_altitude FROM {t} WHERE {f} SAMPLE BY $__interval",
+        c = column("altitude"), t = table, f = time_range);
+    let positions = format!(
+        "SELECT {id} plane_id, {cs} callsign, {lat} latitude, {lon} longitude, {alt} altitude FROM {t} WHERE {f} LATEST ON timestamp PARTITION BY {id}",
+        id = column("plane_id"), cs = column("callsign"), lat = column("latitude"), lon = column("longitude"),
+        alt = column("altitude"), t = table, f = time_range);
+    // The airline code at the start of the callsign tells the fleet
+    let fleet_rates = format!(
+        "SELECT timestamp, left({cs}, 3) fleet, count() * 1000.0 / $__interval_ms rows_per_sec FROM {t} WHERE {f} SAMPLE BY $__interval ORDER BY timestamp",
+        cs = column("callsign"), t = table, f = time_range);
+    let total_rate = format!(
+        "SELECT timestamp, count() * 1000.0 / $__interval_ms rows_per_sec FROM {t} WHERE {f} SAMPLE BY $__interval",
+        t = table, f = time_range);
+
+    let datasource = json!({ "type": opt.datasource_type.plugin_id(), "uid": opt.datasource_uid });
+    json!({
+        "title": opt.title,
+        "uid": format!("airplanes-{}", opt.table_name.to_lowercase().replace(|c: char| !c.is_ascii_alphanumeric(), "-")),
+        "tags": ["airplanes", "questdb"],
+        "timezone": "browser",
+        "schemaVersion": 39,
+        "refresh": "5s",
+        "time": { "from": "now-15m", "to": "now" },
+        "panels": [
+            time_series_panel(1, "Ingestion rate (rows/s)", &datasource, opt.datasource_type, &total_rate, [0, 0, 12, 8]),
+            time_series_panel(2, "Ingestion rate per fleet (rows/s)", &datasource, opt.datasource_type, &fleet_rates, [12, 0, 12, 8]),
+            time_series_panel(3, "Airspeed", &datasource, opt.datasource_type, &airspeed, [0, 8, 12, 8]),
+            time_series_panel(4, "Altitude", &datasource, opt.datasource_type, &altitude, [12, 8, 12, 8]),
+            map_panel(5, "Planes", &datasource, opt.datasource_type, &positions, [0, 16, 24, 14]),
+        ],
+    })
+}
+
+fn target(datasource: &Value, datasource_type: DatasourceType, sql: &str, time_series: bool) -> Value {
+    match datasource_type {
+        DatasourceType::QuestDb => json!({
+            "refId": "A",
+            "datasource": datasource,
+            "queryType": "sql",
+            "rawSql": sql,
+            "format": if time_series { 0 } else { 1 },
+        }),
+        DatasourceType::Postgres => json!({
+            "refId": "A",
+            "datasource": datasource,
+            "editorMode": "code",
+            "rawQuery": true,
+            "rawSql": sql,
+            "format": if time_series { "time_series" } else { "table" },
+        }),
+    }
+}
+
+fn grid_pos([x, y, w, h]: [u32; 4]) -> Value {
+    json!({ "x": x, "y": y, "w": w, "h": h })
+}
+
+pub fn time_series_panel(id: u32, title: &str, datasource: &Value, datasource_type: DatasourceType, sql: &str, pos: [u32; 4]) -> Value {
+    json!({
+        "id": id,
+        "type": "timeseries",
+        "title": title,
+        "datasource": datasource,
+        "gridPos": grid_pos(pos),
+        // SAMPLE BY does not take sub-second Grafana intervals such as 500ms
+        "interval": "1s",
+        "targets": [target(datasource, datasource_type, sql, true)],
+    })
+}
+
+pub fn map_panel(id: u32, title: &str, datasource: &Value, datasource_type: DatasourceType, sql: &str, pos: [u32; 4]) -> Value {
+    json!({
+        "id": id,
+        "type": "geomap",
+        "title": title,
+        "datasource": datasource,
+        "gridPos": grid_pos(pos),
+        "targets": [target(datasource, datasource_type, sql, false)],
+        "options": {
+            "view": { "id": "zero", "lat": 0, "lon": 0, "zoom": 1 },
+            "layers": [{
+                "type": "markers",
+                "name": title,
+                "location": { "mode": "coords", "latitude": "latitude", "longitude": "longitude" },
+                "tooltip": true,
+                "config": {
+                    "showLegend": false,
+                    "style": { "size": { "fixed": 5 }, "color": { "fixed": "dark-green" } },
+                },
+            }],
+        },
+    })
+}
@@ -0,0 +1,176 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use rand::Rng;
+
+use crate::estimate::size;
+use crate::quota::RowQuota;
+use crate::self_profile::Sample;
+
+/// What --max-mem-mb and --max-cpu-pct do when the generator goes over them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnLimit {
+    /// Hold back generation until the process is back under the limits
+    Throttle,
+    /// End the run, as if its rows or time were up
+    Abort,
+}
+
+impl FromStr for OnLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "throttle" => Ok(OnLimit::Throttle),
+            "abort" => Ok(OnLimit::Abort),
+            _ => Err(format!("invalid resource limit action '{}', expected throttle or abort", s)),
+        }
+    }
+}
+
+/// Limits on the resident memory and CPU use of the process, so a run with too many
+/// planes or a server that stops taking rows cannot take down a shared host. Checked
+/// against each sample of the self profile, once a second. Throttling over the CPU
+/// limit sends a share of the ticks only, lowered until the process is under it; over
+/// the memory limit no tick gets a row until it is back under.
+#[derive(Debug)]
+pub struct ResourceGuard {
+    max_rss_bytes: Option<u64>,
+    max_cpu_pct: Option<f64>,
+    on_limit: OnLimit,
+    /// Share of the ticks that get their rows, from 0 to 1, as f64 bits
+    share: AtomicU64,
+    /// Seconds with the generation held back
+    throttled_secs: AtomicU64,
+    /// Why the run was aborted
+    aborted: Mutex<Option<String>>,
+}
+
+impl ResourceGuard {
+    pub fn new(max_mem_mb: Option<u64>, max_cpu_pct: Option<f64>, on_limit: OnLimit) -> Self {
+        ResourceGuard {
+            max_rss_bytes: max_mem_mb.map(|mb| mb * 1024 * 1024),
+            max_cpu_pct,
+            on_limit,
+            share: AtomicU64::new(1f64.to_bits()),
+            throttled_secs: AtomicU64::new(0),
+            aborted: Mutex::new(None),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.max_rss_bytes.is_some() || self.max_cpu_pct.is_some()
+    }
+
+    fn share(&self) -> f64 {
+        f64::from_bits(self.share.load(Ordering::Relaxed))
+    }
+
+    /// Whether the tick of a plane is skipped to stay under the limits
+    pub fn holds_back(&self) -> bool {
+        let share = self.share();
+        share < 1.0 && !rand::thread_rng().gen_bool(share.max(0.0))
+    }
+
+    /// What is over the limits in a sample, if anything
+    fn over(&self, sample: &Sample) -> Option<String> {
+        match (self.max_rss_bytes, self.max_cpu_pct) {
+            (Some(max), _) if sample.rss_bytes > max => {
+                Some(format!("{} resident, over --max-mem-mb {}", size(sample.rss_bytes as f64), size(max as f64)))
+            }
+            (_, Some(max)) if sample.cpu_pct > max => Some(format!("{:.0}% CPU, over --max-cpu-pct {}", sample.cpu_pct, max)),
+            _ => None,
+        }
+    }
+
+    /// Throttles generation or ends the run when a sample is over the limits, and lets
+    /// the rate back up a step at a time once it is under them with some room.
+    pub fn check(&self, sample: &Sample, quota: &RowQuota) {
+        let share = self.share();
+        let over = self.over(sample);
+        if let (Some(over), OnLimit::Abort) = (&over, self.on_limit) {
+            let mut aborted = self.aborted.lock().unwrap();
+            if aborted.is_none() {
+                eprintln!("Resource guard: {}, ending the run", over);
+                *aborted = Some(over.clone());
+                quota.end_now();
+            }
+            return;
+        }
+        let memory_over = self.max_rss_bytes.is_some_and(|max| sample.rss_bytes > max);
+        let room = self.max_cpu_pct.map(|max| sample.cpu_pct < 0.9 * max).unwrap_or(true);
+        let next = match (memory_over, &over, self.max_cpu_pct) {
+            (true, _, _) => 0.0,
+            (false, Some(_), Some(max)) => (share * max / sample.cpu_pct).clamp(0.02, 1.0),
+            _ if share < 1.0 && room => (share.max(0.02) * 1.25).min(1.0),
+            _ => share,
+        };
+        if next < 1.0 {
+            self.throttled_secs.fetch_add(1, Ordering::Relaxed);
+        }
+        match (share < 1.0, next < 1.0, &over) {
+            (false, true, Some(over)) if memory_over => eprintln!("Resource guard: {}, generation paused until it is back under", over),
+            (false, true, Some(over)) => eprintln!("Resource guard: {}, generation throttled", over),
+            (true, false, _) => eprintln!("Resource guard: back under the limits, generation at full rate"),
+            _ => {}
+        }
+        self.share.store(next.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Why the run was ended before its time, if it was
+    pub fn aborted(&self) -> Option<String> {
+        self.aborted.lock().unwrap().clone()
+    }
+
+    pub fn print_summary(&self) {
+        let secs = self.throttled_secs.load(Ordering::Relaxed);
+        if secs > 0 {
+            println!("Resource guard held the generation back for {}s to stay under the limits", secs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(cpu_pct: f64, rss_mb: u64) -> Sample {
+        Sample { secs: 0.0, cpu_pct, rss_bytes: rss_mb * 1024 * 1024 }
+    }
+
+    #[test]
+    fn throttles_down_to_the_limits_and_back_up() {
+        let quota = RowQuota::new(1_000, None);
+        let guard = ResourceGuard::new(Some(100), Some(50.0), OnLimit::Throttle);
+        guard.check(&sample(40.0, 50), &quota);
+        assert_eq!(guard.share(), 1.0);
+        assert!(!guard.holds_back());
+        // Twice the CPU limit sends half the ticks
+        guard.check(&sample(100.0, 50), &quota);
+        assert_eq!(guard.share(), 0.5);
+        guard.check(&sample(60.0, 50), &quota);
+        assert!(guard.share() < 0.5);
+        // Over the memory limit nothing is sent, whatever the CPU
+        guard.check(&sample(10.0, 120), &quota);
+        assert_eq!(guard.share(), 0.0);
+        assert!(guard.holds_back());
+        for _ in 0..30 {
+            guard.check(&sample(10.0, 80), &quota);
+        }
+        assert_eq!(guard.share(), 1.0);
+        assert!(guard.throttled_secs.load(Ordering::Relaxed) > 3);
+        assert_eq!(quota.remaining(), 1_000);
+        assert!(guard.aborted().is_none());
+    }
+
+    #[test]
+    fn aborting_ends_the_quota() {
+        let quota = RowQuota::new(1_000, None);
+        let guard = ResourceGuard::new(Some(100), None, OnLimit::Abort);
+        guard.check(&sample(400.0, 99), &quota);
+        assert_eq!(quota.remaining(), 1_000);
+        guard.check(&sample(10.0, 101), &quota);
+        assert_eq!(quota.remaining(), 0);
+        assert!(guard.aborted().unwrap().contains("--max-mem-mb"));
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Instant;
+use questdb::ErrorCode;
+use tokio::sync::Notify;
+use tokio::time::{Duration, sleep};
+
+use crate::RunContext;
+use crate::questdb_http::QueryClient;
+use crate::sink;
+
+/// Tracks whether the server is reachable. A burst of consecutive connection errors
+/// marks it as down; planes then keep buffering rows instead of flushing until the
+/// watcher sees the server answering again.
+#[derive(Debug, Default)]
+pub struct ServerHealth {
+    down: AtomicBool,
+    consecutive_failures: AtomicU32,
+    up: Notify,
+}
+
+impl ServerHealth {
+    pub fn is_down(&self) -> bool {
+        self.down.load(Ordering::SeqCst)
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::SeqCst);
+    }
+
+    /// Counts a failed flush. Returns true when it is the one that marks the server as down.
+    pub fn record_failure(&self, error: &questdb::Error, threshold: u32) -> bool {
+        if threshold == 0 || !matches!(error.code(), ErrorCode::SocketError | ErrorCode::CouldNotResolveAddr) {
+            return false;
+        }
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        failures >= threshold && !self.down.swap(true, Ordering::SeqCst)
+    }
+
+    /// Waits until the server is back up, or for at most `timeout`.
+    pub async fn wait_until_up(&self, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Created before checking, so a notification in between is not missed
+            let up = self.up.notified();
+            if !self.is_down() || tokio::time::timeout_at(deadline, up).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Polls `/ping` while the server is marked as down and resumes ingestion once it
+/// answers, reconnecting the senders whose connection is unusable.
+pub async fn watch(ctx: Arc<RunContext>) {
+    // The other sinks have no server to watch
+    if ctx.opt.sink() != sink::QUESTDB {
+        return;
+    }
+    let client = match QueryClient::from_conf(&ctx.opt.connection_string) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Cannot watch server health: {}", e);
+            return;
+        }
+    };
+    loop {
+        sleep(Duration::from_millis(200)).await;
+        if !ctx.health.is_down() {
+            continue;
+        }
+
+        eprintln!("Server looks down after {} consecutive connection failures, buffering rows until it is back", ctx.opt.restart_failures);
+        let down_since = Instant::now();
+        loop {
+            sleep(Duration::from_secs(1)).await;
+            let ping_client = client.clone();
+            if !matches!(tokio::task::spawn_blocking(move || ping_client.ping()).await, Ok(Ok(()))) {
+                continue;
+            }
+            match ctx.sink.reconnect().await {
+                Ok(()) => break,
+                Err(e) => eprintln!("Server answers but cannot reconnect yet: {}", e),
+            }
+        }
+
+        eprintln!("Server is back after {:.1}s, resuming and flushing buffered rows", down_since.elapsed().as_secs_f64());
+        ctx.health.record_success();
+        ctx.health.down.store(false, Ordering::SeqCst);
+        ctx.health.up.notify_waiters();
+    }
+}
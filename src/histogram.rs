@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use hdrhistogram::Histogram;
+
+/// Latencies are recorded in µs up to an hour, longer ones count as an hour
+const HIGHEST_VALUE: u64 = 3_600_000_000;
+const SIGNIFICANT_DIGITS: u8 = 3;
+/// Sub-buckets of a histogram with 3 significant digits, for the footer of the .hgrm output
+const SUB_BUCKET_COUNT: u32 = 2048;
+/// Lines per halving of the distance to 100% in the .hgrm output
+const TICKS_PER_HALF_DISTANCE: u32 = 5;
+
+fn histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, HIGHEST_VALUE, SIGNIFICANT_DIGITS).unwrap()
+}
+
+/// Writes the percentile distribution in the .hgrm text format of HdrHistogram's
+/// outputPercentileDistribution, with values divided by `scale`.
+fn write_hgrm(histogram: &Histogram<u64>, out: &mut impl Write, scale: f64) -> io::Result<()> {
+    writeln!(out, "{:>12} {:>14} {:>10} {:>14}\n", "Value", "Percentile", "TotalCount", "1/(1-Percentile)")?;
+    let mut seen = 0;
+    for step in histogram.iter_quantiles(TICKS_PER_HALF_DISTANCE) {
+        seen += step.count_since_last_iteration();
+        let (value, quantile) = (step.value_iterated_to() as f64 / scale, step.quantile_iterated_to());
+        if quantile < 1.0 {
+            writeln!(out, "{:12.3} {:2.12} {:10} {:14.2}", value, quantile, seen, 1.0 / (1.0 - quantile))?;
+        } else {
+            writeln!(out, "{:12.3} {:2.12} {:10}", value, quantile, seen)?;
+        }
+    }
+    let max = if histogram.is_empty() { 0.0 } else { histogram.max() as f64 / scale };
+    writeln!(out, "#[Mean    = {:12.3}, StdDeviation   = {:12.3}]", histogram.mean() / scale, histogram.stdev() / scale)?;
+    writeln!(out, "#[Max     = {:12.3}, Total count    = {:12}]", max, histogram.len())?;
+    writeln!(out, "#[Buckets = {:12}, SubBuckets     = {:12}]", histogram.buckets(), SUB_BUCKET_COUNT)
+}
+
+/// Percentiles of one kind of latency, at the end of the run.
+#[derive(Debug, Clone)]
+pub struct LatencySummary {
+    pub name: &'static str,
+    pub batches: u64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Flush latencies, from a generator asking to flush to the flush returning, and
+/// end-to-end latencies, from a batch starting to fill to the server accepting it, in µs.
+#[derive(Debug)]
+pub struct Latencies {
+    flush: Mutex<Histogram<u64>>,
+    end_to_end: Mutex<Histogram<u64>>,
+}
+
+impl Latencies {
+    pub fn new() -> Self {
+        Latencies { flush: Mutex::new(histogram()), end_to_end: Mutex::new(histogram()) }
+    }
+
+    pub fn record_flush(&self, elapsed: Duration) {
+        self.flush.lock().unwrap().saturating_record(elapsed.as_micros() as u64);
+    }
+
+    pub fn record_end_to_end(&self, elapsed: Duration) {
+        self.end_to_end.lock().unwrap().saturating_record(elapsed.as_micros() as u64);
+    }
+
+    fn histograms(&self) -> [(&'static str, Histogram<u64>); 2] {
+        [("flush", self.flush.lock().unwrap().clone()), ("end-to-end", self.end_to_end.lock().unwrap().clone())]
+    }
+
+    /// Percentiles of the latencies recorded so far, skipping the kinds without any
+    pub fn summaries(&self) -> Vec<LatencySummary> {
+        self.histograms().iter().filter(|(_, histogram)| !histogram.is_empty()).map(|(name, histogram)| {
+            let ms = |quantile| histogram.value_at_quantile(quantile) as f64 / 1000.0;
+            LatencySummary { name, batches: histogram.len(), p50_ms: ms(0.5), p99_ms: ms(0.99), p999_ms: ms(0.999), max_ms: ms(1.0) }
+        }).collect()
+    }
+
+    pub fn print_summary(&self) {
+        for s in self.summaries() {
+            println!("{} latency over {} batches: p50 {:.1} ms, p99 {:.1} ms, p99.9 {:.1} ms, max {:.1} ms",
+                     s.name, s.batches, s.p50_ms, s.p99_ms, s.p999_ms, s.max_ms);
+        }
+    }
+
+    /// Writes `<prefix>-flush.hgrm` and `<prefix>-end-to-end.hgrm`, in ms, and returns their paths.
+    pub fn write_hgrm(&self, prefix: &Path) -> io::Result<Vec<PathBuf>> {
+        self.histograms().iter().map(|(name, histogram)| {
+            let path = PathBuf::from(format!("{}-{}.hgrm", prefix.display(), name));
+            let mut file = BufWriter::new(File::create(&path)?);
+            write_hgrm(histogram, &mut file, 1000.0)?;
+            file.flush()?;
+            Ok(path)
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The percentiles of the summary are those of the sorted latencies, to the 3 digits kept
+    #[test]
+    fn percentiles_match_the_sorted_latencies() {
+        let latencies = Latencies::new();
+        // Skewed like flush latencies, from tens of µs to seconds
+        let mut micros: Vec<u64> = (0..10_000u64).map(|n| 20 + n * n * n % 2_000_003).collect();
+        for value in &micros {
+            latencies.record_flush(Duration::from_micros(*value));
+        }
+        micros.sort();
+        let summary = &latencies.summaries()[0];
+        assert_eq!(summary.batches, 10_000);
+        for (pct, ms) in [(50.0, summary.p50_ms), (99.0, summary.p99_ms), (99.9, summary.p999_ms), (100.0, summary.max_ms)].iter() {
+            let wanted = micros[((pct / 100.0 * micros.len() as f64).ceil() as usize).max(1) - 1] as f64 / 1000.0;
+            assert!((ms - wanted).abs() <= wanted * 1e-3, "p{}: {} ms, sorted {} ms", pct, ms, wanted);
+        }
+    }
+}
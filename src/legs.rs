@@ -0,0 +1,42 @@
+use questdb::ingress::{Buffer, TimestampMicros, TimestampNanos};
+
+use crate::config::Columns;
+
+/// What a plane did over one flight leg, written to --legs-table when the leg ends: one
+/// row per flight, to JOIN with the raw telemetry on plane_id and callsign.
+#[derive(Debug, Clone)]
+pub struct LegSummary {
+    pub plane_id: String,
+    pub callsign: String,
+    pub origin: &'static str,
+    pub destination: &'static str,
+    pub departed_at: i64,
+    pub arrived_at: i64,
+    pub max_altitude: f64,
+    /// Average of the airspeed over the ticks of the leg, in knots
+    pub avg_airspeed: f64,
+    /// Distance over the ground, in nautical miles
+    pub distance_nm: f64,
+    pub fuel_burned_kg: f64,
+    /// False for the leg a plane joined mid-flight, of which only the end was simulated
+    pub complete: bool,
+}
+
+pub fn write(leg: &LegSummary, buffer: &mut Buffer, table_name: &str, columns: &Columns) -> questdb::Result<()> {
+    buffer.table(table_name)?
+        .symbol(columns.name("plane_id"), &leg.plane_id)?
+        .symbol(columns.name("callsign"), &leg.callsign)?
+        .symbol("origin", leg.origin)?
+        .symbol("destination", leg.destination)?;
+    if let Some((name, label)) = columns.run_label() {
+        buffer.symbol(name, label)?;
+    }
+    buffer.column_ts("departed_at", TimestampMicros::new(leg.departed_at / 1000))?
+        .column_f64("duration_secs", (leg.arrived_at - leg.departed_at) as f64 / 1e9)?
+        .column_f64("max_altitude", columns.value("altitude", leg.max_altitude))?
+        .column_f64("avg_airspeed", columns.value("airspeed", leg.avg_airspeed))?
+        .column_f64("distance_nm", leg.distance_nm)?
+        .column_f64("fuel_burned_kg", leg.fuel_burned_kg)?
+        .column_bool("complete", leg.complete)?
+        .at(TimestampNanos::new(leg.arrived_at))
+}
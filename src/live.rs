@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use questdb::ingress::{Buffer, TimestampNanos};
+use serde_json::Value;
+use tokio::time::{Duration, interval, MissedTickBehavior};
+
+use crate::{RunContext, flush};
+use crate::config::Columns;
+
+const FEET_PER_METER: f64 = 3.28084;
+const KNOTS_PER_MPS: f64 = 1.943844;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Synthetic,
+    OpenSky,
+    Dump1090,
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "synthetic" => Ok(Source::Synthetic),
+            "opensky" => Ok(Source::OpenSky),
+            "dump1090" => Ok(Source::Dump1090),
+            _ => Err(format!("invalid source '{}', expected synthetic, opensky or dump1090", s)),
+        }
+    }
+}
+
+impl Source {
+    pub fn default_url(&self) -> &'static str {
+        match self {
+            Source::Synthetic => "",
+            Source::OpenSky => "https://opensky-network.org/api/states/all",
+            Source::Dump1090 => "http://localhost:8080/data/aircraft.json",
+        }
+    }
+}
+
+/// A single observation of a real aircraft. Units match the synthetic data:
+/// feet, knots, degrees and feet per minute.
+#[derive(Debug, Default)]
+struct StateVector {
+    icao24: String,
+    callsign: Option<String>,
+    timestamp: i64,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude: Option<f64>,
+    airspeed: Option<f64>,
+    ground_speed: Option<f64>,
+    track: Option<f64>,
+    vertical_speed: Option<f64>,
+    oat: Option<f64>,
+}
+
+fn parse_opensky(json: &Value) -> Vec<StateVector> {
+    let states = match json["states"].as_array() {
+        Some(states) => states,
+        None => return vec![],
+    };
+    states.iter().filter_map(|s| {
+        let seconds = s[3].as_i64().or_else(|| s[4].as_i64())?;
+        Some(StateVector {
+            icao24: s[0].as_str()?.trim().to_lowercase(),
+            callsign: s[1].as_str().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()),
+            timestamp: seconds * 1_000_000_000,
+            longitude: s[5].as_f64(),
+            latitude: s[6].as_f64(),
+            altitude: s[7].as_f64().map(|m| m * FEET_PER_METER),
+            ground_speed: s[9].as_f64().map(|v| v * KNOTS_PER_MPS),
+            track: s[10].as_f64(),
+            vertical_speed: s[11].as_f64().map(|v| v * FEET_PER_METER * 60.0),
+            ..Default::default()
+        })
+    }).collect()
+}
+
+fn parse_dump1090(json: &Value) -> Vec<StateVector> {
+    // Without the receiver's clock the ages are taken from ours, not from 1970
+    let now = json["now"].as_f64().unwrap_or_else(|| {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0)
+    });
+    let aircraft = match json["aircraft"].as_array() {
+        Some(aircraft) => aircraft,
+        None => return vec![],
+    };
+    aircraft.iter().filter_map(|a| {
+        let seen = a["seen_pos"].as_f64().or_else(|| a["seen"].as_f64()).unwrap_or(0.0);
+        Some(StateVector {
+            icao24: a["hex"].as_str()?.trim().to_lowercase(),
+            callsign: a["flight"].as_str().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()),
+            timestamp: ((now - seen) * 1e9) as i64,
+            latitude: a["lat"].as_f64(),
+            longitude: a["lon"].as_f64(),
+            // alt_baro is the string "ground" for planes on the ground
+            altitude: a["alt_baro"].as_f64().or_else(|| a["altitude"].as_f64()),
+            airspeed: a["tas"].as_f64(),
+            ground_speed: a["gs"].as_f64().or_else(|| a["speed"].as_f64()),
+            track: a["track"].as_f64(),
+            vertical_speed: a["baro_rate"].as_f64().or_else(|| a["vert_rate"].as_f64()),
+            oat: a["oat"].as_f64(),
+        })
+    }).collect()
+}
+
+fn fetch(source: Source, url: &str) -> Result<Vec<StateVector>, String> {
+    let json: Value = ureq::get(url)
+        .timeout(std::time::Duration::from_secs(30))
+        .call()
+        .map_err(|e| e.to_string())?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    Ok(match source {
+        Source::OpenSky => parse_opensky(&json),
+        _ => parse_dump1090(&json),
+    })
+}
+
+fn write_row(buffer: &mut Buffer, table_name: &str, columns: &Columns, state: &StateVector) -> questdb::Result<()> {
+    buffer.table(table_name)?
+        .symbol(columns.name("plane_id"), &state.icao24)?
+        .symbol(columns.name("icao24"), &state.icao24)?;
+    if let Some(callsign) = &state.callsign {
+        buffer.symbol(columns.name("callsign"), callsign)?;
+    }
+    if let Some((name, label)) = columns.run_label() {
+        buffer.symbol(name, label)?;
+    }
+    let values = [
+        ("airspeed", state.airspeed),
+        ("altitude", state.altitude),
+        ("oat", state.oat),
+        ("latitude", state.latitude),
+        ("longitude", state.longitude),
+        ("ground_speed", state.ground_speed),
+        ("track", state.track),
+        ("vertical_speed", state.vertical_speed),
+    ];
+    for (name, value) in values.iter() {
+        if let Some(value) = value {
+            buffer.column_f64(columns.name(name), columns.value(name, *value))?;
+        }
+    }
+    buffer.at(TimestampNanos::new(state.timestamp))
+}
+
+/// Polls live aircraft positions and ingests them into the same table as the
+/// synthetic data. Positions that did not change since the previous poll are skipped.
+pub async fn run(ctx: Arc<RunContext>) {
+    let opt = &ctx.opt;
+    let url = opt.live_url.clone().unwrap_or_else(|| opt.source.default_url().to_string());
+    let mut interval = interval(Duration::from_secs(opt.live_poll_secs.max(1)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_seen: HashMap<String, i64> = HashMap::new();
+    let mut buffer = ctx.pool.empty();
+    let mut allowance = ctx.quota.allowance(1.0);
+
+    while ctx.quota.remaining() > 0 {
+        interval.tick().await;
+
+        let source = opt.source;
+        let poll_url = url.clone();
+        let states = match tokio::task::spawn_blocking(move || fetch(source, &poll_url)).await {
+            Ok(Ok(states)) => states,
+            Ok(Err(e)) => {
+                ctx.errors.record("live fetch failures", &format!("Failed to fetch live data from {}", url), &e);
+                continue;
+            }
+            Err(e) => {
+                ctx.errors.record("live fetch failures", &format!("Fetching live data from {} panicked", url), &crate::panic_message(&e.into_panic()));
+                continue;
+            }
+        };
+
+        for state in states.iter() {
+            if last_seen.get(&state.icao24) == Some(&state.timestamp) {
+                continue;
+            }
+            if !allowance.take() {
+                break;
+            }
+            let written = buffer.set_marker().and_then(|_| write_row(&mut buffer, &opt.table_name, &ctx.columns(), state));
+            if let Err(e) = written {
+                // The row may be half written
+                let _ = buffer.rewind_to_marker();
+                ctx.row_errors.fetch_add(1, Ordering::SeqCst);
+                ctx.errors.record("live row errors", &format!("Skipping live row for {}", state.icao24), e.msg());
+                continue;
+            }
+            last_seen.insert(state.icao24.clone(), state.timestamp);
+        }
+
+        buffer.clear_marker();
+        // The same flush as the generators, so a rejected batch, poisoned or not, is dropped
+        // and counted with theirs, and one that failed is kept for the next poll
+        if !buffer.is_empty() {
+            flush(&ctx, &mut buffer, "live source", &mut [], None).await;
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use questdb::ingress::{Buffer, Sender, TimestampNanos};
+use clap::Parser;
+
+use crate::error_summary::thousands;
+use crate::rate_limit::RateLimit;
+use crate::secrets;
+use crate::units;
+
+/// Options of `airplanes load`.
+#[derive(Parser, Debug)]
+pub struct LoadOpt {
+    /// CSV file to load, with a header row of column names, e.g. a table exported from QuestDB
+    file: PathBuf,
+
+    /// Where to send the rows, with `${NAME}` replaced by the environment variable NAME
+    #[arg(long)]
+    connection_string: String,
+
+    /// File with the token of the connection, added to the connection string
+    #[arg(long)]
+    token_file: Option<PathBuf>,
+
+    /// File with the password of the connection, added to the connection string
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+
+    #[arg(long)]
+    table_name: String,
+
+    /// Rows per second to send the file at, all at once when 0
+    #[arg(long, value_parser = units::count, default_value = "0")]
+    rate: u64,
+
+    /// Rows per flush
+    #[arg(long, value_parser = units::count_usize, default_value = "1000")]
+    batch_size: usize,
+
+    /// Column with the designated timestamp of the rows, as RFC 3339 or nanoseconds since
+    /// the epoch. Without it in the file, the server stamps the rows as it receives them
+    #[arg(long, default_value = "timestamp")]
+    timestamp_column: String,
+
+    /// Columns sent as symbols, separated by commas. Other columns go as doubles when they
+    /// are numbers, booleans for true and false, and strings otherwise
+    #[arg(long, value_delimiter = ',', default_value = "plane_id,icao24,callsign,phase,quality,run_label")]
+    symbols: Vec<String>,
+}
+
+/// The fields of a CSV line, with double quotes around fields that have commas or quotes
+fn fields(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn timestamp(value: &str) -> Result<i64, String> {
+    value.parse::<i64>()
+        .or_else(|_| chrono::DateTime::parse_from_rfc3339(value).map_err(|e| e.to_string())
+            .and_then(|at| at.timestamp_nanos_opt().ok_or_else(|| "out of range".to_string())))
+        .map_err(|e| format!("invalid timestamp '{}': {}", value, e))
+}
+
+/// Adds the row of a CSV line to the buffer, symbols first as ILP wants them.
+fn write_row(buffer: &mut Buffer, opt: &LoadOpt, header: &[String], values: &[String]) -> Result<(), String> {
+    if values.len() != header.len() {
+        return Err(format!("{} fields, the header has {}", values.len(), header.len()));
+    }
+    let mut at = None;
+    let row = header.iter().zip(values).filter(|(_, value)| !value.is_empty());
+    buffer.table(opt.table_name.as_str()).map_err(|e| e.to_string())?;
+    for (name, value) in row.clone().filter(|(name, _)| opt.symbols.contains(name)) {
+        buffer.symbol(name.as_str(), value.as_str()).map_err(|e| e.to_string())?;
+    }
+    for (name, value) in row.filter(|(name, _)| !opt.symbols.contains(name)) {
+        let written = if *name == opt.timestamp_column {
+            at = Some(timestamp(value)?);
+            continue;
+        } else if let Ok(number) = value.parse::<f64>() {
+            buffer.column_f64(name.as_str(), number)
+        } else if let Ok(flag) = value.parse::<bool>() {
+            buffer.column_bool(name.as_str(), flag)
+        } else {
+            buffer.column_str(name.as_str(), value.as_str())
+        };
+        written.map_err(|e| e.to_string())?;
+    }
+    match at {
+        Some(at) => buffer.at(TimestampNanos::new(at)),
+        None => buffer.at_now(),
+    }.map_err(|e| e.to_string())
+}
+
+/// Streams the rows of a CSV file into QuestDB at --rate, to replay data generated
+/// earlier with the timing of the ingestion decided at load time.
+pub fn run(opt: &LoadOpt) -> Result<(), String> {
+    let path = opt.file.to_string_lossy();
+    if path.contains("://") || path.ends_with(".parquet") {
+        return Err(format!("cannot load {}: only local CSV files can be loaded, Parquet and object storage are not supported", path));
+    }
+    let file = File::open(&opt.file).map_err(|e| format!("cannot open {}: {}", path, e))?;
+    let mut lines = BufReader::new(file).lines();
+    let header = match lines.next() {
+        Some(line) => fields(line.map_err(|e| format!("cannot read {}: {}", path, e))?.trim_end()),
+        None => return Err(format!("{} is empty, it needs a header row", path)),
+    };
+    let conf = secrets::connection_string(&opt.connection_string, opt.token_file.as_deref(), opt.password_file.as_deref())?;
+    let mut sender = Sender::from_conf(&conf).map_err(|e| format!("cannot connect: {}", e))?;
+    let limit = (opt.rate > 0).then(|| RateLimit::new(opt.rate));
+    let mut buffer = sender.new_buffer();
+    let (mut loaded, mut batch, mut skipped) = (0u64, 0usize, 0u64);
+    let started = Instant::now();
+    for (number, line) in lines.enumerate() {
+        let line = line.map_err(|e| format!("cannot read {}: {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        buffer.set_marker().map_err(|e| e.to_string())?;
+        if let Err(e) = write_row(&mut buffer, opt, &header, &fields(line.trim_end())).map_err(|e| format!("line {}: {}", number + 2, e)) {
+            buffer.rewind_to_marker().map_err(|e| e.to_string())?;
+            if skipped == 0 {
+                eprintln!("Skipping rows that cannot be sent, the first at {}", e);
+            }
+            skipped += 1;
+            continue;
+        }
+        batch += 1;
+        if batch >= opt.batch_size.max(1) {
+            flush(&mut sender, &mut buffer, limit.as_ref(), batch)?;
+            loaded += batch as u64;
+            batch = 0;
+        }
+    }
+    if batch > 0 {
+        flush(&mut sender, &mut buffer, limit.as_ref(), batch)?;
+        loaded += batch as u64;
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    println!("Loaded {} rows from {} into {} in {:.1}s, {:.0} rows/s", thousands(loaded), path, opt.table_name,
+             elapsed, loaded as f64 / elapsed.max(f64::MIN_POSITIVE));
+    if skipped > 0 {
+        println!("Skipped {} rows that could not be sent", thousands(skipped));
+    }
+    Ok(())
+}
+
+fn flush(sender: &mut Sender, buffer: &mut Buffer, limit: Option<&RateLimit>, rows: usize) -> Result<(), String> {
+    let wait = limit.map(|limit| limit.reserve(rows)).unwrap_or(Duration::ZERO);
+    std::thread::sleep(wait);
+    sender.flush(buffer).map_err(|e| format!("flush failed: {}", e))
+}
@@ -1,191 +1,2709 @@
-use chrono::Utc;
-use rand::Rng;
-use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, RwLock, atomic::{AtomicU64, Ordering}};
 use tokio::sync::Semaphore;
-use tokio::time::{Duration, interval};
+use tokio::time::{Duration, interval, MissedTickBehavior};
 use questdb::{
     Result,
-    ingress::{Sender, Buffer, TimestampNanos}
+    ingress::{Buffer, ProtocolVersion}
 };
-use structopt::StructOpt;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use rand::Rng;
 use futures::future::join_all;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+mod added_columns;
+mod auto_batch;
+mod auto_flush;
+mod backfill;
+mod blocking;
+#[cfg(feature = "object-store")]
+mod bucket;
+mod cold;
+mod column_drop;
+mod companion;
+mod config;
+mod cpu_profile;
+mod daemon;
+mod dropout;
+mod error;
+mod error_summary;
+mod estimate;
+mod file_sink;
+mod formation;
+mod grafana;
+mod guard;
+mod health;
+mod histogram;
+mod legs;
+mod live;
+mod load;
+mod matview;
+mod memory;
+mod meter;
+mod mimic;
+mod mirror;
+mod model;
+mod network;
+mod noise;
+mod outlier;
+mod phases;
+mod plane;
+mod plane_id;
+mod poison;
+mod pool;
+mod preflight;
+mod queries;
+mod query_load;
+mod questdb_http;
+mod quota;
+mod rate;
+mod rate_limit;
+mod reload;
+mod retention;
+mod rollover;
+mod run_log;
+mod runs;
+mod sample_log;
+mod scaffold;
+mod secrets;
+mod schedule;
+mod seasonal;
+mod seed;
+mod self_profile;
+mod sender_pool;
+mod sensors;
+mod silence;
+mod sink;
+mod skew;
+mod spectrum;
+mod stats;
+mod status;
+mod storage;
+mod suite;
+mod sweep;
+mod task;
+#[cfg(test)]
+mod test_support;
+mod ticks;
+mod trajectory;
+mod tui;
+mod turbine;
+mod turbulence;
+mod type_flip;
+mod units;
+mod vehicle;
+mod vessel;
+mod weather;
+
+use auto_batch::AutoBatch;
+use auto_flush::AutoFlush;
+use backfill::BackfillClock;
+use cold::ColdWrites;
+use column_drop::{ColumnDrops, DropColumnAt};
+use companion::Clearances;
+use added_columns::{AddColumnAt, AddedColumns};
+use config::{Config, Columns};
+use dropout::{Coverage, Signal};
+use error::SimError;
+use error_summary::ErrorSummary;
+use file_sink::Compression;
+use formation::Formations;
+use guard::{OnLimit, ResourceGuard};
+use health::ServerHealth;
+use histogram::Latencies;
+use live::Source;
+use matview::MatView;
+use memory::{BufferFull, MemoryBudget};
+use mimic::{MimicRow, MimicTable};
+use mirror::Mirror;
+use model::DeviceModel;
+use network::Network;
+use outlier::Outliers;
+use phases::RunPhases;
+use plane::{PlaneData, Quality, Schema, Variations};
+use plane_id::{PlaneIdScheme, PlaneIds};
+use pool::BufferPool;
+use preflight::Preflight;
+use query_load::QueryLoad;
+use questdb_http::QueryClient;
+use quota::RowQuota;
+use rate::RateTracker;
+use rate_limit::RateLimits;
+use retention::Retention;
+use rollover::{Partitioning, RolloverClock};
+use run_log::RunRecord;
+use sample_log::SampleLog;
+use schedule::RateSchedule;
+use seasonal::Seasonality;
+use self_profile::SelfProfile;
+use cpu_profile::CpuProfile;
+use sender_pool::SenderPool;
+use sensors::SensorSchedule;
+use silence::{Silence, SilenceAt};
+use sink::Sink;
+use skew::SkewShare;
+use spectrum::Spectrum;
+use stats::PlaneStats;
+use status::StatusStream;
+use sweep::Sweep;
+use task::{Engine, TaskBuffer};
+use trajectory::{TrackRecorder, Trajectories, TrajectoryFormat};
+use tui::Dashboard;
+use type_flip::{FlipType, TypeFlips};
 
-#[derive(StructOpt, Debug)]
-#[structopt(name = "flight-data-generator")]
+#[derive(Parser, Debug)]
+#[command(name = "flight-data-generator", version, after_help = COMMANDS_HELP)]
 struct Opt {
-    #[structopt(long)]
+    /// Where to send the rows. `${NAME}` is replaced with the environment variable NAME,
+    /// to keep credentials out of the shell history and the process list. Only the
+    /// `questdb` sink needs it
+    #[arg(long, default_value = "")]
     connection_string: String,
 
-    #[structopt(long)]
+    /// File with the token of the connection, added to the connection string
+    #[arg(long)]
+    token_file: Option<PathBuf>,
+
+    /// File with the password of the connection, added to the connection string
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+
+    /// Rows to generate across all planes. Can be left out with --duration
+    #[arg(long = "total-rows", value_name = "TOTAL_ROWS", required_unless_present = "duration", value_parser = units::count)]
+    total_rows_arg: Option<u64>,
+
+    /// --total-rows, or no limit with --duration alone
+    #[arg(skip)]
     total_rows: u64,
 
-    #[structopt(long)]
+    /// Stop generating after this long, e.g. 2h30m; with --total-rows, at whichever comes first
+    #[arg(long, value_parser = units::secs_f64)]
+    duration: Option<f64>,
+
+    /// Rows per second of each plane
+    #[arg(long = "rate-per-plane", value_name = "RATE_PER_PLANE", required_unless_present_any = ["total_rate", "rate_schedule"], conflicts_with = "total_rate",
+                value_parser = units::count)]
+    rate_per_plane_arg: Option<u64>,
+
+    /// Rows per second across all the planes, e.g. 250k/s, split evenly between them
+    #[arg(long, value_parser = units::count)]
+    total_rate: Option<u64>,
+
+    /// Follow the total rate of this schedule of the --config file through the day and
+    /// the week, e.g. busy in business hours. --total-rate or --rate-per-plane is the rate
+    /// outside its windows, for a schedule without an `otherwise` rate
+    #[arg(long)]
+    rate_schedule: Option<String>,
+
+    /// Skew the rows towards a few hot planes, following a Zipf law of this exponent: the
+    /// plane of rank n in the order of the ids sends --rate-per-plane / n^s rows per
+    /// second. 1 is a classic Zipf law, higher is more skewed. With --total-rate, the
+    /// first plane's rate is set so they add up to it
+    #[arg(long)]
+    plane_skew: Option<f64>,
+
+    /// Rows per second each connection sends at most, e.g. 20k/s: flushes wait for their
+    /// turn. Many connections under a moderate cap model many moderate producers
+    #[arg(long, value_parser = units::count)]
+    max_rate_per_conn: Option<u64>,
+
+    /// Rows per second the whole process sends at most, across all its connections. A few
+    /// connections under a high cap model a few firehose producers
+    #[arg(long, value_parser = units::count)]
+    max_total_rate: Option<u64>,
+
+    /// Round trip added to every flush, to study client-side buffering over a slow network
+    /// without tc or netem
+    #[arg(long, default_value = "0")]
+    net_latency_ms: u64,
+
+    /// Random variation of --net-latency-ms, up to this much more or less
+    #[arg(long, default_value = "0")]
+    net_jitter_ms: u64,
+
+    /// Bandwidth of the simulated network, e.g. 1MB: a batch takes its size over this to go through
+    #[arg(long, value_parser = units::bytes)]
+    net_bytes_per_sec: Option<u64>,
+
+    /// Percentage of flushes that lose a packet and wait for a retransmission, at least
+    /// 200 ms and doubling for every further loss
+    #[arg(long, default_value = "0")]
+    net_loss_pct: f64,
+
+    /// --rate-per-plane, or each plane's share of --total-rate
+    #[arg(skip)]
     rate_per_plane: u64,
 
-    #[structopt(long)]
+    #[arg(long)]
     plane_count: u32,
 
-    #[structopt(long)]
+    /// Table the rows go to. Can be left out with a --model other than planes, which then
+    /// writes to a table named after it
+    #[arg(long = "table-name", value_name = "TABLE_NAME")]
+    table_name_arg: Option<String>,
+
+    /// --table-name, or the table of the --model
+    #[arg(skip)]
     table_name: String,
 
-    #[structopt(long, default_value = "AA00")]
-    starting_plane_id: String,
+    /// First plane id, in the format of the chosen scheme (AA00, a00000 or N10000 by default)
+    #[arg(long)]
+    starting_plane_id: Option<String>,
+
+    /// How plane ids are generated: `sequential` (letters + digits), `icao` (24-bit hex addresses)
+    /// or `tail` (tail numbers like N12345)
+    #[arg(long, default_value = "sequential")]
+    plane_id_scheme: PlaneIdScheme,
 
-    #[structopt(long)]
+    #[arg(long)]
     quiet: bool,
 
-    #[structopt(long, default_value = "1000")]
+    /// Consecutive connection failures after which the server is considered to be restarting.
+    /// Flushes are then paused, rows kept in memory, and sent once the server is back. 0 disables it
+    #[arg(long, default_value = "3")]
+    restart_failures: u32,
+
+    /// Connections the async generators and live sources flush through, each flush taking
+    /// the next free one
+    #[arg(long, default_value = "1")]
+    senders: usize,
+
+    /// Where the rows go. `questdb` sends ILP to the --connection-string through the
+    /// --senders pool, `csv` writes a CSV file for each table to --sink-path. Defaults to the
+    /// `kind` of the `[sink]` of the --config file, or questdb
+    #[arg(long)]
+    sink: Option<String>,
+
+    /// Directory the `csv` sink writes to, or with the object-store feature a bucket and prefix
+    /// to upload the files to, e.g. s3://exports/flights. Defaults to the `path` of the `[sink]`
+    /// of the --config file
+    #[arg(long)]
+    sink_path: Option<PathBuf>,
+
+    /// Size of the parts the files are uploaded in when --sink-path is a bucket, at least 5MiB [default: 16MiB]
+    #[arg(long, value_parser = units::bytes)]
+    sink_part_size: Option<u64>,
+
+    /// Compress the files of the `csv` sink: none, gzip, zstd or lz4
+    #[arg(long)]
+    sink_compression: Option<Compression>,
+
+    /// Start the next file of a table once one holds this much CSV, before compression, e.g. 1GB
+    #[arg(long, value_parser = units::bytes)]
+    sink_rotate_size: Option<u64>,
+
+    /// Put the rows of each window of this length, by their timestamps, in files of their own, e.g. 1h
+    #[arg(long, value_parser = units::secs)]
+    sink_rotate_every: Option<u64>,
+
+    /// Also send every flush to this second endpoint, e.g. the new cluster of a migration,
+    /// and report the flushes only one of the two accepted
+    #[arg(long)]
+    mirror_connection_string: Option<String>,
+
+    /// Pin the ILP protocol version of the connections, 1 for text only or 2 for binary
+    /// doubles and arrays, instead of the one the server offers over HTTP or 1 over TCP
+    #[arg(long, value_parser = sink::protocol_version)]
+    protocol_version: Option<ProtocolVersion>,
+
+    /// Replace a connection that has been idle for this long, before a load balancer or
+    /// firewall drops it silently
+    #[arg(long, value_parser = units::secs)]
+    sender_keepalive_secs: Option<u64>,
+
+    /// Wait before opening a closed connection again after a failed attempt, doubled after
+    /// each further one
+    #[arg(long, value_parser = units::millis, default_value = "100")]
+    reconnect_backoff_ms: u64,
+
+    /// Longest wait between two attempts to open a closed connection
+    #[arg(long, value_parser = units::millis, default_value = "10000")]
+    reconnect_max_backoff_ms: u64,
+
+    /// How long to wait for a restarting server at the end of the run before giving up on the buffered rows
+    #[arg(long, value_parser = units::secs, default_value = "600")]
+    restart_timeout_secs: u64,
+
+    /// Checks to run before starting: `off`, `connect` validates the connection string and that the
+    /// server answers, `probe` also writes one probe row (plane_id 'preflight') to the table, and
+    /// `scratch` writes it to a `<table>_preflight` table that is dropped afterwards
+    #[arg(long, default_value = "connect")]
+    preflight: Preflight,
+
+    /// Generate random rows matching the schema of this existing table, read with SHOW COLUMNS,
+    /// instead of flight data. Rows are still written to --table-name
+    #[arg(long)]
+    mimic_table: Option<String>,
+
+    /// Write per-plane statistics (rows generated and flushed, failures, achieved rate) to this CSV file
+    #[arg(long)]
+    stats_csv: Option<PathBuf>,
+
+    /// Sample the CPU of the run with pprof-rs and write the profile to this file at exit: a
+    /// flamegraph when it ends in .svg, else a pprof protobuf. Needs the profile feature
+    #[arg(long)]
+    profile: Option<PathBuf>,
+
+    /// Write the flush and end-to-end latency histograms to `<prefix>-flush.hgrm` and
+    /// `<prefix>-end-to-end.hgrm`, in ms, in HdrHistogram's percentile distribution format
+    #[arg(long, value_name = "PREFIX")]
+    latency_hgrm: Option<PathBuf>,
+
+    /// Write the results of the run to this JSON file at the end: parameters, ingestion totals,
+    /// rate buckets, per-plane statistics and, with --query-workers, per-query latencies
+    #[arg(long)]
+    report_json: Option<PathBuf>,
+
+    /// Run the simulator once for each value of an option, e.g. batch_size=100,1000,10000,
+    /// each for --duration, and write the throughput and latencies of each run to
+    /// --sweep-csv. Given twice, every combination of the two runs
+    #[arg(long)]
+    sweep: Vec<Sweep>,
+
+    /// CSV file for the results of --sweep. The report of each run goes next to it
+    #[arg(long, default_value = "sweep.csv")]
+    sweep_csv: PathBuf,
+
+    /// Save the JSON report of the run in --runs-dir, and add it to the index there that
+    /// `airplanes history` lists
+    #[arg(long)]
+    save_run: bool,
+
+    /// Directory of the saved runs
+    #[arg(long, default_value = "runs")]
+    runs_dir: PathBuf,
+
+    /// How many of the most recent saved runs to keep, older reports are deleted
+    #[arg(long, default_value = "20")]
+    keep_runs: usize,
+
+    /// Write example queries (SAMPLE BY, LATEST ON, ASOF JOIN) for this table and its column
+    /// names to this SQL file before starting
+    #[arg(long)]
+    emit_queries: Option<PathBuf>,
+
+    /// Write the flight paths of the planes to this GeoJSON (.geojson) or KML (.kml) file at the
+    /// end of the run, one line per flight leg, to overlay them on a map
+    #[arg(long)]
+    trajectory_file: Option<PathBuf>,
+
+    /// Seconds between two points of the exported trajectories
+    #[arg(long, value_parser = units::secs_f64, default_value = "10")]
+    trajectory_every_secs: f64,
+
+    /// Rows sent during the first seconds of the run are left out of the rate statistics,
+    /// so server warm-up (JIT, symbol caches, page cache) does not skew them
+    #[arg(long, value_parser = units::secs, default_value = "0")]
+    warmup_secs: u64,
+
+    /// Length of the buckets over which the achieved aggregate rate is compared with the requested one
+    #[arg(long, value_parser = units::secs, default_value = "60")]
+    rate_bucket_secs: u64,
+
+    /// Flag rate buckets that are more than this percentage below the requested rate
+    #[arg(long, default_value = "5")]
+    rate_tolerance_pct: f64,
+
+    /// Show a live table of the planes in the terminal instead of printing progress, with keys to
+    /// pause, change the rate and kill planes. Implies --quiet
+    #[arg(long)]
+    tui: bool,
+
+    /// Run in the background, writing the pid to --pid-file and the output to --log-file
+    #[arg(long)]
+    daemon: bool,
+
+    /// Pid file of a --daemon run, removed when it exits. Stop the daemon by killing this pid
+    #[arg(long, default_value = "airplanes.pid")]
+    pid_file: PathBuf,
+
+    /// Log file of a --daemon run
+    #[arg(long, default_value = "airplanes.log")]
+    log_file: PathBuf,
+
+    /// Rotate the log file when it reaches this size, 0 never rotates
+    #[arg(long, value_parser = units::bytes, default_value = "10000000")]
+    log_max_bytes: u64,
+
+    /// Rotated log files to keep
+    #[arg(long, default_value = "5")]
+    log_keep: u32,
+
+    /// Repeated errors are counted and printed as one line per message this often, instead of
+    /// one line per error. 0 prints every error
+    #[arg(long, value_parser = units::secs, default_value = "10")]
+    error_summary_secs: u64,
+
+    /// Print this fraction of the rows as they are generated, e.g. 0.001 for one in a
+    /// thousand, to spot-check a long run
+    #[arg(long)]
+    sample_log: Option<f64>,
+
+    /// Append the --sample-log rows to this file instead of printing them
+    #[arg(long)]
+    sample_log_file: Option<PathBuf>,
+
+    /// TOML file with extra settings, such as column names and units
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Apply changes to the --config file while running: noise, drift and outliers of the
+    /// columns, sensor group rates, the airlines of the fleets and the rate schedules.
+    /// Others need a restart
+    #[arg(long)]
+    watch_config: bool,
+
+    /// Rows per flush. Each plane has its own batch, or each worker with --workers
+    #[arg(long, value_parser = units::count_usize, default_value = "1000")]
     batch_size: usize,
+
+    /// Try batch sizes during --warmup-secs and keep the one the server takes the most rows
+    /// per second of flush time with, then as many of --senders as pay off, printing both
+    /// so they can be pinned with --batch-size and --senders
+    #[arg(long)]
+    auto_batch: bool,
+
+    /// Flush once a buffer holds this many rows, as auto_flush_rows does in the other QuestDB
+    /// clients. With this or --auto-flush-interval-ms, --batch-size no longer triggers flushes
+    #[arg(long, value_parser = units::count)]
+    auto_flush_rows: Option<u64>,
+
+    /// Flush when a row is added this long after the previous flush, as auto_flush_interval
+    /// does in the other QuestDB clients
+    #[arg(long, value_parser = units::millis)]
+    auto_flush_interval_ms: Option<u64>,
+
+    /// Run on plain threads with blocking senders, without an async runtime. Each plane, or
+    /// each worker with --workers, gets a thread and a connection of its own
+    #[arg(long)]
+    sync: bool,
+
+    /// Spread the connections of the --sync threads and --backfill-workers, and so their
+    /// first flushes, evenly over this window instead of opening them all at once
+    #[arg(long, value_parser = units::millis)]
+    connect_stagger_ms: Option<u64>,
+
+    /// Simulate the planes on this many worker tasks, each one owning a share of the planes
+    /// and a single buffer, instead of one task per plane. Use it for tens of thousands of planes
+    #[arg(long)]
+    workers: Option<usize>,
+
+    /// Flush a plane's buffer as soon as it holds this many bytes, whatever --batch-size says
+    #[arg(long, value_parser = units::bytes)]
+    max_buffer_bytes: Option<u64>,
+
+    /// Flush buffers when all of them together hold this many bytes
+    #[arg(long, value_parser = units::bytes)]
+    memory_budget_bytes: Option<u64>,
+
+    /// Throttle or abort generation when the resident memory of the process goes over this
+    #[arg(long)]
+    max_mem_mb: Option<u64>,
+
+    /// Throttle or abort generation when the process uses more CPU than this, 100 for a
+    /// whole core
+    #[arg(long)]
+    max_cpu_pct: Option<f64>,
+
+    /// What --max-mem-mb and --max-cpu-pct do when the process goes over them: `throttle`
+    /// holds back generation until it is back under, `abort` ends the run with an error
+    #[arg(long, default_value = "throttle")]
+    on_resource_limit: OnLimit,
+
+    /// What planes do when their buffers are over the limits while the server is down:
+    /// `block` stops generating until it is back, `drop` discards the new rows
+    #[arg(long, default_value = "block")]
+    on_buffer_full: BufferFull,
+
+    /// Percentage of rows written with a value of the wrong type on purpose, to test how
+    /// the server reports errors and how rejected batches are handled
+    #[arg(long, default_value = "0")]
+    poison_pct: f64,
+
+    /// Percentage of the readings of every value column sent as statistical outliers, for
+    /// outlier detection demos. The config file can set it per column with outlier_pct
+    #[arg(long, default_value = "0")]
+    outliers_pct: f64,
+
+    /// How many standard deviations from the recent readings of the plane outliers are.
+    /// The config file can set it per column with outlier_sigmas
+    #[arg(long, default_value = "6")]
+    outlier_sigmas: f64,
+
+    /// Percentage of rows sent with timestamps days in the past, so the server has to merge
+    /// them out of order into old partitions. The report compares the flushes of batches with
+    /// and without them
+    #[arg(long, default_value = "0")]
+    cold_writes_pct: f64,
+
+    /// How many days back cold rows go at most, from one day up to this
+    #[arg(long, default_value = "7")]
+    cold_writes_days: f64,
+
+    /// Tag every row with this value in a `run_label` symbol column, so several runs can share
+    /// a table and still be told apart, e.g. before and after a tuning change
+    #[arg(long)]
+    run_label: Option<String>,
+
+    /// Table where a row with the parameters is written when the run starts, and one with
+    /// the totals when it ends
+    #[arg(long, default_value = "sim_runs")]
+    runs_table: String,
+
+    /// Do not write the start and end rows to --runs-table
+    #[arg(long)]
+    no_run_metadata: bool,
+
+    /// Also write ATC clearances for the planes to this table, at a lower rate than the flight
+    /// data and with the same plane_id and timestamps, for ASOF JOIN demos
+    #[arg(long)]
+    companion_table: Option<String>,
+
+    /// Flight data rows of a plane for each clearance in --companion-table
+    #[arg(long, default_value = "60")]
+    companion_ratio: u64,
+
+    /// Also keep the current phase, origin and destination of each plane in this table, one
+    /// slow row stream per plane, for LATEST ON demos next to the flight data
+    #[arg(long)]
+    status_table: Option<String>,
+
+    /// Seconds between the rows of a plane in --status-table when its phase and flight leg
+    /// do not change; a change always writes a row
+    #[arg(long, value_parser = units::secs_f64, default_value = "60")]
+    status_every_secs: f64,
+
+    /// Write a summary row to this table at the end of each flight leg: duration, maximum
+    /// altitude, average airspeed, distance and fuel burned, a fact table for JOIN demos
+    /// with the flight data
+    #[arg(long)]
+    legs_table: Option<String>,
+
+    /// After the run, read the on-disk size of the tables with table_partitions() and report
+    /// the bytes per row and the compression relative to the ILP bytes sent
+    #[arg(long)]
+    storage_report: bool,
+
+    /// Stress partition creation: give rows timestamps that straddle the partition boundaries
+    /// of the table, `hour`, `day`, `week`, `month`, `year` or `auto` to read its partitioning
+    /// from the server. Each boundary gets --rollover-window-secs of rows on both sides before
+    /// the clock jumps to the next one
+    #[arg(long)]
+    partition_rollover: Option<Partitioning>,
+
+    /// Seconds of rows written on each side of a boundary with --partition-rollover
+    #[arg(long, value_parser = units::secs_f64, default_value = "5")]
+    rollover_window_secs: f64,
+
+    /// Backfill history: start the simulated clock this many hours in the past and run it
+    /// faster than real time, by --backfill-speed, until it catches up with the wall clock
+    #[arg(long)]
+    backfill_hours: Option<f64>,
+
+    /// Simulated seconds per real second while backfilling
+    #[arg(long, default_value = "60")]
+    backfill_speed: f64,
+
+    /// Instead of running the clock faster, split the --backfill-hours between this many
+    /// threads, each writing the rows of every plane for its slice of time as fast as it can,
+    /// then go on at the wall clock
+    #[arg(long)]
+    backfill_workers: Option<usize>,
+
+    /// Drop the partitions of the table older than this many hours, measured on the
+    /// simulated clock, with ALTER TABLE ... DROP PARTITION WHERE every --retention-every-secs
+    #[arg(long)]
+    retention_hours: Option<f64>,
+
+    #[arg(long, value_parser = units::secs, default_value = "60")]
+    retention_every_secs: u64,
+
+    /// Create this materialized view over the table before the run, one-minute OHLC bars of
+    /// the airspeed of each plane, and measure how far its refreshes lag during the run
+    #[arg(long)]
+    mat_view: Option<String>,
+
+    /// Seconds between refresh lag samples of --mat-view
+    #[arg(long, value_parser = units::secs, default_value = "5")]
+    mat_view_every_secs: u64,
+
+    /// Run SELECT queries over PG wire against the table on this many threads during the
+    /// run, and report their latencies under the ingestion load
+    #[arg(long, default_value = "0")]
+    query_workers: usize,
+
+    /// Queries for --query-workers, separated by `;`, such as a file written by --emit-queries.
+    /// The example queries are used by default
+    #[arg(long)]
+    queries_file: Option<PathBuf>,
+
+    /// PG wire connection for --query-workers, as `host=... port=... user=...` or a
+    /// postgresql:// URL. Defaults to port 8812 on the host of the connection string, with
+    /// QuestDB's default credentials
+    #[arg(long)]
+    #[cfg_attr(not(feature = "query-load"), allow(dead_code))]
+    pg_connection: Option<String>,
+
+    /// `wide` sends one row per plane and tick with a column per sensor, `narrow` one row per
+    /// sensor reading with plane_id, sensor and value columns. --total-rows counts ticks in both
+    #[arg(long, default_value = "wide")]
+    schema: Schema,
+
+    /// What sends the telemetry: `planes`, `meters` for smart meters, `ticks` for market data,
+    /// `turbines` for wind turbines, `vehicles` for fleet telematics, `vessels` for AIS
+    /// reports of ships or `weather` for weather stations. Other models have --plane-count
+    /// devices sending a row each at --rate-per-plane, and none of the options that shape the
+    /// flights. Ticks send at that rate at their busiest only, weather stations once a
+    /// --sample-interval-ms
+    #[arg(long, default_value = "planes")]
+    model: String,
+
+    /// Milliseconds between the samples of each station with --model weather, a minute by
+    /// default. --rate-per-plane is how often the stations are looked at, so 1 is plenty
+    #[arg(long)]
+    sample_interval_ms: Option<u64>,
+
+    /// Send the altitude column as a LONG or a STRING, alternating, in every nth batch, to
+    /// reproduce ILP schema conflicts. How the server answered is reported at the end
+    #[arg(long)]
+    type_flip_every: Option<u64>,
+
+    /// Add a column to every row from a time into the run, as TIME:NAME:TYPE with a type of
+    /// f64, i64, bool or str, e.g. 5m:wind_shear:f64, so the server adds it to the table
+    /// under load. The throughput before and after is reported at the end. Can be given
+    /// more than once
+    #[arg(long)]
+    add_column_at: Vec<AddColumnAt>,
+
+    /// Leave a column out of the rows for a while, as TIME:COLUMN:DURATION such as
+    /// 5m:altitude:2m, so it is null in that window. The windows follow the timestamps of
+    /// the rows and are reported at the end. Can be given more than once
+    #[arg(long)]
+    drop_column_at: Vec<DropColumnAt>,
+
+    /// Planes go out of coverage on average every this many seconds, sending no rows
+    /// until they are back
+    #[arg(long, value_parser = units::secs_f64)]
+    dropout_every_secs: Option<f64>,
+
+    /// Average length of a coverage gap
+    #[arg(long, value_parser = units::secs_f64, default_value = "60")]
+    dropout_secs: f64,
+
+    /// Keep recording rows while out of coverage and send them when the plane is back
+    #[arg(long)]
+    dropout_backfill: bool,
+
+    /// Tag every row with a `quality` symbol, as avionics feeds mark sample validity: `bad`
+    /// for rows with an --outliers-pct outlier, `suspect` for rows within 5 seconds of a
+    /// coverage gap, recorded out of coverage or shaken by a --formations event, `good` for the rest
+    #[arg(long)]
+    quality_flags: bool,
+
+    /// Add a `vibration_spectrum` array column with the engine vibration amplitude, in g, of
+    /// this many frequency bins, peaking at the fan speed and its harmonics. Arrays need ILP
+    /// protocol version 2, the column is left out with --protocol-version 1
+    #[arg(long)]
+    spectrum_bins: Option<usize>,
+
+    /// Restart a plane, or an async generator task, at most this many times after it panics;
+    /// the plane stops after that
+    #[arg(long, default_value = "10")]
+    max_restarts: u64,
+
+    /// Stop a plane after this many rows fail to build. By default planes skip bad rows and keep going
+    #[arg(long)]
+    max_row_errors: Option<u64>,
+
+    /// What to do when ticks are missed (e.g. after a pause): `burst` catches up
+    /// as fast as possible, `skip` drops the missed ticks, `delay` shifts the schedule
+    #[arg(long, default_value = "burst")]
+    on_missed_tick: MissedTick,
+
+    /// Print the expected duration, rates and bytes of the run and exit without generating anything
+    #[arg(long)]
+    estimate: bool,
+
+    /// Keep the timestamps of each plane strictly increasing, moving a row 1 ns after the
+    /// previous one when the clock has not advanced since, e.g. at rates finer than its resolution
+    #[arg(long)]
+    monotonic_per_plane: bool,
+
+    /// Send rows without a designated timestamp, so the server stamps them with the time it
+    /// receives them, to compare with client-assigned timestamps
+    #[arg(long)]
+    server_timestamps: bool,
+
+    /// Average duration of a flight leg in seconds. Every new leg gets a new callsign
+    #[arg(long, value_parser = units::secs, default_value = "3600")]
+    flight_leg_secs: u64,
+
+    /// Turbulence intensity applied to pitch, roll, yaw and angle of attack. 0 is perfectly
+    /// smooth air, 1 is light turbulence, 3 or more is severe
+    #[arg(long, default_value = "1.0")]
+    turbulence: f64,
+
+    /// Seed the flights, so the same seed and plane ids give the same airports, callsigns,
+    /// legs and flight paths from one run to the next. Each plane draws from its own seed,
+    /// derived from this one and its plane id. Random when not set
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Write the seed of the run, and the seed and starting state of every plane, to this
+    /// JSON file, to regenerate the stream of one plane with --replay-plane
+    #[arg(long)]
+    seeds_file: Option<PathBuf>,
+
+    /// Only simulate this plane of the --plane-count planes, with the seed it gets in a full
+    /// run with the same --seed, to look into the stream of one plane in isolation
+    #[arg(long)]
+    replay_plane: Option<String>,
+
+    /// Add daily and yearly cycles to the outside air temperature, and fewer planes flying at
+    /// night and at weekends, following the timestamps of the rows. Best seen over a
+    /// --backfill-hours range of days or months
+    #[arg(long)]
+    seasonality: bool,
+
+    /// With --seasonality, difference between the warmest and coldest hours of a day, in °C
+    #[arg(long, default_value = "10")]
+    day_temperature_swing: f64,
+
+    /// With --seasonality, share of the planes flying at night, in percent of the afternoon peak
+    #[arg(long, default_value = "30")]
+    night_traffic_pct: f64,
+
+    /// With --seasonality, share of the planes flying at weekends, in percent of weekdays
+    #[arg(long, default_value = "80")]
+    weekend_traffic_pct: f64,
+
+    /// Split the planes into this many formations, tagged in a `formation` symbol column,
+    /// whose planes all go through the same turbulence events at the same time
+    #[arg(long)]
+    formations: Option<u32>,
+
+    /// Each formation goes through one event in every period of this length, at a random time
+    #[arg(long, value_parser = units::secs_f64, default_value = "60")]
+    formation_event_every_secs: f64,
+
+    #[arg(long, value_parser = units::secs_f64, default_value = "10")]
+    formation_event_secs: f64,
+
+    /// How hard the events shake the planes, on the scale of --turbulence
+    #[arg(long, default_value = "2.0")]
+    formation_intensity: f64,
+
+    /// Stop all the planes for --silence-for, to test no-data alerts: every day at a UTC
+    /// time of day such as 00:10, or once at a time into the run such as 10m. Can be given
+    /// more than once
+    #[arg(long)]
+    silence_at: Vec<SilenceAt>,
+
+    /// How long each --silence-at window lasts
+    #[arg(long, value_parser = units::secs_f64, default_value = "60")]
+    silence_for: f64,
+
+    /// Where the data comes from: `synthetic`, or live traffic from the `opensky` REST API
+    /// or a local `dump1090` receiver. In live mode --rate-per-plane and --plane-count are ignored
+    #[arg(long, default_value = "synthetic")]
+    source: Source,
+
+    /// Overrides the default OpenSky or dump1090 URL, e.g. to add a bounding box
+    #[arg(long)]
+    live_url: Option<String>,
+
+    /// Seconds between live data polls
+    #[arg(long, value_parser = units::secs, default_value = "10")]
+    live_poll_secs: u64,
 }
 
-#[derive(Clone)]
-struct PlaneData {
-    plane_id: String,
-    timestamp: i64,
-    airspeed: f64,
-    altitude: f64,
-    pitch: f64,
-    roll: f64,
-    yaw: f64,
-    aoa: f64,
-    oat: f64,
-}
-
-impl PlaneData {
-    fn new(plane_id: String) -> Self {
-        let mut rng = rand::thread_rng();
-        PlaneData {
-            plane_id,
-            timestamp: Utc::now().timestamp_nanos_opt().unwrap(),
-            airspeed: rng.gen_range(200.0..300.0),
-            altitude: rng.gen_range(30000.0..40000.0),
-            pitch: rng.gen_range(-10.0..10.0),
-            roll: rng.gen_range(-10.0..10.0),
-            yaw: rng.gen_range(-10.0..10.0),
-            aoa: rng.gen_range(0.0..15.0),
-            oat: rng.gen_range(-60.0..20.0),
-        }
-    }
-
-    fn update(&mut self) {
-        let mut rng = rand::thread_rng();
-        self.timestamp = Utc::now().timestamp_nanos_opt().unwrap();
-        self.airspeed = (self.airspeed + rng.gen_range(-1.0..1.0)).clamp(200.0, 300.0);
-        self.altitude = (self.altitude + rng.gen_range(-10.0..10.0)).clamp(30000.0, 40000.0);
-        self.pitch = (self.pitch + rng.gen_range(-1.0..1.0)).clamp(-10.0, 10.0);
-        self.roll = (self.roll + rng.gen_range(-1.0..1.0)).clamp(-10.0, 10.0);
-        self.yaw = (self.yaw + rng.gen_range(-1.0..1.0)).clamp(-10.0, 10.0);
-        self.aoa = (self.aoa + rng.gen_range(-0.5..0.5)).clamp(0.0, 15.0);
-        self.oat = (self.oat + rng.gen_range(-1.0..1.0)).clamp(-60.0, 20.0);
-    }
-}
-
-fn generate_plane_id(starting_id: &str, n: u32) -> String {
-    let letters = &starting_id[..2];
-    let digits = starting_id[2..].parse::<u32>().unwrap() + n;
-    format!("{}{:02}", letters, digits)
-}
-
-async fn generate_data(
-    sender: Arc<tokio::sync::Mutex<Sender>>,
-    plane_id: String,
-    rate: u64,
-    total_rows: Arc<AtomicU64>,
-    sem: Arc<Semaphore>,
-    table_name: Arc<String>,
-    quiet: bool,
-    batch_size: usize, // Batch size per plane
-) {
-    let mut plane_data = PlaneData::new(plane_id);
-    let interval_duration = if rate > 0 { 1000 / rate } else { 1 }; // Set minimum interval duration to 1 ms
-    let mut interval = interval(Duration::from_millis(interval_duration.max(1)));
-    let mut rows_generated = 0;
-    let mut buffer = Buffer::new();
+/// Helper commands, run instead of the simulator when given as the first argument.
+#[derive(Parser, Debug)]
+#[command(name = "flight-data-generator", version)]
+enum Command {
+    /// Print a Grafana dashboard for the table the simulator writes to
+    GrafanaDashboard(grafana::DashboardOpt),
+    /// List the most recent runs saved with --save-run, with their key metrics
+    History(runs::HistoryOpt),
+    /// Stream the rows of a CSV file into QuestDB at a target rate
+    Load(load::LoadOpt),
+    /// Write the table, example queries and Grafana dashboard of a --model
+    Scaffold(scaffold::ScaffoldOpt),
+    /// Run a suite of benchmarks one after the other and compare their results
+    #[command(subcommand)]
+    Suite(suite::SuiteCommand),
+    /// Print the completion script of a shell: bash, zsh, fish, powershell or elvish
+    Completions {
+        #[arg(value_enum, ignore_case = true)]
+        shell: Shell,
+    },
+}
 
-    loop {
-        interval.tick().await;
+const COMMANDS: &[&str] = &["grafana-dashboard", "history", "load", "scaffold", "suite", "completions"];
 
-        if total_rows.load(Ordering::SeqCst) == 0 {
-            break;
-        }
+const COMMANDS_HELP: &str = "COMMANDS:
+    grafana-dashboard    Print a Grafana dashboard for the table the simulator writes to
+    history              List the most recent runs saved with --save-run, with their key metrics
+    load                 Stream the rows of a CSV file into QuestDB at a target rate
+    scaffold             Write the table, example queries and Grafana dashboard of a --model
+    suite                Run a suite of benchmarks one after the other and compare their results
+    completions          Print the completion script of a shell: bash, zsh, fish, powershell or elvish
 
-        plane_data.update();
-        rows_generated += 1;
-        let remaining_rows = total_rows.fetch_sub(1, Ordering::SeqCst);
-
-        buffer.table(table_name.as_str()).unwrap()
-            .symbol("plane_id", &plane_data.plane_id).unwrap()
-            .column_f64("airspeed", plane_data.airspeed).unwrap()
-            .column_f64("altitude", plane_data.altitude).unwrap()
-            .column_f64("pitch", plane_data.pitch).unwrap()
-            .column_f64("roll", plane_data.roll).unwrap()
-            .column_f64("yaw", plane_data.yaw).unwrap()
-            .column_f64("aoa", plane_data.aoa).unwrap()
-            .column_f64("oat", plane_data.oat).unwrap()
-            .at(TimestampNanos::new(plane_data.timestamp)).unwrap();
-
-        // Flush buffer when batch size is reached or if it's the last batch
-        if rows_generated % batch_size == 0 || remaining_rows == 1 {
-            let _permit = sem.acquire().await.unwrap();
-            let mut sender = sender.lock().await;
-            if !quiet {
-                match sender.flush(&mut buffer) {
-                    Ok(_) => println!("Successfully flushed buffer for plane {} with {} rows", plane_data.plane_id, rows_generated),
-                    Err(e) => eprintln!("Failed to flush buffer for plane {}: {}", plane_data.plane_id, e),
-                }
-            } else {
-                let _ = sender.flush(&mut buffer);
-            }
-        }
+Run `<command> --help` for the options of a command.";
 
-        if remaining_rows == 1 {
-            break;
+/// Runs a helper command if the first argument names one, then exits.
+fn run_command() {
+    if !std::env::args().nth(1).map(|arg| COMMANDS.contains(&arg.as_str())).unwrap_or(false) {
+        return;
+    }
+    let result = match Command::parse() {
+        Command::GrafanaDashboard(opt) => grafana::run(&opt),
+        Command::History(opt) => runs::run(&opt),
+        Command::Load(opt) => load::run(&opt),
+        Command::Scaffold(opt) => scaffold::run(&opt),
+        Command::Suite(suite::SuiteCommand::Run(opt)) => suite::run(&opt),
+        Command::Completions { shell } => {
+            print_completions(shell);
+            Ok(())
         }
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
+}
+
+/// Completes the options of the simulator and the helper commands with their own options.
+fn print_completions(shell: Shell) {
+    let mut app = Opt::command()
+        .subcommand(grafana::DashboardOpt::command().name("grafana-dashboard"))
+        .subcommand(runs::HistoryOpt::command().name("history"))
+        .subcommand(load::LoadOpt::command().name("load"))
+        .subcommand(scaffold::ScaffoldOpt::command().name("scaffold"))
+        .subcommand(suite::SuiteCommand::augment_subcommands(clap::Command::new("suite")))
+        .subcommand(clap::Command::new("completions")
+            .arg(clap::Arg::new("shell").value_parser(clap::builder::EnumValueParser::<Shell>::new())));
+    clap_complete::generate(shell, &mut app, env!("CARGO_PKG_NAME"), &mut std::io::stdout());
+}
+
+/// Interval between two rows of a plane, at least 1 ms
+fn tick_millis(rate_per_plane: u64) -> u64 {
+    1000u64.checked_div(rate_per_plane).unwrap_or(1).max(1)
+}
+
+impl Opt {
+    fn tick_millis(&self) -> u64 {
+        tick_millis(self.rate_per_plane)
     }
 
-    // Flush any remaining rows in the buffer
-    if buffer.len() > 0 {
-        let _permit = sem.acquire().await.unwrap();
-        let mut sender = sender.lock().await;
-        let _ = sender.flush(&mut buffer);
+    /// How long connection `index` of `count` waits with --connect-stagger-ms
+    fn connect_delay(&self, index: usize, count: usize) -> Duration {
+        let window = self.connect_stagger_ms.unwrap_or(0);
+        Duration::from_millis(window * index as u64 / count.max(1) as u64)
     }
 
-    if !quiet {
-        println!("Plane {} generated {} rows.", plane_data.plane_id, rows_generated);
+    /// Fills in the settings given in more than one way: the rows as --total-rows or
+    /// --duration, the rate per plane or in total, the table as --table-name or --model.
+    fn resolve(&mut self) {
+        self.total_rows = self.total_rows_arg.unwrap_or(u64::MAX);
+        self.table_name = self.table_name_arg.clone()
+            .or_else(|| model::find(&self.model).map(|kind| kind.table.to_string()))
+            .unwrap_or_default();
+        self.rate_per_plane = match (self.rate_per_plane_arg, self.total_rate) {
+            (Some(rate), _) => rate,
+            (None, Some(total)) => self.per_plane_rate(total),
+            (None, None) => 0,
+        };
+    }
+
+    /// Reads the --config file, and takes the sink of the run from it when --sink and
+    /// --sink-path do not say.
+    fn load_config(&mut self) -> std::result::Result<Config, String> {
+        let config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        let sink = &config.sink;
+        let parsed = |value: &Option<String>, key: &str, parse: fn(&str) -> std::result::Result<u64, String>| {
+            value.as_deref().map(parse).transpose().map_err(|e| format!("invalid {} in the [sink] of the config file: {}", key, e))
+        };
+        self.sink = self.sink.take().or_else(|| sink.kind.clone());
+        self.sink_path = self.sink_path.take().or_else(|| sink.path.clone());
+        self.sink_compression = match self.sink_compression {
+            Some(compression) => Some(compression),
+            None => sink.compression.as_deref().map(str::parse).transpose()
+                .map_err(|e| format!("invalid compression in the [sink] of the config file: {}", e))?,
+        };
+        self.sink_rotate_size = self.sink_rotate_size.or(parsed(&sink.rotate_size, "rotate_size", units::bytes)?);
+        self.sink_rotate_every = self.sink_rotate_every.or(parsed(&sink.rotate_every, "rotate_every", units::secs)?);
+        self.sink_part_size = self.sink_part_size.or(parsed(&sink.part_size, "part_size", units::bytes)?);
+        Ok(config)
     }
-}
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let opt = Opt::from_args();
-    let connection_string = opt.connection_string.clone();
-    let sender = Arc::new(
-        tokio::sync::Mutex::new(Sender::from_conf(&connection_string)?),
-    );
-    let total_rows = Arc::new(AtomicU64::new(opt.total_rows));
-    let rate_per_plane = opt.rate_per_plane;
-    let plane_count = opt.plane_count;
-    let sem = Arc::new(Semaphore::new(plane_count as usize * opt.batch_size));
-    let table_name = Arc::new(opt.table_name.clone());
-    let starting_plane_id = opt.starting_plane_id.clone();
-    let quiet = opt.quiet;
-    let batch_size = opt.batch_size;
+    /// The --sink-path, when it is the URL of a bucket rather than a directory
+    fn sink_url(&self) -> Option<&str> {
+        self.sink_path.as_deref().and_then(|path| path.to_str()).filter(|path| path.contains("://"))
+    }
 
-    let mut tasks = vec![];
+    /// The --sink of the run
+    fn sink(&self) -> &str {
+        self.sink.as_deref().unwrap_or(sink::QUESTDB)
+    }
 
-    for plane_id in 0..plane_count {
-        let sender = sender.clone();
-        let total_rows = total_rows.clone();
-        let sem = sem.clone();
-        let table_name = table_name.clone();
-        let plane_id_str = generate_plane_id(&starting_plane_id, plane_id);
-        tasks.push(tokio::spawn(generate_data(sender, plane_id_str, rate_per_plane, total_rows, sem, table_name, quiet, batch_size)));
+    /// Expands the environment variables of the connection strings and adds the secrets
+    /// of --token-file and --password-file
+    fn read_secrets(&mut self) -> std::result::Result<(), String> {
+        self.connection_string = secrets::connection_string(&self.connection_string, self.token_file.as_deref(), self.password_file.as_deref())?;
+        if let Some(mirror) = &self.mirror_connection_string {
+            self.mirror_connection_string = Some(secrets::expand(mirror).map_err(|e| format!("--mirror-connection-string: {}", e))?);
+        }
+        Ok(())
     }
 
-    join_all(tasks).await;
+    /// Rate of each plane for a total rate: split evenly, or so the skewed rates add up to it
+    fn per_plane_rate(&self, total: u64) -> u64 {
+        match self.plane_skew {
+            Some(exponent) => (total as f64 / skew::total_weight(exponent, self.plane_count.max(1) as u64)).round() as u64,
+            None => total / self.plane_count.max(1) as u64,
+        }
+    }
 
-    let generated_rows = opt.total_rows - total_rows.load(Ordering::SeqCst);
-    println!("Data generation completed. Total rows generated: {}", generated_rows);
+    /// Checks the arguments make sense together. Returns the errors that prevent the run
+    /// from starting and warnings about settings that probably do not do what was intended.
+    fn validate(&self) -> (Vec<String>, Vec<String>) {
+        let mut errors = vec![];
+        let mut warnings = vec![];
+        let synthetic = self.source == Source::Synthetic;
 
-    Ok(())
+        if self.total_rows == 0 {
+            errors.push("--total-rows must be greater than 0".to_string());
+        }
+        if let Some(duration) = self.duration.filter(|duration| *duration <= 0.0) {
+            errors.push(format!("--duration must be longer than 0, got {}s", duration));
+        }
+        if let Some(exponent) = self.plane_skew {
+            if exponent <= 0.0 || exponent.is_nan() {
+                errors.push(format!("--plane-skew must be more than 0, leave it out for the same rate on every plane, got {}", exponent));
+            }
+            if !synthetic {
+                errors.push("--plane-skew only works with synthetic flight data".to_string());
+            }
+        }
+        if let Some(total) = self.total_rate.filter(|_| synthetic && self.plane_skew.is_none()) {
+            let planes = self.plane_count.max(1) as u64;
+            if total < planes {
+                errors.push(format!("--total-rate {} is less than 1 row/s for each of the {} planes", total, planes));
+            } else if total % planes != 0 {
+                warnings.push(format!("--total-rate {} does not split evenly between {} planes, sending {} rows/s each, {} in total",
+                                      total, planes, self.rate_per_plane, self.rate_per_plane * planes));
+            }
+        }
+        if self.net_bytes_per_sec == Some(0) {
+            errors.push("--net-bytes-per-sec must be at least 1, leave it out for no bandwidth limit".to_string());
+        }
+        if !(0.0..100.0).contains(&self.net_loss_pct) {
+            errors.push(format!("--net-loss-pct must be at least 0 and less than 100, got {}", self.net_loss_pct));
+        }
+        if self.net_jitter_ms > self.net_latency_ms {
+            warnings.push(format!("--net-jitter-ms {} is more than --net-latency-ms {}, some flushes get no latency at all",
+                                  self.net_jitter_ms, self.net_latency_ms));
+        }
+        if self.max_mem_mb == Some(0) {
+            errors.push("--max-mem-mb must be at least 1, leave it out for no memory limit".to_string());
+        }
+        if let Some(pct) = self.max_cpu_pct.filter(|pct| *pct <= 0.0 || pct.is_nan()) {
+            errors.push(format!("--max-cpu-pct must be more than 0, got {}", pct));
+        }
+        if self.on_resource_limit == OnLimit::Abort && self.max_mem_mb.is_none() && self.max_cpu_pct.is_none() {
+            warnings.push("--on-resource-limit abort without --max-mem-mb or --max-cpu-pct does nothing".to_string());
+        }
+        if self.max_rate_per_conn == Some(0) || self.max_total_rate == Some(0) {
+            errors.push("--max-rate-per-conn and --max-total-rate must be at least 1 row/s, leave them out for no limit".to_string());
+        }
+        if let (Some(per_conn), Some(total)) = (self.max_rate_per_conn, self.max_total_rate) {
+            if per_conn >= total {
+                warnings.push(format!("--max-rate-per-conn {} is no lower than --max-total-rate {}, it never applies", per_conn, total));
+            }
+        }
+        if self.keep_runs == 0 {
+            errors.push("--keep-runs must be at least 1".to_string());
+        }
+        match &self.table_name_arg {
+            Some(table) if table.trim().is_empty() => errors.push("--table-name cannot be empty".to_string()),
+            None if self.table_name.is_empty() => errors.push("--table-name is needed for the planes, only the other --model choices have a table of their own".to_string()),
+            _ => {}
+        }
+        if self.batch_size == 0 {
+            errors.push("--batch-size must be at least 1 row per plane".to_string());
+        }
+        if self.auto_flush_rows == Some(0) || self.auto_flush_interval_ms == Some(0) {
+            errors.push("--auto-flush-rows and --auto-flush-interval-ms must be at least 1, leave them out to turn them off".to_string());
+        }
+        if self.auto_batch {
+            if self.warmup_secs == 0 {
+                errors.push("--auto-batch tries batch sizes during the warm-up, set --warmup-secs".to_string());
+            }
+            if self.auto_flush_rows.is_some() {
+                errors.push("--auto-batch chooses the rows per flush, it cannot be combined with --auto-flush-rows".to_string());
+            }
+            if !synthetic {
+                errors.push("--auto-batch only works with synthetic data, live sources flush every poll".to_string());
+            }
+        }
+        if (self.auto_flush_rows.is_some() || self.auto_flush_interval_ms.is_some()) && !synthetic {
+            errors.push("--auto-flush-rows and --auto-flush-interval-ms only work with synthetic data, live sources flush every poll".to_string());
+        }
+        if self.sync && !synthetic {
+            errors.push("--sync only works with synthetic data, live sources need the async engine".to_string());
+        }
+        if !(0.0..=100.0).contains(&self.poison_pct) {
+            errors.push(format!("--poison-pct must be between 0 and 100, got {}", self.poison_pct));
+        }
+        if self.tui && self.daemon {
+            errors.push("--tui needs a terminal, it cannot be used with --daemon".to_string());
+        } else if self.tui && !std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+            errors.push("--tui needs a terminal, standard output is not one".to_string());
+        }
+        if let Some(table) = &self.companion_table {
+            if table.trim().is_empty() || *table == self.table_name {
+                errors.push("--companion-table needs a name of its own, different from --table-name".to_string());
+            }
+            if self.companion_ratio == 0 {
+                errors.push("--companion-ratio must be at least 1".to_string());
+            }
+            if self.mimic_table.is_some() || !synthetic {
+                errors.push("--companion-table only works with the simulated flight data".to_string());
+            }
+        }
+        if let Some(table) = &self.status_table {
+            if table.trim().is_empty() || *table == self.table_name || Some(table) == self.companion_table.as_ref() {
+                errors.push("--status-table needs a name of its own, different from --table-name and --companion-table".to_string());
+            }
+            if self.status_every_secs <= 0.0 {
+                errors.push("--status-every-secs must be positive".to_string());
+            }
+            if self.mimic_table.is_some() || !synthetic {
+                errors.push("--status-table only works with the simulated flight data".to_string());
+            }
+        }
+        if let Some(table) = &self.legs_table {
+            let others = [Some(&self.table_name), self.companion_table.as_ref(), self.status_table.as_ref()];
+            if table.trim().is_empty() || others.contains(&Some(table)) {
+                errors.push("--legs-table needs a name of its own, different from --table-name, --companion-table and --status-table".to_string());
+            }
+            if self.mimic_table.is_some() || !synthetic {
+                errors.push("--legs-table only works with the simulated flight data".to_string());
+            }
+        }
+        if self.partition_rollover.is_some() {
+            // Past half an hour, the windows around consecutive HOUR boundaries would overlap
+            if self.rollover_window_secs <= 0.0 || self.rollover_window_secs > 1800.0 {
+                errors.push("--rollover-window-secs must be positive and at most 1800".to_string());
+            }
+            if !synthetic {
+                errors.push("--partition-rollover only works with the simulated flight data, live positions keep their own timestamps".to_string());
+            }
+        }
+        if let Some(hours) = self.backfill_hours {
+            if hours <= 0.0 {
+                errors.push("--backfill-hours must be positive".to_string());
+            }
+            if self.backfill_speed <= 1.0 {
+                errors.push("--backfill-speed must be more than 1, or the clock never catches up".to_string());
+            }
+            if self.partition_rollover.is_some() {
+                errors.push("--backfill-hours and --partition-rollover both set the row timestamps, pick one".to_string());
+            }
+            if !synthetic {
+                errors.push("--backfill-hours only works with the simulated flight data, live positions keep their own timestamps".to_string());
+            }
+        }
+        match (self.backfill_workers, self.backfill_hours) {
+            (Some(0), _) => errors.push("--backfill-workers must be at least 1".to_string()),
+            (Some(_), None) => errors.push("--backfill-workers needs --backfill-hours".to_string()),
+            _ => {}
+        }
+        if let Some(hours) = self.retention_hours {
+            if hours <= 0.0 {
+                errors.push("--retention-hours must be positive".to_string());
+            }
+            if self.retention_every_secs == 0 {
+                errors.push("--retention-every-secs must be at least 1".to_string());
+            }
+            if let Some(backfill) = self.backfill_hours.filter(|backfill| *backfill <= hours) {
+                warnings.push(format!("--backfill-hours {} does not reach past --retention-hours {}, no partition will be old enough to drop", backfill, hours));
+            }
+        }
+        if let Some(view) = &self.mat_view {
+            if view.trim().is_empty() || *view == self.table_name {
+                errors.push("--mat-view needs a name of its own, different from --table-name".to_string());
+            }
+            if self.mat_view_every_secs == 0 {
+                errors.push("--mat-view-every-secs must be at least 1".to_string());
+            }
+            if self.schema != Schema::Wide || self.mimic_table.is_some() || !synthetic {
+                errors.push("--mat-view aggregates the airspeed column, it needs the simulated flight data in the wide schema".to_string());
+            }
+        }
+        if self.query_workers > 0 && self.queries_file.is_none() && self.mimic_table.is_some() {
+            errors.push("--query-workers needs a --queries-file with --mimic-table, the example queries are for the flight data columns".to_string());
+        }
+        if self.emit_queries.is_some() && self.mimic_table.is_some() {
+            errors.push("--emit-queries writes queries for the flight data columns, it cannot be used with --mimic-table".to_string());
+        }
+        if let Some(path) = &self.trajectory_file {
+            if let Err(e) = TrajectoryFormat::from_path(path) {
+                errors.push(format!("--trajectory-file: {}", e));
+            }
+            if !synthetic {
+                errors.push("--trajectory-file only works with --source synthetic".to_string());
+            }
+            if self.trajectory_every_secs <= 0.0 || self.trajectory_every_secs.is_nan() {
+                errors.push("--trajectory-every-secs must be greater than 0".to_string());
+            }
+        }
+        if self.tui && !synthetic {
+            errors.push("--tui only works with --source synthetic".to_string());
+        }
+        if self.run_label.as_deref().map(|label| label.trim().is_empty()).unwrap_or(false) {
+            errors.push("--run-label cannot be empty".to_string());
+        }
+        if self.dropout_every_secs.map(|every| every <= 0.0 || every.is_nan()).unwrap_or(false)
+            || self.dropout_secs <= 0.0 || self.dropout_secs.is_nan() {
+            errors.push("--dropout-every-secs and --dropout-secs must be greater than 0".to_string());
+        }
+        if self.type_flip_every == Some(0) {
+            errors.push("--type-flip-every must be at least 1 batch".to_string());
+        }
+        if self.senders == 0 {
+            errors.push("--senders must be at least 1".to_string());
+        }
+        if self.model != model::PLANES {
+            if model::find(&self.model).is_none() {
+                errors.push(format!("unknown --model '{}', expected one of: {}", self.model, model::describe()));
+            }
+            let plane_only = [
+                (!synthetic, "--source"), (self.schema == Schema::Narrow, "--schema narrow"), (self.config.is_some(), "--config"),
+                (self.mimic_table.is_some(), "--mimic-table"), (matches!(self.preflight, Preflight::Probe | Preflight::Scratch), "--preflight probe and scratch"),
+                (self.sync, "--sync"), (self.backfill_workers.is_some(), "--backfill-workers"), (self.tui, "--tui"), (self.estimate, "--estimate"),
+                (self.emit_queries.is_some() || self.query_workers > 0, "--emit-queries and --query-workers"), (self.replay_plane.is_some(), "--replay-plane"),
+                (self.seeds_file.is_some(), "--seeds-file"), (self.trajectory_file.is_some(), "--trajectory-file"), (self.plane_skew.is_some(), "--plane-skew"),
+                (self.companion_table.is_some(), "--companion-table"), (self.status_table.is_some(), "--status-table"), (self.legs_table.is_some(), "--legs-table"),
+                (self.formations.is_some(), "--formations"), (self.seasonality, "--seasonality"), (!self.silence_at.is_empty(), "--silence-at"),
+                (self.dropout_every_secs.is_some(), "--dropout-every-secs"), (self.type_flip_every.is_some(), "--type-flip-every"),
+                (!self.add_column_at.is_empty(), "--add-column-at"), (!self.drop_column_at.is_empty(), "--drop-column-at"),
+                (self.poison_pct > 0.0, "--poison-pct"), (self.outliers_pct > 0.0, "--outliers-pct"), (self.quality_flags, "--quality-flags"),
+                (self.spectrum_bins.is_some(), "--spectrum-bins"), (self.cold_writes_pct > 0.0, "--cold-writes-pct"), (self.monotonic_per_plane, "--monotonic-per-plane"), (self.server_timestamps, "--server-timestamps"),
+                (self.rate_schedule.is_some(), "--rate-schedule"),
+            ];
+            for (_, option) in plane_only.iter().filter(|(set, _)| *set) {
+                errors.push(format!("{} only applies to planes, not to --model {}", option, self.model));
+            }
+        }
+        if self.rate_schedule.is_some() {
+            if self.config.is_none() {
+                errors.push("--rate-schedule needs a --config file with the schedule".to_string());
+            }
+            if !synthetic {
+                errors.push("--rate-schedule only works with synthetic flight data".to_string());
+            }
+            if self.tui {
+                errors.push("--rate-schedule and --tui cannot be used together, the keys of the terminal UI set the rate".to_string());
+            }
+        }
+        if !self.sweep.is_empty() {
+            if self.sweep.len() > 2 {
+                errors.push(format!("--sweep goes over one or two options, got {}", self.sweep.len()));
+            }
+            if self.duration.is_none() && !self.sweep.iter().any(|sweep| sweep.name == "duration") {
+                errors.push("--sweep needs a --duration for each run".to_string());
+            }
+            let sets = [(self.report_json.is_some(), "--report-json"), (self.daemon, "--daemon"), (self.tui, "--tui")];
+            for (_, option) in sets.iter().filter(|(set, _)| *set) {
+                errors.push(format!("{} cannot be used with --sweep, which writes a report of each run next to --sweep-csv", option));
+            }
+        }
+        if self.watch_config && self.config.is_none() {
+            errors.push("--watch-config needs a --config file".to_string());
+        }
+        match self.sample_interval_ms {
+            Some(_) if self.model != "weather" => errors.push(format!("--sample-interval-ms only applies to --model weather, not to --model {}", self.model)),
+            Some(0) => errors.push("--sample-interval-ms must be at least 1".to_string()),
+            _ => {}
+        }
+        if self.model != model::PLANES && self.plane_count > 1000 && self.workers.is_none() {
+            warnings.push(format!("--model {} with {} devices runs a task and a batch for each of them, --workers shares them out over fewer",
+                                  self.model, self.plane_count));
+        }
+        if self.tui && !cfg!(feature = "tui") {
+            errors.push("--tui needs the tui feature, this airplanes was built without it".to_string());
+        }
+        if self.query_workers > 0 && !cfg!(feature = "query-load") {
+            errors.push("--query-workers needs the query-load feature, this airplanes was built without it".to_string());
+        }
+        if self.profile.is_some() && !cfg!(feature = "profile") {
+            errors.push("--profile needs the profile feature, this airplanes was built without it".to_string());
+        }
+        if sink::find(self.sink()).is_none() {
+            errors.push(format!("unknown --sink '{}', expected one of: {}", self.sink(), sink::describe()));
+        }
+        if self.sink() == sink::QUESTDB && self.connection_string.is_empty() {
+            errors.push("--connection-string is needed to send the rows to QuestDB".to_string());
+        }
+        if self.sink() == sink::CSV && self.sink_path.is_none() {
+            errors.push("--sink csv needs a --sink-path directory, or a path in the [sink] of the --config file".to_string());
+        }
+        if self.sink() != sink::CSV && (self.sink_compression.is_some() || self.sink_rotate_size.is_some() || self.sink_rotate_every.is_some()) {
+            errors.push(format!("--sink-compression, --sink-rotate-size and --sink-rotate-every only apply to --sink csv, not {}", self.sink()));
+        }
+        if self.sink_rotate_size == Some(0) || self.sink_rotate_every == Some(0) {
+            errors.push("--sink-rotate-size and --sink-rotate-every must be more than 0".to_string());
+        }
+        if let Some(url) = self.sink_url().filter(|_| self.sink() == sink::CSV && !cfg!(feature = "object-store")) {
+            errors.push(format!("--sink-path {} needs the object-store feature, this airplanes was built without it", url));
+        }
+        if self.sink_part_size.is_some() && (self.sink() != sink::CSV || self.sink_url().is_none()) {
+            errors.push("--sink-part-size only applies to --sink csv with a bucket URL as --sink-path".to_string());
+        }
+        if self.sink_part_size.is_some_and(|size| size < sink::MIN_PART_SIZE) {
+            errors.push("--sink-part-size must be at least 5MiB, the smallest part S3 takes".to_string());
+        }
+        if self.sink() != sink::QUESTDB {
+            // The threads of the blocking engine send with senders of their own, not through the sink
+            let server_only = [
+                (self.sync || self.backfill_workers.is_some(), "--sync and --backfill-workers"), (self.mimic_table.is_some(), "--mimic-table"),
+                (self.mirror_connection_string.is_some(), "--mirror-connection-string"), (self.partition_rollover.is_some(), "--partition-rollover"),
+                (matches!(self.preflight, Preflight::Probe | Preflight::Scratch), "--preflight probe and scratch"), (self.storage_report, "--storage-report"),
+                (self.mat_view.is_some(), "--mat-view"), (self.retention_hours.is_some(), "--retention-hours"), (self.query_workers > 0, "--query-workers"),
+                (self.protocol_version.is_some(), "--protocol-version"),
+            ];
+            for (_, option) in server_only.iter().filter(|(set, _)| *set) {
+                errors.push(format!("{} cannot be used with --sink {}, the rows do not go to QuestDB", option, self.sink()));
+            }
+        }
+        if self.sync && (self.senders > 1 || self.sender_keepalive_secs.is_some()) {
+            warnings.push("--senders and --sender-keepalive-secs have no effect with --sync, each thread has a connection of its own".to_string());
+        }
+        if self.reconnect_backoff_ms > self.reconnect_max_backoff_ms {
+            errors.push("--reconnect-backoff-ms cannot be more than --reconnect-max-backoff-ms".to_string());
+        }
+        if self.connect_stagger_ms.is_some() && !self.sync && self.backfill_workers.is_none() {
+            warnings.push("--connect-stagger-ms has no effect without --sync or --backfill-workers, the async generators share the --senders pool".to_string());
+        }
+        if self.workers == Some(0) {
+            errors.push("--workers must be at least 1".to_string());
+        }
+        if self.rate_bucket_secs == 0 {
+            errors.push("--rate-bucket-secs must be at least 1 second".to_string());
+        }
+        if self.seasonality {
+            if !synthetic || self.mimic_table.is_some() {
+                errors.push("--seasonality only works with synthetic flight data".to_string());
+            }
+            for (name, pct) in [("--night-traffic-pct", self.night_traffic_pct), ("--weekend-traffic-pct", self.weekend_traffic_pct)] {
+                if !(0.0..=100.0).contains(&pct) {
+                    errors.push(format!("{} must be between 0 and 100, got {}", name, pct));
+                }
+            }
+            if self.day_temperature_swing < 0.0 || self.day_temperature_swing.is_nan() {
+                errors.push(format!("--day-temperature-swing must be 0 or more, got {}", self.day_temperature_swing));
+            }
+            if self.backfill_hours.is_none() && self.partition_rollover.is_none() {
+                warnings.push("--seasonality follows the time of day and year, it shows best over a --backfill-hours range of days".to_string());
+            }
+        }
+        if (self.seeds_file.is_some() || self.replay_plane.is_some()) && (!synthetic || self.mimic_table.is_some()) {
+            errors.push("--seeds-file and --replay-plane only work with synthetic flight data".to_string());
+        }
+        if self.replay_plane.is_some() && self.seed.is_none() {
+            errors.push("--replay-plane needs the --seed of the run to replay, it is in its --seeds-file".to_string());
+        }
+        if self.mirror_connection_string.as_ref() == Some(&self.connection_string) {
+            warnings.push("--mirror-connection-string is the --connection-string, every row is sent twice to the same server".to_string());
+        }
+        if let Some(fraction) = self.sample_log {
+            if fraction <= 0.0 || fraction > 1.0 || fraction.is_nan() {
+                errors.push(format!("--sample-log is a fraction of the rows, more than 0 and at most 1, got {}", fraction));
+            }
+            if !synthetic {
+                errors.push("--sample-log only works with synthetic flight data".to_string());
+            }
+            if self.tui && self.sample_log_file.is_none() {
+                errors.push("--sample-log would print over the terminal UI, send it to a --sample-log-file".to_string());
+            }
+        } else if self.sample_log_file.is_some() {
+            errors.push("--sample-log-file needs --sample-log".to_string());
+        }
+        if !self.silence_at.is_empty() {
+            if !synthetic {
+                errors.push("--silence-at only works with synthetic flight data".to_string());
+            }
+            if self.silence_for <= 0.0 {
+                errors.push("--silence-for must be positive".to_string());
+            } else if self.silence_for >= 86_400.0 && self.silence_at.iter().any(|at| matches!(at, SilenceAt::TimeOfDay(_))) {
+                warnings.push("--silence-for of a day or more with a time of day in --silence-at silences the planes for good".to_string());
+            }
+        }
+        if let Some(formations) = self.formations {
+            if formations == 0 {
+                errors.push("--formations must be at least 1".to_string());
+            }
+            if !synthetic || self.mimic_table.is_some() {
+                errors.push("--formations only works with synthetic flight data".to_string());
+            }
+            if !(self.formation_event_secs > 0.0 && self.formation_event_secs <= self.formation_event_every_secs) {
+                errors.push(format!("--formation-event-secs must be more than 0 and at most --formation-event-every-secs {}, got {}",
+                                    self.formation_event_every_secs, self.formation_event_secs));
+            }
+            if self.formation_intensity < 0.0 || self.formation_intensity.is_nan() {
+                errors.push(format!("--formation-intensity must be 0 or more, got {}", self.formation_intensity));
+            }
+        }
+        if self.turbulence < 0.0 || self.turbulence.is_nan() {
+            errors.push(format!("--turbulence must be 0 or more, got {}", self.turbulence));
+        }
+        if synthetic && self.plane_count == 0 {
+            errors.push("--plane-count must be at least 1".to_string());
+        }
+
+        if synthetic && self.rate_per_plane == 0 && self.total_rate.is_none() && self.rate_schedule.is_none() {
+            warnings.push("--rate-per-plane 0 means as fast as the 1 ms tick allows, 1000 rows/s per plane".to_string());
+        } else if synthetic && self.rate_per_plane > 1000 {
+            warnings.push(format!(
+                "--rate-per-plane {} is above 1000 rows/s, the maximum for a single plane with 1 ms ticks. \
+                 For more throughput use more planes, e.g. --plane-count {} --rate-per-plane 1000",
+                self.rate_per_plane,
+                self.plane_count as u64 * self.rate_per_plane.div_ceil(1000)
+            ));
+        } else if synthetic && self.rate_per_plane > 0 && 1000 % self.rate_per_plane != 0 {
+            warnings.push(format!(
+                "--rate-per-plane {} does not divide 1000 ms evenly, the actual rate will be {:.1} rows/s per plane",
+                self.rate_per_plane,
+                1000.0 / (1000 / self.rate_per_plane) as f64
+            ));
+        }
+        let expected_secs = (self.total_rows as f64 / (self.plane_count.max(1) as f64 * 1000.0 / self.tick_millis() as f64))
+            .min(self.duration.unwrap_or(f64::INFINITY));
+        if synthetic && self.warmup_secs > 0 && self.warmup_secs as f64 >= expected_secs {
+            warnings.push(format!(
+                "--warmup-secs {} is longer than the expected run of {:.0}s, no rows will be measured",
+                self.warmup_secs, expected_secs
+            ));
+        }
+        if synthetic && self.plane_count as u64 > self.total_rows {
+            warnings.push(format!(
+                "--plane-count {} is more than --total-rows {}, some planes will not send any row",
+                self.plane_count, self.total_rows
+            ));
+        }
+        if !(0.0..=100.0).contains(&self.cold_writes_pct) {
+            errors.push(format!("--cold-writes-pct must be between 0 and 100, got {}", self.cold_writes_pct));
+        }
+        if self.cold_writes_pct > 0.0 {
+            if self.cold_writes_days < 1.0 {
+                errors.push(format!("--cold-writes-days must be at least 1, got {}", self.cold_writes_days));
+            }
+            if !synthetic {
+                errors.push("--cold-writes-pct only works with the simulated flight data".to_string());
+            }
+        }
+        if !(0.0..=100.0).contains(&self.outliers_pct) {
+            errors.push(format!("--outliers-pct must be between 0 and 100, got {}", self.outliers_pct));
+        }
+        if self.outlier_sigmas <= 0.0 || self.outlier_sigmas.is_nan() {
+            errors.push(format!("--outlier-sigmas must be greater than 0, got {}", self.outlier_sigmas));
+        }
+        if self.quality_flags && !synthetic {
+            errors.push("--quality-flags only works with the simulated flight data".to_string());
+        } else if self.quality_flags && self.mimic_table.is_some() {
+            warnings.push("--quality-flags is ignored with --mimic-table".to_string());
+        }
+        if let Some(bins) = self.spectrum_bins {
+            if !(1..=spectrum::MAX_BINS).contains(&bins) {
+                errors.push(format!("--spectrum-bins must be between 1 and {}, got {}", spectrum::MAX_BINS, bins));
+            }
+            if !synthetic {
+                errors.push("--spectrum-bins only works with the simulated flight data".to_string());
+            } else if self.schema == Schema::Narrow {
+                errors.push("--spectrum-bins needs --schema wide, narrow rows have a single double value column".to_string());
+            } else if self.mimic_table.is_some() {
+                warnings.push("--spectrum-bins is ignored with --mimic-table".to_string());
+            }
+        }
+        if self.outliers_pct > 0.0 && !synthetic {
+            errors.push("--outliers-pct only works with the simulated flight data".to_string());
+        } else if self.outliers_pct > 0.0 && self.mimic_table.is_some() {
+            warnings.push("--outliers-pct is ignored with --mimic-table".to_string());
+        }
+        if self.poison_pct > 0.0 && self.mimic_table.is_some() {
+            warnings.push("--poison-pct is ignored with --mimic-table".to_string());
+        }
+        if self.type_flip_every.is_some() && self.mimic_table.is_some() {
+            warnings.push("--type-flip-every is ignored with --mimic-table".to_string());
+        }
+        if !self.add_column_at.is_empty() {
+            if !synthetic || self.mimic_table.is_some() {
+                errors.push("--add-column-at only works with synthetic flight data, without --mimic-table".to_string());
+            }
+            if self.schema == Schema::Narrow {
+                errors.push("--add-column-at needs --schema wide, narrow rows have a single value column".to_string());
+            }
+            let mut names: Vec<&str> = self.add_column_at.iter().map(|column| column.name.as_str()).collect();
+            names.sort_unstable();
+            if let Some(name) = names.windows(2).find(|pair| pair[0] == pair[1]).map(|pair| pair[0]) {
+                errors.push(format!("--add-column-at adds the column {} more than once", name));
+            }
+        }
+        if !self.drop_column_at.is_empty() {
+            if !synthetic || self.mimic_table.is_some() {
+                errors.push("--drop-column-at only works with synthetic flight data, without --mimic-table".to_string());
+            }
+            if self.schema == Schema::Narrow {
+                errors.push("--drop-column-at needs --schema wide, narrow rows have no null values".to_string());
+            }
+            if let Some(window) = self.drop_column_at.iter().find(|window| window.for_secs <= 0.0) {
+                errors.push(format!("--drop-column-at {} needs a positive duration", window.column));
+            }
+        }
+        if !synthetic && self.mimic_table.is_some() {
+            warnings.push("--mimic-table is ignored with live data".to_string());
+        }
+        if self.server_timestamps {
+            let clocks = [
+                (self.partition_rollover.is_some(), "--partition-rollover"),
+                (self.backfill_hours.is_some(), "--backfill-hours"),
+                (self.cold_writes_pct > 0.0, "--cold-writes-pct"),
+            ];
+            for (_, flag) in clocks.iter().filter(|(set, _)| *set) {
+                errors.push(format!("{} needs client-assigned timestamps, it cannot be combined with --server-timestamps", flag));
+            }
+            if !synthetic {
+                errors.push("--server-timestamps only works with the simulated flight data, live rows keep the times the source reported".to_string());
+            }
+            if self.monotonic_per_plane {
+                warnings.push("--monotonic-per-plane is ignored with --server-timestamps".to_string());
+            }
+            if self.dropout_backfill {
+                warnings.push("rows sent late by --dropout-backfill get the time the server receives them with --server-timestamps".to_string());
+            }
+        }
+        if !synthetic && self.estimate {
+            errors.push("--estimate only works with the simulated flight data, live rates depend on the traffic".to_string());
+        }
+        if !synthetic && self.monotonic_per_plane {
+            warnings.push("--monotonic-per-plane is ignored with live data, the rows keep the times the source reported".to_string());
+        }
+        let pinned = |conf: &str| questdb_confstr::parse_conf_str(conf).map(|conf| conf.get("protocol_version").is_some()).unwrap_or(false);
+        if self.protocol_version.is_some() && (pinned(&self.connection_string) || self.mirror_connection_string.as_deref().is_some_and(pinned)) {
+            errors.push("--protocol-version pins the version of the connection strings, leave protocol_version out of them".to_string());
+        }
+        (errors, warnings)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MissedTick {
+    Burst,
+    Skip,
+    Delay,
+}
+
+impl FromStr for MissedTick {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "burst" => Ok(MissedTick::Burst),
+            "skip" => Ok(MissedTick::Skip),
+            "delay" => Ok(MissedTick::Delay),
+            _ => Err(format!("invalid missed tick behavior '{}', expected burst, skip or delay", s)),
+        }
+    }
+}
+
+impl From<MissedTick> for MissedTickBehavior {
+    fn from(m: MissedTick) -> Self {
+        match m {
+            MissedTick::Burst => MissedTickBehavior::Burst,
+            MissedTick::Skip => MissedTickBehavior::Skip,
+            MissedTick::Delay => MissedTickBehavior::Delay,
+        }
+    }
+}
+
+/// Everything the plane tasks share for the whole run.
+struct RunContext {
+    sink: Box<dyn Sink>,
+    /// The --model, none for planes
+    model: Option<Box<dyn DeviceModel>>,
+    quota: RowQuota,
+    sem: Semaphore,
+    opt: Opt,
+    /// Swapped for the changed config with --watch-config
+    columns: RwLock<Arc<Columns>>,
+    mimic: Option<MimicTable>,
+    health: ServerHealth,
+    /// Rows that could not be serialized, across all planes
+    row_errors: AtomicU64,
+    memory: MemoryBudget,
+    auto_flush: AutoFlush,
+    pool: BufferPool,
+    /// Rows discarded because the buffers were full while the server was down
+    rows_dropped: AtomicU64,
+    /// Invalid rows written on purpose with --poison-pct
+    rows_poisoned: AtomicU64,
+    /// Rows moved forward by --monotonic-per-plane
+    rows_bumped: AtomicU64,
+    /// Planes that went on from their last state after a panic, and generator tasks
+    /// started again after one
+    plane_restarts: AtomicU64,
+    task_restarts: AtomicU64,
+    /// Rows written to --companion-table
+    rows_companion: AtomicU64,
+    /// Rows written to --status-table
+    rows_status: AtomicU64,
+    rows_legs: AtomicU64,
+    /// Rows in batches the server refused
+    rows_rejected: AtomicU64,
+    /// Rows and ILP bytes in the batches the server accepted, for --storage-report, and
+    /// the time spent flushing them, for --auto-batch
+    rows_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    sent_nanos: AtomicU64,
+    type_flips: Option<TypeFlips>,
+    added_columns: Option<AddedColumns>,
+    column_drops: Option<ColumnDrops>,
+    rate: RateTracker,
+    errors: ErrorSummary,
+    /// Counters shown and controls set by the terminal UI, with --tui
+    dashboard: Option<Dashboard>,
+    schedule: Option<RateSchedule>,
+    trajectories: Option<Trajectories>,
+    rollover: Option<RolloverClock>,
+    backfill: Option<BackfillClock>,
+    cold: Option<ColdWrites>,
+    phases: RunPhases,
+    latencies: Latencies,
+    formations: Option<Formations>,
+    seasonality: Option<Seasonality>,
+    silence: Option<Silence>,
+    /// With --mirror-connection-string
+    mirror: Option<Mirror>,
+    /// Bins of the --spectrum-bins array column, none without it or when the server only
+    /// takes protocol version 1
+    spectrum_bins: Option<usize>,
+    sample_log: Option<SampleLog>,
+    /// Share of the ticks of each plane id, with --plane-skew
+    skew: Option<HashMap<String, f64>>,
+    /// --seed, or a random one, that the seeds of the planes derive from
+    seed: u64,
+    rate_limits: RateLimits,
+    network: Network,
+    /// The generator's own CPU and memory use
+    profile: SelfProfile,
+    /// --max-mem-mb and --max-cpu-pct
+    guard: ResourceGuard,
+}
+
+impl RunContext {
+    /// Interval between two rows of a plane, which can be changed from the terminal UI or
+    /// follow the --rate-schedule
+    fn tick_millis(&self) -> u64 {
+        match (&self.dashboard, &self.schedule) {
+            (Some(dashboard), _) => dashboard.tick_millis(),
+            (None, Some(schedule)) => tick_millis(schedule.rate_per_plane()),
+            (None, None) => self.opt.tick_millis(),
+        }
+    }
+
+    fn columns(&self) -> Arc<Columns> {
+        self.columns.read().unwrap().clone()
+    }
+
+    /// Whether the tick is skipped, paused from the terminal UI or held back to stay
+    /// under the resource limits
+    fn is_paused(&self) -> bool {
+        self.dashboard.as_ref().map(Dashboard::is_paused).unwrap_or(false) || self.guard.holds_back()
+    }
+
+    /// Whether the plane was killed from the terminal UI
+    fn is_killed(&self, plane: &Plane) -> bool {
+        self.dashboard.as_ref().map(|dashboard| dashboard.is_killed(plane)).unwrap_or(false)
+    }
+
+    fn publish(&self, planes: &[Plane]) {
+        if let Some(dashboard) = &self.dashboard {
+            for plane in planes.iter() {
+                dashboard.publish(plane);
+            }
+        }
+    }
+
+    /// Timestamp for a row generated now: the wall clock, or the --partition-rollover or
+    /// --backfill-hours one
+    fn now(&self) -> i64 {
+        match (&self.rollover, &self.backfill) {
+            (Some(clock), _) => clock.now(),
+            (None, Some(clock)) => clock.now(),
+            (None, None) => chrono::Utc::now().timestamp_nanos_opt().unwrap(),
+        }
+    }
+
+    /// Counts a batch the server accepted, flushed in `elapsed`. `cold` tells whether it had
+    /// --cold-writes-pct rows.
+    fn record_sent(&self, rows: usize, bytes: usize, cold: bool, elapsed: Duration) {
+        self.rows_sent.fetch_add(rows as u64, Ordering::SeqCst);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::SeqCst);
+        self.sent_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::SeqCst);
+        self.phases.record_sent(rows, bytes, elapsed);
+        if let Some(added_columns) = &self.added_columns {
+            added_columns.record_sent(rows);
+        }
+        if let Some(cold_writes) = &self.cold {
+            cold_writes.record_flush(cold, rows, elapsed);
+        }
+    }
+
+    fn record_flush(&self, elapsed: Duration) {
+        self.rate.record_flush(elapsed);
+        self.latencies.record_flush(elapsed);
+        if let Some(dashboard) = &self.dashboard {
+            dashboard.record_flush(elapsed);
+        }
+    }
+
+    /// Rows built so far, whether or not the server accepted them
+    fn rows_generated(&self) -> u64 {
+        self.quota.used() - self.row_errors.load(Ordering::SeqCst) - self.rows_dropped.load(Ordering::SeqCst)
+    }
+}
+
+/// A plane simulated by a generator task.
+struct Plane {
+    data: PlaneData,
+    mimic_row: Option<MimicRow>,
+    stats: PlaneStats,
+    /// Rows of this plane waiting in the task's buffer
+    buffered: u64,
+    /// How many of them are --cold-writes-pct rows
+    cold_buffered: u64,
+    /// Set when the plane gave up after too many row errors
+    stopped: bool,
+    /// Coverage gaps, with --dropout-every-secs
+    coverage: Option<Coverage>,
+    /// Rows recorded while out of coverage, sent when the plane is back with --dropout-backfill
+    backlog: Buffer,
+    /// When each sensor group has a reading, and which ones have one at the current tick
+    sensors: SensorSchedule,
+    due: u64,
+    /// Row of the plane in the terminal UI
+    slot: Option<usize>,
+    /// Positions for the trajectory export, with --trajectory-file
+    track: Option<TrackRecorder>,
+    /// When the next row in --companion-table is due
+    clearances: Option<Clearances>,
+    /// When the next row in --status-table is due
+    status: Option<StatusStream>,
+    /// With --formations
+    formation: Option<u32>,
+    /// With --plane-skew
+    skew: Option<SkewShare>,
+}
+
+impl Plane {
+    fn new(ctx: &RunContext, plane_id: String, icao24: String) -> Self {
+        let seed = seed::plane_seed(ctx.seed, &plane_id);
+        Plane::starting_at(ctx, plane_id, icao24, ctx.now(), seed)
+    }
+
+    /// A plane whose clock starts at `timestamp` instead of now, drawing from `seed`.
+    fn starting_at(ctx: &RunContext, plane_id: String, icao24: String, timestamp: i64, seed: u64) -> Self {
+        let opt = &ctx.opt;
+        let mimic_row = ctx.mimic.as_ref().map(|table| MimicRow::new(table, plane_id.clone()));
+        let formation = ctx.formations.as_ref().map(|formations| formations.of(&plane_id));
+        let mut data = PlaneData::new(plane_id, icao24, opt.flight_leg_secs, opt.turbulence, timestamp, seed);
+        data.server_timestamps = opt.server_timestamps;
+        data.formation = formation.map(Formations::label);
+        data.outliers = ctx.columns().has_outliers().then(Outliers::new);
+        data.quality = opt.quality_flags.then_some(Quality::Good);
+        data.spectrum = ctx.spectrum_bins.map(|bins| Spectrum::new(bins, formation::mix(seed)));
+        let skew = ctx.skew.as_ref().map(|weights| SkewShare::new(weights.get(&data.plane_id).copied().unwrap_or(1.0)));
+        let share = skew.as_ref().map(SkewShare::weight).unwrap_or(1.0);
+        let stats = PlaneStats::new(data.plane_id.clone(), 1000.0 / opt.tick_millis() as f64 * share, Duration::from_secs(opt.warmup_secs));
+        let coverage = opt.dropout_every_secs.map(|every| Coverage::new(every, opt.dropout_secs, data.timestamp));
+        let sensors = SensorSchedule::new(ctx.columns().group_rates(), data.timestamp);
+        let slot = ctx.dashboard.as_ref().and_then(|dashboard| dashboard.slot(&data.plane_id));
+        let track = ctx.trajectories.as_ref().map(Trajectories::recorder);
+        let clearances = opt.companion_table.as_ref().map(|_| Clearances::new(opt.companion_ratio));
+        let status = opt.status_table.as_ref().map(|_| StatusStream::new(opt.status_every_secs));
+        Plane {
+            data, mimic_row, stats, buffered: 0, cold_buffered: 0, stopped: false, coverage, backlog: ctx.pool.empty(), sensors, due: u64::MAX, slot, track,
+            clearances, status, formation, skew,
+        }
+    }
+
+    /// Advances the plane to the next tick and tells whether it is in coverage, going on
+    /// with the plane as it was before when it panics. None when it did, and the tick is
+    /// skipped.
+    fn advance(&mut self, ctx: &RunContext) -> Option<Signal> {
+        self.advance_at(ctx, ctx.now())
+    }
+
+    /// `advance` to `now`, in nanoseconds, for the simulated clock of a backfill slice
+    fn advance_at(&mut self, ctx: &RunContext, now: i64) -> Option<Signal> {
+        let timestamp = self.data.timestamp;
+        match panic::catch_unwind(AssertUnwindSafe(|| self.update_at(ctx, now))) {
+            Ok(signal) => Some(signal),
+            Err(panic) => {
+                self.data.timestamp = timestamp;
+                self.restart(ctx, panic);
+                None
+            }
+        }
+    }
+
+    /// Counts a panic of the plane, which goes on with its last state, and stops it after
+    /// --max-restarts of them.
+    fn restart(&mut self, ctx: &RunContext, panic: Box<dyn Any + Send>) {
+        self.stats.restarts += 1;
+        ctx.plane_restarts.fetch_add(1, Ordering::SeqCst);
+        ctx.errors.record("plane restarts", &format!("Plane {} panicked and was restarted", self.data.plane_id), &panic_message(&panic));
+        if self.stats.restarts >= ctx.opt.max_restarts {
+            eprintln!("Plane {} stopped after {} panics", self.data.plane_id, self.stats.restarts);
+            self.stopped = true;
+        }
+    }
+
+    /// Advances the plane to `now`, in nanoseconds.
+    fn update_at(&mut self, ctx: &RunContext, mut now: i64) -> Signal {
+        if ctx.opt.monotonic_per_plane {
+            if let Some(bumped) = bump_timestamp(self.data.timestamp, now) {
+                now = bumped;
+                ctx.rows_bumped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.data.update(now);
+        let shock = match (&ctx.formations, self.formation) {
+            (Some(formations), Some(formation)) => formations.shock(formation, self.data.timestamp),
+            _ => 0.0,
+        };
+        if shock != 0.0 {
+            self.data.jolt(shock);
+        }
+        if let Some(seasonality) = &ctx.seasonality {
+            self.data.oat += seasonality.temperature(self.data.timestamp, self.data.latitude, self.data.longitude);
+        }
+        if ctx.opt.watch_config {
+            let columns = ctx.columns();
+            self.sensors.set_rates(columns.group_rates());
+            if self.data.outliers.is_none() && columns.has_outliers() {
+                self.data.outliers = Some(Outliers::new());
+            }
+        }
+        let values = self.data.values();
+        if let Some(outliers) = self.data.outliers.as_mut() {
+            outliers.observe(&values);
+        }
+        self.due = self.sensors.due(self.data.timestamp);
+        if let Some(skew) = self.skew.as_mut() {
+            skew.tick();
+        }
+        if let Some(track) = self.track.as_mut() {
+            track.record(&self.data);
+        }
+        let signal = match self.coverage.as_mut() {
+            Some(coverage) => coverage.check(self.data.timestamp),
+            None => Signal::Covered,
+        };
+        if self.data.quality.is_some() {
+            let marginal = self.coverage.as_ref().map(|coverage| coverage.is_marginal(self.data.timestamp)).unwrap_or(false);
+            self.data.quality = Some(match signal != Signal::Covered || marginal || shock != 0.0 {
+                true => Quality::Suspect,
+                false => Quality::Good,
+            });
+        }
+        signal
+    }
+
+    /// Rows the plane sends per tick on average, less than one with --plane-skew
+    fn share(&self) -> f64 {
+        self.skew.as_ref().map(SkewShare::weight).unwrap_or(1.0)
+    }
+
+    /// Whether the plane sends a row at this tick with --plane-skew, where the colder planes
+    /// skip ticks. Those are in the target rate already, not counted as skipped.
+    fn is_hot(&self) -> bool {
+        self.skew.as_ref().map(SkewShare::is_due).unwrap_or(true)
+    }
+
+    /// Whether any column has a reading at this tick. Only sensor groups slower than the
+    /// row rate can leave a tick without any, with --seasonality planes not flying at this
+    /// hour have none, and in a --silence-at window no plane has any.
+    fn has_readings(&self, ctx: &RunContext) -> bool {
+        let flying = ctx.seasonality.as_ref()
+            .map(|seasonality| seasonality.flies(&self.data.plane_id, self.data.timestamp, self.data.longitude))
+            .unwrap_or(true);
+        let silent = ctx.silence.as_ref().map(|silence| silence.is_silent(self.data.timestamp)).unwrap_or(false);
+        flying && !silent && (ctx.mimic.is_some() || ctx.columns().any_due(self.due))
+    }
+
+    /// Records a row while out of coverage, to be sent later.
+    fn record_dark_row(&mut self, ctx: &RunContext) {
+        let mut backlog = std::mem::replace(&mut self.backlog, ctx.pool.empty());
+        self.generate_row(ctx, &mut backlog, None);
+        self.backlog = backlog;
+    }
+
+    /// Writes the plane's current state as a row. A row that fails to serialize is
+    /// rolled back, the rest of the buffer is kept.
+    fn write(&mut self, ctx: &RunContext, buffer: &mut Buffer, flip: Option<FlipType>) -> Result<()> {
+        let columns = ctx.columns();
+        let table_name = match ctx.mimic {
+            Some(_) => ctx.opt.table_name.as_str(),
+            None => columns.table_for(&self.data.callsign, &ctx.opt.table_name),
+        };
+        let poisoned = ctx.opt.poison_pct > 0.0 && ctx.mimic.is_none() && rand::thread_rng().gen_bool(ctx.opt.poison_pct / 100.0);
+        // Only the flight data row goes back in time, the other tables keep the plane's clock
+        let cold_offset = ctx.cold.as_ref().and_then(ColdWrites::offset).unwrap_or(0);
+        self.data.timestamp -= cold_offset;
+        let start = buffer.len();
+        let written = buffer.set_marker().and_then(|_| match (self.mimic_row.as_mut(), ctx.mimic.as_ref()) {
+            (Some(row), Some(table)) => row.write(table, buffer, table_name, columns.run_label(), &self.data),
+            _ if poisoned => poison::write(&self.data, buffer, table_name, &columns),
+            _ => {
+                let variations = Variations { flip, added: ctx.added_columns.as_ref(), dropped: ctx.column_drops.as_ref() };
+                self.data.write_sensors(buffer, table_name, &columns, ctx.opt.schema, self.due, variations)
+            }
+        });
+        self.data.timestamp += cold_offset;
+        if let (Some(sample_log), Ok(())) = (ctx.sample_log.as_ref(), &written) {
+            if sample_log.draw() {
+                sample_log.log(&String::from_utf8_lossy(&buffer.as_bytes()[start..]));
+            }
+        }
+        if let (Some(cold), Ok(())) = (ctx.cold.as_ref(), &written) {
+            if cold_offset > 0 {
+                cold.record_row();
+                self.cold_buffered += 1;
+            }
+        }
+        if poisoned && written.is_ok() {
+            ctx.rows_poisoned.fetch_add(1, Ordering::SeqCst);
+        }
+        let clearance_due = self.clearances.as_mut().map(Clearances::due).unwrap_or(false);
+        let written = match (written, ctx.opt.companion_table.as_deref()) {
+            (Ok(()), Some(companion_table)) if clearance_due => {
+                let clearance = companion::write(&self.data, buffer, companion_table, &columns);
+                if clearance.is_ok() {
+                    ctx.rows_companion.fetch_add(1, Ordering::SeqCst);
+                }
+                clearance
+            }
+            (written, _) => written,
+        };
+        let data = &self.data;
+        let status_due = self.status.as_mut().map(|status| status.due(data)).unwrap_or(false);
+        let written = match (written, ctx.opt.status_table.as_deref()) {
+            (Ok(()), Some(status_table)) if status_due => {
+                let status = status::write(&self.data, buffer, status_table, &columns);
+                if status.is_ok() {
+                    ctx.rows_status.fetch_add(1, Ordering::SeqCst);
+                }
+                status
+            }
+            (written, _) => written,
+        };
+        let written = match (written, ctx.opt.legs_table.as_deref(), self.data.finished_leg.take()) {
+            (Ok(()), Some(legs_table), Some(leg)) => {
+                let summary = legs::write(&leg, buffer, legs_table, &columns);
+                match summary {
+                    Ok(()) => {
+                        ctx.rows_legs.fetch_add(1, Ordering::SeqCst);
+                    }
+                    // Kept for the next row, the whole row is rolled back
+                    Err(_) => self.data.finished_leg = Some(leg),
+                }
+                summary
+            }
+            (written, _, leg) => {
+                self.data.finished_leg = leg;
+                written
+            }
+        };
+        if written.is_err() {
+            let _ = buffer.rewind_to_marker();
+        }
+        buffer.clear_marker();
+        written
+    }
+
+    fn finish(mut self, ctx: &RunContext) -> PlaneStats {
+        self.stats.finish();
+        if let (Some(trajectories), Some(track)) = (ctx.trajectories.as_ref(), self.track.take()) {
+            trajectories.collect(track);
+        }
+        if !ctx.opt.quiet {
+            println!("Plane {} generated {} rows.", self.data.plane_id, self.stats.rows_generated);
+        }
+        self.stats
+    }
+
+    /// Writes the next row and counts it, or counts the error. Returns true if the row
+    /// is in the buffer, the caller then counts it as buffered.
+    fn generate_row(&mut self, ctx: &RunContext, buffer: &mut Buffer, flip: Option<FlipType>) -> bool {
+        let timestamp = self.data.timestamp;
+        let written = match panic::catch_unwind(AssertUnwindSafe(|| self.write(ctx, buffer, flip))) {
+            Ok(written) => written,
+            Err(panic) => {
+                // The row may be half written
+                let _ = buffer.rewind_to_marker();
+                buffer.clear_marker();
+                self.data.timestamp = timestamp;
+                self.restart(ctx, panic);
+                return false;
+            }
+        };
+        match written {
+            Ok(_) => {
+                self.stats.record_row();
+                ctx.rate.record_row();
+                ctx.phases.record_row();
+                true
+            }
+            Err(e) => {
+                self.stats.row_errors += 1;
+                ctx.row_errors.fetch_add(1, Ordering::SeqCst);
+                if !ctx.opt.quiet || self.stats.row_errors == 1 {
+                    eprintln!("Plane {} could not build a row: {}", self.data.plane_id, e);
+                }
+                if ctx.opt.max_row_errors.map(|max| self.stats.row_errors >= max).unwrap_or(false) {
+                    eprintln!("Plane {} stopped after {} row errors", self.data.plane_id, self.stats.row_errors);
+                    self.stopped = true;
+                }
+                false
+            }
+        }
+    }
+}
+
+/// The timestamp just after `last` when the clock says `now` is not after it, for
+/// --monotonic-per-plane
+fn bump_timestamp(last: i64, now: i64) -> Option<i64> {
+    (now <= last).then(|| last + 1)
+}
+
+/// The message a panic was raised with
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (None, Some(message)) => message.clone(),
+        (None, None) => "unknown panic".to_string(),
+    }
+}
+
+/// Flushes a task's buffer unless the server is known to be down, in which case the
+/// rows stay buffered until it is back. Tells whether the server accepted the rows.
+async fn flush(ctx: &RunContext, buffer: &mut Buffer, name: &str, planes: &mut [Plane], flip: Option<FlipType>) -> bool {
+    if ctx.health.is_down() {
+        return false;
+    }
+    let started = std::time::Instant::now();
+    tokio::time::sleep(ctx.rate_limits.reserve_total(buffer.row_count())).await;
+    let _permit = ctx.sem.acquire().await.unwrap();
+    let (rows, bytes) = (buffer.row_count(), buffer.len());
+    let cold = planes.iter().any(|plane| plane.cold_buffered > 0);
+    // The mirror gets its copy first, the flush to the primary empties the buffer
+    let mirrored = match &ctx.mirror {
+        Some(mirror) => Some(mirror.send(ctx, buffer, name).await),
+        None => None,
+    };
+    let flush_started = std::time::Instant::now();
+    let result = ctx.sink.write_batch(buffer, &ctx.rate_limits, &ctx.network).await;
+    let accepted = result.is_ok();
+    if let (Some(mirror), Some(mirrored)) = (&ctx.mirror, mirrored) {
+        mirror.record(accepted, mirrored, rows);
+    }
+    if accepted {
+        ctx.record_sent(rows, bytes, cold, flush_started.elapsed());
+    }
+    record_flush(ctx, result, buffer, rows, name, planes, flip);
+    ctx.record_flush(started.elapsed());
+    accepted
+}
+
+/// Sends the rows a plane recorded while out of coverage, as a batch of their own. They
+/// are kept for a later retry if the flush fails.
+async fn flush_backlog(ctx: &RunContext, plane: &mut Plane, name: &str) {
+    let mut backlog = std::mem::replace(&mut plane.backlog, ctx.pool.empty());
+    let buffered = std::mem::replace(&mut plane.buffered, backlog.row_count() as u64);
+    flush(ctx, &mut backlog, name, std::slice::from_mut(plane), None).await;
+    plane.buffered = buffered;
+    plane.backlog = backlog;
+}
+
+/// Counts the outcome of a flush against the planes that had rows in the buffer.
+///
+/// A batch the server rejects (e.g. a column of the wrong type) would be rejected again
+/// on every retry, so it is dropped from the buffer and its rows counted as rejected.
+fn record_flush(ctx: &RunContext, result: Result<()>, buffer: &mut Buffer, rows: usize, name: &str, planes: &mut [Plane], flip: Option<FlipType>) {
+    if let (Some(flips), Some(flip)) = (ctx.type_flips.as_ref(), flip) {
+        match &result {
+            Ok(_) => flips.record(flip, None),
+            Err(e) if e.code() == questdb::ErrorCode::ServerFlushError => flips.record(flip, Some(e.msg().to_string())),
+            Err(_) => {}
+        }
+    }
+    match result {
+        Ok(_) => {
+            ctx.health.record_success();
+            for plane in planes.iter_mut() {
+                plane.stats.rows_flushed += plane.buffered;
+                plane.buffered = 0;
+                plane.cold_buffered = 0;
+            }
+            if !ctx.opt.quiet {
+                println!("Successfully flushed buffer for {} with {} rows", name, rows);
+            }
+        }
+        Err(e) if e.code() == questdb::ErrorCode::ServerFlushError => {
+            buffer.clear();
+            for plane in planes.iter_mut().filter(|plane| plane.buffered > 0) {
+                plane.stats.flush_failures += 1;
+                plane.stats.rows_rejected += plane.buffered;
+                plane.buffered = 0;
+                plane.cold_buffered = 0;
+            }
+            ctx.rows_rejected.fetch_add(rows as u64, Ordering::SeqCst);
+            ctx.errors.record("rejected batches", &format!("Server rejected a batch of {} rows from {}", rows, name), e.msg());
+        }
+        Err(e) => {
+            ctx.health.record_failure(&e, ctx.opt.restart_failures);
+            for plane in planes.iter_mut().filter(|plane| plane.buffered > 0) {
+                plane.stats.flush_failures += 1;
+            }
+            ctx.errors.record("flush failures", &format!("Failed to flush buffer for {}", name), e.msg());
+        }
+    }
+}
+
+/// Generates rows for a group of planes on one task: on every tick each plane writes one
+/// row into the task's buffer, which is flushed every --batch-size rows or as the
+/// --auto-flush-* options say. By default each plane has a task of its own, --workers
+/// shares a few tasks between many planes.
+async fn generate_data(ctx: Arc<RunContext>, name: String, planes: Vec<(String, String)>) -> Vec<PlaneStats> {
+    let opt = &ctx.opt;
+    let mut planes: Vec<Plane> = planes.into_iter().map(|(plane_id, icao24)| Plane::new(&ctx, plane_id, icao24)).collect();
+    let mut tick_millis = ctx.tick_millis();
+    let mut interval = interval(Duration::from_millis(tick_millis));
+    interval.set_missed_tick_behavior(opt.on_missed_tick.into());
+    let mut engine = Engine::Async;
+    let mut buffer = TaskBuffer::new(&ctx);
+    let mut allowance = ctx.quota.allowance(rows_per_tick(&planes));
+
+    'ticks: loop {
+        interval.tick().await;
+        if ctx.tick_millis() != tick_millis {
+            tick_millis = ctx.tick_millis();
+            interval = tokio::time::interval(Duration::from_millis(tick_millis));
+            interval.set_missed_tick_behavior(opt.on_missed_tick.into());
+        }
+        ctx.publish(&planes);
+
+        for i in 0..planes.len() {
+            if planes[i].stopped {
+                continue;
+            }
+            if ctx.is_killed(&planes[i]) {
+                planes[i].stopped = true;
+                continue;
+            }
+            if ctx.is_paused() {
+                ctx.rate.record_skipped();
+                continue;
+            }
+
+            let plane = &mut planes[i];
+            let Some(signal) = plane.advance(&ctx) else {
+                ctx.rate.record_skipped();
+                continue;
+            };
+            if !plane.is_hot() {
+                // A cold plane can go many ticks without a row, it stops with the others
+                if allowance.is_exhausted() {
+                    break 'ticks;
+                }
+                continue;
+            }
+            if !plane.has_readings(&ctx) {
+                ctx.rate.record_skipped();
+                continue;
+            }
+            if signal == Signal::Dark && !opt.dropout_backfill {
+                ctx.rate.record_skipped();
+                continue;
+            }
+            if signal != Signal::Dark && !plane.backlog.is_empty() && !ctx.health.is_down() {
+                flush_backlog(&ctx, plane, &name).await;
+            }
+            if !allowance.take() {
+                break 'ticks;
+            }
+            let last_row = allowance.is_exhausted();
+            if last_row {
+                ctx.phases.drain();
+            }
+
+            if signal == Signal::Dark {
+                plane.record_dark_row(&ctx);
+            } else {
+                buffer.write(&ctx, &mut engine, &name, planes.as_mut_slice(), i, last_row).await;
+            }
+
+            if last_row {
+                break 'ticks;
+            }
+        }
+
+        if planes.iter().all(|plane| plane.stopped) {
+            // The rows it will not write go to the other tasks right away
+            allowance.release();
+            break;
+        }
+    }
+    // Once all rows are handed out, what is left is flushing them
+    if ctx.quota.remaining() == 0 {
+        ctx.phases.drain();
+    }
+    // Rows a stopped task did not get to write go back to the others
+    drop(allowance);
+
+    buffer.finish(&ctx, &mut engine, &name, planes.as_mut_slice()).await;
+    for plane in planes.iter_mut().filter(|plane| !plane.backlog.is_empty()) {
+        flush_backlog(&ctx, plane, &name).await;
+    }
+    ctx.publish(&planes);
+
+    planes.into_iter().map(|plane| plane.finish(&ctx)).collect()
+}
+
+fn main() {
+    run_command();
+    if let Err(e) = simulate() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn simulate() -> std::result::Result<(), SimError> {
+    let mut opt = Opt::parse();
+    opt.read_secrets().map_err(|e| SimError::Validation(vec![e]))?;
+    opt.resolve();
+    let config = opt.load_config().map_err(SimError::Config)?;
+    let (errors, warnings) = opt.validate();
+    for warning in warnings.iter() {
+        eprintln!("Warning: {}", warning);
+    }
+    if !errors.is_empty() {
+        return Err(SimError::Validation(errors));
+    }
+    if !opt.sweep.is_empty() {
+        return sweep::run(&opt.sweep, &opt.sweep_csv).map_err(SimError::Setup);
+    }
+    // Progress lines would be drawn over the terminal UI
+    opt.quiet |= opt.tui;
+    match daemon::role() {
+        daemon::Role::Foreground if opt.daemon && !opt.estimate => daemon::detach(&opt),
+        daemon::Role::Supervisor => daemon::supervise(&opt),
+        _ => {}
+    }
+    let starting_plane_id = opt.starting_plane_id.as_deref().unwrap_or(opt.plane_id_scheme.default_start());
+    let plane_ids = match PlaneIds::new(opt.plane_id_scheme, starting_plane_id) {
+        Ok(ids) if ids.available() >= opt.plane_count as u64 => ids,
+        Ok(ids) => {
+            return Err(SimError::Config(format!("Only {} plane ids available starting from {}, but --plane-count is {}",
+                                                ids.available(), starting_plane_id, opt.plane_count)));
+        }
+        Err(e) => {
+            return Err(SimError::Config(format!("Invalid --starting-plane-id: {} (ids in this --plane-id-scheme look like {})",
+                                                e, opt.plane_id_scheme.default_start())));
+        }
+    };
+    if let Some(replay) = &opt.replay_plane {
+        if !(0..opt.plane_count as u64).any(|n| plane_ids.get(n) == *replay) {
+            return Err(SimError::Config(format!("--replay-plane {} is not one of the {} planes starting from {}", replay, opt.plane_count, starting_plane_id)));
+        }
+    }
+    let columns = Columns::new(&config).map_err(SimError::Config)?
+        .with_run_label(opt.run_label.clone()).with_outliers(opt.outliers_pct, opt.outlier_sigmas);
+    let fallback_rate = opt.total_rate.or(opt.rate_per_plane_arg.map(|rate| rate * opt.plane_count as u64));
+    let schedule = opt.rate_schedule.as_ref().map(|name| RateSchedule::new(&config, name, fallback_rate)).transpose().map_err(SimError::Config)?;
+    if let Some(schedule) = &schedule {
+        if let Some(rate) = schedule.too_low(opt.plane_count as u64) {
+            return Err(SimError::Config(format!("--rate-schedule has a rate of {} rows/s, less than 1 row/s for each of the {} planes", rate, opt.plane_count)));
+        }
+        // Until the follower sees a change, the planes start at the rate of now
+        opt.rate_per_plane = opt.per_plane_rate(schedule.total_rate(chrono::Utc::now().timestamp_nanos_opt().unwrap()));
+        schedule.set_rate_per_plane(opt.rate_per_plane);
+    }
+    let row_rate = 1000.0 / opt.tick_millis() as f64;
+    if columns.group_rates().iter().any(|rate| *rate > row_rate) {
+        eprintln!("Warning: sensor groups faster than the {:.1} rows/s per plane get a reading on every row, raise --rate-per-plane", row_rate);
+    }
+    if opt.estimate {
+        estimate::print(&opt, &columns);
+        return Ok(());
+    }
+    if let Some(path) = &opt.emit_queries {
+        let planes: Vec<String> = (0..opt.plane_count.min(2) as u64).map(|n| plane_ids.get(n)).collect();
+        match queries::write(path, &opt, &columns, &planes) {
+            Ok(()) => println!("Example queries written to {}", path.display()),
+            Err(e) => eprintln!("Cannot write example queries to {}: {}", path.display(), e),
+        }
+    }
+    let load_queries = match (&opt.queries_file, opt.query_workers) {
+        (_, 0) => vec![],
+        (Some(path), _) => queries::read(path).map_err(SimError::Config)?,
+        (None, _) => {
+            let planes: Vec<String> = (0..opt.plane_count.min(2) as u64).map(|n| plane_ids.get(n)).collect();
+            queries::examples(&opt, &columns, &planes)
+        }
+    };
+    if opt.preflight != Preflight::Off && opt.sink() == sink::QUESTDB {
+        preflight::check_connection(&opt).map_err(|e| SimError::Setup(format!("Preflight failed: {}", e)))?;
+    }
+    let mimic = opt.mimic_table.as_ref().map(|table| {
+        QueryClient::from_conf(&opt.connection_string).and_then(|client| MimicTable::introspect(&client, table))
+    }).transpose().map_err(SimError::Setup)?;
+    let partitioning = opt.partition_rollover.map(|partitioning| {
+        QueryClient::from_conf(&opt.connection_string).and_then(|client| partitioning.resolve(&client, &opt.table_name))
+    }).transpose().map_err(|e| SimError::Setup(format!("Cannot tell the partitioning of '{}': {}", opt.table_name, e)))?;
+    if matches!(opt.preflight, Preflight::Probe | Preflight::Scratch) {
+        preflight::probe(&opt, &columns, mimic.as_ref()).map_err(|e| SimError::Setup(format!("Preflight failed: {}", e)))?;
+    }
+    // A replay runs a single plane, and its rate is that of one
+    let plane_count = if opt.replay_plane.is_some() { 1 } else { opt.plane_count };
+    let (tick_millis, warmup_secs) = (opt.tick_millis(), opt.warmup_secs);
+    let (max_buffer_bytes, memory_budget_bytes, batch_size) = (opt.max_buffer_bytes, opt.memory_budget_bytes, opt.batch_size);
+    let auto_flush = AutoFlush::new(batch_size, opt.auto_flush_rows, opt.auto_flush_interval_ms);
+    let dashboard = if opt.tui {
+        Some(Dashboard::new((0..opt.plane_count as u64).map(|n| plane_ids.get(n)).collect(), opt.rate_per_plane))
+    } else {
+        None
+    };
+    let trajectories = opt.trajectory_file.clone().and_then(|path| {
+        TrajectoryFormat::from_path(&path).ok().map(|format| Trajectories::new(path, format, opt.trajectory_every_secs))
+    });
+    let rollover = partitioning.map(|partitioning| RolloverClock::new(partitioning, opt.rollover_window_secs));
+    if let Some(clock) = &rollover {
+        println!("Writing across {} boundaries starting at {}, {}s on each side", clock.partitioning().as_str(),
+                 clock.first_boundary().format("%Y-%m-%dT%H:%M:%SZ"), opt.rollover_window_secs);
+    }
+    let backfill = opt.backfill_hours.filter(|_| opt.backfill_workers.is_none()).map(|hours| BackfillClock::new(hours, opt.backfill_speed));
+    if let Some(clock) = &backfill {
+        println!("Backfilling from {} at {}x, catching up with the wall clock in {:.0}s",
+                 chrono::DateTime::from_timestamp_nanos(clock.start()).format("%Y-%m-%dT%H:%M:%SZ"), opt.backfill_speed, clock.catch_up_secs());
+    }
+    let (cold_writes_pct, cold_writes_days) = (opt.cold_writes_pct, opt.cold_writes_days);
+    let (type_flip_every, error_summary_every) = (opt.type_flip_every, Duration::from_secs(opt.error_summary_secs));
+    let formations = opt.formations.map(|count| Formations::new(count, opt.formation_event_every_secs, opt.formation_event_secs, opt.formation_intensity));
+    let seasonality = opt.seasonality.then(|| Seasonality::new(opt.day_temperature_swing, opt.night_traffic_pct, opt.weekend_traffic_pct));
+    let rate_limits = RateLimits::new(opt.max_total_rate, opt.max_rate_per_conn);
+    let seed = opt.seed.unwrap_or_else(rand::random);
+    let silence = (!opt.silence_at.is_empty()).then(|| {
+        let start = backfill.as_ref().map(BackfillClock::start).unwrap_or_else(|| chrono::Utc::now().timestamp_nanos_opt().unwrap());
+        Silence::new(opt.silence_at.clone(), opt.silence_for, start)
+    });
+    let column_drops = (!opt.drop_column_at.is_empty()).then(|| {
+        let start = backfill.as_ref().map(BackfillClock::start).unwrap_or_else(|| chrono::Utc::now().timestamp_nanos_opt().unwrap());
+        ColumnDrops::new(opt.drop_column_at.clone(), start)
+    });
+    let sink = sink::open(&opt, &rate_limits)?;
+    let mirror = match &opt.mirror_connection_string {
+        Some(conf) => Some(Mirror::new(SenderPool::connect(conf, opt.protocol_version, opt.senders, opt.sender_keepalive_secs.map(Duration::from_secs),
+                                                           Duration::from_millis(opt.reconnect_backoff_ms), Duration::from_millis(opt.reconnect_max_backoff_ms),
+                                                           &RateLimits::new(None, None))?)),
+        None => None,
+    };
+    // The mirror gets the same buffers as the sink
+    if let Some(mirror) = mirror.as_ref().filter(|mirror| mirror.senders.protocol_version() != sink.protocol_version()) {
+        return Err(SimError::Setup(format!("The server speaks ILP protocol {} and the mirror {}, pin one with --protocol-version",
+                                           sink.protocol_version(), mirror.senders.protocol_version())));
+    }
+    let spectrum_bins = match opt.spectrum_bins.filter(|_| opt.mimic_table.is_none()) {
+        Some(_) if sink.protocol_version() == ProtocolVersion::V1 => {
+            eprintln!("Warning: arrays need ILP protocol version 2 and the sink writes version 1, the rows go without the --spectrum-bins column");
+            None
+        }
+        bins => bins,
+    };
+    let sample_log = opt.sample_log.map(|fraction| SampleLog::new(fraction, opt.sample_log_file.as_deref())).transpose()
+        .map_err(SimError::io("open the sample log", opt.sample_log_file.clone().unwrap_or_default()))?;
+    let skew_share = opt.plane_skew.map(|exponent| skew::total_weight(exponent, plane_count as u64) / plane_count.max(1) as f64).unwrap_or(1.0);
+    let skew = opt.plane_skew.map(|exponent| {
+        if !opt.quiet {
+            println!("{}", skew::describe(exponent, opt.plane_count as u64, opt.rate_per_plane));
+        }
+        (0..opt.plane_count as u64).map(|n| (plane_ids.get(n), skew::weight(exponent, n + 1))).collect()
+    });
+    let added_columns = (!opt.add_column_at.is_empty()).then(|| AddedColumns::new(opt.add_column_at.clone()));
+    let ctx = Arc::new(RunContext {
+        pool: BufferPool::new(sink.protocol_version(), batch_size, max_buffer_bytes, plane_count as usize),
+        sink,
+        model: model::find(&opt.model).map(|kind| (kind.open)(&opt)),
+        rate_limits,
+        network: Network::new(opt.net_latency_ms, opt.net_jitter_ms, opt.net_bytes_per_sec, opt.net_loss_pct),
+        profile: SelfProfile::new(),
+        guard: ResourceGuard::new(opt.max_mem_mb, opt.max_cpu_pct, opt.on_resource_limit),
+        quota: RowQuota::new(opt.total_rows, opt.duration.map(Duration::from_secs_f64)),
+        sem: Semaphore::new(opt.plane_count as usize * opt.batch_size),
+        opt,
+        columns: RwLock::new(Arc::new(columns)),
+        mimic,
+        health: ServerHealth::default(),
+        row_errors: AtomicU64::new(0),
+        memory: MemoryBudget::new(max_buffer_bytes, memory_budget_bytes),
+        auto_flush,
+        rows_dropped: AtomicU64::new(0),
+        rows_poisoned: AtomicU64::new(0),
+        rows_bumped: AtomicU64::new(0),
+        plane_restarts: AtomicU64::new(0),
+        task_restarts: AtomicU64::new(0),
+        rows_companion: AtomicU64::new(0),
+        rows_status: AtomicU64::new(0),
+        rows_legs: AtomicU64::new(0),
+        rows_rejected: AtomicU64::new(0),
+        rows_sent: AtomicU64::new(0),
+        bytes_sent: AtomicU64::new(0),
+        sent_nanos: AtomicU64::new(0),
+        type_flips: type_flip_every.map(TypeFlips::new),
+        added_columns,
+        column_drops,
+        rate: RateTracker::new(plane_count, 1000.0 / tick_millis as f64, Duration::from_secs(warmup_secs)).with_share(skew_share),
+        errors: ErrorSummary::new(error_summary_every),
+        dashboard,
+        schedule,
+        trajectories,
+        rollover,
+        phases: RunPhases::new(Duration::from_secs_f64(
+            (warmup_secs as f64).max(backfill.as_ref().map(BackfillClock::catch_up_secs).unwrap_or(0.0)))),
+        backfill,
+        latencies: Latencies::new(),
+        formations,
+        seasonality,
+        silence,
+        mirror,
+        spectrum_bins,
+        sample_log,
+        skew,
+        seed,
+        cold: (cold_writes_pct > 0.0).then(|| ColdWrites::new(cold_writes_pct, cold_writes_days)),
+    });
+    let groups = plane_groups(&ctx.opt, &plane_ids, ctx.model.as_deref());
+    if let Some(path) = &ctx.opt.seeds_file {
+        // The same seeds give the same planes, built again here as they start
+        let timestamp = ctx.backfill.as_ref().map(BackfillClock::start).unwrap_or_else(|| ctx.now());
+        let planes: Vec<(u64, PlaneData)> = groups.iter().flat_map(|(_, planes)| planes).map(|(plane_id, icao24)| {
+            let plane_seed = seed::plane_seed(ctx.seed, plane_id);
+            (plane_seed, PlaneData::new(plane_id.clone(), icao24.clone(), ctx.opt.flight_leg_secs, ctx.opt.turbulence, timestamp, plane_seed))
+        }).collect();
+        seed::export(path, ctx.seed, &planes).map_err(SimError::io("write the seeds to", path))?;
+        if !ctx.opt.quiet {
+            println!("Seed {}, the seeds of the planes are in {}", ctx.seed, path.display());
+        }
+    }
+    let run_record = RunRecord::new();
+    if !ctx.opt.no_run_metadata && ctx.opt.sink() == sink::QUESTDB {
+        run_record.write_start(&ctx.opt);
+    }
+    if ctx.dashboard.is_some() {
+        Dashboard::open(ctx.clone());
+    }
+    let mat_view = ctx.opt.mat_view.clone().map(|view| MatView::start(ctx.clone(), view, Duration::from_secs(ctx.opt.mat_view_every_secs)))
+        .transpose().map_err(|e| SimError::Setup(format!("Cannot watch the materialized view: {}", e)))?;
+    let query_load = (ctx.opt.query_workers > 0).then(|| QueryLoad::start(ctx.clone(), load_queries, ctx.opt.query_workers))
+        .transpose().map_err(|e| SimError::Setup(format!("Cannot start the query workers: {}", e)))?;
+    let cpu_profile = ctx.opt.profile.clone().map(CpuProfile::start)
+        .transpose().map_err(|e| SimError::Setup(format!("Cannot start the CPU profiler: {}", e)))?;
+    let profiler = SelfProfile::start(ctx.clone());
+    if profiler.is_none() && ctx.guard.is_set() {
+        eprintln!("Warning: the CPU and memory of the process cannot be read on this platform, --max-mem-mb and --max-cpu-pct are not enforced");
+    }
+    let auto_batch = ctx.opt.auto_batch.then(|| AutoBatch::start(ctx.clone(), groups.len()))
+        .transpose().map_err(|e| SimError::Setup(format!("Cannot tune the batch size: {}", e)))?;
+    let retention = ctx.opt.retention_hours.map(|hours| Retention::start(ctx.clone(), hours, Duration::from_secs(ctx.opt.retention_every_secs)))
+        .transpose().map_err(|e| SimError::Setup(format!("Cannot start dropping old partitions: {}", e)))?;
+
+    if let (Some(workers), Some(hours)) = (ctx.opt.backfill_workers, ctx.opt.backfill_hours) {
+        let planes: Vec<(String, String)> = groups.iter().flat_map(|(_, planes)| planes.iter().cloned()).collect();
+        backfill::run_parallel(&ctx, &planes, hours, workers);
+    }
+
+    let plane_stats = if ctx.opt.sync {
+        let plane_stats = blocking::run(ctx.clone(), groups);
+        if let Some(dashboard) = &ctx.dashboard {
+            dashboard.close();
+        }
+        ctx.rate.finish(&ctx);
+        ctx.phases.finish();
+        report(&ctx, &plane_stats);
+        plane_stats
+    } else {
+        let runtime = tokio::runtime::Runtime::new().map_err(|e| SimError::Setup(format!("Cannot start the async runtime: {}", e)))?;
+        runtime.block_on(run(ctx.clone(), groups))
+    };
+    ctx.profile.stop(profiler);
+    if let Some(cpu_profile) = cpu_profile {
+        if let Err(e) = cpu_profile.write() {
+            eprintln!("Cannot write the CPU profile: {}", e);
+        }
+    }
+    if let Some(auto_batch) = auto_batch {
+        auto_batch.stop();
+    }
+    if let Some(retention) = retention {
+        retention.stop();
+    }
+    if let Some(mat_view) = mat_view {
+        mat_view.stop();
+    }
+    let queries = query_load.map(QueryLoad::stop).unwrap_or_default();
+    if ctx.opt.storage_report {
+        storage::report(&ctx);
+    }
+    if !ctx.opt.no_run_metadata && ctx.opt.sink() == sink::QUESTDB {
+        run_record.write_end(&ctx, &plane_stats);
+    }
+    if ctx.opt.report_json.is_some() || ctx.opt.save_run {
+        let report = run_record.report(&ctx, &plane_stats, &queries);
+        if let Some(path) = &ctx.opt.report_json {
+            if let Err(e) = run_log::write_json(&report, path) {
+                eprintln!("Cannot write the JSON report to {}: {}", path.display(), e);
+            }
+        }
+        if ctx.opt.save_run {
+            match runs::save(&ctx.opt.runs_dir, ctx.opt.keep_runs, &report) {
+                Ok(path) if !ctx.opt.quiet => println!("Run saved to {}", path.display()),
+                Ok(_) => {}
+                Err(e) => eprintln!("Cannot save the run in {}: {}", ctx.opt.runs_dir.display(), e),
+            }
+        }
+    }
+    match ctx.guard.aborted() {
+        Some(reason) => Err(SimError::Setup(format!("Run aborted by the resource guard: {}", reason))),
+        None => Ok(()),
+    }
+}
+
+/// Rows a task sends per tick on average
+fn rows_per_tick(planes: &[Plane]) -> f64 {
+    planes.iter().map(Plane::share).sum()
+}
+
+/// Splits the planes between the generator tasks: one per plane, or `--workers` tasks
+/// that get the planes round robin. Returns the name of each task and its planes. The
+/// devices of a --model are split the same way, with no icao24.
+fn plane_groups(opt: &Opt, plane_ids: &PlaneIds, model: Option<&dyn DeviceModel>) -> Vec<(String, Vec<(String, String)>)> {
+    let planes = (0..opt.plane_count as u64).map(|n| match model {
+        Some(model) => (model.device_id(n), String::new()),
+        None => (plane_ids.get(n), plane_ids.icao24(n)),
+    })
+        .filter(|(plane_id, _)| opt.replay_plane.as_ref().map(|replay| replay == plane_id).unwrap_or(true));
+    match opt.workers {
+        Some(workers) => {
+            let mut groups = vec![vec![]; workers.min(opt.plane_count as usize)];
+            for (n, plane) in planes.enumerate() {
+                let group = n % groups.len();
+                groups[group].push(plane);
+            }
+            groups.into_iter().enumerate().map(|(worker, planes)| (format!("worker {}", worker), planes)).collect()
+        }
+        None => planes.map(|plane| (format!("{} {}", if model.is_some() { "device" } else { "plane" }, plane.0), vec![plane])).collect(),
+    }
+}
+
+/// Runs a generator task, and starts it again when it panics outside of the planes, up
+/// to --max-restarts times. Its planes then start again too, its buffered rows are lost.
+async fn supervise(ctx: Arc<RunContext>, name: String, planes: Vec<(String, String)>) -> Vec<PlaneStats> {
+    let mut restarts = 0;
+    loop {
+        let task = match &ctx.model {
+            Some(_) => tokio::spawn(model::generate(ctx.clone(), name.clone(), planes.iter().map(|(id, _)| id.clone()).collect())),
+            None => tokio::spawn(generate_data(ctx.clone(), name.clone(), planes.clone())),
+        };
+        match task.await {
+            Ok(stats) => return stats,
+            Err(e) if e.is_panic() && restarts < ctx.opt.max_restarts && ctx.quota.remaining() > 0 => {
+                restarts += 1;
+                ctx.task_restarts.fetch_add(1, Ordering::SeqCst);
+                ctx.errors.record("task restarts", &format!("Generator {} panicked and was restarted", name), &panic_message(&e.into_panic()));
+            }
+            Err(e) => {
+                eprintln!("A generator task failed: {}", e);
+                return vec![];
+            }
+        }
+    }
+}
+
+/// Sends what the sink still holds back and closes it, after the last batch.
+async fn close_sink(ctx: &RunContext) {
+    if let Err(e) = ctx.sink.flush().await {
+        ctx.errors.record("flush failures", "Failed to flush the sink at the end of the run", e.msg());
+    }
+    ctx.sink.close().await;
+}
+
+/// Runs the whole simulation on the async runtime.
+async fn run(ctx: Arc<RunContext>, groups: Vec<(String, Vec<(String, String)>)>) -> Vec<PlaneStats> {
+    let watcher = tokio::spawn(health::watch(ctx.clone()));
+    let keepalive = tokio::spawn(sink::keep_alive(ctx.clone()));
+    let reloader = ctx.opt.watch_config.then(|| tokio::spawn(reload::watch(ctx.clone())));
+
+    if ctx.opt.source != Source::Synthetic {
+        live::run(ctx.clone()).await;
+        watcher.abort();
+        keepalive.abort();
+        if let Some(reloader) = &reloader {
+            reloader.abort();
+        }
+        close_sink(&ctx).await;
+        let ingested_rows = ctx.quota.used();
+        println!("Live ingestion completed. Total rows ingested: {}", ingested_rows);
+        return vec![];
+    }
+
+    let tracker = tokio::spawn(rate::track(ctx.clone()));
+    let follower = ctx.schedule.is_some().then(|| tokio::spawn(schedule::follow(ctx.clone())));
+    let tasks: Vec<_> = groups.into_iter().map(|(name, planes)| supervise(ctx.clone(), name, planes)).collect();
+    let plane_stats: Vec<PlaneStats> = join_all(tasks).await.into_iter().flatten().collect();
+    watcher.abort();
+    keepalive.abort();
+    tracker.abort();
+    for task in reloader.iter().chain(follower.iter()) {
+        task.abort();
+    }
+    close_sink(&ctx).await;
+    if let Some(dashboard) = &ctx.dashboard {
+        dashboard.close();
+    }
+    ctx.rate.finish(&ctx);
+    ctx.phases.finish();
+    report(&ctx, &plane_stats);
+    plane_stats
+}
+
+/// Prints the end of run statistics.
+fn report(ctx: &RunContext, plane_stats: &[PlaneStats]) {
+    stats::print_table(plane_stats, !ctx.opt.quiet);
+    ctx.rate.print_summary();
+    ctx.phases.print_summary();
+    ctx.latencies.print_summary();
+    ctx.profile.print_summary();
+    ctx.guard.print_summary();
+    if let Some(prefix) = &ctx.opt.latency_hgrm {
+        match ctx.latencies.write_hgrm(prefix) {
+            Ok(paths) => println!("Latency histograms written to {}", paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(" and ")),
+            Err(e) => eprintln!("Cannot write the latency histograms to {}: {}", prefix.display(), e),
+        }
+    }
+    if !ctx.opt.quiet {
+        let (created, reused) = ctx.pool.counts();
+        println!("Buffers allocated: {}, reused from the pool: {}", created, reused);
+        ctx.sink.print_summary();
+        ctx.rate_limits.print_summary();
+        ctx.network.print_summary();
+    }
+    if let Some(mirror) = &ctx.mirror {
+        mirror.print_summary();
+    }
+    if let (Some(sample_log), false) = (&ctx.sample_log, ctx.opt.quiet) {
+        sample_log.print_summary();
+    }
+    if !ctx.opt.quiet || ctx.opt.auto_flush_rows.is_some() || ctx.opt.auto_flush_interval_ms.is_some() {
+        ctx.auto_flush.print_summary(ctx.memory.peak());
+    }
+    if let Some(path) = &ctx.opt.stats_csv {
+        if let Err(e) = stats::write_csv(plane_stats, path) {
+            eprintln!("Cannot write per-plane stats to {}: {}", path.display(), e);
+        }
+    }
+    if let Some(trajectories) = &ctx.trajectories {
+        if let Err(e) = trajectories.write() {
+            eprintln!("Cannot write trajectories to {}: {}", trajectories.path().display(), e);
+        }
+    }
+
+    ctx.errors.finish();
+    let row_errors = ctx.row_errors.load(Ordering::SeqCst);
+    println!("Data generation completed. Total rows generated: {}", ctx.rows_generated());
+    if row_errors > 0 {
+        eprintln!("Rows that could not be built: {}", row_errors);
+    }
+    let rows_dropped = ctx.rows_dropped.load(Ordering::SeqCst);
+    if rows_dropped > 0 {
+        eprintln!("Rows dropped because the buffers were full while the server was down: {}", rows_dropped);
+    }
+    if let Some(table) = &ctx.opt.companion_table {
+        println!("Clearances written to {}: {}", table, ctx.rows_companion.load(Ordering::SeqCst));
+    }
+    if let Some(table) = &ctx.opt.status_table {
+        println!("Status rows written to {}: {}", table, ctx.rows_status.load(Ordering::SeqCst));
+    }
+    if let Some(table) = &ctx.opt.legs_table {
+        println!("Flight legs written to {}: {}", table, ctx.rows_legs.load(Ordering::SeqCst));
+    }
+    if let Some(cold) = &ctx.cold {
+        cold.print_summary(ctx.rows_generated());
+    }
+    if ctx.opt.monotonic_per_plane {
+        println!("Timestamps moved forward to keep them increasing per plane: {}", ctx.rows_bumped.load(Ordering::SeqCst));
+    }
+    let (plane_restarts, task_restarts) = (ctx.plane_restarts.load(Ordering::SeqCst), ctx.task_restarts.load(Ordering::SeqCst));
+    if plane_restarts > 0 || task_restarts > 0 {
+        eprintln!("Restarts after panics: {} of planes, {} of generator tasks", plane_restarts, task_restarts);
+    }
+    let rows_poisoned = ctx.rows_poisoned.load(Ordering::SeqCst);
+    if rows_poisoned > 0 {
+        eprintln!("Invalid rows injected on purpose: {}", rows_poisoned);
+    }
+    if let Some(flips) = &ctx.type_flips {
+        flips.print_summary(ctx.columns().name("altitude"));
+    }
+    if let Some(added_columns) = &ctx.added_columns {
+        added_columns.print_summary();
+    }
+    if let Some(drops) = &ctx.column_drops {
+        drops.print_summary(&ctx.columns(), &ctx.opt.table_name);
+    }
+    let rows_rejected = ctx.rows_rejected.load(Ordering::SeqCst);
+    if rows_rejected > 0 {
+        eprintln!("Rows in batches rejected by the server: {}", rows_rejected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use super::*;
+
+    proptest! {
+        /// A clock that stands still or jumps back now and then, as after an NTP step
+        #[test]
+        fn bumped_timestamps_keep_increasing(start in 0..1_000_000_000_000i64,
+                                             steps in prop::collection::vec(prop_oneof![1 => Just(0i64), 1 => -1_000_000_000..0i64, 8 => 1..10_000_000i64], 1..1000)) {
+            let (mut clock, mut last) = (start, i64::MIN);
+            for step in steps {
+                clock += step;
+                let timestamp = bump_timestamp(last, clock).unwrap_or(clock);
+                prop_assert!(timestamp > last, "{} after {}", timestamp, last);
+                prop_assert!(timestamp >= clock, "{} is before the clock at {}", timestamp, clock);
+                last = timestamp;
+            }
+        }
+    }
 }
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::RunContext;
+use crate::questdb_http::{QueryClient, QueryResult};
+
+fn identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// One-minute OHLC bars of the airspeed of every plane, refreshed incrementally by the
+/// server as rows come in.
+fn create_sql(ctx: &RunContext, view: &str) -> String {
+    let airspeed = identifier(ctx.columns().name("airspeed"));
+    format!("CREATE MATERIALIZED VIEW IF NOT EXISTS {v} AS (\
+             SELECT timestamp, {id}, first({a}) open, max({a}) high, min({a}) low, last({a}) close, count() readings \
+             FROM {t} SAMPLE BY 1m) PARTITION BY DAY",
+            v = identifier(view), id = identifier(ctx.columns().name("plane_id")), a = airspeed, t = identifier(&ctx.opt.table_name))
+}
+
+/// How far behind its base table the view was at one sample.
+#[derive(Debug, Default)]
+struct Lag {
+    samples: u64,
+    behind: u64,
+    max_txns: i64,
+    max_secs: f64,
+    total_secs: f64,
+    status: String,
+}
+
+fn field<'a>(result: &'a QueryResult, row: &'a [Value], names: &[&str]) -> &'a Value {
+    names.iter().find_map(|name| result.column_index(name)).and_then(|index| row.get(index)).unwrap_or(&Value::Null)
+}
+
+fn timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    value.as_str().and_then(|text| DateTime::parse_from_rfc3339(text).ok()).map(|time| time.with_timezone(&Utc))
+}
+
+impl Lag {
+    /// Samples `materialized_views()`. The view is behind while the base table has
+    /// transactions it has not refreshed from; the lag is then the time since the last
+    /// refresh finished.
+    fn sample(&mut self, client: &QueryClient, view: &str) -> Result<(), String> {
+        let result = client.exec("SELECT * FROM materialized_views()")?;
+        let row = result.dataset.iter()
+            .find(|row| field(&result, row, &["view_name"]).as_str() == Some(view))
+            .ok_or_else(|| format!("view '{}' not found in materialized_views()", view))?;
+        let status = field(&result, row, &["view_status"]).as_str().unwrap_or("unknown");
+        self.status = match field(&result, row, &["invalidation_reason"]).as_str().filter(|reason| !reason.is_empty()) {
+            Some(reason) => format!("{}: {}", status, reason),
+            None => status.to_string(),
+        };
+        let base_txn = field(&result, row, &["base_table_txn"]).as_i64().unwrap_or(0);
+        let refreshed_txn = field(&result, row, &["refresh_base_table_txn", "last_refresh_base_table_txn"]).as_i64().unwrap_or(0);
+        let finished = timestamp(field(&result, row, &["last_refresh_finish_timestamp", "last_refresh_timestamp"]));
+        self.samples += 1;
+        let txns = base_txn - refreshed_txn;
+        if txns > 0 {
+            let secs = finished.map(|finished| (Utc::now() - finished).num_milliseconds() as f64 / 1000.0).unwrap_or(0.0).max(0.0);
+            self.behind += 1;
+            self.max_txns = self.max_txns.max(txns);
+            self.max_secs = self.max_secs.max(secs);
+            self.total_secs += secs;
+        }
+        Ok(())
+    }
+}
+
+/// Creates a materialized view over the table with --mat-view and checks every
+/// --mat-view-every-secs how far its refreshes lag behind the ingestion. The view can
+/// only be created once the table exists, so until then creating it is retried.
+pub struct MatView {
+    name: String,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Lag>,
+}
+
+impl MatView {
+    pub fn start(ctx: Arc<RunContext>, name: String, every: Duration) -> Result<Self, String> {
+        let client = QueryClient::from_conf(&ctx.opt.connection_string)?;
+        let mut created = client.exec(&create_sql(&ctx, &name)).is_ok();
+        if created {
+            println!("Materialized view {} ready", name);
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let view = name.clone();
+        let thread = thread::Builder::new().name("mat-view".to_string()).spawn(move || {
+            let mut lag = Lag::default();
+            let mut next = Instant::now() + every;
+            while !stopped.load(Ordering::Relaxed) {
+                if Instant::now() >= next {
+                    next += every;
+                    if !created {
+                        match client.exec(&create_sql(&ctx, &view)) {
+                            Ok(_) => {
+                                created = true;
+                                println!("Materialized view {} ready", view);
+                            }
+                            Err(e) => ctx.errors.record("view failures", &format!("Cannot create materialized view {}", view), &e),
+                        }
+                    } else if let Err(e) = lag.sample(&client, &view) {
+                        ctx.errors.record("view failures", &format!("Cannot check materialized view {}", view), &e);
+                    }
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            lag
+        }).map_err(|e| e.to_string())?;
+        Ok(MatView { name, stop, thread })
+    }
+
+    /// Stops sampling and prints how well the view kept up.
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let lag = match self.thread.join() {
+            Ok(lag) => lag,
+            Err(_) => return,
+        };
+        if lag.samples == 0 {
+            println!("Materialized view {}: no refresh lag samples, run longer than --mat-view-every-secs", self.name);
+            return;
+        }
+        println!("Materialized view {} ({}): behind in {} of {} samples, by up to {} transactions and {:.1}s, {:.1}s on average when behind",
+                 self.name, lag.status, lag.behind, lag.samples, lag.max_txns, lag.max_secs,
+                 lag.total_secs / lag.behind.max(1) as f64);
+    }
+}
@@ -0,0 +1,64 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// What a plane does when its buffer is over the memory limits and cannot be flushed
+/// because the server is down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BufferFull {
+    /// Stop generating until the server is back
+    Block,
+    /// Keep generating but discard the new rows
+    Drop,
+}
+
+impl FromStr for BufferFull {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(BufferFull::Block),
+            "drop" => Ok(BufferFull::Drop),
+            _ => Err(format!("invalid buffer full policy '{}', expected block or drop", s)),
+        }
+    }
+}
+
+/// Bytes held in the plane buffers, checked against a per-plane cap and a global
+/// budget so a slow or restarting server cannot make memory grow without bounds.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    used: AtomicU64,
+    peak: AtomicU64,
+    per_plane: Option<u64>,
+    global: Option<u64>,
+}
+
+impl MemoryBudget {
+    pub fn new(per_plane: Option<u64>, global: Option<u64>) -> Self {
+        MemoryBudget { used: AtomicU64::new(0), peak: AtomicU64::new(0), per_plane, global }
+    }
+
+    /// Updates the global count with the new size of a buffer. `accounted` is the size
+    /// last reported for that buffer.
+    pub fn track(&self, accounted: &mut usize, len: usize) {
+        if len >= *accounted {
+            let used = self.used.fetch_add((len - *accounted) as u64, Ordering::Relaxed) + (len - *accounted) as u64;
+            self.peak.fetch_max(used, Ordering::Relaxed);
+        } else {
+            self.used.fetch_sub((*accounted - len) as u64, Ordering::Relaxed);
+        }
+        *accounted = len;
+    }
+
+    /// Most bytes held in all the buffers together at any time
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// True when a buffer of `len` bytes is over its own cap, or all buffers together
+    /// are over the budget.
+    pub fn exceeded(&self, len: usize) -> bool {
+        self.per_plane.map(|max| len as u64 >= max).unwrap_or(false)
+            || self.global.map(|max| self.used.load(Ordering::Relaxed) >= max).unwrap_or(false)
+    }
+}
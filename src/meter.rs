@@ -0,0 +1,247 @@
+use chrono::{Datelike, Duration, Timelike, Weekday};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use questdb::ingress::{Buffer, TimestampNanos};
+
+use crate::model::{Device, DeviceModel};
+use crate::scaffold::Bundle;
+use crate::turbulence::OrnsteinUhlenbeck;
+
+/// Grids the meters are on: name, UTC offset in hours without daylight saving time, and
+/// nominal voltage between phase and neutral.
+const GRIDS: &[(&str, i64, f64)] = &[
+    ("uk", 0, 230.0),
+    ("de", 1, 230.0),
+    ("us_east", -5, 120.0),
+];
+
+/// Every COMMERCIAL_EVERY-th meter is on a shop or an office instead of a home
+const COMMERCIAL_EVERY: u64 = 10;
+
+/// Share of the peak demand at each local hour. Homes use a little overnight, peak in the
+/// morning and most in the evening; businesses use most during opening hours.
+const RESIDENTIAL: [f64; 24] = [
+    0.25, 0.2, 0.18, 0.18, 0.2, 0.3, 0.55, 0.75, 0.6, 0.45, 0.4, 0.4,
+    0.45, 0.42, 0.4, 0.45, 0.6, 0.85, 1.0, 0.95, 0.85, 0.7, 0.5, 0.35,
+];
+const COMMERCIAL: [f64; 24] = [
+    0.15, 0.15, 0.15, 0.15, 0.15, 0.15, 0.25, 0.55, 0.9, 1.0, 1.0, 1.0,
+    0.95, 1.0, 1.0, 1.0, 0.95, 0.8, 0.5, 0.3, 0.2, 0.15, 0.15, 0.15,
+];
+/// Businesses at the weekend, as a share of their peak
+const COMMERCIAL_WEEKEND: f64 = 0.2;
+
+/// Who a meter is on, the `customer` symbol.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Customer {
+    /// A home on a single phase
+    Residential,
+    /// A shop or an office on three phases
+    Commercial,
+}
+
+impl Customer {
+    fn of(number: u64) -> Self {
+        if number % COMMERCIAL_EVERY == COMMERCIAL_EVERY - 1 { Customer::Commercial } else { Customer::Residential }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Customer::Residential => "residential",
+            Customer::Commercial => "commercial",
+        }
+    }
+}
+
+/// Share of the peak demand of a customer at a time, in the local time of its grid,
+/// interpolated between the hours of its curve
+fn demand(customer: Customer, utc_offset: i64, timestamp: i64) -> f64 {
+    let local = chrono::DateTime::from_timestamp_nanos(timestamp) + Duration::hours(utc_offset);
+    let hour = local.hour() as usize;
+    let within = (local.minute() as f64 * 60.0 + local.second() as f64) / 3600.0;
+    let curve = match customer {
+        Customer::Residential => &RESIDENTIAL,
+        Customer::Commercial if matches!(local.weekday(), Weekday::Sat | Weekday::Sun) => return COMMERCIAL_WEEKEND,
+        Customer::Commercial => &COMMERCIAL,
+    };
+    curve[hour] + (curve[(hour + 1) % 24] - curve[hour]) * within
+}
+
+/// Table, queries and dashboard of `airplanes scaffold --model meters`
+pub const BUNDLE: Bundle = Bundle {
+    key: "meter_id",
+    symbols: &["meter_id", "customer", "grid"],
+    columns: &[
+        ("energy_kwh", "DOUBLE"), ("power_kw", "DOUBLE"), ("voltage", "DOUBLE"), ("current", "DOUBLE"), ("power_factor", "DOUBLE"),
+    ],
+    queries: &[
+        ("Latest register reading of every meter",
+         "SELECT meter_id, customer, grid, energy_kwh, power_kw, timestamp\nFROM {table}\nLATEST ON timestamp PARTITION BY meter_id;"),
+        ("Energy used by each meter, per hour, from the counter",
+         "SELECT timestamp, meter_id, last(energy_kwh) - first(energy_kwh) kwh\nFROM {table}\nSAMPLE BY 1h;"),
+        ("Demand of each grid and kind of customer, per 15 minutes",
+         "SELECT timestamp, grid, customer, avg(power_kw) power_kw\nFROM {table}\nSAMPLE BY 15m;"),
+        ("Lowest voltage and power factor of each grid",
+         "SELECT grid, min(voltage) min_voltage, avg(voltage) avg_voltage, min(power_factor) min_power_factor\nFROM {table}\nORDER BY grid;"),
+    ],
+    panels: &[
+        ("Demand (kW)", "SELECT timestamp, avg(power_kw) avg_kw, max(power_kw) max_kw FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+        ("Voltage (V)", "SELECT timestamp, grid, avg(voltage) voltage FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+    ],
+    map: None,
+};
+
+/// Smart meters, `--model meters`: homes and businesses on three grids, each drawing power
+/// along the daily curve of its kind of customer in its local time, with the noise of
+/// appliances switching on and off, and counting the energy in a kWh register that only
+/// ever goes up.
+pub struct Meters;
+
+impl DeviceModel for Meters {
+    /// MTR000000 onwards, the grid going round the list
+    fn device_id(&self, n: u64) -> String {
+        format!("MTR{:06}", n)
+    }
+
+    fn device(&self, id: &str, seed: u64, timestamp: i64) -> Box<dyn Device> {
+        let number: u64 = id.trim_start_matches("MTR").parse().unwrap_or(0);
+        Box::new(Meter::new(id, Customer::of(number), GRIDS[number as usize % GRIDS.len()], seed, timestamp))
+    }
+}
+
+struct Meter {
+    meter_id: String,
+    customer: Customer,
+    grid: &'static str,
+    utc_offset: i64,
+    nominal_voltage: f64,
+    /// kW the customer draws at the top of its curve
+    peak_kw: f64,
+    rng: StdRng,
+    timestamp: i64,
+    /// Appliances switching on and off, as a factor of the demand
+    appliances: OrnsteinUhlenbeck,
+    /// Swings of the supply voltage, in volts
+    supply: OrnsteinUhlenbeck,
+    /// The register, in kWh
+    energy_kwh: f64,
+    voltage: f64,
+    /// Amps on each phase
+    current: f64,
+    power_factor: f64,
+    power_kw: f64,
+}
+
+impl Meter {
+    fn new(id: &str, customer: Customer, (grid, utc_offset, nominal_voltage): (&'static str, i64, f64), seed: u64, timestamp: i64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (peak_kw, energy_kwh) = match customer {
+            Customer::Residential => (rng.gen_range(3.0..7.0), rng.gen_range(1_000.0..60_000.0)),
+            Customer::Commercial => (rng.gen_range(20.0..80.0), rng.gen_range(10_000.0..900_000.0)),
+        };
+        let appliances = OrnsteinUhlenbeck::new(1.0, 1.0 / 120.0, 0.025, &mut rng);
+        let supply = OrnsteinUhlenbeck::new(0.0, 1.0 / 60.0, 0.01 * nominal_voltage / 10.0, &mut rng);
+        let mut meter = Meter {
+            meter_id: id.to_string(),
+            customer,
+            grid,
+            utc_offset,
+            nominal_voltage,
+            peak_kw,
+            rng,
+            timestamp,
+            appliances,
+            supply,
+            energy_kwh,
+            voltage: nominal_voltage,
+            current: 0.0,
+            power_factor: 1.0,
+            power_kw: 0.0,
+        };
+        meter.update(timestamp);
+        meter
+    }
+
+    fn phases(&self) -> f64 {
+        match self.customer {
+            Customer::Residential => 1.0,
+            Customer::Commercial => 3.0,
+        }
+    }
+}
+
+impl Device for Meter {
+    fn update(&mut self, timestamp: i64) {
+        let dt = ((timestamp - self.timestamp) as f64 / 1e9).clamp(0.0, 10.0);
+        self.timestamp = timestamp;
+        let share = demand(self.customer, self.utc_offset, timestamp);
+        let appliances = self.appliances.step(dt, &mut self.rng).clamp(0.3, 2.0);
+        // The previous power runs until now, so the register counts what was shown
+        self.energy_kwh += self.power_kw * dt / 3600.0;
+        self.power_kw = (self.peak_kw * share * appliances).max(0.05);
+        // The voltage sags a little as the neighbourhood draws more
+        let sag = 0.03 * self.nominal_voltage * share;
+        self.voltage = self.nominal_voltage * 1.02 - sag + self.supply.step(dt, &mut self.rng);
+        // Electronics and motors at low loads, mostly resistive heating and cooking at high ones
+        let load = (self.power_kw / self.peak_kw).min(1.0);
+        self.power_factor = (0.82 + 0.15 * load + self.rng.gen_range(-0.01..0.01)).clamp(0.5, 1.0);
+        self.current = self.power_kw * 1000.0 / (self.phases() * self.voltage * self.power_factor);
+    }
+
+    fn write(&self, buffer: &mut Buffer, table_name: &str) -> questdb::Result<()> {
+        buffer.table(table_name)?
+            .symbol("meter_id", &self.meter_id)?
+            .symbol("customer", self.customer.as_str())?
+            .symbol("grid", self.grid)?
+            .column_f64("energy_kwh", self.energy_kwh)?
+            .column_f64("power_kw", self.power_kw)?
+            .column_f64("voltage", self.voltage)?
+            .column_f64("current", self.current)?
+            .column_f64("power_factor", self.power_factor)?
+            .at(TimestampNanos::new(self.timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::monday_at;
+
+    #[test]
+    fn demand_follows_the_day_in_local_time() {
+        let home = |hours| demand(Customer::Residential, 0, monday_at(hours));
+        assert!(home(18.0) > home(12.0) && home(12.0) > home(3.0));
+        assert!(home(7.5) > home(10.0));
+        // 18:00 on the US east coast is 23:00 UTC
+        assert_eq!(demand(Customer::Residential, -5, monday_at(23.0)), home(18.0));
+        let office = |hours| demand(Customer::Commercial, 0, monday_at(hours));
+        assert!(office(11.0) > office(20.0));
+        // Saturday
+        assert_eq!(office(5.0 * 24.0 + 11.0), COMMERCIAL_WEEKEND);
+    }
+
+    /// Over two days of 30 meters the register never goes back, and the readings stay
+    /// within what a supply gives
+    #[test]
+    fn the_register_only_goes_up() {
+        for n in 0..30 {
+            let id = Meters.device_id(n);
+            let mut meter = Meter::new(&id, Customer::of(n), GRIDS[n as usize % GRIDS.len()], n, monday_at(0.0));
+            let start = meter.energy_kwh;
+            let mut previous = start;
+            for second in (10..=2 * 86_400).step_by(10) {
+                meter.update(monday_at(0.0) + second * 1_000_000_000);
+                assert!(meter.energy_kwh >= previous, "{}: {} after {}", id, meter.energy_kwh, previous);
+                previous = meter.energy_kwh;
+                let volts = meter.voltage / meter.nominal_voltage;
+                assert!((0.94..1.06).contains(&volts), "{}: {} V", id, meter.voltage);
+                assert!((0.5..=1.0).contains(&meter.power_factor), "{}: power factor {}", id, meter.power_factor);
+                assert!(meter.current > 0.0);
+            }
+            // Two days at somewhere between the night and the peak demand
+            let used = meter.energy_kwh - start;
+            assert!(used > meter.peak_kw * 48.0 * 0.1 && used < meter.peak_kw * 48.0, "{}: {} kWh in two days", id, used);
+        }
+    }
+}
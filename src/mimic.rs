@@ -0,0 +1,118 @@
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use questdb::ingress::{Buffer, TimestampMicros};
+
+use crate::plane::PlaneData;
+use crate::questdb_http::QueryClient;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Symbol,
+    Double,
+    Long,
+    Boolean,
+    Text,
+    Char,
+    Timestamp,
+}
+
+#[derive(Debug, Clone)]
+struct MimicColumn {
+    name: String,
+    kind: ColumnKind,
+}
+
+/// Schema of an existing table, used to generate random rows that fit into it.
+#[derive(Debug, Clone)]
+pub struct MimicTable {
+    /// Symbols first, as ILP requires
+    columns: Vec<MimicColumn>,
+}
+
+impl MimicTable {
+    /// Reads the table schema with `SHOW COLUMNS`. Columns of types that cannot be
+    /// sent over ILP are left out, so they will be null.
+    pub fn introspect(client: &QueryClient, table: &str) -> Result<Self, String> {
+        let result = client.exec(&format!("SHOW COLUMNS FROM '{}'", table.replace('\'', "''")))
+            .map_err(|e| format!("cannot read schema of table '{}': {}", table, e))?;
+        let (name_idx, type_idx) = match (result.column_index("column"), result.column_index("type")) {
+            (Some(n), Some(t)) => (n, t),
+            _ => return Err(format!("unexpected SHOW COLUMNS output for table '{}'", table)),
+        };
+        let designated_idx = result.column_index("designated");
+
+        let mut columns = vec![];
+        for row in result.dataset.iter() {
+            let name = row[name_idx].as_str().unwrap_or_default().to_string();
+            let column_type = row[type_idx].as_str().unwrap_or_default();
+            if designated_idx.map(|i| row[i].as_bool() == Some(true)).unwrap_or(false) {
+                continue;
+            }
+            let kind = match column_type {
+                "SYMBOL" => ColumnKind::Symbol,
+                "DOUBLE" | "FLOAT" => ColumnKind::Double,
+                "LONG" | "INT" | "SHORT" | "BYTE" => ColumnKind::Long,
+                "BOOLEAN" => ColumnKind::Boolean,
+                "STRING" | "VARCHAR" => ColumnKind::Text,
+                "CHAR" => ColumnKind::Char,
+                "TIMESTAMP" => ColumnKind::Timestamp,
+                other => {
+                    eprintln!("Column '{}' of type {} cannot be generated, it will be left null", name, other);
+                    continue;
+                }
+            };
+            columns.push(MimicColumn { name, kind });
+        }
+        if columns.is_empty() {
+            return Err(format!("table '{}' has no columns that can be generated", table));
+        }
+        columns.sort_by_key(|c| c.kind != ColumnKind::Symbol);
+        Ok(MimicTable { columns })
+    }
+}
+
+/// Per-plane state for a mimicked table: numeric columns random walk so they look
+/// like time series, and the first symbol column identifies the plane.
+#[derive(Debug, Clone)]
+pub struct MimicRow {
+    device_id: String,
+    values: Vec<f64>,
+}
+
+impl MimicRow {
+    pub fn new(table: &MimicTable, device_id: String) -> Self {
+        let mut rng = rand::thread_rng();
+        let values = table.columns.iter().map(|_| rng.gen_range(0.0..100.0)).collect();
+        MimicRow { device_id, values }
+    }
+
+    pub fn write(&mut self, table: &MimicTable, buffer: &mut Buffer, table_name: &str, run_label: Option<(&str, &str)>, plane: &PlaneData) -> questdb::Result<()> {
+        let mut rng = rand::thread_rng();
+        buffer.table(table_name)?;
+        if let Some((name, label)) = run_label {
+            buffer.symbol(name, label)?;
+        }
+        let mut first_symbol = true;
+        for (column, value) in table.columns.iter().zip(self.values.iter_mut()) {
+            *value = (*value + rng.gen_range(-1.0..1.0)).clamp(0.0, 100.0);
+            let name = column.name.as_str();
+            match column.kind {
+                ColumnKind::Symbol if first_symbol => {
+                    first_symbol = false;
+                    buffer.symbol(name, &self.device_id)?
+                }
+                ColumnKind::Symbol => buffer.symbol(name, format!("{}_{}", name, rng.gen_range(0..10)))?,
+                ColumnKind::Double => buffer.column_f64(name, *value)?,
+                ColumnKind::Long => buffer.column_i64(name, value.round() as i64)?,
+                ColumnKind::Boolean => buffer.column_bool(name, rng.gen_bool(0.5))?,
+                ColumnKind::Text => {
+                    let text: String = (&mut rng).sample_iter(&Alphanumeric).take(8).map(char::from).collect();
+                    buffer.column_str(name, text)?
+                }
+                ColumnKind::Char => buffer.column_str(name, (rng.sample(Alphanumeric) as char).to_string())?,
+                ColumnKind::Timestamp => buffer.column_ts(name, TimestampMicros::new(plane.timestamp / 1000))?,
+            };
+        }
+        plane.at(buffer)
+    }
+}
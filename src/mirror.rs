@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use questdb::ingress::Buffer;
+
+use crate::RunContext;
+use crate::error_summary::thousands;
+use crate::sender_pool::SenderPool;
+
+/// The second endpoint of --mirror-connection-string, that gets a copy of every flush, and
+/// how the flushes of the two endpoints compare: for a migration, the old cluster and the
+/// new one get the same traffic and any flush only one of them accepted is a divergence.
+pub struct Mirror {
+    pub senders: SenderPool,
+    both: AtomicU64,
+    primary_only: AtomicU64,
+    mirror_only: AtomicU64,
+    neither: AtomicU64,
+    rows_primary: AtomicU64,
+    rows_mirror: AtomicU64,
+}
+
+impl Mirror {
+    pub fn new(senders: SenderPool) -> Self {
+        Mirror {
+            senders,
+            both: AtomicU64::new(0),
+            primary_only: AtomicU64::new(0),
+            mirror_only: AtomicU64::new(0),
+            neither: AtomicU64::new(0),
+            rows_primary: AtomicU64::new(0),
+            rows_mirror: AtomicU64::new(0),
+        }
+    }
+
+    /// Sends a copy of the buffer to the mirror. Tells whether it accepted the rows.
+    pub async fn send(&self, ctx: &RunContext, buffer: &Buffer, name: &str) -> bool {
+        match self.senders.flush_and_keep(buffer).await {
+            Ok(()) => true,
+            Err(e) => {
+                ctx.errors.record("mirror flush failures", &format!("{} could not flush to the mirror", name), e.msg());
+                false
+            }
+        }
+    }
+
+    /// Counts a flush of `rows`, by whether each endpoint accepted it
+    pub fn record(&self, primary: bool, mirror: bool, rows: usize) {
+        let outcome = match (primary, mirror) {
+            (true, true) => &self.both,
+            (true, false) => &self.primary_only,
+            (false, true) => &self.mirror_only,
+            (false, false) => &self.neither,
+        };
+        outcome.fetch_add(1, Ordering::Relaxed);
+        if primary {
+            self.rows_primary.fetch_add(rows as u64, Ordering::Relaxed);
+        }
+        if mirror {
+            self.rows_mirror.fetch_add(rows as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn print_summary(&self) {
+        let load = |count: &AtomicU64| count.load(Ordering::Relaxed);
+        let (rows_primary, rows_mirror) = (load(&self.rows_primary), load(&self.rows_mirror));
+        println!("Mirror: {} flushes accepted by both endpoints, {} only by the primary, {} only by the mirror, {} by neither",
+                 thousands(load(&self.both)), thousands(load(&self.primary_only)), thousands(load(&self.mirror_only)), thousands(load(&self.neither)));
+        println!("Mirror: {} rows accepted by the primary, {} by the mirror{}", thousands(rows_primary), thousands(rows_mirror),
+                 if load(&self.primary_only) + load(&self.mirror_only) > 0 { ", the endpoints diverged" } else { "" });
+    }
+}
@@ -0,0 +1,239 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use questdb::ingress::Buffer;
+use tokio::time::{Duration, interval};
+
+use crate::{Opt, Plane, RunContext};
+use crate::meter::{self, Meters};
+use crate::scaffold::Bundle;
+use crate::seed;
+use crate::stats::PlaneStats;
+use crate::task::{Engine, Producers, TaskBuffer};
+use crate::ticks::{self, Ticks};
+use crate::turbine::{self, Turbines};
+use crate::type_flip::FlipType;
+use crate::vehicle::{self, Vehicles};
+use crate::vessel::{self, Vessels};
+use crate::weather::{self, Stations};
+
+/// A kind of device the simulator can send telemetry for, other than the planes, which
+/// have a generation loop of their own with all the options that go with it. The others
+/// share the loop of `generate`: one row per device and tick, at --rate-per-plane for
+/// --plane-count devices, through the same quota, buffers and sink.
+pub trait DeviceModel: Send + Sync {
+    /// Id of the `n`-th device, the symbol its rows are told apart by
+    fn device_id(&self, n: u64) -> String;
+
+    /// A device whose clock starts at `timestamp`, its readings drawn from `seed` so the
+    /// same --seed gives the same device
+    fn device(&self, id: &str, seed: u64, timestamp: i64) -> Box<dyn Device>;
+
+    /// Rows a device sends per tick of `tick_millis` on average, one unless its `has_row`
+    /// skips ticks, so that a task claims rows from the quota at the pace it writes them
+    fn rows_per_tick(&self, _tick_millis: u64) -> f64 {
+        1.0
+    }
+}
+
+/// A device of a model, in a generator task.
+pub trait Device: Send {
+    /// Whether the device has a row at `timestamp`, for devices that do not report on
+    /// every tick. Their skipped ticks lower the rate they are compared against.
+    fn has_row(&mut self, _timestamp: i64) -> bool {
+        true
+    }
+
+    /// Advances the device to `timestamp`, in nanoseconds
+    fn update(&mut self, timestamp: i64);
+
+    /// Writes the current readings as a row of `table_name`
+    fn write(&self, buffer: &mut Buffer, table_name: &str) -> questdb::Result<()>;
+}
+
+/// A model --model can choose: its name, what it simulates, the table its rows go to
+/// without a --table-name, its demo bundle and the model itself.
+pub struct ModelKind {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub table: &'static str,
+    /// What `airplanes scaffold` writes for it
+    pub bundle: &'static Bundle,
+    pub open: fn(&Opt) -> Box<dyn DeviceModel>,
+}
+
+/// The default --model, which is not in `MODELS`
+pub const PLANES: &str = "planes";
+
+pub const MODELS: &[ModelKind] = &[
+    ModelKind { name: "meters", about: "smart meters of homes and businesses, with a kWh register, voltage, current and power factor along daily curves", table: "meters", bundle: &meter::BUNDLE, open: |_| Box::new(Meters) },
+    ModelKind { name: "ticks", about: "market data of equities, crypto and FX, with bid, ask, trade price and size, busiest at the open and close", table: "ticks", bundle: &ticks::BUNDLE, open: |_| Box::new(Ticks) },
+    ModelKind { name: "turbines", about: "wind turbines in farms of 20, with wind, power curve, pitch and temperatures", table: "turbines", bundle: &turbine::BUNDLE, open: |_| Box::new(Turbines) },
+    ModelKind { name: "vehicles", about: "fleet telematics of road vehicles driving city streets, with speed, RPM, fuel, odometer and harsh brakes", table: "vehicles", bundle: &vehicle::BUNDLE, open: |_| Box::new(Vehicles) },
+    ModelKind { name: "vessels", about: "AIS position reports of ships sailing between ports, with MMSI, SOG, COG, draught and nav status", table: "vessels", bundle: &vessel::BUNDLE, open: |_| Box::new(Vessels) },
+    ModelKind { name: "weather", about: "weather stations sampling once a minute each, with temperature, humidity, pressure, wind, rain and sunshine", table: "weather", bundle: &weather::BUNDLE, open: |opt| {
+        let interval_ms = opt.sample_interval_ms.unwrap_or(weather::SAMPLE_INTERVAL_MS);
+        Box::new(Stations { interval_nanos: interval_ms as i64 * 1_000_000 })
+    } },
+];
+
+pub fn find(name: &str) -> Option<&'static ModelKind> {
+    MODELS.iter().find(|kind| kind.name == name)
+}
+
+/// "planes, turbines (wind turbines ...)", for the error of an unknown --model
+pub fn describe() -> String {
+    let models: Vec<String> = MODELS.iter().map(|kind| format!("{} ({})", kind.name, kind.about)).collect();
+    format!("{}, {}", PLANES, models.join(", "))
+}
+
+/// A device with its counters.
+struct Running {
+    id: String,
+    device: Box<dyn Device>,
+    stats: PlaneStats,
+    /// Rows of this device waiting in the task's buffer
+    buffered: u64,
+    stopped: bool,
+}
+
+impl Running {
+    /// Writes the next row and counts it, or counts the error. Returns true if the row is
+    /// in the buffer. A device that panics skips the row, as its state may be half updated.
+    fn write_row(&mut self, ctx: &RunContext, buffer: &mut Buffer, timestamp: i64) -> bool {
+        let written = buffer.set_marker().and_then(|_| {
+            match panic::catch_unwind(AssertUnwindSafe(|| {
+                self.device.update(timestamp);
+                self.device.write(buffer, &ctx.opt.table_name)
+            })) {
+                Ok(written) => written,
+                Err(_) => Err(questdb::Error::new(questdb::ErrorCode::InvalidApiCall, "the device panicked".to_string())),
+            }
+        });
+        if written.is_err() {
+            let _ = buffer.rewind_to_marker();
+        }
+        buffer.clear_marker();
+        match written {
+            Ok(()) => {
+                self.stats.record_row();
+                self.buffered += 1;
+                ctx.rate.record_row();
+                ctx.phases.record_row();
+                true
+            }
+            Err(e) => {
+                self.stats.row_errors += 1;
+                ctx.row_errors.fetch_add(1, Ordering::SeqCst);
+                if !ctx.opt.quiet || self.stats.row_errors == 1 {
+                    eprintln!("Device {} could not build a row: {}", self.id, e.msg());
+                }
+                if ctx.opt.max_row_errors.map(|max| self.stats.row_errors >= max).unwrap_or(false) {
+                    eprintln!("Device {} stopped after {} row errors", self.id, self.stats.row_errors);
+                    self.stopped = true;
+                }
+                false
+            }
+        }
+    }
+}
+
+impl Producers for [Running] {
+    fn planes(&mut self) -> &mut [Plane] {
+        &mut []
+    }
+
+    fn write_row(&mut self, ctx: &RunContext, i: usize, buffer: &mut Buffer, _flip: Option<FlipType>) -> bool {
+        self[i].write_row(ctx, buffer, ctx.now())
+    }
+
+    fn drop_row(&mut self, ctx: &RunContext, i: usize) {
+        self[i].stats.rows_dropped += 1;
+        ctx.rows_dropped.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Counts the flush against the devices that had rows in the buffer. A batch the server
+    /// rejects is gone from the buffer, one that failed is kept.
+    fn flushed(&mut self, accepted: bool, emptied: bool) {
+        for device in self.iter_mut().filter(|device| device.buffered > 0) {
+            if accepted {
+                device.stats.rows_flushed += device.buffered;
+            } else {
+                device.stats.flush_failures += 1;
+                if emptied {
+                    device.stats.rows_rejected += device.buffered;
+                }
+            }
+            if accepted || emptied {
+                device.buffered = 0;
+            }
+        }
+    }
+}
+
+/// Generates rows for a group of devices of the --model on one task, like
+/// `generate_data` does for planes.
+pub async fn generate(ctx: Arc<RunContext>, name: String, ids: Vec<String>) -> Vec<PlaneStats> {
+    let Some(model) = ctx.model.as_ref() else { return vec![] };
+    let opt = &ctx.opt;
+    let target_rate = 1000.0 / opt.tick_millis() as f64;
+    let start = ctx.now();
+    let mut devices: Vec<Running> = ids.into_iter().map(|id| Running {
+        device: model.device(&id, seed::plane_seed(ctx.seed, &id), start),
+        stats: PlaneStats::new(id.clone(), target_rate, Duration::from_secs(opt.warmup_secs)),
+        id,
+        buffered: 0,
+        stopped: false,
+    }).collect();
+    let mut interval = interval(Duration::from_millis(opt.tick_millis()));
+    interval.set_missed_tick_behavior(opt.on_missed_tick.into());
+    let mut engine = Engine::Async;
+    let mut buffer = TaskBuffer::new(&ctx);
+    let mut allowance = ctx.quota.allowance(devices.len() as f64 * model.rows_per_tick(opt.tick_millis()));
+
+    'ticks: loop {
+        interval.tick().await;
+        for i in 0..devices.len() {
+            if devices[i].stopped {
+                continue;
+            }
+            if !devices[i].device.has_row(ctx.now()) || ctx.is_paused() {
+                ctx.rate.record_skipped();
+                continue;
+            }
+            if !allowance.take() {
+                break 'ticks;
+            }
+            let last_row = allowance.is_exhausted();
+            if last_row {
+                ctx.phases.drain();
+            }
+
+            buffer.write(&ctx, &mut engine, &name, devices.as_mut_slice(), i, last_row).await;
+
+            if last_row {
+                break 'ticks;
+            }
+        }
+
+        if devices.iter().all(|device| device.stopped) {
+            // The rows it will not write go to the other tasks right away
+            allowance.release();
+            break;
+        }
+    }
+    if ctx.quota.remaining() == 0 {
+        ctx.phases.drain();
+    }
+    drop(allowance);
+
+    buffer.finish(&ctx, &mut engine, &name, devices.as_mut_slice()).await;
+
+    devices.into_iter().map(|mut device| {
+        device.stats.finish();
+        if !opt.quiet {
+            println!("Device {} generated {} rows.", device.id, device.stats.rows_generated);
+        }
+        device.stats
+    }).collect()
+}
@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use rand::Rng;
+
+use crate::error_summary::thousands;
+
+/// Shortest retransmission timeout of TCP, the least a lost packet costs
+const MIN_RTO: Duration = Duration::from_millis(200);
+
+/// A bad network between the client and the server, for --net-latency-ms, --net-jitter-ms,
+/// --net-bytes-per-sec and --net-loss-pct, simulated by holding each flush back for as
+/// long as the network would: the round trip, the time the batch takes to go through at
+/// the bandwidth, and a retransmission timeout for every lost packet, doubling as TCP's
+/// does. The connection stays busy meanwhile, so the client buffers as it would.
+#[derive(Debug)]
+pub struct Network {
+    latency: Duration,
+    jitter: Duration,
+    bytes_per_sec: Option<u64>,
+    loss_pct: f64,
+    delayed: AtomicU64,
+    delay_nanos: AtomicU64,
+    losses: AtomicU64,
+}
+
+impl Network {
+    pub fn new(latency_ms: u64, jitter_ms: u64, bytes_per_sec: Option<u64>, loss_pct: f64) -> Self {
+        Network {
+            latency: Duration::from_millis(latency_ms),
+            jitter: Duration::from_millis(jitter_ms),
+            bytes_per_sec,
+            loss_pct,
+            delayed: AtomicU64::new(0),
+            delay_nanos: AtomicU64::new(0),
+            losses: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether any condition is set, so flushes are held back at all
+    pub fn is_shaped(&self) -> bool {
+        !self.latency.is_zero() || !self.jitter.is_zero() || self.bytes_per_sec.is_some() || self.loss_pct > 0.0
+    }
+
+    /// How long sending a batch of `bytes` takes on top of the real flush
+    pub fn delay(&self, bytes: usize) -> Duration {
+        if !self.is_shaped() {
+            return Duration::ZERO;
+        }
+        let mut rng = rand::thread_rng();
+        let jitter = rng.gen_range(-1.0..=1.0) * self.jitter.as_secs_f64();
+        let round_trip = (self.latency.as_secs_f64() + jitter).max(0.0);
+        let transfer = self.bytes_per_sec.map(|rate| bytes as f64 / rate.max(1) as f64).unwrap_or(0.0);
+        let mut delay = Duration::from_secs_f64(round_trip + transfer);
+        let mut rto = MIN_RTO.max(Duration::from_secs_f64(round_trip * 3.0));
+        while self.loss_pct > 0.0 && rng.gen_bool((self.loss_pct / 100.0).min(0.99)) {
+            self.losses.fetch_add(1, Ordering::Relaxed);
+            delay += rto;
+            rto *= 2;
+        }
+        self.delayed.fetch_add(1, Ordering::Relaxed);
+        self.delay_nanos.fetch_add(delay.as_nanos() as u64, Ordering::Relaxed);
+        delay
+    }
+
+    pub fn print_summary(&self) {
+        if !self.is_shaped() {
+            return;
+        }
+        let delayed = self.delayed.load(Ordering::Relaxed);
+        let delay = Duration::from_nanos(self.delay_nanos.load(Ordering::Relaxed));
+        println!("Network: {} flushes held back {:.1} ms on average, {} lost packets",
+                 thousands(delayed), delay.as_secs_f64() * 1000.0 / delayed.max(1) as f64,
+                 thousands(self.losses.load(Ordering::Relaxed)));
+    }
+}
@@ -0,0 +1,66 @@
+use std::str::FromStr;
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal, Pareto, StandardNormal};
+
+/// Shape of the sensor noise of a column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseDistribution {
+    /// Evenly spread between -scale and scale
+    Uniform,
+    /// Normal, with scale as the standard deviation
+    Gaussian,
+    /// Skewed towards positive errors, centered on 0
+    Lognormal,
+    /// Positive bursts: mostly close to 0, now and then many times the scale
+    Pareto,
+}
+
+impl FromStr for NoiseDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(NoiseDistribution::Uniform),
+            "gaussian" => Ok(NoiseDistribution::Gaussian),
+            "lognormal" => Ok(NoiseDistribution::Lognormal),
+            "pareto" => Ok(NoiseDistribution::Pareto),
+            _ => Err(format!("invalid noise '{}', expected uniform, gaussian, lognormal or pareto", s)),
+        }
+    }
+}
+
+/// Error added to the readings of a column, in the unit it is sent in: noise drawn
+/// afresh for every reading, and a drift that builds up over each flight leg, as if the
+/// sensor was calibrated again on the ground.
+#[derive(Debug, Clone)]
+pub struct Noise {
+    distribution: NoiseDistribution,
+    scale: f64,
+    drift_per_hour: f64,
+}
+
+/// Tail index of the pareto noise, low enough for the bursts to dominate
+const PARETO_SHAPE: f64 = 1.5;
+
+impl Noise {
+    pub fn new(distribution: NoiseDistribution, scale: f64, drift_per_hour: f64) -> Self {
+        Noise { distribution, scale, drift_per_hour }
+    }
+
+    fn sample(&self) -> f64 {
+        let mut rng = rand::thread_rng();
+        let unit = match self.distribution {
+            NoiseDistribution::Uniform => rng.gen_range(-1.0..=1.0),
+            NoiseDistribution::Gaussian => rng.sample::<f64, _>(StandardNormal),
+            // exp(sigma² / 2) is the mean of the lognormal
+            NoiseDistribution::Lognormal => LogNormal::new(0.0, 1.0).unwrap().sample(&mut rng) - 0.5f64.exp(),
+            NoiseDistribution::Pareto => Pareto::new(1.0, PARETO_SHAPE).unwrap().sample(&mut rng) - 1.0,
+        };
+        self.scale * unit
+    }
+
+    /// A reading of `value`, `leg_hours` into the flight leg
+    pub fn apply(&self, value: f64, leg_hours: f64) -> f64 {
+        value + self.sample() + self.drift_per_hour * leg_hours
+    }
+}
@@ -0,0 +1,50 @@
+use rand::Rng;
+
+/// Weight of the latest reading in the running mean and variance, about the last 100
+/// readings of a column
+const ALPHA: f64 = 0.01;
+/// Readings to see before the variance is good enough to place outliers
+const WARMUP: u64 = 30;
+
+/// Running mean and variance of each value column of one plane, to place --outliers-pct
+/// outliers the wanted number of standard deviations away from the recent readings.
+/// Exponentially weighted, so a plane climbing or descending does not inflate them.
+#[derive(Debug, Clone)]
+pub struct Outliers {
+    /// Mean and variance, in the order of `PlaneData::values`
+    stats: Vec<(f64, f64)>,
+    seen: u64,
+}
+
+impl Outliers {
+    pub fn new() -> Self {
+        Outliers { stats: vec![], seen: 0 }
+    }
+
+    pub fn observe(&mut self, values: &[(&str, f64)]) {
+        if self.stats.is_empty() {
+            self.stats = values.iter().map(|(_, value)| (*value, 0.0)).collect();
+        }
+        for ((mean, variance), (_, value)) in self.stats.iter_mut().zip(values) {
+            let delta = value - *mean;
+            *mean += ALPHA * delta;
+            *variance = (1.0 - ALPHA) * (*variance + ALPHA * delta * delta);
+        }
+        self.seen += 1;
+    }
+
+    /// `pct` percent of the time, the value of column `index` moved `sigmas` standard
+    /// deviations up or down.
+    pub fn apply(&self, index: usize, value: f64, pct: f64, sigmas: f64) -> Option<f64> {
+        if self.seen < WARMUP || pct <= 0.0 {
+            return None;
+        }
+        let mut rng = rand::thread_rng();
+        if !rng.gen_bool((pct / 100.0).min(1.0)) {
+            return None;
+        }
+        let deviation = self.stats.get(index).map(|(_, variance)| variance.sqrt()).unwrap_or(0.0);
+        let sign = if rng.gen() { 1.0 } else { -1.0 };
+        Some(value + sign * sigmas * deviation)
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error_summary::thousands;
+
+/// Parts of a run that are reported separately, so startup and shutdown effects do not
+/// show in the steady-state numbers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// --warmup-secs, a --backfill-hours backfill and its catch-up with the wall clock
+    Fill,
+    /// What the run is measured over
+    Steady,
+    /// From the first generator done with its rows to the last flush
+    Drain,
+}
+
+impl Phase {
+    const ALL: [Phase; 3] = [Phase::Fill, Phase::Steady, Phase::Drain];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Fill => "fill",
+            Phase::Steady => "steady",
+            Phase::Drain => "drain",
+        }
+    }
+}
+
+const NOT_YET: u64 = u64::MAX;
+
+#[derive(Debug, Default)]
+struct Counters {
+    rows: AtomicU64,
+    rows_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    flushes: AtomicU64,
+    flush_nanos: AtomicU64,
+}
+
+/// What one phase did, at the end of the run.
+#[derive(Debug, Clone)]
+pub struct PhaseSummary {
+    pub phase: Phase,
+    pub secs: f64,
+    pub rows: u64,
+    pub rows_sent: u64,
+    pub bytes_sent: u64,
+    pub flushes: u64,
+    pub flush_secs: f64,
+}
+
+impl PhaseSummary {
+    pub fn rate(&self) -> f64 {
+        self.rows as f64 / self.secs.max(f64::MIN_POSITIVE)
+    }
+
+    pub fn sent_rate(&self) -> f64 {
+        self.rows_sent as f64 / self.secs.max(f64::MIN_POSITIVE)
+    }
+}
+
+/// Counts rows and flushes by the phase of the run they happen in. The steady state
+/// starts once the fill is over, the drain once a generator has written all its rows.
+/// Times are nanoseconds since the start of the run.
+#[derive(Debug)]
+pub struct RunPhases {
+    started: Instant,
+    steady_from: AtomicU64,
+    drain_from: AtomicU64,
+    ended: AtomicU64,
+    counters: [Counters; 3],
+}
+
+impl RunPhases {
+    /// Phases of a run whose fill lasts `fill`
+    pub fn new(fill: Duration) -> Self {
+        RunPhases {
+            started: Instant::now(),
+            steady_from: AtomicU64::new(fill.as_nanos() as u64),
+            drain_from: AtomicU64::new(NOT_YET),
+            ended: AtomicU64::new(NOT_YET),
+            counters: Default::default(),
+        }
+    }
+
+    fn elapsed(&self) -> u64 {
+        self.started.elapsed().as_nanos() as u64
+    }
+
+    /// Makes the fill last `fill` more from now, once a --backfill-workers backfill is done
+    pub fn fill_for(&self, fill: Duration) {
+        self.steady_from.store(self.elapsed() + fill.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Starts the drain, unless it already started
+    pub fn drain(&self) {
+        let _ = self.drain_from.compare_exchange(NOT_YET, self.elapsed(), Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    pub fn finish(&self) {
+        self.ended.store(self.elapsed(), Ordering::Relaxed);
+    }
+
+    fn at(&self, nanos: u64) -> Phase {
+        if nanos >= self.drain_from.load(Ordering::Relaxed) {
+            Phase::Drain
+        } else if nanos >= self.steady_from.load(Ordering::Relaxed) {
+            Phase::Steady
+        } else {
+            Phase::Fill
+        }
+    }
+
+    fn current(&self) -> &Counters {
+        &self.counters[self.at(self.elapsed()) as usize]
+    }
+
+    pub fn record_row(&self) {
+        self.current().rows.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a batch the server accepted, flushed in `elapsed`
+    pub fn record_sent(&self, rows: usize, bytes: usize, elapsed: Duration) {
+        let counters = self.current();
+        counters.rows_sent.fetch_add(rows as u64, Ordering::Relaxed);
+        counters.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        counters.flushes.fetch_add(1, Ordering::Relaxed);
+        counters.flush_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// The phases with something in them, in order
+    pub fn summaries(&self) -> Vec<PhaseSummary> {
+        let ended = match self.ended.load(Ordering::Relaxed) {
+            NOT_YET => self.elapsed(),
+            ended => ended,
+        };
+        let drain_from = self.drain_from.load(Ordering::Relaxed).min(ended);
+        let steady_from = self.steady_from.load(Ordering::Relaxed).min(drain_from);
+        let bounds = [(0, steady_from), (steady_from, drain_from), (drain_from, ended)];
+        Phase::ALL.iter().zip(bounds).zip(self.counters.iter()).map(|((phase, (from, to)), counters)| PhaseSummary {
+            phase: *phase,
+            secs: (to - from) as f64 / 1e9,
+            rows: counters.rows.load(Ordering::Relaxed),
+            rows_sent: counters.rows_sent.load(Ordering::Relaxed),
+            bytes_sent: counters.bytes_sent.load(Ordering::Relaxed),
+            flushes: counters.flushes.load(Ordering::Relaxed),
+            flush_secs: counters.flush_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+        }).filter(|summary| summary.secs > 0.0 || summary.rows > 0 || summary.flushes > 0).collect()
+    }
+
+    pub fn print_summary(&self) {
+        println!("{:<8} {:>8} {:>12} {:>10} {:>12} {:>10} {:>10} {:>9}",
+                 "phase", "secs", "rows", "rows/s", "rows sent", "sent/s", "flushes", "flush ms");
+        for s in self.summaries() {
+            println!("{:<8} {:>8.1} {:>12} {:>10.1} {:>12} {:>10.1} {:>10} {:>9.1}",
+                     s.phase.as_str(), s.secs, thousands(s.rows), s.rate(), thousands(s.rows_sent), s.sent_rate(),
+                     thousands(s.flushes), 1000.0 * s.flush_secs / s.flushes.max(1) as f64);
+        }
+    }
+}
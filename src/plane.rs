@@ -0,0 +1,662 @@
+use std::str::FromStr;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use questdb::ingress::{Buffer, TimestampNanos};
+
+use crate::added_columns::AddedColumns;
+use crate::column_drop::ColumnDrops;
+use crate::config::Columns;
+use crate::legs::LegSummary;
+use crate::outlier::Outliers;
+use crate::spectrum::Spectrum;
+use crate::turbulence::OrnsteinUhlenbeck;
+use crate::type_flip::FlipType;
+
+const AIRLINES: &[&str] = &[
+    "AAL", "DAL", "UAL", "SWA", "BAW", "DLH", "AFR", "KLM", "RYR", "EZY", "IBE", "UAE", "QTR", "SIA", "ANA", "QFA",
+];
+
+/// ICAO codes of the airports flight legs go between
+const AIRPORTS: &[&str] = &[
+    "KJFK", "KLAX", "KORD", "KATL", "KDFW", "KSFO", "EGLL", "LFPG", "EHAM", "EDDF", "LEMD", "LIRF", "OMDB", "OTHH",
+    "WSSS", "RJTT", "VHHH", "YSSY", "ZBAA", "SBGR", "CYYZ", "FAOR",
+];
+
+/// International Standard Atmosphere temperature in °C for a pressure altitude in feet:
+/// 15 °C at sea level, dropping 1.98 °C per 1000 ft up to the tropopause at 36,089 ft,
+/// and a constant -56.5 °C above it.
+pub fn isa_temperature(altitude: f64) -> f64 {
+    15.0 - 1.98 * altitude.min(36089.0) / 1000.0
+}
+
+/// Share of each flight leg spent climbing, and descending at the end.
+const CLIMB_FRACTION: f64 = 0.2;
+const DESCENT_FRACTION: f64 = 0.2;
+/// Fuel kept on board on top of the planned burn, in kg
+const RESERVE_FUEL: f64 = 2500.0;
+
+/// How a reading is laid out in the table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schema {
+    /// One row per plane and tick, one column per sensor
+    Wide,
+    /// One row per sensor reading: plane_id, sensor, value
+    Narrow,
+}
+
+impl FromStr for Schema {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wide" => Ok(Schema::Wide),
+            "narrow" => Ok(Schema::Narrow),
+            _ => Err(format!("invalid schema '{}', expected wide or narrow", s)),
+        }
+    }
+}
+
+/// Changes to the columns of a row, to exercise how the server handles schema changes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Variations<'a> {
+    /// The altitude sent as another type, with --type-flip-every
+    pub flip: Option<FlipType>,
+    /// Columns added partway through the run, with --add-column-at
+    pub added: Option<&'a AddedColumns>,
+    /// Columns left out for a while, with --drop-column-at
+    pub dropped: Option<&'a ColumnDrops>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlightPhase {
+    Climb,
+    Cruise,
+    Descent,
+}
+
+impl FlightPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlightPhase::Climb => "climb",
+            FlightPhase::Cruise => "cruise",
+            FlightPhase::Descent => "descent",
+        }
+    }
+
+    /// Typical pitch attitude in degrees
+    fn pitch(&self) -> f64 {
+        match self {
+            FlightPhase::Climb => 8.0,
+            FlightPhase::Cruise => 2.0,
+            FlightPhase::Descent => -3.0,
+        }
+    }
+}
+
+/// Validity of a row, as avionics data feeds mark their samples, with --quality-flags.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quality {
+    Good,
+    /// Around a coverage gap, or shaken by a formation event
+    Suspect,
+    /// With an injected outlier
+    Bad,
+}
+
+impl Quality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quality::Good => "good",
+            Quality::Suspect => "suspect",
+            Quality::Bad => "bad",
+        }
+    }
+}
+
+/// Fuel burn in kg per hour for a medium twin-jet. Climbing at full thrust burns the most,
+/// descending at idle the least, and cruise gets more efficient the higher the plane flies.
+pub fn fuel_burn_rate(phase: FlightPhase, altitude: f64) -> f64 {
+    match phase {
+        FlightPhase::Climb => 7000.0,
+        FlightPhase::Cruise => 2500.0 * (1.0 + 0.3 * (35000.0 - altitude) / 35000.0),
+        FlightPhase::Descent => 1000.0,
+    }
+}
+
+/// Running totals of the current flight leg, for its summary.
+#[derive(Debug, Clone, Default)]
+struct LegTotals {
+    max_altitude: f64,
+    airspeed_sum: f64,
+    ticks: u64,
+    distance_nm: f64,
+    starting_fuel_kg: f64,
+    /// Whether the plane flew the whole leg, rather than joining it mid-flight
+    complete: bool,
+}
+
+#[derive(Clone)]
+pub struct PlaneData {
+    pub plane_id: String,
+    /// Stable airframe identity
+    pub icao24: String,
+    /// Flight identity, changes on every new flight leg
+    pub callsign: String,
+    /// Airports of the current leg; the next leg departs from this one's destination
+    pub origin: &'static str,
+    pub destination: &'static str,
+    pub timestamp: i64,
+    /// Leave the timestamps of the rows to the server, with --server-timestamps
+    pub server_timestamps: bool,
+    /// Label of the plane's formation, with --formations
+    pub formation: Option<String>,
+    /// Recent spread of the readings, with --outliers-pct
+    pub outliers: Option<Outliers>,
+    /// Quality of the current tick before outliers, with --quality-flags
+    pub quality: Option<Quality>,
+    /// Engine vibration spectrum, with --spectrum-bins
+    pub spectrum: Option<Spectrum>,
+    /// Summary of the leg that just ended, until it is taken for --legs-table
+    pub finished_leg: Option<LegSummary>,
+    leg: LegTotals,
+    pub airspeed: f64,
+    pub altitude: f64,
+    pub pitch: f64,
+    pub roll: f64,
+    pub yaw: f64,
+    pub aoa: f64,
+    pub oat: f64,
+    pub phase: FlightPhase,
+    pub fuel_kg: f64,
+    pub gross_weight_kg: f64,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Ground speed in knots, from the position change since the previous tick
+    pub ground_speed: f64,
+    /// Direction of travel over the ground in degrees, from the position change since the previous tick
+    pub track: f64,
+    /// Feet per minute, from the altitude change since the previous tick
+    pub vertical_speed: f64,
+    /// Direction the nose points to, in degrees
+    heading: f64,
+    wind_speed: f64,
+    wind_direction: f64,
+    /// Small altitude changes around the cruise altitude, in feet per minute
+    cruise_vertical_speed: OrnsteinUhlenbeck,
+    empty_weight_kg: f64,
+    payload_kg: f64,
+    cruise_altitude: f64,
+    /// Deviation from ISA temperature of the air mass the plane is flying through
+    isa_deviation: f64,
+    pitch_process: OrnsteinUhlenbeck,
+    roll_process: OrnsteinUhlenbeck,
+    yaw_process: OrnsteinUhlenbeck,
+    aoa_process: OrnsteinUhlenbeck,
+    leg_secs: u64,
+    leg_started_at: i64,
+    leg_ends_at: i64,
+    /// Draws everything random about the flight, from the seed of the plane
+    rng: StdRng,
+}
+
+impl PlaneData {
+    pub fn new(plane_id: String, icao24: String, leg_secs: u64, turbulence: f64, timestamp: i64, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let turbulence = turbulence.max(0.0);
+        let pitch_process = OrnsteinUhlenbeck::new(2.0, 0.5, 0.8 * turbulence, &mut rng);
+        let roll_process = OrnsteinUhlenbeck::new(0.0, 0.3, 1.5 * turbulence, &mut rng);
+        let yaw_process = OrnsteinUhlenbeck::new(0.0, 0.5, 0.4 * turbulence, &mut rng);
+        let aoa_process = OrnsteinUhlenbeck::new(3.0, 0.8, 0.5 * turbulence, &mut rng);
+        let mut plane = PlaneData {
+            plane_id,
+            icao24,
+            callsign: String::new(),
+            origin: AIRPORTS.choose(&mut rng).unwrap(),
+            destination: "",
+            timestamp,
+            server_timestamps: false,
+            formation: None,
+            outliers: None,
+            quality: None,
+            spectrum: None,
+            finished_leg: None,
+            leg: LegTotals::default(),
+            airspeed: rng.gen_range(200.0..300.0),
+            altitude: 0.0,
+            pitch: pitch_process.value.clamp(-10.0, 10.0),
+            roll: roll_process.value.clamp(-10.0, 10.0),
+            yaw: yaw_process.value.clamp(-10.0, 10.0),
+            aoa: aoa_process.value.clamp(0.0, 15.0),
+            oat: 0.0,
+            phase: FlightPhase::Climb,
+            fuel_kg: 0.0,
+            gross_weight_kg: 0.0,
+            latitude: rng.gen_range(-60.0..70.0),
+            longitude: rng.gen_range(-180.0..180.0),
+            ground_speed: 0.0,
+            track: 0.0,
+            vertical_speed: 0.0,
+            heading: rng.gen_range(0.0..360.0),
+            wind_speed: rng.gen_range(0.0..80.0),
+            wind_direction: rng.gen_range(0.0..360.0),
+            cruise_vertical_speed: OrnsteinUhlenbeck::new(0.0, 0.2, 40.0 * turbulence, &mut rng),
+            empty_weight_kg: rng.gen_range(40000.0..45000.0),
+            payload_kg: 0.0,
+            cruise_altitude: 0.0,
+            isa_deviation: rng.gen_range(-10.0..10.0),
+            pitch_process,
+            roll_process,
+            yaw_process,
+            aoa_process,
+            leg_secs,
+            leg_started_at: 0,
+            leg_ends_at: 0,
+            rng,
+        };
+        plane.start_leg();
+        // Planes join mid-flight rather than all taking off at the same time
+        let elapsed = plane.rng.gen_range(0.0..1.0) * (plane.leg_ends_at - plane.leg_started_at) as f64;
+        plane.leg_started_at -= elapsed as i64;
+        plane.leg_ends_at -= elapsed as i64;
+        plane.fuel_kg -= plane.planned_burn(elapsed / 1e9);
+        let progress = plane.leg_progress();
+        plane.altitude = if progress < CLIMB_FRACTION {
+            plane.cruise_altitude * progress / CLIMB_FRACTION
+        } else if progress < 1.0 - DESCENT_FRACTION {
+            plane.cruise_altitude
+        } else {
+            plane.cruise_altitude * (1.0 - progress) / DESCENT_FRACTION
+        };
+        plane.fly(0.0);
+        plane.track = plane.heading;
+        plane.ground_speed = plane.airspeed;
+        plane.leg = LegTotals { starting_fuel_kg: plane.fuel_kg, complete: false, ..LegTotals::default() };
+        plane
+    }
+
+    /// Starts a new flight leg with a fresh callsign, payload and fuel load. Legs last
+    /// between half and one and a half times the configured leg duration.
+    fn start_leg(&mut self) {
+        if self.leg.ticks > 0 {
+            self.finished_leg = Some(self.leg_summary());
+        }
+        let rng = &mut self.rng;
+        self.callsign = format!("{}{}", AIRLINES.choose(rng).unwrap(), rng.gen_range(1..10000));
+        if !self.destination.is_empty() {
+            self.origin = self.destination;
+        }
+        self.destination = loop {
+            let airport = AIRPORTS.choose(rng).unwrap();
+            if *airport != self.origin {
+                break airport;
+            }
+        };
+        let leg_nanos = (self.leg_secs as f64 * rng.gen_range(0.5..1.5) * 1e9) as i64;
+        self.leg_started_at = self.timestamp;
+        self.leg_ends_at = self.timestamp + leg_nanos.max(1);
+        self.cruise_altitude = rng.gen_range(30000.0..40000.0);
+        self.altitude = 0.0;
+        self.payload_kg = rng.gen_range(10000.0..18000.0);
+        self.fuel_kg = self.planned_burn(leg_nanos as f64 / 1e9) + RESERVE_FUEL;
+        self.leg = LegTotals { starting_fuel_kg: self.fuel_kg, complete: true, ..LegTotals::default() };
+    }
+
+    fn leg_summary(&self) -> LegSummary {
+        LegSummary {
+            plane_id: self.plane_id.clone(),
+            callsign: self.callsign.clone(),
+            origin: self.origin,
+            destination: self.destination,
+            departed_at: self.leg_started_at,
+            arrived_at: self.leg_ends_at,
+            max_altitude: self.leg.max_altitude,
+            avg_airspeed: self.leg.airspeed_sum / self.leg.ticks.max(1) as f64,
+            distance_nm: self.leg.distance_nm,
+            fuel_burned_kg: (self.leg.starting_fuel_kg - self.fuel_kg).max(0.0),
+            complete: self.leg.complete,
+        }
+    }
+
+    /// Fuel needed for the first `secs` seconds of the current leg, assuming the
+    /// climb, cruise and descent profile is flown as planned.
+    fn planned_burn(&self, secs: f64) -> f64 {
+        let leg_secs = (self.leg_ends_at - self.leg_started_at) as f64 / 1e9;
+        let climb = secs.min(leg_secs * CLIMB_FRACTION);
+        let descent = (secs - leg_secs * (1.0 - DESCENT_FRACTION)).max(0.0);
+        let cruise = secs - climb - descent;
+        (fuel_burn_rate(FlightPhase::Climb, self.cruise_altitude) * climb
+            + fuel_burn_rate(FlightPhase::Cruise, self.cruise_altitude) * cruise
+            + fuel_burn_rate(FlightPhase::Descent, self.cruise_altitude) * descent) / 3600.0
+    }
+
+    /// When the current leg ends, in nanoseconds
+    pub fn arrival(&self) -> i64 {
+        self.leg_ends_at
+    }
+
+    fn leg_progress(&self) -> f64 {
+        let leg_nanos = (self.leg_ends_at - self.leg_started_at).max(1) as f64;
+        ((self.timestamp - self.leg_started_at) as f64 / leg_nanos).clamp(0.0, 1.0)
+    }
+
+    /// Moves the plane along the climb, cruise and descent profile of the current leg
+    /// and burns the fuel used over the last `dt` seconds.
+    fn fly(&mut self, dt: f64) {
+        let progress = self.leg_progress();
+        self.phase = if progress < CLIMB_FRACTION {
+            FlightPhase::Climb
+        } else if progress < 1.0 - DESCENT_FRACTION {
+            FlightPhase::Cruise
+        } else {
+            FlightPhase::Descent
+        };
+        let previous_altitude = self.altitude;
+        let leg_secs = (self.leg_ends_at - self.leg_started_at) as f64 / 1e9;
+        self.altitude = match self.phase {
+            // Climbs and descents head for the end altitude at a constant rate, so they
+            // join up with the cruise wherever the plane happens to be
+            FlightPhase::Climb => {
+                let remaining = (CLIMB_FRACTION - progress) * leg_secs;
+                self.altitude + (self.cruise_altitude - self.altitude) * (dt / remaining.max(dt)).min(1.0)
+            }
+            FlightPhase::Cruise => {
+                // Gently hold the cruise altitude instead of random walking away from it
+                let correction = ((self.cruise_altitude - self.altitude) * 3.0).clamp(-500.0, 500.0);
+                self.cruise_vertical_speed.set_mean(correction);
+                (self.altitude + self.cruise_vertical_speed.step(dt, &mut self.rng) * dt / 60.0)
+                    .clamp(self.cruise_altitude - 500.0, self.cruise_altitude + 500.0)
+            }
+            FlightPhase::Descent => {
+                let remaining = (1.0 - progress) * leg_secs;
+                self.altitude - self.altitude * (dt / remaining.max(dt)).min(1.0)
+            }
+        };
+        self.navigate(dt);
+        if dt > 0.0 {
+            self.vertical_speed = (self.altitude - previous_altitude) / dt * 60.0;
+        }
+        self.pitch_process.set_mean(self.phase.pitch());
+        self.fuel_kg = (self.fuel_kg - fuel_burn_rate(self.phase, self.altitude) * dt / 3600.0).max(0.0);
+        self.gross_weight_kg = self.empty_weight_kg + self.payload_kg + self.fuel_kg;
+        self.oat = isa_temperature(self.altitude) + self.isa_deviation + self.rng.gen_range(-0.5..0.5);
+    }
+
+    /// Turns according to the bank angle and moves the plane through the air mass, drifting
+    /// with the wind. Ground speed and track come out of the resulting position change.
+    fn navigate(&mut self, dt: f64) {
+        if dt <= 0.0 {
+            return;
+        }
+        // Rate one turn formula: degrees per second for a bank angle at a true airspeed in knots
+        let turn_rate = 1091.0 * self.roll.to_radians().tan() / self.airspeed.max(1.0);
+        self.heading = (self.heading + turn_rate * dt).rem_euclid(360.0);
+
+        let heading = self.heading.to_radians();
+        // Wind direction is where it blows from
+        let wind = (self.wind_direction + 180.0).to_radians();
+        let north = self.airspeed * heading.cos() + self.wind_speed * wind.cos();
+        let east = self.airspeed * heading.sin() + self.wind_speed * wind.sin();
+
+        // One degree of latitude is 60 nautical miles
+        let hours = dt / 3600.0;
+        let d_lat = north * hours / 60.0;
+        let d_lon = east * hours / (60.0 * self.latitude.to_radians().cos().max(0.01));
+        let previous_latitude = self.latitude;
+        self.latitude = (self.latitude + d_lat).clamp(-85.0, 85.0);
+        self.longitude = (self.longitude + d_lon + 180.0).rem_euclid(360.0) - 180.0;
+
+        let d_north = (self.latitude - previous_latitude) * 60.0;
+        let d_east = d_lon * 60.0 * previous_latitude.to_radians().cos();
+        self.ground_speed = (d_north * d_north + d_east * d_east).sqrt() / hours;
+        self.track = d_east.atan2(d_north).to_degrees().rem_euclid(360.0);
+    }
+
+    /// Shakes the plane by a --formations event, `shock` being between about -3 and 3 for
+    /// severe ones. Only what is reported moves, the flight path stays the same.
+    pub fn jolt(&mut self, shock: f64) {
+        self.pitch = (self.pitch + 3.0 * shock).clamp(-10.0, 10.0);
+        self.roll = (self.roll + 6.0 * shock).clamp(-10.0, 10.0);
+        self.aoa = (self.aoa + 1.5 * shock).clamp(0.0, 15.0);
+        self.vertical_speed += 1200.0 * shock;
+    }
+
+    /// The value columns with their default names, in the order they are written
+    pub fn values(&self) -> [(&'static str, f64); 14] {
+        [
+            ("airspeed", self.airspeed),
+            ("altitude", self.altitude),
+            ("pitch", self.pitch),
+            ("roll", self.roll),
+            ("yaw", self.yaw),
+            ("aoa", self.aoa),
+            ("oat", self.oat),
+            ("fuel_kg", self.fuel_kg),
+            ("gross_weight_kg", self.gross_weight_kg),
+            ("latitude", self.latitude),
+            ("longitude", self.longitude),
+            ("ground_speed", self.ground_speed),
+            ("track", self.track),
+            ("vertical_speed", self.vertical_speed),
+        ]
+    }
+
+    /// Ends a row at the plane's time, or at the time the server receives it with
+    /// --server-timestamps.
+    pub fn at(&self, buffer: &mut Buffer) -> questdb::Result<()> {
+        match self.server_timestamps {
+            true => buffer.at_now(),
+            false => buffer.at(TimestampNanos::new(self.timestamp)),
+        }
+    }
+
+    /// Advances the plane to `now`, in nanoseconds.
+    pub fn update(&mut self, now: i64) {
+        let dt = ((now - self.timestamp) as f64 / 1e9).clamp(0.0, 10.0);
+        self.timestamp = now;
+        if self.timestamp >= self.leg_ends_at {
+            self.start_leg();
+        }
+        self.airspeed = (self.airspeed + self.rng.gen_range(-1.0..1.0)).clamp(200.0, 300.0);
+        self.pitch = self.pitch_process.step(dt, &mut self.rng).clamp(-10.0, 10.0);
+        self.roll = self.roll_process.step(dt, &mut self.rng).clamp(-10.0, 10.0);
+        self.yaw = self.yaw_process.step(dt, &mut self.rng).clamp(-10.0, 10.0);
+        self.aoa = self.aoa_process.step(dt, &mut self.rng).clamp(0.0, 15.0);
+        self.isa_deviation = (self.isa_deviation + self.rng.gen_range(-0.1..0.1)).clamp(-15.0, 15.0);
+        self.fly(dt);
+        if let Some(spectrum) = &mut self.spectrum {
+            spectrum.update(self.phase, self.airspeed);
+        }
+        self.leg.max_altitude = self.leg.max_altitude.max(self.altitude);
+        self.leg.airspeed_sum += self.airspeed;
+        self.leg.ticks += 1;
+        self.leg.distance_nm += self.ground_speed * dt / 3600.0;
+    }
+
+    pub fn write(&self, buffer: &mut Buffer, table_name: &str, columns: &Columns, schema: Schema) -> questdb::Result<()> {
+        self.write_sensors(buffer, table_name, columns, schema, u64::MAX, Variations::default())
+    }
+
+    /// Writes the readings of the sensor groups in the `due` bitmask, with the `variations`
+    /// of the columns that exercise how the server handles schema changes.
+    pub fn write_sensors(&self, buffer: &mut Buffer, table_name: &str, columns: &Columns, schema: Schema, due: u64, variations: Variations) -> questdb::Result<()> {
+        // Outliers are drawn first, the quality flag goes with the symbols before the values
+        let leg_hours = (self.timestamp - self.leg_started_at).max(0) as f64 / 3.6e12;
+        let dropped = variations.dropped.map(|drops| drops.dropped(self.timestamp)).unwrap_or_default();
+        let readings: Vec<_> = self.values().iter().enumerate()
+            .filter(|(_, (column, _))| columns.is_due(column, due) && !dropped.contains(column))
+            .map(|(index, (column, value))| {
+                let outlier = self.outliers.as_ref().and_then(|outliers| {
+                    let (pct, sigmas) = columns.outliers(column);
+                    outliers.apply(index, *value, pct, sigmas)
+                });
+                (*column, columns.reading(column, outlier.unwrap_or(*value), leg_hours), outlier.is_some())
+            })
+            .collect();
+        if schema == Schema::Wide {
+            buffer.table(table_name)?
+                .symbol(columns.name("plane_id"), &self.plane_id)?
+                .symbol(columns.name("icao24"), &self.icao24)?
+                .symbol(columns.name("callsign"), &self.callsign)?
+                .symbol(columns.name("phase"), self.phase.as_str())?;
+            if let Some((name, label)) = columns.run_label() {
+                buffer.symbol(name, label)?;
+            }
+            if let Some((name, fleet)) = columns.fleet(&self.callsign) {
+                buffer.symbol(name, fleet)?;
+            }
+            if let Some(formation) = &self.formation {
+                buffer.symbol(columns.name("formation"), formation)?;
+            }
+            if let Some(quality) = self.quality {
+                let quality = match readings.iter().any(|(_, _, outlier)| *outlier) {
+                    true => Quality::Bad,
+                    false => quality,
+                };
+                buffer.symbol(columns.name("quality"), quality.as_str())?;
+            }
+        }
+        for (column, value, outlier) in readings {
+            let mut name = columns.name(column);
+            if schema == Schema::Narrow {
+                buffer.table(table_name)?
+                    .symbol(columns.name("plane_id"), &self.plane_id)?
+                    .symbol("sensor", name)?;
+                if let Some((label_column, label)) = columns.run_label() {
+                    buffer.symbol(label_column, label)?;
+                }
+                if let Some((fleet_column, fleet)) = columns.fleet(&self.callsign) {
+                    buffer.symbol(fleet_column, fleet)?;
+                }
+                if let Some(formation) = &self.formation {
+                    buffer.symbol(columns.name("formation"), formation)?;
+                }
+                if let Some(quality) = self.quality {
+                    buffer.symbol(columns.name("quality"), if outlier { Quality::Bad } else { quality }.as_str())?;
+                }
+                name = "value";
+            }
+            match variations.flip {
+                Some(FlipType::Long) if column == "altitude" => buffer.column_i64(name, value.round() as i64)?,
+                Some(FlipType::Text) if column == "altitude" => buffer.column_str(name, format!("{:.0}", value))?,
+                _ => buffer.column_f64(name, value)?,
+            };
+            if schema == Schema::Narrow {
+                self.at(buffer)?;
+            }
+        }
+        if let (Schema::Wide, Some(spectrum)) = (schema, &self.spectrum) {
+            if !dropped.contains(&"vibration_spectrum") {
+                buffer.column_arr(columns.name("vibration_spectrum"), &spectrum.bins.as_slice())?;
+            }
+        }
+        if let (Schema::Wide, Some(added)) = (schema, variations.added) {
+            added.write(buffer)?;
+        }
+        match schema {
+            Schema::Wide => self.at(buffer),
+            Schema::Narrow => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use super::*;
+
+    /// A plane with random settings: its leg length, turbulence, starting clock and seed
+    fn plane() -> impl Strategy<Value = (u64, f64, i64, u64)> {
+        (60..20000u64, 0.0..5.0f64, 0..2_000_000_000_000_000_000i64, any::<u64>())
+    }
+
+    /// Random ticks of up to a few seconds, some of them not moving the clock, and now and
+    /// then a jolt
+    fn ticks() -> impl Strategy<Value = Vec<(i64, Option<f64>)>> {
+        let dt = prop_oneof![1 => Just(0i64), 9 => 1..5_000_000_000i64];
+        prop::collection::vec((dt, prop::option::weighted(0.05, -5.0..5.0f64)), 1..500)
+    }
+
+    fn new_plane((leg_secs, turbulence, timestamp, seed): (u64, f64, i64, u64)) -> PlaneData {
+        PlaneData::new("AA00".to_string(), "a00000".to_string(), leg_secs, turbulence, timestamp, seed)
+    }
+
+    /// Flies the plane through the ticks, calling `check` after each
+    fn fly(plane: &mut PlaneData, ticks: &[(i64, Option<f64>)], mut check: impl FnMut(&PlaneData) -> Result<(), TestCaseError>) -> Result<(), TestCaseError> {
+        for (dt, jolt) in ticks.iter() {
+            plane.update(plane.timestamp + dt);
+            if let Some(jolt) = jolt {
+                plane.jolt(*jolt);
+            }
+            check(plane)?;
+        }
+        Ok(())
+    }
+
+    fn check_clamps(plane: &PlaneData) -> Result<(), TestCaseError> {
+        let within = |name: &str, value: f64, min: f64, max: f64| {
+            prop_assert!(value.is_finite() && (min..=max).contains(&value), "{} is {}, outside {}..={}", name, value, min, max);
+            Ok(())
+        };
+        within("airspeed", plane.airspeed, 200.0, 300.0)?;
+        within("pitch", plane.pitch, -10.0, 10.0)?;
+        within("roll", plane.roll, -10.0, 10.0)?;
+        within("yaw", plane.yaw, -10.0, 10.0)?;
+        within("aoa", plane.aoa, 0.0, 15.0)?;
+        within("latitude", plane.latitude, -85.0, 85.0)?;
+        within("longitude", plane.longitude, -180.0, 180.0)?;
+        within("track", plane.track, 0.0, 360.0)?;
+        within("altitude", plane.altitude, 0.0, 40500.0)?;
+        within("fuel_kg", plane.fuel_kg, 0.0, f64::MAX)?;
+        within("oat", plane.oat, -90.0, 40.0)?;
+        within("gross_weight_kg", plane.gross_weight_kg, plane.empty_weight_kg + plane.payload_kg, f64::MAX)?;
+        for (column, value) in plane.values().iter() {
+            prop_assert!(value.is_finite(), "{} is {}", column, value);
+        }
+        if plane.phase == FlightPhase::Cruise {
+            within("cruise altitude", plane.altitude, plane.cruise_altitude - 500.0, plane.cruise_altitude + 500.0)?;
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(200))]
+
+        #[test]
+        fn readings_stay_within_their_clamps(settings in plane(), ticks in ticks()) {
+            let mut plane = new_plane(settings);
+            check_clamps(&plane)?;
+            fly(&mut plane, &ticks, check_clamps)?;
+        }
+
+        #[test]
+        fn timestamps_follow_the_clock(settings in plane(), ticks in ticks()) {
+            let mut plane = new_plane(settings);
+            let mut previous = plane.timestamp;
+            fly(&mut plane, &ticks, |plane| {
+                prop_assert!(plane.timestamp >= previous, "timestamp went from {} back to {}", previous, plane.timestamp);
+                prop_assert!(plane.leg_started_at <= plane.timestamp && plane.timestamp < plane.leg_ends_at,
+                             "timestamp {} outside of its leg {}..{}", plane.timestamp, plane.leg_started_at, plane.leg_ends_at);
+                previous = plane.timestamp;
+                Ok(())
+            })?;
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(20))]
+
+        #[test]
+        fn the_same_seed_flies_the_same_plane(seed in any::<u64>()) {
+            let flight = |seed: u64| {
+                let mut plane = PlaneData::new("AA00".to_string(), "a00000".to_string(), 3600, 1.0, 1_000_000_000_000_000_000, seed);
+                (0..200).map(|tick| {
+                    plane.update(1_000_000_000_000_000_000 + tick * 100_000_000);
+                    (plane.callsign.clone(), plane.values())
+                }).collect::<Vec<_>>()
+            };
+            prop_assert_eq!(flight(seed), flight(seed));
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy)]
+pub enum PlaneIdScheme {
+    /// Letters followed by zero-padded digits, e.g. `AA00`, `AAL1` or `PLANE00000`.
+    /// When the digits overflow, the carry goes into the letters (`AA99` -> `AB00`).
+    Sequential,
+    /// ICAO 24-bit addresses as 6 hex digits, e.g. `a00000`
+    Icao,
+    /// US style tail numbers, e.g. `N10000`
+    Tail,
+}
+
+impl FromStr for PlaneIdScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sequential" => Ok(PlaneIdScheme::Sequential),
+            "icao" => Ok(PlaneIdScheme::Icao),
+            "tail" => Ok(PlaneIdScheme::Tail),
+            _ => Err(format!("invalid plane id scheme '{}', expected sequential, icao or tail", s)),
+        }
+    }
+}
+
+impl PlaneIdScheme {
+    pub fn default_start(&self) -> &'static str {
+        match self {
+            PlaneIdScheme::Sequential => "AA00",
+            PlaneIdScheme::Icao => "a00000",
+            PlaneIdScheme::Tail => "N10000",
+        }
+    }
+}
+
+/// Generates consecutive plane ids in a given scheme starting from a given id.
+#[derive(Debug, Clone)]
+pub struct PlaneIds {
+    scheme: PlaneIdScheme,
+    start: u64,
+    capacity: u64,
+    letters: usize,
+    width: usize,
+}
+
+impl PlaneIds {
+    pub fn new(scheme: PlaneIdScheme, starting_id: &str) -> Result<Self, String> {
+        match scheme {
+            PlaneIdScheme::Sequential => {
+                let letters = starting_id.bytes().take_while(|b| !b.is_ascii_digit()).count();
+                let (prefix, digits) = starting_id.split_at(letters);
+                if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_uppercase()) {
+                    return Err(format!("'{}' must start with uppercase letters (A-Z)", starting_id));
+                }
+                if digits.is_empty() || digits.len() > 9 || !digits.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(format!("'{}' must end with 1 to 9 digits", starting_id));
+                }
+                let letter_space = 26u64.checked_pow(letters as u32)
+                    .ok_or_else(|| format!("'{}' has too many letters", starting_id))?;
+                let digit_space = 10u64.pow(digits.len() as u32);
+                let letter_value = prefix.bytes().fold(0u64, |acc, b| acc * 26 + (b - b'A') as u64);
+                Ok(PlaneIds {
+                    scheme,
+                    start: letter_value * digit_space + digits.parse::<u64>().unwrap(),
+                    capacity: letter_space.saturating_mul(digit_space),
+                    letters,
+                    width: digits.len(),
+                })
+            }
+            PlaneIdScheme::Icao => {
+                // from_str_radix takes a leading sign, which is not a hex digit
+                if starting_id.len() != 6 || !starting_id.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("'{}' must be a 24-bit ICAO address of 6 hex digits", starting_id));
+                }
+                let start = u64::from_str_radix(starting_id, 16).unwrap();
+                Ok(PlaneIds { scheme, start, capacity: 1 << 24, letters: 0, width: 6 })
+            }
+            PlaneIdScheme::Tail => {
+                let digits = starting_id.strip_prefix('N')
+                    .filter(|d| !d.is_empty() && d.len() <= 5 && !d.starts_with('0') && d.chars().all(|c| c.is_ascii_digit()))
+                    .ok_or_else(|| format!("'{}' must be a tail number like N12345", starting_id))?;
+                Ok(PlaneIds { scheme, start: digits.parse().unwrap(), capacity: 100_000, letters: 0, width: 0 })
+            }
+        }
+    }
+
+    /// How many ids can be generated from the starting id before running out of space.
+    pub fn available(&self) -> u64 {
+        self.capacity - self.start
+    }
+
+    /// Stable 24-bit ICAO address for the nth plane. Unique across runs with
+    /// non-overlapping starting ids in the same scheme.
+    pub fn icao24(&self, n: u64) -> String {
+        match self.scheme {
+            PlaneIdScheme::Icao => self.get(n),
+            _ => format!("{:06x}", (0xa00000 + self.start + n) & 0xffffff),
+        }
+    }
+
+    pub fn get(&self, n: u64) -> String {
+        let index = self.start + n;
+        assert!(index < self.capacity, "plane id index {} out of range", n);
+        match self.scheme {
+            PlaneIdScheme::Sequential => {
+                let digit_space = 10u64.pow(self.width as u32);
+                let mut letter_value = index / digit_space;
+                let mut letters = vec![b'A'; self.letters];
+                for l in letters.iter_mut().rev() {
+                    *l = b'A' + (letter_value % 26) as u8;
+                    letter_value /= 26;
+                }
+                format!("{}{:0width$}", String::from_utf8(letters).unwrap(), index % digit_space, width = self.width)
+            }
+            PlaneIdScheme::Icao => format!("{:06x}", index),
+            PlaneIdScheme::Tail => format!("N{}", index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icao_addresses_are_six_hex_digits() {
+        assert_eq!(PlaneIds::new(PlaneIdScheme::Icao, "a0000f").unwrap().get(1), "a00010");
+        for invalid in ["+a0000", "-a0000", "a0000", "a0000g", "a00000f"] {
+            assert!(PlaneIds::new(PlaneIdScheme::Icao, invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use rand::Rng;
+use questdb::ingress::Buffer;
+
+use crate::config::Columns;
+use crate::plane::PlaneData;
+
+/// Writes a row the server will reject: a value of the wrong type for one of the
+/// flight data columns. Used by --poison-pct to exercise server-side error handling.
+pub fn write(plane: &PlaneData, buffer: &mut Buffer, table_name: &str, columns: &Columns) -> questdb::Result<()> {
+    buffer.table(table_name)?
+        .symbol(columns.name("plane_id"), &plane.plane_id)?;
+    if let Some((name, label)) = columns.run_label() {
+        buffer.symbol(name, label)?;
+    }
+    match rand::thread_rng().gen_range(0..3) {
+        // A symbol where the table has a double
+        0 => buffer.symbol(columns.name("altitude"), "unknown")?,
+        // A string in a double column
+        1 => buffer.column_str(columns.name("airspeed"), "n/a")?,
+        // A boolean in a double column
+        _ => buffer.column_bool(columns.name("fuel_kg"), true)?,
+    };
+    plane.at(buffer)
+}
@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use questdb::ingress::{Buffer, ProtocolVersion};
+
+/// Rough size of a flight data row in ILP, used to pre-size buffers
+const ROW_BYTES_ESTIMATE: usize = 400;
+
+/// Pre-sized buffers shared by the planes. A plane hands its buffer back after a
+/// successful flush and takes one when it writes the next row, so idle planes hold
+/// no memory and buffers do not keep the capacity they grew to during an outage.
+#[derive(Debug)]
+pub struct BufferPool {
+    free: Mutex<Vec<Buffer>>,
+    /// What the sink takes, the buffers are written in it
+    protocol_version: ProtocolVersion,
+    buffer_capacity: usize,
+    max_pooled: usize,
+    created: AtomicU64,
+    reused: AtomicU64,
+}
+
+impl BufferPool {
+    /// Buffers are sized for `batch_size` rows, but never more than `max_buffer_bytes`.
+    pub fn new(protocol_version: ProtocolVersion, batch_size: usize, max_buffer_bytes: Option<u64>, max_pooled: usize) -> Self {
+        let mut buffer_capacity = batch_size.saturating_mul(ROW_BYTES_ESTIMATE);
+        if let Some(max) = max_buffer_bytes {
+            buffer_capacity = buffer_capacity.min(max as usize);
+        }
+        BufferPool {
+            free: Mutex::new(vec![]),
+            protocol_version,
+            buffer_capacity,
+            max_pooled,
+            created: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+        }
+    }
+
+    pub fn take(&self) -> Buffer {
+        if let Some(buffer) = self.free.lock().unwrap().pop() {
+            self.reused.fetch_add(1, Ordering::Relaxed);
+            return buffer;
+        }
+        self.created.fetch_add(1, Ordering::Relaxed);
+        let mut buffer = self.empty();
+        buffer.reserve(self.buffer_capacity);
+        buffer
+    }
+
+    /// A buffer with no memory yet, for the protocol version of the run
+    pub fn empty(&self) -> Buffer {
+        Buffer::new(self.protocol_version)
+    }
+
+    /// Returns a buffer to the pool. Buffers that grew well past the usual size are
+    /// freed instead, as are the ones the pool has no room for.
+    pub fn give(&self, mut buffer: Buffer) {
+        if buffer.capacity() > 2 * self.buffer_capacity.max(ROW_BYTES_ESTIMATE) {
+            return;
+        }
+        let mut free = self.free.lock().unwrap();
+        if free.len() < self.max_pooled {
+            buffer.clear();
+            free.push(buffer);
+        }
+    }
+
+    /// Buffers allocated and buffers handed out again from the pool
+    pub fn counts(&self) -> (u64, u64) {
+        (self.created.load(Ordering::Relaxed), self.reused.load(Ordering::Relaxed))
+    }
+}
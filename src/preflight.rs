@@ -0,0 +1,101 @@
+use std::str::FromStr;
+use questdb::ErrorCode;
+use questdb::ingress::ProtocolVersion;
+
+use crate::Opt;
+use crate::config::Columns;
+use crate::mimic::{MimicRow, MimicTable};
+use crate::plane::PlaneData;
+use crate::questdb_http::QueryClient;
+use crate::sink;
+use crate::spectrum::Spectrum;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preflight {
+    Off,
+    /// Validate the connection string and check the server answers
+    Connect,
+    /// Also write a probe row into the table
+    Probe,
+    /// Write the probe row into a scratch table that is dropped afterwards
+    Scratch,
+}
+
+impl FromStr for Preflight {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Preflight::Off),
+            "connect" => Ok(Preflight::Connect),
+            "probe" => Ok(Preflight::Probe),
+            "scratch" => Ok(Preflight::Scratch),
+            _ => Err(format!("invalid preflight '{}', expected off, connect, probe or scratch", s)),
+        }
+    }
+}
+
+fn describe(e: &questdb::Error, conf: &str) -> String {
+    let addr = questdb_confstr::parse_conf_str(conf).ok()
+        .and_then(|c| c.get("addr").map(str::to_string))
+        .unwrap_or_default();
+    match e.code() {
+        ErrorCode::ConfigError | ErrorCode::InvalidApiCall => format!("invalid connection string: {}", e.msg()),
+        ErrorCode::CouldNotResolveAddr => format!("cannot resolve server address '{}': {}", addr, e.msg()),
+        ErrorCode::SocketError => format!("cannot reach the server at {}, is QuestDB running? {}", addr, e.msg()),
+        ErrorCode::AuthError => format!("authentication failed, check token, username and password: {}", e.msg()),
+        ErrorCode::TlsError => format!("TLS error, check the https/tcps scheme and tls_verify or tls_roots: {}", e.msg()),
+        ErrorCode::HttpNotSupported => format!("server does not support ILP over HTTP, use a tcp:: connection string: {}", e.msg()),
+        _ => e.msg().to_string(),
+    }
+}
+
+/// Checks the connection string is valid and the server is reachable, before any plane starts.
+pub fn check_connection(opt: &Opt) -> Result<(), String> {
+    let sender = sink::connect(&opt.connection_string, opt.protocol_version).map_err(|e| describe(&e, &opt.connection_string))?;
+    drop(sender);
+    // TCP senders connect on creation, HTTP ones only on the first flush
+    if opt.connection_string.starts_with("http") {
+        let client = QueryClient::from_conf(&opt.connection_string)?;
+        client.ping().map_err(|e| format!("cannot reach the server, is QuestDB running? {}", e))?;
+    }
+    Ok(())
+}
+
+/// Sends a single row, built exactly like the real ones, to check credentials and that the
+/// table accepts the schema. Over TCP the server does not acknowledge rows, so only
+/// connection and authentication problems show up.
+pub fn probe(opt: &Opt, columns: &Columns, mimic: Option<&MimicTable>) -> Result<(), String> {
+    let table_name = match opt.preflight {
+        Preflight::Scratch => format!("{}_preflight", opt.table_name),
+        _ => opt.table_name.clone(),
+    };
+    let mut sender = sink::connect(&opt.connection_string, opt.protocol_version).map_err(|e| describe(&e, &opt.connection_string))?;
+    let mut buffer = sender.new_buffer();
+    let mut plane = PlaneData::new("preflight".to_string(), "000000".to_string(), opt.flight_leg_secs, opt.turbulence,
+                                   chrono::Utc::now().timestamp_nanos_opt().unwrap(), rand::random());
+    plane.spectrum = opt.spectrum_bins.filter(|_| buffer.protocol_version() != ProtocolVersion::V1).map(|bins| Spectrum::new(bins, 0));
+    let row = match mimic {
+        Some(table) => MimicRow::new(table, plane.plane_id.clone()).write(table, &mut buffer, &table_name, columns.run_label(), &plane),
+        None => plane.write(&mut buffer, &table_name, columns, opt.schema),
+    };
+    row.map_err(|e| format!("cannot build probe row: {}", e))?;
+
+    sender.flush(&mut buffer)
+        .map_err(|e| format!("table '{}' rejected the probe row: {}", table_name, describe(&e, &opt.connection_string)))?;
+
+    if opt.preflight == Preflight::Scratch {
+        let client = QueryClient::from_conf(&opt.connection_string)?;
+        // Rows over ILP are committed asynchronously, the table may take a moment to appear
+        let mut dropped = Err(String::new());
+        for _ in 0..10 {
+            dropped = client.exec(&format!("DROP TABLE IF EXISTS '{}'", table_name)).map(|_| ());
+            if dropped.is_ok() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
+        dropped.map_err(|e| format!("probe row accepted, but cannot drop scratch table '{}': {}", table_name, e))?;
+    }
+    Ok(())
+}
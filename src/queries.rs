@@ -0,0 +1,148 @@
+use std::io;
+use std::path::Path;
+
+use crate::Opt;
+use crate::config::Columns;
+use crate::plane::Schema;
+
+fn identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Example queries for the run's table, columns and planes, each with a comment saying
+/// what it shows. `planes` are the ids of the first planes.
+pub fn examples(opt: &Opt, columns: &Columns, planes: &[String]) -> Vec<(String, String)> {
+    let table = identifier(&opt.table_name);
+    let column = |name: &str| identifier(columns.name(name));
+    let plane = planes.first().map(|id| literal(id)).unwrap_or_else(|| "'AA00'".to_string());
+    let mut queries: Vec<(String, String)> = vec![];
+    let mut add = |comment: &str, sql: String| queries.push((comment.to_string(), sql));
+
+    match opt.schema {
+        Schema::Wide => {
+            add("Latest position of every plane",
+                format!("SELECT {id}, {cs}, {lat}, {lon}, {alt}, timestamp\nFROM {t}\nLATEST ON timestamp PARTITION BY {id};",
+                        id = column("plane_id"), cs = column("callsign"), lat = column("latitude"), lon = column("longitude"),
+                        alt = column("altitude"), t = table));
+            add("Average airspeed and altitude of the whole fleet, per minute",
+                format!("SELECT timestamp, avg({spd}) airspeed, avg({alt}) altitude, count() readings\nFROM {t}\nSAMPLE BY 1m;",
+                        spd = column("airspeed"), alt = column("altitude"), t = table));
+            add("One plane every 10 seconds over the last hour, gaps filled with the previous value",
+                format!("SELECT timestamp, avg({alt}) altitude, avg({spd}) airspeed, last({fuel}) fuel\nFROM {t}\nWHERE {id} = {p} AND timestamp > dateadd('h', -1, now())\nSAMPLE BY 10s FILL(PREV);",
+                        alt = column("altitude"), spd = column("airspeed"), fuel = column("fuel_kg"), id = column("plane_id"),
+                        p = plane, t = table));
+            add("Rows per flight phase",
+                format!("SELECT {ph}, count() readings, avg({alt}) altitude\nFROM {t}\nORDER BY readings DESC;",
+                        ph = column("phase"), alt = column("altitude"), t = table));
+            add("Highest and fastest flights, one per callsign",
+                format!("SELECT {cs}, max({alt}) max_altitude, max({spd}) max_airspeed, min(timestamp) departed, max(timestamp) last_seen\nFROM {t}\nORDER BY max_altitude DESC\nLIMIT 20;",
+                        cs = column("callsign"), alt = column("altitude"), spd = column("airspeed"), t = table));
+            add("Ingestion rate per fleet, the airline code at the start of the callsign",
+                format!("SELECT timestamp, left({cs}, 3) fleet, count() readings\nFROM {t}\nSAMPLE BY 1m;",
+                        cs = column("callsign"), t = table));
+            if let Some(other) = planes.get(1).map(|id| literal(id)) {
+                add("Distance in degrees between two planes, each reading of the first matched with the latest one of the second",
+                    format!("SELECT a.timestamp, a.{id}, b.{id} other,\n       sqrt(power(a.{lat} - b.{lat}, 2) + power(a.{lon} - b.{lon}, 2)) distance_deg\nFROM (SELECT * FROM {t} WHERE {id} = {p}) a\nASOF JOIN (SELECT * FROM {t} WHERE {id} = {o}) b;",
+                            id = column("plane_id"), lat = column("latitude"), lon = column("longitude"), p = plane, o = other, t = table));
+            }
+        }
+        Schema::Narrow => {
+            add("Latest reading of every sensor of every plane",
+                format!("SELECT {id}, sensor, value, timestamp\nFROM {t}\nLATEST ON timestamp PARTITION BY {id}, sensor;",
+                        id = column("plane_id"), t = table));
+            add("Average of each sensor of the whole fleet, per minute",
+                format!("SELECT timestamp, sensor, avg(value) value\nFROM {t}\nSAMPLE BY 1m;", t = table));
+            add("Altitude of one plane every 10 seconds over the last hour, gaps filled with the previous value",
+                format!("SELECT timestamp, avg(value) altitude\nFROM {t}\nWHERE {id} = {p} AND sensor = {s} AND timestamp > dateadd('h', -1, now())\nSAMPLE BY 10s FILL(PREV);",
+                        id = column("plane_id"), p = plane, s = literal(columns.name("altitude")), t = table));
+            add("Readings per sensor",
+                format!("SELECT sensor, count() readings, min(value), max(value)\nFROM {t}\nORDER BY readings DESC;", t = table));
+            add("Airspeed next to the altitude read at the same time or just before",
+                format!("SELECT a.timestamp, a.{id}, a.value airspeed, b.value altitude\nFROM (SELECT * FROM {t} WHERE sensor = {spd}) a\nASOF JOIN (SELECT * FROM {t} WHERE sensor = {alt}) b ON ({id})\nWHERE a.{id} = {p};",
+                        id = column("plane_id"), spd = literal(columns.name("airspeed")), alt = literal(columns.name("altitude")),
+                        p = plane, t = table));
+        }
+    }
+    if columns.has_fleets() {
+        let tables: Vec<_> = std::iter::once(opt.table_name.as_str()).chain(columns.fleet_tables()).map(identifier).collect();
+        let source = match tables.len() {
+            1 => table.clone(),
+            _ => format!("(\n  {}\n)", tables.iter()
+                .map(|t| format!("SELECT {f}, timestamp FROM {t}", f = column("fleet"), t = t))
+                .collect::<Vec<_>>().join("\n  UNION ALL\n  ")),
+        };
+        add("Rows per fleet, from the fleet column, across the fleet tables",
+            format!("SELECT {f}, count() readings\nFROM {s}\nORDER BY readings DESC;", f = column("fleet"), s = source));
+    }
+    if let Some(companion) = &opt.companion_table {
+        let (altitude, filter) = match opt.schema {
+            Schema::Wide => (format!("f.{}", column("altitude")), String::new()),
+            Schema::Narrow => ("f.value".to_string(), format!(" AND f.sensor = {}", literal(columns.name("altitude")))),
+        };
+        add("Each altitude reading next to the ATC clearance in force for the plane, from the companion table",
+            format!("SELECT f.timestamp, f.{id}, {alt} altitude, c.instruction, c.assigned_altitude, c.sector\nFROM {t} f\nASOF JOIN {c} c ON ({id})\nWHERE f.{id} = {p}{filter};",
+                    id = column("plane_id"), alt = altitude, p = plane, filter = filter, t = table, c = identifier(companion)));
+    }
+    if let Some(status) = &opt.status_table {
+        add("Current phase and leg of every plane, from the status table",
+            format!("SELECT {id}, {cs}, {ph}, origin, destination, eta, timestamp\nFROM {s}\nLATEST ON timestamp PARTITION BY {id};",
+                    id = column("plane_id"), cs = column("callsign"), ph = column("phase"), s = identifier(status)));
+        add("Planes per phase right now",
+            format!("SELECT {ph}, count() planes\nFROM (SELECT * FROM {s} LATEST ON timestamp PARTITION BY {id});",
+                    id = column("plane_id"), ph = column("phase"), s = identifier(status)));
+    }
+    if let Some(view) = &opt.mat_view {
+        add("Airspeed bars of one plane over the last hour, from the materialized view",
+            format!("SELECT timestamp, open, high, low, close, readings\nFROM {v}\nWHERE {id} = {p} AND timestamp > dateadd('h', -1, now());",
+                    v = identifier(view), id = column("plane_id"), p = plane));
+    }
+    if let Some(label) = &opt.run_label {
+        add("Rows of this run only, with its --run-label",
+            format!("SELECT count(), min(timestamp), max(timestamp)\nFROM {t}\nWHERE {rl} = {l};",
+                    rl = column("run_label"), l = literal(label), t = table));
+    }
+    if !opt.no_run_metadata {
+        add("Parameters of the run each row was written by, from the run metadata table",
+            format!("SELECT d.timestamp, d.{id}, r.run_id, r.rate_per_plane, r.plane_count, r.version\nFROM (SELECT * FROM {t} WHERE {id} = {p}) d\nASOF JOIN (SELECT * FROM {runs} WHERE event = 'start' AND table_name = {tl}) r\nLIMIT 100;",
+                    id = column("plane_id"), p = plane, t = table, runs = identifier(&opt.runs_table), tl = literal(&opt.table_name)));
+    }
+    queries
+}
+
+/// Writes the example queries to `path`, so there is something to run against the data
+/// right away.
+pub fn write(path: &Path, opt: &Opt, columns: &Columns, planes: &[String]) -> io::Result<()> {
+    let queries = examples(opt, columns, planes);
+    let mut text = format!("-- Example queries for table {}, written by airplanes --emit-queries\n", opt.table_name);
+    for (comment, sql) in queries.iter() {
+        text.push_str(&format!("\n-- {}\n{}\n", comment, sql));
+    }
+    std::fs::write(path, text)
+}
+
+/// Reads queries separated by `;` from a SQL file such as the one --emit-queries writes.
+/// The `--` comment before a query names it, otherwise its first line does.
+pub fn read(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let mut queries = vec![];
+    for statement in text.split(';') {
+        let (mut comment, mut sql) = (None, vec![]);
+        for line in statement.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match line.strip_prefix("--") {
+                Some(text) => comment = Some(text.trim().to_string()),
+                None => sql.push(line),
+            }
+        }
+        if let Some(first) = sql.first() {
+            queries.push((comment.unwrap_or_else(|| first.to_string()), sql.join("\n")));
+        }
+    }
+    if queries.is_empty() {
+        return Err(format!("no queries in {}", path.display()));
+    }
+    Ok(queries)
+}
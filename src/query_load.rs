@@ -0,0 +1,165 @@
+#[cfg(feature = "query-load")]
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+#[cfg(feature = "query-load")]
+use std::{thread, time::Instant};
+
+use crate::RunContext;
+
+/// Latencies and errors of one query, over all workers.
+#[derive(Debug, Default, Clone)]
+struct QueryStats {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+/// Latency percentiles and errors of one query at the end of the run, in ms; none when
+/// the query never succeeded.
+#[derive(Debug, Clone)]
+pub struct QuerySummary {
+    pub name: String,
+    pub runs: usize,
+    pub errors: u64,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+impl QueryStats {
+    fn summary(mut self, name: &str) -> QuerySummary {
+        self.latencies.sort();
+        let sorted = &self.latencies;
+        let at = |pct| (!sorted.is_empty()).then(|| percentile(sorted, pct));
+        QuerySummary {
+            name: name.to_string(),
+            runs: sorted.len(),
+            errors: self.errors,
+            p50_ms: at(50.0),
+            p95_ms: at(95.0),
+            p99_ms: at(99.0),
+            max_ms: at(100.0),
+        }
+    }
+}
+
+/// PG wire connection for the workers: --pg-connection, or QuestDB's defaults on the host
+/// of the ILP connection string.
+#[cfg(feature = "query-load")]
+pub fn pg_config(ctx: &RunContext) -> Result<postgres::Config, String> {
+    if let Some(connection) = &ctx.opt.pg_connection {
+        return postgres::Config::from_str(connection).map_err(|e| format!("invalid --pg-connection: {}", e));
+    }
+    let conf = questdb_confstr::parse_conf_str(&ctx.opt.connection_string).map_err(|e| format!("invalid connection string: {}", e))?;
+    let addr = conf.get("addr").ok_or("connection string has no addr")?;
+    let mut config = postgres::Config::new();
+    config.host(addr.split(':').next().unwrap_or(addr)).port(8812).user("admin").password("quest").dbname("qdb")
+        .connect_timeout(Duration::from_secs(10));
+    Ok(config)
+}
+
+/// Runs SELECT queries over PG wire against the table while it is written, on
+/// --query-workers threads that each go round the queries as fast as they answer, to
+/// measure query latencies under ingestion load.
+pub struct QueryLoad {
+    names: Vec<String>,
+    stop: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<Vec<QueryStats>>>,
+}
+
+#[cfg(feature = "query-load")]
+fn run_worker(ctx: &RunContext, config: &postgres::Config, queries: &[(String, String)], worker: usize, stop: &AtomicBool) -> Vec<QueryStats> {
+    let mut stats = vec![QueryStats::default(); queries.len()];
+    let mut client = None;
+    // Workers start on different queries, so they do not all run the same one at once
+    let mut next = worker % queries.len();
+    while !stop.load(Ordering::Relaxed) {
+        let connected = match client.as_mut() {
+            Some(client) => client,
+            None => match config.connect(postgres::NoTls) {
+                Ok(connected) => client.insert(connected),
+                Err(e) => {
+                    ctx.errors.record("query failures", &format!("Query worker {} cannot connect", worker), &describe(&e));
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+            },
+        };
+        let (name, sql) = &queries[next];
+        let started = Instant::now();
+        match connected.simple_query(sql) {
+            Ok(_) => stats[next].latencies.push(started.elapsed()),
+            Err(e) => {
+                stats[next].errors += 1;
+                ctx.errors.record("query failures", &format!("Query '{}' failed", name), &describe(&e));
+                if connected.is_closed() {
+                    client = None;
+                }
+            }
+        }
+        next = (next + 1) % queries.len();
+    }
+    stats
+}
+
+/// The server's message for query errors, which `Display` only calls "db error".
+#[cfg(feature = "query-load")]
+fn describe(e: &postgres::Error) -> String {
+    match e.as_db_error() {
+        Some(db) => db.message().to_string(),
+        None => e.to_string(),
+    }
+}
+
+fn percentile(sorted: &[Duration], pct: f64) -> f64 {
+    let index = ((sorted.len() - 1) as f64 * pct / 100.0).round() as usize;
+    sorted[index].as_secs_f64() * 1000.0
+}
+
+impl QueryLoad {
+    #[cfg(feature = "query-load")]
+    pub fn start(ctx: Arc<RunContext>, queries: Vec<(String, String)>, workers: usize) -> Result<Self, String> {
+        let config = pg_config(&ctx)?;
+        let queries = Arc::new(queries);
+        let stop = Arc::new(AtomicBool::new(false));
+        let handles = (0..workers).map(|worker| {
+            let (ctx, config, queries, stop) = (ctx.clone(), config.clone(), queries.clone(), stop.clone());
+            thread::Builder::new().name(format!("query-{}", worker))
+                .spawn(move || run_worker(&ctx, &config, &queries, worker, &stop))
+                .map_err(|e| e.to_string())
+        }).collect::<Result<Vec<_>, String>>()?;
+        Ok(QueryLoad { names: queries.iter().map(|(name, _)| name.clone()).collect(), stop, workers: handles })
+    }
+
+    #[cfg(not(feature = "query-load"))]
+    pub fn start(_ctx: Arc<RunContext>, _queries: Vec<(String, String)>, _workers: usize) -> Result<Self, String> {
+        Err("--query-workers needs the query-load feature".to_string())
+    }
+
+    /// Stops the workers, prints the latencies of each query and returns them.
+    pub fn stop(self) -> Vec<QuerySummary> {
+        self.stop.store(true, Ordering::Relaxed);
+        let mut total = vec![QueryStats::default(); self.names.len()];
+        for worker in self.workers {
+            if let Ok(stats) = worker.join() {
+                for (total, stats) in total.iter_mut().zip(stats) {
+                    total.latencies.extend(stats.latencies);
+                    total.errors += stats.errors;
+                }
+            }
+        }
+        let summaries: Vec<QuerySummary> = self.names.iter().zip(total).map(|(name, stats)| stats.summary(name)).collect();
+        println!("{:>8} {:>7} {:>9} {:>9} {:>9} {:>9}  query", "runs", "errors", "p50 ms", "p95 ms", "p99 ms", "max ms");
+        for q in summaries.iter() {
+            match (q.p50_ms, q.p95_ms, q.p99_ms, q.max_ms) {
+                (Some(p50), Some(p95), Some(p99), Some(max)) =>
+                    println!("{:>8} {:>7} {:>9.1} {:>9.1} {:>9.1} {:>9.1}  {}", q.runs, q.errors, p50, p95, p99, max, q.name),
+                _ => println!("{:>8} {:>7} {:>9} {:>9} {:>9} {:>9}  {}", q.runs, q.errors, "-", "-", "-", "-", q.name),
+            }
+        }
+        summaries
+    }
+}
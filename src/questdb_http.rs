@@ -0,0 +1,90 @@
+use std::time::Duration;
+use base64::Engine;
+use serde_json::Value;
+
+/// Minimal client for the QuestDB REST API, used to run SQL next to the ILP sender.
+#[derive(Debug, Clone)]
+pub struct QueryClient {
+    base_url: String,
+    authorization: Option<String>,
+    agent: ureq::Agent,
+}
+
+#[derive(Debug)]
+pub struct QueryResult {
+    /// Column names and types
+    pub columns: Vec<(String, String)>,
+    pub dataset: Vec<Vec<Value>>,
+}
+
+impl QueryResult {
+    /// Position of a column in the dataset rows
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|(n, _)| n == name)
+    }
+}
+
+impl QueryClient {
+    /// Derives the REST endpoint from an ILP connection string. HTTP transports reuse the
+    /// same address and credentials, TCP transports assume the default HTTP port 9000.
+    pub fn from_conf(conf: &str) -> Result<Self, String> {
+        let conf = questdb_confstr::parse_conf_str(conf).map_err(|e| format!("invalid connection string: {}", e))?;
+        let addr = conf.get("addr").ok_or("connection string has no addr")?;
+        let host = addr.split(':').next().unwrap_or(addr);
+        let (scheme, addr) = match conf.service() {
+            "http" => ("http", addr.to_string()),
+            "https" => ("https", addr.to_string()),
+            "tcps" => ("https", format!("{}:9000", host)),
+            _ => ("http", format!("{}:9000", host)),
+        };
+        let addr = if addr.contains(':') { addr } else { format!("{}:9000", addr) };
+        let http = conf.service().starts_with("http");
+        let authorization = match (conf.get("token"), conf.get("username"), conf.get("password")) {
+            (Some(token), _, _) if http => Some(format!("Bearer {}", token)),
+            (_, Some(user), Some(password)) => Some(format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, password)))),
+            _ => None,
+        };
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(60)).build();
+        Ok(QueryClient { base_url: format!("{}://{}", scheme, addr), authorization, agent })
+    }
+
+    fn get(&self, path: &str) -> ureq::Request {
+        let request = self.agent.get(&format!("{}{}", self.base_url, path));
+        match &self.authorization {
+            Some(auth) => request.set("Authorization", auth),
+            None => request,
+        }
+    }
+
+    /// Checks the server is up and accepting requests.
+    pub fn ping(&self) -> Result<(), String> {
+        self.get("/ping").call().map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    /// Runs a SQL statement through `/exec` and returns its result set.
+    pub fn exec(&self, sql: &str) -> Result<QueryResult, String> {
+        let json: Value = match self.get("/exec").query("query", sql).call() {
+            Ok(response) => response.into_json().map_err(|e| e.to_string())?,
+            Err(ureq::Error::Status(code, response)) => {
+                let body: Value = response.into_json().unwrap_or(Value::Null);
+                return Err(match body["error"].as_str() {
+                    Some(error) => format!("{} (HTTP {})", error, code),
+                    None => format!("HTTP {} running query", code),
+                });
+            }
+            Err(e) => return Err(format!("cannot reach {}: {}", self.base_url, e)),
+        };
+        if let Some(error) = json["error"].as_str() {
+            return Err(error.to_string());
+        }
+        let columns = json["columns"].as_array().map(|columns| {
+            columns.iter().map(|c| {
+                (c["name"].as_str().unwrap_or_default().to_string(), c["type"].as_str().unwrap_or_default().to_string())
+            }).collect()
+        }).unwrap_or_default();
+        let dataset = json["dataset"].as_array().map(|rows| {
+            rows.iter().map(|row| row.as_array().cloned().unwrap_or_default()).collect()
+        }).unwrap_or_default();
+        Ok(QueryResult { columns, dataset })
+    }
+}
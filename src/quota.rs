@@ -0,0 +1,219 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// The `--total-rows` of a run, handed out to the generator tasks in chunks. A task only
+/// writes rows it has claimed, so however many tasks race for the last rows, exactly
+/// `--total-rows` are generated. A task finding nothing left to claim waits while others
+/// still hold rows, since a task that stops early hands its rows back. With `--duration`
+/// the quota also runs out when the time is up, whatever is left, and it can be ended
+/// early.
+#[derive(Debug)]
+pub struct RowQuota {
+    total: u64,
+    unclaimed: AtomicU64,
+    /// Rows claimed by the allowances and neither used nor handed back yet
+    held: AtomicU64,
+    used: AtomicU64,
+    deadline: Option<Instant>,
+    ended: AtomicBool,
+}
+
+impl RowQuota {
+    pub fn new(total: u64, duration: Option<Duration>) -> Self {
+        RowQuota { total, unclaimed: AtomicU64::new(total), held: AtomicU64::new(0), used: AtomicU64::new(0), deadline: duration.map(|d| Instant::now() + d), ended: AtomicBool::new(false) }
+    }
+
+    fn expired(&self) -> bool {
+        self.ended.load(Ordering::Relaxed) || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Runs the quota out now, whatever is left: the tasks write the rows they hold and stop.
+    pub fn end_now(&self) {
+        self.ended.store(true, Ordering::Relaxed);
+    }
+
+    /// Takes up to `max` rows from those not claimed yet.
+    fn try_claim(&self, max: u64) -> u64 {
+        // Held before they are taken, so a waiting task never sees them nowhere
+        self.held.fetch_add(max, Ordering::SeqCst);
+        let claimed = match self.unclaimed.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| Some(left - left.min(max))) {
+            Ok(left) => left.min(max),
+            Err(_) => 0,
+        };
+        self.held.fetch_sub(max - claimed, Ordering::SeqCst);
+        claimed
+    }
+
+    /// Takes up to `max` rows, fewer or none once the quota runs out. With every row
+    /// claimed it waits for the tasks holding some to write them or hand them back, the
+    /// caller holding none itself. An async task waits off its worker thread, so the
+    /// holders keep running.
+    fn claim(&self, max: u64) -> u64 {
+        loop {
+            if self.expired() {
+                return 0;
+            }
+            let claimed = self.try_claim(max);
+            if claimed > 0 {
+                return claimed;
+            }
+            // Rows handed back are unclaimed before they stop being held
+            if self.held.load(Ordering::SeqCst) == 0 {
+                return self.try_claim(max);
+            }
+            wait();
+        }
+    }
+
+    /// Hands back claimed rows that will not be written.
+    fn release(&self, rows: u64) {
+        if rows > 0 {
+            self.unclaimed.fetch_add(rows, Ordering::SeqCst);
+            self.held.fetch_sub(rows, Ordering::SeqCst);
+        }
+    }
+
+    /// Rows taken for writing so far
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// Rows not taken for writing yet, including the ones claimed by a task
+    pub fn remaining(&self) -> u64 {
+        match self.expired() {
+            true => 0,
+            false => self.total - self.used(),
+        }
+    }
+
+    /// An allowance for a task writing `rows_per_tick` rows per tick, one per plane unless
+    /// --plane-skew has them skip ticks, claiming ten ticks' worth at a time: few enough
+    /// claims to keep them off the hot path, small enough chunks that the planes finish
+    /// together.
+    pub fn allowance(&self, rows_per_tick: f64) -> Allowance<'_> {
+        Allowance { quota: self, left: 0, chunk: ((rows_per_tick * 10.0).ceil() as u64).max(1) }
+    }
+}
+
+/// Sleeps a moment while other tasks still hold rows
+fn wait() {
+    let pause = || std::thread::sleep(Duration::from_millis(1));
+    match tokio::runtime::Handle::try_current().map(|handle| handle.runtime_flavor()) {
+        Ok(tokio::runtime::RuntimeFlavor::MultiThread) => tokio::task::block_in_place(pause),
+        _ => pause(),
+    }
+}
+
+/// The rows a task has claimed and not written yet. It claims `chunk` more whenever it
+/// runs out, and hands back what is left once its planes stop or when dropped, so a task
+/// that stops early does not keep rows from the others.
+#[derive(Debug)]
+pub struct Allowance<'a> {
+    quota: &'a RowQuota,
+    left: u64,
+    chunk: u64,
+}
+
+impl Allowance<'_> {
+    /// Takes one row, false once the whole quota is used.
+    pub fn take(&mut self) -> bool {
+        if self.quota.expired() {
+            return false;
+        }
+        if self.left == 0 {
+            self.left = self.quota.claim(self.chunk);
+        }
+        if self.left == 0 {
+            return false;
+        }
+        self.left -= 1;
+        self.quota.used.fetch_add(1, Ordering::SeqCst);
+        self.quota.held.fetch_sub(1, Ordering::SeqCst);
+        true
+    }
+
+    /// Hands back the rows left, for a task whose planes all stopped.
+    pub fn release(&mut self) {
+        self.quota.release(std::mem::take(&mut self.left));
+    }
+
+    /// Whether the row just taken was the last one this task will get, so it can flush
+    /// right away instead of waiting for the next tick.
+    pub fn is_exhausted(&mut self) -> bool {
+        if self.quota.expired() {
+            return true;
+        }
+        if self.left == 0 {
+            self.left = self.quota.claim(self.chunk);
+        }
+        self.left == 0
+    }
+}
+
+impl Drop for Allowance<'_> {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use super::*;
+
+    /// Races tasks writing `rows_per_tick` rows per tick, each stopping after the rows of
+    /// its option, and returns the rows they wrote
+    fn race(quota: &RowQuota, tasks: &[(f64, Option<u64>)]) -> u64 {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = tasks.iter().map(|&(rows_per_tick, stop_after)| scope.spawn(move || {
+                let mut allowance = quota.allowance(rows_per_tick);
+                let mut written = 0;
+                while stop_after.is_none_or(|stop_after| written < stop_after) && allowance.take() {
+                    written += 1;
+                }
+                written
+            })).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(100))]
+
+        /// Some tasks give up early, after the rows of their option, and the rows they
+        /// claimed go back to the others
+        #[test]
+        fn racing_tasks_write_exactly_the_total(total in 0..20_000u64,
+                                                tasks in prop::collection::vec((0.01..50.0f64, prop::option::weighted(0.3, 0..500u64)), 1..8)) {
+            let quota = RowQuota::new(total, None);
+            let written = race(&quota, &tasks);
+            if tasks.iter().any(|(_, stop_after)| stop_after.is_none()) {
+                prop_assert_eq!(written, total);
+            }
+            prop_assert!(written <= total, "{} rows written of {}", written, total);
+            prop_assert_eq!(quota.used(), written);
+            prop_assert_eq!(quota.remaining(), total - written);
+        }
+    }
+
+    /// A case that used to end short of the total when the tasks stopping early handed
+    /// their rows back after the others had given up
+    #[test]
+    fn tasks_stopping_early_leave_their_rows_to_the_others() {
+        let tasks = [(21.675, Some(120)), (1.073, None), (5.175, Some(315)), (36.403, None)];
+        for _ in 0..50 {
+            let quota = RowQuota::new(9437, None);
+            assert_eq!(race(&quota, &tasks), 9437);
+            assert_eq!(quota.remaining(), 0);
+        }
+    }
+
+    #[test]
+    fn an_expired_quota_hands_out_nothing() {
+        let quota = RowQuota::new(1000, Some(Duration::ZERO));
+        let mut allowance = quota.allowance(1.0);
+        assert!(!allowance.take());
+        assert!(allowance.is_exhausted());
+        assert_eq!(quota.remaining(), 0);
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::time::{Duration, interval, sleep, MissedTickBehavior};
+
+use crate::RunContext;
+
+/// Why a bucket fell short of the target rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bottleneck {
+    /// Planes spent enough time waiting for flushes to explain the missing rows
+    Server,
+    /// Planes were not waiting on the server, so producing rows was too slow
+    Generator,
+    /// The server was down for at least part of the bucket
+    ServerDown,
+}
+
+impl Bottleneck {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bottleneck::Server => "server",
+            Bottleneck::Generator => "generator",
+            Bottleneck::ServerDown => "server down",
+        }
+    }
+}
+
+/// Achieved rate over one time bucket of the run.
+#[derive(Debug, Clone)]
+pub struct RateBucket {
+    pub index: u64,
+    pub secs: f64,
+    pub rows: u64,
+    pub target_rate: f64,
+    /// Fraction of the planes' time spent inside flushes
+    pub flush_share: f64,
+    /// Set when the bucket is more than the tolerance below target
+    pub bottleneck: Option<Bottleneck>,
+}
+
+impl RateBucket {
+    pub fn rate(&self) -> f64 {
+        if self.secs > 0.0 { self.rows as f64 / self.secs } else { 0.0 }
+    }
+
+    /// Deviation from the target rate, in percent (negative when slower)
+    pub fn deviation_pct(&self) -> f64 {
+        if self.target_rate > 0.0 { 100.0 * (self.rate() - self.target_rate) / self.target_rate } else { 0.0 }
+    }
+}
+
+/// Counts rows and time spent flushing across all planes, so the achieved aggregate
+/// rate can be compared with the requested one bucket by bucket.
+#[derive(Debug)]
+pub struct RateTracker {
+    rows: AtomicU64,
+    /// Ticks where a plane sent nothing on purpose, e.g. out of coverage
+    skipped: AtomicU64,
+    flush_nanos: AtomicU64,
+    /// Whether the server was seen down during the current bucket
+    server_was_down: AtomicBool,
+    /// Rate each plane targets, as the bits of an f64 since the terminal UI can change it
+    rate_per_plane: AtomicU64,
+    /// Planes expected to keep sending, all of them unless killed from the terminal UI
+    flying: AtomicU32,
+    planes: u32,
+    /// Share of the rate of each plane the planes target on average, below 1 with --plane-skew
+    share: f64,
+    /// End of the warm-up, nothing is measured before
+    measured_from: Instant,
+    bucket_started: Mutex<Instant>,
+    buckets: Mutex<Vec<RateBucket>>,
+}
+
+impl RateTracker {
+    pub fn new(planes: u32, rate_per_plane: f64, warmup: Duration) -> Self {
+        RateTracker {
+            measured_from: Instant::now() + warmup,
+            rows: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            flush_nanos: AtomicU64::new(0),
+            server_was_down: AtomicBool::new(false),
+            rate_per_plane: AtomicU64::new(rate_per_plane.to_bits()),
+            flying: AtomicU32::new(planes),
+            planes,
+            share: 1.0,
+            bucket_started: Mutex::new(Instant::now()),
+            buckets: Mutex::new(vec![]),
+        }
+    }
+
+    /// The planes send `share` of the rate per plane on average, for --plane-skew
+    pub fn with_share(self, share: f64) -> Self {
+        RateTracker { share, ..self }
+    }
+
+    pub fn record_row(&self) {
+        self.rows.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a tick without a row that was not supposed to have one; the target of
+    /// the bucket is lowered accordingly.
+    pub fn record_skipped(&self) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// New rate of each plane, for the buckets closed from now on
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub fn set_rate_per_plane(&self, rate_per_plane: f64) {
+        self.rate_per_plane.store(rate_per_plane.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Takes a plane that stopped for good out of the target
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub fn remove_plane(&self) {
+        let _ = self.flying.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |flying| flying.checked_sub(1));
+    }
+
+    pub fn record_flush(&self, elapsed: Duration) {
+        self.flush_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Drops everything counted so far and starts a new bucket, used when the warm-up or a
+    /// --backfill-workers backfill ends.
+    pub fn reset(&self) {
+        self.rows.store(0, Ordering::Relaxed);
+        self.skipped.store(0, Ordering::Relaxed);
+        self.flush_nanos.store(0, Ordering::Relaxed);
+        *self.bucket_started.lock().unwrap() = Instant::now();
+    }
+
+    /// Number of planes still flying times the rate each one targets
+    fn target_rate(&self) -> f64 {
+        self.flying.load(Ordering::Relaxed) as f64 * f64::from_bits(self.rate_per_plane.load(Ordering::Relaxed)) * self.share
+    }
+
+    pub fn buckets(&self) -> Vec<RateBucket> {
+        self.buckets.lock().unwrap().clone()
+    }
+
+    /// Closes the current bucket, taking the counters accumulated since the previous one.
+    fn close_bucket(&self, server_down: bool, tolerance_pct: f64) -> RateBucket {
+        let server_was_down = self.server_was_down.swap(false, Ordering::Relaxed) || server_down;
+        let secs = {
+            let mut started = self.bucket_started.lock().unwrap();
+            let secs = started.elapsed().as_secs_f64();
+            *started = Instant::now();
+            secs
+        };
+        let rows = self.rows.swap(0, Ordering::Relaxed);
+        let skipped = self.skipped.swap(0, Ordering::Relaxed);
+        let flush_secs = self.flush_nanos.swap(0, Ordering::Relaxed) as f64 / 1e9;
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut bucket = RateBucket {
+            index: buckets.len() as u64,
+            secs,
+            rows,
+            target_rate: (self.target_rate() - skipped as f64 / secs).max(0.0),
+            flush_share: (flush_secs / (secs * self.planes as f64)).min(1.0),
+            bottleneck: None,
+        };
+        if bucket.deviation_pct() < -tolerance_pct {
+            bucket.bottleneck = Some(if server_was_down {
+                Bottleneck::ServerDown
+            } else if bucket.flush_share >= -bucket.deviation_pct() / 100.0 {
+                // Time lost waiting for flushes is enough to explain the missing rows
+                Bottleneck::Server
+            } else {
+                Bottleneck::Generator
+            });
+        }
+        buckets.push(bucket.clone());
+        bucket
+    }
+
+    /// Closes the last, usually partial, bucket once the planes are done.
+    pub fn finish(&self, ctx: &RunContext) {
+        if Instant::now() >= self.measured_from && self.rows.load(Ordering::Relaxed) > 0 {
+            print_bucket(&self.close_bucket(ctx.health.is_down(), ctx.opt.rate_tolerance_pct));
+        }
+    }
+
+    /// Achieved and target rate over all the buckets, if anything was measured
+    pub fn summary(&self) -> Option<(f64, f64)> {
+        let buckets = self.buckets();
+        let secs: f64 = buckets.iter().map(|b| b.secs).sum();
+        let rows: u64 = buckets.iter().map(|b| b.rows).sum();
+        let target_rate = buckets.iter().map(|b| b.target_rate * b.secs).sum::<f64>() / secs;
+        if secs <= 0.0 || target_rate <= 0.0 {
+            return None;
+        }
+        Some((rows as f64 / secs, target_rate))
+    }
+
+    pub fn print_summary(&self) {
+        let (rate, target_rate) = match self.summary() {
+            Some(summary) => summary,
+            None => return,
+        };
+        let buckets = self.buckets();
+        println!("Achieved {:.1} rows/s of {:.1} requested ({:+.1}%)", rate, target_rate, 100.0 * (rate - target_rate) / target_rate);
+        let generator_bound = buckets.iter().filter(|b| b.bottleneck == Some(Bottleneck::Generator)).count();
+        if generator_bound > 0 {
+            println!("The generator itself was the bottleneck in {} of {} buckets, use more planes or a faster machine", generator_bound, buckets.len());
+        }
+    }
+}
+
+fn print_bucket(bucket: &RateBucket) {
+    let flag = match bucket.bottleneck {
+        Some(bottleneck) => format!(", below target, bottleneck: {}", bottleneck.as_str()),
+        None => String::new(),
+    };
+    println!("Rate bucket {}: {:.1} rows/s of {:.1} target ({:+.1}%), {:.0}% of time flushing{}",
+             bucket.index, bucket.rate(), bucket.target_rate, bucket.deviation_pct(), 100.0 * bucket.flush_share, flag);
+}
+
+/// Reports the achieved aggregate rate every `--rate-bucket-secs` until aborted. The
+/// first bucket starts once the warm-up is over.
+pub async fn track(ctx: Arc<RunContext>) {
+    if ctx.opt.warmup_secs > 0 {
+        sleep(Duration::from_secs(ctx.opt.warmup_secs)).await;
+        ctx.rate.reset();
+        println!("Warm-up of {}s done, measuring from now", ctx.opt.warmup_secs);
+    }
+    let bucket = Duration::from_secs(ctx.opt.rate_bucket_secs);
+    let mut ticks = interval(bucket);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticks.tick().await;
+    loop {
+        // Check the server once a second, so an outage inside the bucket is not missed
+        while tokio::time::timeout(Duration::from_secs(1), ticks.tick()).await.is_err() {
+            ctx.rate.server_was_down.fetch_or(ctx.health.is_down(), Ordering::Relaxed);
+        }
+        print_bucket(&ctx.rate.close_bucket(ctx.health.is_down(), ctx.opt.rate_tolerance_pct));
+    }
+}
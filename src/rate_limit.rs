@@ -0,0 +1,82 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error_summary::thousands;
+
+/// A cap on the rows per second sent through a connection or the whole process, for
+/// --max-rate-per-conn and --max-total-rate. A batch goes out once the batches before it
+/// have had their time at the capped rate, so a batch can go out at once after a pause
+/// but the rate never goes over the cap for longer than one batch.
+#[derive(Debug)]
+pub struct RateLimit {
+    nanos_per_row: f64,
+    /// When the rows reserved so far have had their time
+    next_free: Mutex<Option<Instant>>,
+    waited_nanos: AtomicU64,
+}
+
+impl RateLimit {
+    pub fn new(rows_per_sec: u64) -> Self {
+        RateLimit { nanos_per_row: 1e9 / rows_per_sec.max(1) as f64, next_free: Mutex::new(None), waited_nanos: AtomicU64::new(0) }
+    }
+
+    /// Reserves the time `rows` take at the capped rate, and tells how long to wait
+    /// before sending them.
+    pub fn reserve(&self, rows: usize) -> Duration {
+        let now = Instant::now();
+        let mut next_free = self.next_free.lock().unwrap();
+        let start = next_free.map(|next_free| next_free.max(now)).unwrap_or(now);
+        *next_free = Some(start + Duration::from_nanos((rows as f64 * self.nanos_per_row) as u64));
+        let wait = start - now;
+        self.waited_nanos.fetch_add(wait.as_nanos() as u64, Ordering::Relaxed);
+        wait
+    }
+
+    pub fn waited(&self) -> Duration {
+        Duration::from_nanos(self.waited_nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// --max-total-rate, shared by all the connections, and --max-rate-per-conn, that each
+/// connection gets its own `RateLimit` of.
+#[derive(Debug)]
+pub struct RateLimits {
+    total: Option<RateLimit>,
+    per_conn: Option<u64>,
+    conn_waited_nanos: AtomicU64,
+}
+
+impl RateLimits {
+    pub fn new(total: Option<u64>, per_conn: Option<u64>) -> Self {
+        RateLimits { total: total.map(RateLimit::new), per_conn, conn_waited_nanos: AtomicU64::new(0) }
+    }
+
+    /// The limit of a new connection, if there is one
+    pub fn for_connection(&self) -> Option<RateLimit> {
+        self.per_conn.map(RateLimit::new)
+    }
+
+    /// How long to wait before sending `rows` for --max-total-rate
+    pub fn reserve_total(&self, rows: usize) -> Duration {
+        self.total.as_ref().map(|limit| limit.reserve(rows)).unwrap_or_default()
+    }
+
+    /// How long to wait before sending `rows` through a connection limited by `limit`
+    pub fn reserve_conn(&self, limit: Option<&RateLimit>, rows: usize) -> Duration {
+        let wait = limit.map(|limit| limit.reserve(rows)).unwrap_or_default();
+        self.conn_waited_nanos.fetch_add(wait.as_nanos() as u64, Ordering::Relaxed);
+        wait
+    }
+
+    /// What the limits held the flushes back by, at the end of the run
+    pub fn print_summary(&self) {
+        if let Some(total) = &self.total {
+            println!("Flushes waited {}s in total for --max-total-rate", thousands(total.waited().as_secs()));
+        }
+        if self.per_conn.is_some() {
+            println!("Flushes waited {}s in total for --max-rate-per-conn",
+                     thousands(Duration::from_nanos(self.conn_waited_nanos.load(Ordering::Relaxed)).as_secs()));
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::Arc;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, sleep};
+
+use crate::RunContext;
+use crate::config::{ColumnConfig, Config};
+
+/// How long --watch-config waits after a change to the file for the next ones, an editor
+/// saving it can write it in a few steps
+const SETTLE_MILLIS: u64 = 200;
+
+/// Applies the changes to the --config file while the planes fly, as soon as the file
+/// system reports them. A file that does not parse, or changes something the tables
+/// already depend on, is reported and the settings stay as they were.
+pub async fn watch(ctx: Arc<RunContext>) {
+    let path = ctx.opt.config.clone().expect("--watch-config needs a --config file");
+    let mut config = match Config::load(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Not watching the config file: {}", e);
+            return;
+        }
+    };
+    let (changed, mut events) = mpsc::unbounded_channel();
+    let name = path.file_name().map(ToOwned::to_owned);
+    let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            if !matches!(event.kind, EventKind::Access(_)) && event.paths.iter().any(|changed| changed.file_name() == name.as_deref()) {
+                let _ = changed.send(());
+            }
+        }
+    });
+    // The directory rather than the file, editors save by renaming another file over it
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let _watcher = match watcher.and_then(|mut watcher| watcher.watch(dir, RecursiveMode::NonRecursive).map(|_| watcher)) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("Not watching the config file: {}", e);
+            return;
+        }
+    };
+    while events.recv().await.is_some() {
+        sleep(Duration::from_millis(SETTLE_MILLIS)).await;
+        while events.try_recv().is_ok() {}
+        let reloaded = Config::load(&path).and_then(|new| {
+            let changes = changes(&config, &new).map_err(|e| config.redact(new.redact(e)))?;
+            let columns = ctx.columns().reloaded(&new)?;
+            let schedule = ctx.schedule.as_ref().map(|schedule| schedule.reloaded(&new, ctx.opt.plane_count as u64)).transpose()?;
+            Ok((new, columns, schedule, changes))
+        });
+        match reloaded {
+            Ok((_, _, _, changes)) if changes.is_empty() => {}
+            Ok((new, columns, schedule, changes)) => {
+                *ctx.columns.write().unwrap() = Arc::new(columns);
+                if let (Some(running), Some(schedule)) = (&ctx.schedule, schedule) {
+                    running.replace(schedule);
+                }
+                eprintln!("Config file {} reloaded: {}", path.display(), config.redact(new.redact(changes.join(", "))));
+                config = new;
+            }
+            Err(e) => eprintln!("Config file {} not reloaded, the previous settings stay: {}", path.display(), e),
+        }
+    }
+}
+
+/// What changed between two configs, for the changes that can be made while running. Column
+/// names and units, what is in a sensor group and the tables of the fleets shape the rows
+/// the tables already hold, so changing them is an error.
+pub fn changes(old: &Config, new: &Config) -> Result<Vec<String>, String> {
+    let mut changes = vec![];
+    let default = ColumnConfig::default();
+    for name in keys(old.columns.keys(), new.columns.keys()) {
+        let (before, after) = (old.columns.get(name).unwrap_or(&default), new.columns.get(name).unwrap_or(&default));
+        if before.name != after.name || before.unit != after.unit {
+            return Err(format!("the name and unit of column '{}' can only change with a restart", name));
+        }
+        let fields = [
+            ("noise", shown(&before.noise), shown(&after.noise)),
+            ("noise_scale", shown(&before.noise_scale), shown(&after.noise_scale)),
+            ("drift_per_hour", shown(&before.drift_per_hour), shown(&after.drift_per_hour)),
+            ("outlier_pct", shown(&before.outlier_pct), shown(&after.outlier_pct)),
+            ("outlier_sigmas", shown(&before.outlier_sigmas), shown(&after.outlier_sigmas)),
+        ];
+        for (field, before, after) in fields.iter().filter(|(_, before, after)| before != after) {
+            changes.push(format!("column '{}' {} {} -> {}", name, field, before, after));
+        }
+    }
+    for name in keys(old.sensor_groups.keys(), new.sensor_groups.keys()) {
+        match (old.sensor_groups.get(name), new.sensor_groups.get(name)) {
+            (Some(before), Some(after)) if before.columns == after.columns => {
+                if before.rate_hz != after.rate_hz {
+                    changes.push(format!("sensor group '{}' rate_hz {} -> {}", name, before.rate_hz, after.rate_hz));
+                }
+            }
+            _ => return Err(format!("the columns of sensor group '{}' can only change with a restart", name)),
+        }
+    }
+    for name in keys(old.fleets.keys(), new.fleets.keys()) {
+        let (before, after) = (old.fleets.get(name), new.fleets.get(name));
+        if before.and_then(|fleet| fleet.table.as_ref()) != after.and_then(|fleet| fleet.table.as_ref()) {
+            return Err(format!("the table of fleet '{}' can only change with a restart", name));
+        }
+        match (before, after) {
+            (Some(before), Some(after)) if before.airlines != after.airlines => {
+                changes.push(format!("fleet '{}' airlines {} -> {}", name, before.airlines.join(" "), after.airlines.join(" ")));
+            }
+            (None, Some(after)) => changes.push(format!("fleet '{}' added with airlines {}", name, after.airlines.join(" "))),
+            (Some(_), None) => changes.push(format!("fleet '{}' removed", name)),
+            _ => {}
+        }
+    }
+    for name in keys(old.rate_schedules.keys(), new.rate_schedules.keys()) {
+        match (old.rate_schedules.get(name), new.rate_schedules.get(name)) {
+            (Some(before), Some(after)) if before != after => changes.push(format!("rate schedule '{}' changed", name)),
+            (None, Some(_)) => changes.push(format!("rate schedule '{}' added", name)),
+            (Some(_), None) => changes.push(format!("rate schedule '{}' removed", name)),
+            _ => {}
+        }
+    }
+    Ok(changes)
+}
+
+/// The keys of both maps, in order
+fn keys<'a>(old: impl Iterator<Item = &'a String>, new: impl Iterator<Item = &'a String>) -> BTreeSet<&'a String> {
+    old.chain(new).collect()
+}
+
+fn shown<T: Display>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_else(|| "unset".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn changes_while_running() {
+        let old = config(r#"
+            [columns.altitude]
+            unit = "m"
+            noise = "gaussian"
+            [sensor_groups.slow]
+            columns = ["oat"]
+            rate_hz = 1.0
+            [fleets.star]
+            airlines = ["DLH"]
+        "#);
+        let new = config(r#"
+            [columns.altitude]
+            unit = "m"
+            noise = "gaussian"
+            noise_scale = 3.0
+            [columns.airspeed]
+            outlier_pct = 1.0
+            [sensor_groups.slow]
+            columns = ["oat"]
+            rate_hz = 0.5
+            [fleets.star]
+            airlines = ["DLH", "UAL"]
+            [fleets.oneworld]
+            airlines = ["BAW"]
+            [rate_schedules.demo]
+            otherwise = "10k"
+        "#);
+        assert_eq!(changes(&old, &new).unwrap(), [
+            "column 'airspeed' outlier_pct unset -> 1",
+            "column 'altitude' noise_scale unset -> 3",
+            "sensor group 'slow' rate_hz 1 -> 0.5",
+            "fleet 'oneworld' added with airlines BAW",
+            "fleet 'star' airlines DLH -> DLH UAL",
+            "rate schedule 'demo' added",
+        ]);
+        assert!(changes(&new, &new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn changes_to_the_rows_need_a_restart() {
+        let old = config("[columns.altitude]\nunit = \"m\"\n[sensor_groups.slow]\ncolumns = [\"oat\"]\nrate_hz = 1.0\n");
+        let renamed = config("[columns.altitude]\nunit = \"m\"\nname = \"alt\"\n[sensor_groups.slow]\ncolumns = [\"oat\"]\nrate_hz = 1.0\n");
+        assert!(changes(&old, &renamed).unwrap_err().contains("column 'altitude'"));
+        let regrouped = config("[columns.altitude]\nunit = \"m\"\n[sensor_groups.slow]\ncolumns = [\"oat\", \"fuel_kg\"]\nrate_hz = 1.0\n");
+        assert!(changes(&old, &regrouped).unwrap_err().contains("sensor group 'slow'"));
+        let own_table = config("[columns.altitude]\nunit = \"m\"\n[sensor_groups.slow]\ncolumns = [\"oat\"]\nrate_hz = 1.0\n[fleets.star]\nairlines = [\"DLH\"]\ntable = \"star\"\n");
+        assert!(changes(&old, &own_table).unwrap_err().contains("fleet 'star'"));
+    }
+}
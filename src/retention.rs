@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use chrono::{TimeZone, Utc};
+
+use crate::RunContext;
+use crate::questdb_http::QueryClient;
+
+/// Drops the partitions of the table older than --retention-hours every
+/// --retention-every-secs, on a thread of its own. The horizon follows the clock the rows
+/// are generated with, so with --backfill-hours old partitions go as the data moves on.
+pub struct Retention {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+fn drop_old_partitions(ctx: &RunContext, client: &QueryClient, hours: f64) {
+    let horizon = ctx.now() - (hours * 3600e9) as i64;
+    let horizon = Utc.timestamp_nanos(horizon).format("%Y-%m-%dT%H:%M:%S%.6fZ");
+    let table = ctx.opt.table_name.replace('"', "\"\"");
+    let sql = format!("ALTER TABLE \"{}\" DROP PARTITION WHERE timestamp < '{}'", table, horizon);
+    match client.exec(&sql) {
+        Ok(_) if !ctx.opt.quiet => println!("Dropped the partitions of {} before {}", ctx.opt.table_name, horizon),
+        Ok(_) => {}
+        Err(e) => ctx.errors.record("retention failures", &format!("Cannot drop the partitions of {}", ctx.opt.table_name), &e),
+    }
+}
+
+impl Retention {
+    pub fn start(ctx: Arc<RunContext>, hours: f64, every: Duration) -> Result<Self, String> {
+        let client = QueryClient::from_conf(&ctx.opt.connection_string)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = thread::Builder::new().name("retention".to_string()).spawn(move || {
+            let mut next = Instant::now() + every;
+            while !stopped.load(Ordering::Relaxed) {
+                if Instant::now() >= next {
+                    drop_old_partitions(&ctx, &client, hours);
+                    next += every;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        }).map_err(|e| e.to_string())?;
+        Ok(Retention { stop, thread })
+    }
+
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+    }
+}
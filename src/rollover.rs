@@ -0,0 +1,133 @@
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, TimeZone, Timelike, Utc};
+
+use crate::questdb_http::QueryClient;
+
+/// Partitioning of the table, whose boundaries --partition-rollover writes across.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Partitioning {
+    /// Read from the table on the server, DAY if it does not exist yet
+    Auto,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl FromStr for Partitioning {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Partitioning::Auto),
+            "hour" => Ok(Partitioning::Hour),
+            "day" => Ok(Partitioning::Day),
+            "week" => Ok(Partitioning::Week),
+            "month" => Ok(Partitioning::Month),
+            "year" => Ok(Partitioning::Year),
+            _ => Err(format!("invalid partitioning '{}', expected auto, hour, day, week, month or year", s)),
+        }
+    }
+}
+
+impl Partitioning {
+    /// Resolves `Auto` from the `partitionBy` of the table in `tables()`. Tables created
+    /// by ILP are partitioned by DAY, unless the server is configured otherwise.
+    pub fn resolve(self, client: &QueryClient, table: &str) -> Result<Self, String> {
+        if self != Partitioning::Auto {
+            return Ok(self);
+        }
+        let result = client.exec("SELECT * FROM tables()")?;
+        let name = result.column_index("table_name").or_else(|| result.column_index("name"))
+            .ok_or("unexpected tables() output, no table name column")?;
+        let partition_by = result.column_index("partitionBy").ok_or("unexpected tables() output, no partitionBy column")?;
+        let row = result.dataset.iter().find(|row| row.get(name).and_then(|n| n.as_str()) == Some(table));
+        match row.and_then(|row| row.get(partition_by)).and_then(|p| p.as_str()) {
+            Some("NONE") => Err(format!("table '{}' is not partitioned, there are no boundaries to write across", table)),
+            Some(partition_by) => partition_by.parse(),
+            None => {
+                println!("Table '{}' does not exist yet, writing across DAY boundaries as for a table created by ILP", table);
+                Ok(Partitioning::Day)
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Partitioning::Auto => "auto",
+            Partitioning::Hour => "hour",
+            Partitioning::Day => "day",
+            Partitioning::Week => "week",
+            Partitioning::Month => "month",
+            Partitioning::Year => "year",
+        }
+    }
+
+    /// Start of the first partition after `time`.
+    fn next_boundary(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let day = time.date_naive();
+        let start = match self {
+            Partitioning::Hour => day.and_hms_opt(time.hour(), 0, 0).unwrap(),
+            Partitioning::Auto | Partitioning::Day => day.and_hms_opt(0, 0, 0).unwrap(),
+            // QuestDB weeks start on Monday
+            Partitioning::Week => (day - Duration::days(day.weekday().num_days_from_monday() as i64)).and_hms_opt(0, 0, 0).unwrap(),
+            Partitioning::Month => NaiveDate::from_ymd_opt(day.year(), day.month(), 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            Partitioning::Year => NaiveDate::from_ymd_opt(day.year(), 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        };
+        self.step(Utc.from_utc_datetime(&start), 1)
+    }
+
+    /// `n` partitions after the boundary `time`.
+    fn step(&self, time: DateTime<Utc>, n: u32) -> DateTime<Utc> {
+        match self {
+            Partitioning::Hour => time + Duration::hours(n as i64),
+            Partitioning::Auto | Partitioning::Day => time + Duration::days(n as i64),
+            Partitioning::Week => time + Duration::weeks(n as i64),
+            Partitioning::Month => time + Months::new(n),
+            Partitioning::Year => time + Months::new(12 * n),
+        }
+    }
+}
+
+/// Timestamps for --partition-rollover: instead of the wall clock, rows get times that
+/// run for --rollover-window-secs up to a partition boundary and as long after it, then
+/// jump ahead to just before the next boundary. Every cycle of twice the window makes
+/// the server open a new partition while rows for the previous one are still coming
+/// in from other planes.
+#[derive(Debug, Clone)]
+pub struct RolloverClock {
+    partitioning: Partitioning,
+    started_at: i64,
+    first_boundary: DateTime<Utc>,
+    window_nanos: i64,
+}
+
+impl RolloverClock {
+    pub fn new(partitioning: Partitioning, window_secs: f64) -> Self {
+        let now = Utc::now();
+        RolloverClock {
+            partitioning,
+            started_at: now.timestamp_nanos_opt().unwrap(),
+            first_boundary: partitioning.next_boundary(now),
+            window_nanos: (window_secs * 1e9) as i64,
+        }
+    }
+
+    pub fn partitioning(&self) -> Partitioning {
+        self.partitioning
+    }
+
+    /// The boundary the first cycle writes across
+    pub fn first_boundary(&self) -> DateTime<Utc> {
+        self.first_boundary
+    }
+
+    /// Timestamp for a row generated now.
+    pub fn now(&self) -> i64 {
+        let elapsed = Utc::now().timestamp_nanos_opt().unwrap() - self.started_at;
+        let cycle = elapsed / (2 * self.window_nanos);
+        let boundary = self.partitioning.step(self.first_boundary, cycle as u32);
+        boundary.timestamp_nanos_opt().unwrap() - self.window_nanos + elapsed % (2 * self.window_nanos)
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use questdb::ingress::{Buffer, TimestampMicros, TimestampNanos};
+use serde_json::{json, Value};
+
+use crate::{Opt, RunContext};
+use crate::query_load::QuerySummary;
+use crate::sink;
+use crate::stats::PlaneStats;
+
+/// Version of the simulator, with the git commit it was built from.
+const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), " (", env!("AIRPLANES_GIT_VERSION"), ")");
+
+/// One run of the simulator as recorded in the runs table: a `start` row with the parameters
+/// before generating, and an `end` row with the totals, sharing the same `run_id`.
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    run_id: String,
+    host: String,
+    /// The command line, with the credentials of the connection string left out
+    parameters: String,
+    started_at: TimestampNanos,
+}
+
+impl RunRecord {
+    pub fn new() -> Self {
+        let started_at = TimestampNanos::now();
+        let run_id = format!("{}-{}", chrono::Utc::now().format("%Y%m%dT%H%M%S"), std::process::id());
+        RunRecord { run_id, host: hostname(), parameters: parameters(), started_at }
+    }
+
+    pub fn write_start(&self, opt: &Opt) {
+        let result = self.write(opt, "start", |_| Ok(()));
+        match result {
+            Ok(()) if !opt.quiet => println!("Run {} recorded in table {}", self.run_id, opt.runs_table),
+            Ok(()) => {}
+            Err(e) => eprintln!("Cannot record the start of the run in table {}: {}", opt.runs_table, e),
+        }
+    }
+
+    pub fn write_end(&self, ctx: &RunContext, plane_stats: &[PlaneStats]) {
+        let rows_generated = ctx.rows_generated();
+        let rows_flushed: u64 = plane_stats.iter().map(|s| s.rows_flushed).sum();
+        let result = self.write(&ctx.opt, "end", |buffer| {
+            buffer.column_ts("ended_at", TimestampMicros::now())?
+                .column_i64("rows_generated", rows_generated as i64)?
+                .column_i64("rows_flushed", rows_flushed as i64)?
+                .column_i64("rows_rejected", ctx.rows_rejected.load(Ordering::SeqCst) as i64)?
+                .column_i64("rows_dropped", ctx.rows_dropped.load(Ordering::SeqCst) as i64)?
+                .column_i64("row_errors", ctx.row_errors.load(Ordering::SeqCst) as i64)?;
+            if let Some(profile) = ctx.profile.summary() {
+                buffer.column_f64("cpu_avg_pct", profile.cpu_avg_pct)?
+                    .column_f64("cpu_peak_pct", profile.cpu_peak_pct)?
+                    .column_i64("rss_peak_bytes", profile.rss_peak_bytes as i64)?;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            eprintln!("Cannot record the end of the run in table {}: {}", ctx.opt.runs_table, e);
+        }
+    }
+
+    /// The results of the run for --report-json and --save-run: the parameters, the
+    /// ingestion totals and rates, the statistics of each plane, the generator's own CPU and
+    /// memory use and, with --query-workers, the latencies of each query, so a run can be
+    /// compared with others as a whole.
+    pub fn report(&self, ctx: &RunContext, plane_stats: &[PlaneStats], queries: &[QuerySummary]) -> Value {
+        let opt = &ctx.opt;
+        let buckets = ctx.rate.buckets();
+        let (achieved_rate, target_rate) = ctx.rate.summary().unzip();
+        let (by_rows, by_interval, forced, rows_flushed) = ctx.auto_flush.counts();
+        let profile = ctx.profile.summary();
+        json!({
+            "run_id": self.run_id,
+            "host": self.host,
+            "version": VERSION,
+            "parameters": self.parameters,
+            "table_name": opt.table_name,
+            "run_label": opt.run_label,
+            "plane_count": opt.plane_count,
+            "rate_per_plane": opt.rate_per_plane,
+            "total_rows": opt.total_rows_arg,
+            "duration_secs": opt.duration,
+            "started_at": chrono::DateTime::from_timestamp_nanos(self.started_at.as_i64()).to_rfc3339(),
+            "ended_at": chrono::Utc::now().to_rfc3339(),
+            "ingest": {
+                "rows_generated": ctx.rows_generated(),
+                "rows_flushed": plane_stats.iter().map(|s| s.rows_flushed).sum::<u64>(),
+                "rows_sent": ctx.rows_sent.load(Ordering::SeqCst),
+                "bytes_sent": ctx.bytes_sent.load(Ordering::SeqCst),
+                "rows_rejected": ctx.rows_rejected.load(Ordering::SeqCst),
+                "rows_dropped": ctx.rows_dropped.load(Ordering::SeqCst),
+                "row_errors": ctx.row_errors.load(Ordering::SeqCst),
+                "plane_restarts": ctx.plane_restarts.load(Ordering::SeqCst),
+                "task_restarts": ctx.task_restarts.load(Ordering::SeqCst),
+                "achieved_rate": achieved_rate,
+                "target_rate": target_rate,
+                "flushes": {
+                    "settings": ctx.auto_flush.settings(),
+                    "by_rows": by_rows,
+                    "by_interval": by_interval,
+                    "forced": forced,
+                    "rows_per_flush": rows_flushed as f64 / (by_rows + by_interval + forced).max(1) as f64,
+                    "peak_buffer_bytes": ctx.memory.peak(),
+                },
+                "buckets": buckets.iter().map(|b| json!({
+                    "index": b.index,
+                    "secs": b.secs,
+                    "rows": b.rows,
+                    "rate": b.rate(),
+                    "target_rate": b.target_rate,
+                    "flush_share": b.flush_share,
+                    "bottleneck": b.bottleneck.map(|b| b.as_str()),
+                })).collect::<Vec<_>>(),
+            },
+            "phases": ctx.phases.summaries().iter().map(|p| json!({
+                "phase": p.phase.as_str(),
+                "secs": p.secs,
+                "rows": p.rows,
+                "rate": p.rate(),
+                "rows_sent": p.rows_sent,
+                "bytes_sent": p.bytes_sent,
+                "flushes": p.flushes,
+                "flush_secs": p.flush_secs,
+            })).collect::<Vec<_>>(),
+            "latencies": ctx.latencies.summaries().iter().map(|l| json!({
+                "name": l.name,
+                "batches": l.batches,
+                "p50_ms": l.p50_ms,
+                "p99_ms": l.p99_ms,
+                "p99_9_ms": l.p999_ms,
+                "max_ms": l.max_ms,
+            })).collect::<Vec<_>>(),
+            "generator": {
+                "cores": profile.map(|p| p.cores),
+                "cpu_avg_pct": profile.map(|p| p.cpu_avg_pct),
+                "cpu_peak_pct": profile.map(|p| p.cpu_peak_pct),
+                "rss_avg_bytes": profile.map(|p| p.rss_avg_bytes),
+                "rss_peak_bytes": profile.map(|p| p.rss_peak_bytes),
+                "cpu_bound": profile.map(|p| p.is_cpu_bound()),
+                "samples": ctx.profile.samples().iter().map(|s| json!({
+                    "secs": s.secs,
+                    "cpu_pct": s.cpu_pct,
+                    "rss_bytes": s.rss_bytes,
+                })).collect::<Vec<_>>(),
+            },
+            "planes": plane_stats.iter().map(|s| json!({
+                "plane_id": s.plane_id,
+                "rows_generated": s.rows_generated,
+                "rows_flushed": s.rows_flushed,
+                "flush_failures": s.flush_failures,
+                "row_errors": s.row_errors,
+                "rows_rejected": s.rows_rejected,
+                "rows_dropped": s.rows_dropped,
+                "restarts": s.restarts,
+                "achieved_rate": s.achieved_rate(),
+                "target_rate": s.target_rate,
+            })).collect::<Vec<_>>(),
+            "queries": queries.iter().map(|q| json!({
+                "name": q.name,
+                "runs": q.runs,
+                "errors": q.errors,
+                "p50_ms": q.p50_ms,
+                "p95_ms": q.p95_ms,
+                "p99_ms": q.p99_ms,
+                "max_ms": q.max_ms,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Sends one row on a connection of its own, so it does not wait behind the planes' batches.
+    fn write(&self, opt: &Opt, event: &str, totals: impl FnOnce(&mut Buffer) -> questdb::Result<()>) -> questdb::Result<()> {
+        let mut sender = sink::connect(&opt.connection_string, opt.protocol_version)?;
+        let mut buffer = sender.new_buffer();
+        buffer.table(opt.runs_table.as_str())?
+            .symbol("run_id", &self.run_id)?
+            .symbol("event", event)?
+            .symbol("host", &self.host)?
+            .symbol("table_name", &opt.table_name)?;
+        if let Some(label) = &opt.run_label {
+            buffer.symbol("run_label", label)?;
+        }
+        buffer.column_str("version", VERSION)?
+            .column_str("parameters", &self.parameters)?
+            .column_i64("plane_count", opt.plane_count as i64)?
+            .column_i64("rate_per_plane", opt.rate_per_plane as i64)?
+            .column_ts("started_at", TimestampMicros::from(self.started_at))?;
+        if let Some(total_rows) = opt.total_rows_arg {
+            buffer.column_i64("total_rows", total_rows as i64)?;
+        }
+        if let Some(duration) = opt.duration {
+            buffer.column_f64("duration_secs", duration)?;
+        }
+        totals(&mut buffer)?;
+        buffer.at(TimestampNanos::now())?;
+        sender.flush(&mut buffer)
+    }
+}
+
+/// Writes a report, or any JSON value, to a file.
+pub fn write_json(report: &Value, path: &Path) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut file, report)?;
+    writeln!(file)?;
+    file.flush()
+}
+
+fn hostname() -> String {
+    Command::new("hostname")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Options whose value is a connection string
+const CONNECTION_STRINGS: &[&str] = &["--connection-string", "--mirror-connection-string"];
+
+/// Arguments of this run. The connection strings are reduced to their protocol and address,
+/// since they may carry passwords or tokens.
+fn parameters() -> String {
+    redacted(std::env::args().skip(1))
+}
+
+fn redacted(args: impl Iterator<Item = String>) -> String {
+    let mut redacted = vec![];
+    let mut connection_string = false;
+    for arg in args {
+        if connection_string {
+            redacted.push(redact(&arg));
+            connection_string = false;
+        } else if let Some((flag, conf)) = arg.split_once('=').filter(|(flag, _)| CONNECTION_STRINGS.contains(flag)) {
+            redacted.push(format!("{}={}", flag, redact(conf)));
+        } else {
+            connection_string = CONNECTION_STRINGS.contains(&arg.as_str());
+            redacted.push(arg);
+        }
+    }
+    redacted.join(" ")
+}
+
+fn redact(conf: &str) -> String {
+    match questdb_confstr::parse_conf_str(conf) {
+        Ok(parsed) => format!("{}::addr={};", parsed.service(), parsed.get("addr").unwrap_or_default()),
+        Err(_) => "<invalid>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_strings_are_redacted() {
+        let args = ["--connection-string", "https::addr=db:9000;username=admin;password=quest;",
+                    "--mirror-connection-string=http::addr=new:9000;token=abc;", "--table-name", "flights"];
+        assert_eq!(redacted(args.iter().map(|arg| arg.to_string())),
+                   "--connection-string https::addr=db:9000; --mirror-connection-string=http::addr=new:9000; --table-name flights");
+    }
+}
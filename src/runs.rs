@@ -0,0 +1,118 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use clap::Parser;
+
+use crate::error_summary::thousands;
+use crate::run_log;
+
+const INDEX: &str = "index.json";
+
+/// Key metrics of a run for the index, from its report
+pub fn index_entry(report: &Value, file: &str) -> Value {
+    let phase_rate = |name: &str| report["phases"].as_array()
+        .and_then(|phases| phases.iter().find(|phase| phase["phase"] == name))
+        .map(|phase| phase["rate"].clone())
+        .unwrap_or(Value::Null);
+    let latency = |name: &str| report["latencies"].as_array()
+        .and_then(|latencies| latencies.iter().find(|latency| latency["name"] == name))
+        .map(|latency| latency["p99_ms"].clone())
+        .unwrap_or(Value::Null);
+    let ingest = &report["ingest"];
+    let errors = ingest["rows_rejected"].as_u64().unwrap_or(0) + ingest["row_errors"].as_u64().unwrap_or(0);
+    json!({
+        "run_id": report["run_id"],
+        "file": file,
+        "started_at": report["started_at"],
+        "ended_at": report["ended_at"],
+        "run_label": report["run_label"],
+        "table_name": report["table_name"],
+        "parameters": report["parameters"],
+        "rows_sent": ingest["rows_sent"],
+        "achieved_rate": ingest["achieved_rate"],
+        "target_rate": ingest["target_rate"],
+        "steady_rate": phase_rate("steady"),
+        "flush_p99_ms": latency("flush"),
+        "errors": errors,
+    })
+}
+
+/// The runs in the index of `dir`, oldest first. A missing index is an empty one.
+fn read_index(dir: &Path) -> io::Result<Vec<Value>> {
+    match fs::read_to_string(dir.join(INDEX)) {
+        Ok(text) => serde_json::from_str::<Vec<Value>>(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("invalid {}: {}", dir.join(INDEX).display(), e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e),
+    }
+}
+
+/// Saves the report of a run in `dir` for --save-run, as `<run id>.json`, and adds it to
+/// the index. Only the `keep` most recent runs are kept, older reports are deleted.
+/// Returns the path of the report.
+pub fn save(dir: &Path, keep: usize, report: &Value) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let file = format!("{}.json", report["run_id"].as_str().unwrap_or("run"));
+    let path = dir.join(&file);
+    run_log::write_json(report, &path)?;
+    let mut index = read_index(dir)?;
+    index.push(index_entry(report, &file));
+    let removed = index.len().saturating_sub(keep.max(1));
+    for entry in index.drain(..removed) {
+        if let Some(file) = entry["file"].as_str() {
+            match fs::remove_file(dir.join(file)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+    }
+    // Written aside and renamed, so an interrupted run does not leave a truncated index
+    let partial = dir.join(format!("{}.partial", INDEX));
+    run_log::write_json(&Value::Array(index), &partial)?;
+    fs::rename(partial, dir.join(INDEX))?;
+    Ok(path)
+}
+
+/// Options of `airplanes history`.
+#[derive(Parser, Debug)]
+pub struct HistoryOpt {
+    /// Directory the runs were saved to with --save-run
+    #[arg(long, default_value = "runs")]
+    runs_dir: PathBuf,
+
+    /// How many of the most recent runs to list
+    #[arg(long, default_value = "10")]
+    last: usize,
+
+    /// Also print the command line of each run
+    #[arg(long)]
+    parameters: bool,
+}
+
+/// Lists the most recent saved runs with their key metrics, newest last.
+pub fn run(opt: &HistoryOpt) -> Result<(), String> {
+    let index = read_index(&opt.runs_dir).map_err(|e| format!("cannot read the runs in {}: {}", opt.runs_dir.display(), e))?;
+    if index.is_empty() {
+        println!("No runs saved in {}, run the simulator with --save-run", opt.runs_dir.display());
+        return Ok(());
+    }
+    let number = |value: &Value, decimals: usize| value.as_f64().map(|n| format!("{:.*}", decimals, n)).unwrap_or_else(|| "-".to_string());
+    println!("{:<24} {:<20} {:<16} {:>12} {:>10} {:>10} {:>10} {:>9} {:>8}",
+             "run", "started", "label", "rows sent", "rows/s", "target/s", "steady/s", "flush p99", "errors");
+    for entry in index.iter().skip(index.len().saturating_sub(opt.last)) {
+        let started = entry["started_at"].as_str().and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+            .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        println!("{:<24} {:<20} {:<16} {:>12} {:>10} {:>10} {:>10} {:>9} {:>8}",
+                 entry["run_id"].as_str().unwrap_or("-"), started, entry["run_label"].as_str().unwrap_or("-"),
+                 thousands(entry["rows_sent"].as_u64().unwrap_or(0)), number(&entry["achieved_rate"], 1),
+                 number(&entry["target_rate"], 1), number(&entry["steady_rate"], 1),
+                 entry["flush_p99_ms"].as_f64().map(|ms| format!("{:.1} ms", ms)).unwrap_or_else(|| "-".to_string()),
+                 thousands(entry["errors"].as_u64().unwrap_or(0)));
+        if opt.parameters {
+            println!("    {}", entry["parameters"].as_str().unwrap_or(""));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use rand::Rng;
+
+use crate::error_summary::thousands;
+
+/// A random sample of the rows, --sample-log of them, printed as they are sent so a long
+/// run can be spot-checked without recording every row. Each line of a row is printed
+/// as ILP, with the timestamp turned into a date first.
+pub struct SampleLog {
+    fraction: f64,
+    /// --sample-log-file, else standard output
+    file: Option<Mutex<BufWriter<File>>>,
+    sampled: AtomicU64,
+}
+
+impl SampleLog {
+    pub fn new(fraction: f64, path: Option<&Path>) -> io::Result<Self> {
+        let file = match path {
+            Some(path) => Some(Mutex::new(BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?))),
+            None => None,
+        };
+        Ok(SampleLog { fraction: fraction.min(1.0), file, sampled: AtomicU64::new(0) })
+    }
+
+    /// Whether the next row is in the sample
+    pub fn draw(&self) -> bool {
+        rand::thread_rng().gen_bool(self.fraction)
+    }
+
+    /// Logs the ILP lines of a sampled row
+    pub fn log(&self, lines: &str) {
+        self.sampled.fetch_add(1, Ordering::Relaxed);
+        let text: String = lines.lines().map(|line| {
+            let (row, at) = match line.rsplit_once(' ') {
+                Some((row, nanos)) if !nanos.is_empty() && nanos.bytes().all(|b| b.is_ascii_digit()) => {
+                    let at = nanos.parse::<i64>().map(chrono::DateTime::from_timestamp_nanos)
+                        .map(|at| at.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string())
+                        .unwrap_or_else(|_| nanos.to_string());
+                    (row, at)
+                }
+                _ => (line, "server time".to_string()),
+            };
+            format!("Sample {} {}\n", at, row)
+        }).collect();
+        match &self.file {
+            Some(file) => {
+                let mut file = file.lock().unwrap();
+                if let Err(e) = file.write_all(text.as_bytes()).and_then(|_| file.flush()) {
+                    eprintln!("Cannot write to the sample log: {}", e);
+                }
+            }
+            None => print!("{}", text),
+        }
+    }
+
+    pub fn print_summary(&self) {
+        println!("Logged a sample of {} rows", thousands(self.sampled.load(Ordering::Relaxed)));
+    }
+}
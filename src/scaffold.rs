@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use clap::Parser;
+
+use crate::grafana::{self, DatasourceType, quote_identifier};
+use crate::model;
+
+/// What it takes to demo a model end to end: the columns of its table, example queries
+/// and the panels of a dashboard. `{table}` in the SQL is the quoted table name.
+pub struct Bundle {
+    /// The symbol the devices are told apart by
+    pub key: &'static str,
+    pub symbols: &'static [&'static str],
+    /// Name and QuestDB type of the other columns, in the order they are sent
+    pub columns: &'static [(&'static str, &'static str)],
+    /// What each query shows, and the query
+    pub queries: &'static [(&'static str, &'static str)],
+    /// Title and query of the time series panels, filtered on the time range of the dashboard
+    pub panels: &'static [(&'static str, &'static str)],
+    /// Title of a map of the latest position of each device, and its latitude and longitude
+    /// columns, for the models that move
+    pub map: Option<(&'static str, &'static str, &'static str)>,
+}
+
+/// The wide schema of the planes, without the optional columns
+const PLANES: Bundle = Bundle {
+    key: "plane_id",
+    symbols: &["plane_id", "icao24", "callsign", "phase"],
+    columns: &[
+        ("airspeed", "DOUBLE"), ("altitude", "DOUBLE"), ("pitch", "DOUBLE"), ("roll", "DOUBLE"), ("yaw", "DOUBLE"),
+        ("aoa", "DOUBLE"), ("oat", "DOUBLE"), ("fuel_kg", "DOUBLE"), ("gross_weight_kg", "DOUBLE"), ("latitude", "DOUBLE"),
+        ("longitude", "DOUBLE"), ("ground_speed", "DOUBLE"), ("track", "DOUBLE"), ("vertical_speed", "DOUBLE"),
+    ],
+    queries: &[
+        ("Latest position of every plane",
+         "SELECT plane_id, callsign, latitude, longitude, altitude, timestamp\nFROM {table}\nLATEST ON timestamp PARTITION BY plane_id;"),
+        ("Average airspeed and altitude of the whole fleet, per minute",
+         "SELECT timestamp, avg(airspeed) airspeed, avg(altitude) altitude, count() readings\nFROM {table}\nSAMPLE BY 1m;"),
+        ("Rows per flight phase",
+         "SELECT phase, count() readings, avg(altitude) altitude\nFROM {table}\nORDER BY readings DESC;"),
+    ],
+    panels: &[
+        ("Airspeed", "SELECT timestamp, avg(airspeed) avg_airspeed, max(airspeed) max_airspeed FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+        ("Altitude", "SELECT timestamp, avg(altitude) avg_altitude, max(altitude) max_altitude FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+    ],
+    map: Some(("Planes", "latitude", "longitude")),
+};
+
+/// The bundle of a --model, planes included
+pub fn bundle(model: &str) -> Option<&'static Bundle> {
+    match model {
+        model::PLANES => Some(&PLANES),
+        _ => model::find(model).map(|kind| kind.bundle),
+    }
+}
+
+/// Options of `airplanes scaffold`.
+#[derive(Parser, Debug)]
+pub struct ScaffoldOpt {
+    /// The --model to demo
+    #[arg(long)]
+    model: String,
+
+    /// Table the simulator writes to, by default the table of the model
+    #[arg(long)]
+    table_name: Option<String>,
+
+    /// UID of the Grafana data source pointing to QuestDB
+    #[arg(long, default_value = "questdb")]
+    datasource_uid: String,
+
+    /// `questdb` for the QuestDB plugin, `postgres` for the PostgreSQL data source
+    #[arg(long, default_value = "questdb")]
+    datasource_type: DatasourceType,
+
+    /// Directory to write the files to, by default `<model>-demo`
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+}
+
+/// Writes the bundle of a model to a directory: `schema.sql` creating its table,
+/// `queries.sql` with example queries, as --queries-file takes them, and `dashboard.json`
+/// for Grafana.
+pub fn run(opt: &ScaffoldOpt) -> Result<(), String> {
+    let bundle = bundle(&opt.model)
+        .ok_or_else(|| format!("unknown --model '{}', expected one of: {}", opt.model, model::describe()))?;
+    let table_name = match (&opt.table_name, model::find(&opt.model)) {
+        (Some(name), _) => name.clone(),
+        (None, Some(kind)) => kind.table.to_string(),
+        (None, None) => return Err("--table-name is needed for the planes, only the other --model choices have a table of their own".to_string()),
+    };
+    let dir = opt.output_dir.clone().unwrap_or_else(|| PathBuf::from(format!("{}-demo", opt.model)));
+    std::fs::create_dir_all(&dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    let dashboard = serde_json::to_string_pretty(&dashboard(bundle, opt, &table_name)).map_err(|e| e.to_string())?;
+    write(&dir.join("schema.sql"), &create_table(bundle, &table_name))?;
+    write(&dir.join("queries.sql"), &queries(bundle, &opt.model, &table_name))?;
+    write(&dir.join("dashboard.json"), &(dashboard + "\n"))?;
+    eprintln!("Create the table with schema.sql, send rows with `airplanes --model {} --table-name {} ...`, \
+               then run queries.sql or import dashboard.json into Grafana.", opt.model, table_name);
+    Ok(())
+}
+
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    std::fs::write(path, text).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    eprintln!("Written {}", path.display());
+    Ok(())
+}
+
+/// The table of a bundle, partitioned by day and with WAL, as ILP would create it with the
+/// symbols indexed on the device key.
+fn create_table(bundle: &Bundle, table_name: &str) -> String {
+    let mut columns: Vec<String> = bundle.symbols.iter().map(|symbol| match *symbol == bundle.key {
+        true => format!("  {} SYMBOL CAPACITY 16384 INDEX", quote_identifier(symbol)),
+        false => format!("  {} SYMBOL", quote_identifier(symbol)),
+    }).collect();
+    columns.extend(bundle.columns.iter().map(|(name, kind)| format!("  {} {}", quote_identifier(name), kind)));
+    columns.push("  timestamp TIMESTAMP".to_string());
+    format!("CREATE TABLE IF NOT EXISTS {} (\n{}\n) TIMESTAMP(timestamp) PARTITION BY DAY WAL;\n", quote_identifier(table_name), columns.join(",\n"))
+}
+
+/// The example queries, in the format of --emit-queries
+fn queries(bundle: &Bundle, model: &str, table_name: &str) -> String {
+    let table = quote_identifier(table_name);
+    let mut text = format!("-- Example queries for table {}, written by airplanes scaffold --model {}\n", table_name, model);
+    let ingestion = ("Rows per minute", "SELECT timestamp, count() readings\nFROM {table}\nSAMPLE BY 1m;");
+    for (comment, sql) in bundle.queries.iter().chain(std::iter::once(&ingestion)) {
+        text.push_str(&format!("\n-- {}\n{}\n", comment, sql.replace("{table}", &table)));
+    }
+    text
+}
+
+/// The ingestion rate above the panels of the bundle, two to a row, then the map
+fn dashboard(bundle: &Bundle, opt: &ScaffoldOpt, table_name: &str) -> Value {
+    let table = quote_identifier(table_name);
+    let datasource = json!({ "type": opt.datasource_type.plugin_id(), "uid": opt.datasource_uid });
+    let total_rate = "SELECT timestamp, count() * 1000.0 / $__interval_ms rows_per_sec FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval";
+    let mut panels: Vec<Value> = std::iter::once(&("Ingestion rate (rows/s)", total_rate)).chain(bundle.panels.iter())
+        .enumerate()
+        .map(|(n, (title, sql))| {
+            let pos = [n as u32 % 2 * 12, n as u32 / 2 * 8, 12, 8];
+            grafana::time_series_panel(n as u32 + 1, title, &datasource, opt.datasource_type, &sql.replace("{table}", &table), pos)
+        })
+        .collect();
+    if let Some((title, latitude, longitude)) = bundle.map {
+        let sql = format!("SELECT {key}, {lat} latitude, {lon} longitude FROM {t} WHERE $__timeFilter(timestamp) LATEST ON timestamp PARTITION BY {key}",
+                          key = quote_identifier(bundle.key), lat = quote_identifier(latitude), lon = quote_identifier(longitude), t = table);
+        let y = (panels.len() as u32).div_ceil(2) * 8;
+        panels.push(grafana::map_panel(panels.len() as u32 + 1, title, &datasource, opt.datasource_type, &sql, [0, y, 24, 14]));
+    }
+    json!({
+        "title": format!("Airplanes simulator: {}", opt.model),
+        "uid": format!("airplanes-{}", table_name.to_lowercase().replace(|c: char| !c.is_ascii_alphanumeric(), "-")),
+        "tags": ["airplanes", "questdb", opt.model],
+        "timezone": "browser",
+        "schemaVersion": 39,
+        "refresh": "5s",
+        "time": { "from": "now-15m", "to": "now" },
+        "panels": panels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use questdb::ingress::{Buffer, ProtocolVersion};
+    use super::*;
+
+    /// The bundle of each model has the symbols and columns its devices send, in order and
+    /// of the types ILP would give them, so the table it creates takes their rows as they are
+    #[test]
+    fn bundles_match_the_rows_of_their_model() {
+        let opt = crate::Opt::parse_from(["airplanes", "--connection-string", "http::addr=localhost:9000;", "--total-rows", "1", "--rate-per-plane", "1", "--plane-count", "1"]);
+        for kind in model::MODELS {
+            let model = (kind.open)(&opt);
+            let mut buffer = Buffer::new(ProtocolVersion::V1);
+            model.device(&model.device_id(0), 0, 1_700_000_000_000_000_000).write(&mut buffer, kind.table).unwrap();
+            let line = std::str::from_utf8(buffer.as_bytes()).unwrap();
+            let mut sections = line.split(' ');
+            let symbols: Vec<&str> = sections.next().unwrap().split(',').skip(1).map(|pair| pair.split('=').next().unwrap()).collect();
+            // The type ILP creates a column with, from how its value is written
+            let columns: Vec<(&str, &str)> = sections.next().unwrap().split(',').map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap();
+                (name, match value {
+                    "t" | "f" => "BOOLEAN",
+                    _ if value.ends_with('i') => "LONG",
+                    _ => "DOUBLE",
+                })
+            }).collect();
+            assert_eq!(symbols, kind.bundle.symbols, "{}", kind.name);
+            assert!(symbols.contains(&kind.bundle.key), "{}", kind.name);
+            assert_eq!(columns, kind.bundle.columns, "{}", kind.name);
+            for query in kind.bundle.queries.iter().chain(kind.bundle.panels) {
+                assert!(query.1.contains("{table}"), "{}: {}", kind.name, query.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use chrono::{Datelike, Timelike};
+use tokio::time::{Duration, sleep};
+
+use crate::config::{Config, RateWindowConfig};
+use crate::error_summary::thousands;
+use crate::{RunContext, units};
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// A window of the week with a total rate of its own.
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    /// Bit n for the n-th day from Monday
+    days: u8,
+    /// Minutes of the day, from `start` to before `end`. A window ending before it starts
+    /// runs over midnight, on the days it starts on and the mornings after them
+    start: u32,
+    end: u32,
+    total_rate: u64,
+}
+
+impl Window {
+    fn parse(window: &RateWindowConfig) -> Result<Self, String> {
+        let days = match &window.days {
+            Some(days) => parse_days(days)?,
+            None => 0x7f,
+        };
+        let (start, end) = match &window.hours {
+            Some(hours) => {
+                let (start, end) = hours.split_once('-').ok_or_else(|| format!("invalid hours '{}', expected e.g. 9-17", hours))?;
+                (parse_time(start)?, parse_time(end)?)
+            }
+            None => (0, 24 * 60),
+        };
+        if start == end {
+            return Err(format!("the hours '{}' of a window are empty", window.hours.as_deref().unwrap_or_default()));
+        }
+        Ok(Window { days, start, end, total_rate: units::count(&window.total_rate)? })
+    }
+
+    /// Whether a time is in the window, given its day from Monday and minute
+    fn contains(&self, day: u32, minute: u32) -> bool {
+        let yesterday = (day + 6) % 7;
+        match self.start < self.end {
+            true => self.days & (1 << day) != 0 && (self.start..self.end).contains(&minute),
+            false => (self.days & (1 << day) != 0 && minute >= self.start) || (self.days & (1 << yesterday) != 0 && minute < self.end),
+        }
+    }
+}
+
+/// Days like "mon-fri,sun", "weekdays" or "weekends", as a bit per day from Monday
+fn parse_days(days: &str) -> Result<u8, String> {
+    let day = |name: &str| DAYS.iter().position(|day| name.trim().eq_ignore_ascii_case(day))
+        .ok_or_else(|| format!("invalid day '{}' in '{}', expected one of: {}, weekdays or weekends", name.trim(), days, DAYS.join(", ")));
+    let mut mask = 0;
+    for part in days.split(',') {
+        mask |= match part.trim() {
+            "weekdays" => 0x1f,
+            "weekends" => 0x60,
+            range => match range.split_once('-') {
+                // Ranges go round the week, fri-mon is four days
+                Some((from, to)) => {
+                    let (from, to) = (day(from)?, day(to)?);
+                    (0..=(to + 7 - from) % 7).fold(0, |mask, n| mask | 1 << ((from + n) % 7))
+                }
+                None => 1 << day(range)?,
+            },
+        };
+    }
+    Ok(mask)
+}
+
+/// "9", "09:30" or "24", in minutes of the day
+fn parse_time(time: &str) -> Result<u32, String> {
+    let time = time.trim();
+    let (hours, minutes) = time.split_once(':').unwrap_or((time, "0"));
+    match (hours.parse::<u32>(), minutes.parse::<u32>()) {
+        (Ok(hours), Ok(minutes)) if minutes < 60 && hours * 60 + minutes <= 24 * 60 => Ok(hours * 60 + minutes),
+        _ => Err(format!("invalid time '{}', expected hours from 0 to 24, e.g. 9 or 17:30", time)),
+    }
+}
+
+/// The windows and rates of a schedule, replaced when --watch-config reloads it
+#[derive(Debug)]
+struct Rates {
+    windows: Vec<Window>,
+    otherwise: u64,
+    utc_offset_nanos: i64,
+}
+
+/// The total rate of a --rate-schedule, changing with the time of the rows so a demo that
+/// runs for weeks gets busy hours and quiet nights and weekends. The planes read the rate
+/// the follower last set, as they do for the keys of the terminal UI.
+#[derive(Debug)]
+pub struct RateSchedule {
+    name: String,
+    /// The total rate of the command line, for a schedule without an `otherwise` rate
+    fallback: Option<u64>,
+    rates: RwLock<Rates>,
+    /// Rows per second of each plane now, set by `follow`
+    rate_per_plane: AtomicU64,
+}
+
+impl RateSchedule {
+    /// The schedule called `name` in the config. `fallback` is the total rate of the
+    /// command line, for a schedule without an `otherwise` rate.
+    pub fn new(config: &Config, name: &str, fallback: Option<u64>) -> Result<Self, String> {
+        let rates = RateSchedule::build(config, name, fallback).map_err(|e| config.redact(e))?;
+        Ok(RateSchedule { name: name.to_string(), fallback, rates: RwLock::new(rates), rate_per_plane: AtomicU64::new(0) })
+    }
+
+    fn build(config: &Config, name: &str, fallback: Option<u64>) -> Result<Rates, String> {
+        let schedule = config.rate_schedules.get(name).ok_or_else(|| {
+            let mut names: Vec<&str> = config.rate_schedules.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            format!("no rate schedule '{}' in the config file, it has: {}", name, if names.is_empty() { "none".to_string() } else { names.join(", ") })
+        })?;
+        let windows = schedule.windows.iter().enumerate()
+            .map(|(n, window)| Window::parse(window).map_err(|e| format!("window {} of rate schedule '{}': {}", n + 1, name, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let otherwise = match (&schedule.otherwise, fallback) {
+            (Some(otherwise), _) => units::count(otherwise).map_err(|e| format!("rate schedule '{}': {}", name, e))?,
+            (None, Some(fallback)) => fallback,
+            (None, None) => return Err(format!("rate schedule '{}' needs an otherwise rate, or --total-rate or --rate-per-plane for the times outside its windows", name)),
+        };
+        if let Some(offset) = schedule.utc_offset.filter(|offset| !(-14.0..=14.0).contains(offset)) {
+            return Err(format!("utc_offset of rate schedule '{}' must be between -14 and 14 hours, got {}", name, offset));
+        }
+        Ok(Rates { windows, otherwise, utc_offset_nanos: (schedule.utc_offset.unwrap_or(0.0) * 3.6e12) as i64 })
+    }
+
+    /// The schedule of the same name in a reloaded config, checked as the one of the start
+    /// is, so it gives every one of the `planes` a row a second
+    pub fn reloaded(&self, config: &Config, planes: u64) -> Result<RateSchedule, String> {
+        let schedule = RateSchedule::new(config, &self.name, self.fallback)?;
+        match schedule.too_low(planes) {
+            Some(rate) => Err(format!("rate schedule '{}' has a rate of {} rows/s, less than 1 row/s for each of the {} planes", self.name, rate, planes)),
+            None => Ok(schedule),
+        }
+    }
+
+    /// Follows the windows and rates of a reloaded schedule from the next check on
+    pub fn replace(&self, reloaded: RateSchedule) {
+        *self.rates.write().unwrap() = reloaded.rates.into_inner().unwrap();
+    }
+
+    /// Rows per second across all the planes at `timestamp`
+    pub fn total_rate(&self, timestamp: i64) -> u64 {
+        let rates = self.rates.read().unwrap();
+        let local = chrono::DateTime::from_timestamp_nanos(timestamp + rates.utc_offset_nanos);
+        let (day, minute) = (local.weekday().num_days_from_monday(), local.hour() * 60 + local.minute());
+        rates.windows.iter().find(|window| window.contains(day, minute)).map(|window| window.total_rate).unwrap_or(rates.otherwise)
+    }
+
+    /// The rates of the schedule that are too low to give every plane a row a second
+    pub fn too_low(&self, planes: u64) -> Option<u64> {
+        let rates = self.rates.read().unwrap();
+        rates.windows.iter().map(|window| window.total_rate).chain(std::iter::once(rates.otherwise)).find(|rate| *rate < planes)
+    }
+
+    pub fn rate_per_plane(&self) -> u64 {
+        self.rate_per_plane.load(Ordering::Relaxed)
+    }
+
+    pub fn set_rate_per_plane(&self, rate: u64) {
+        self.rate_per_plane.store(rate, Ordering::Relaxed);
+    }
+}
+
+/// Moves the rate of the planes along the --rate-schedule, checking it every second, and
+/// prints each change.
+pub async fn follow(ctx: Arc<RunContext>) {
+    let schedule = ctx.schedule.as_ref().expect("following a rate schedule without one");
+    let mut total = schedule.total_rate(ctx.now());
+    loop {
+        sleep(Duration::from_secs(1)).await;
+        let now = schedule.total_rate(ctx.now());
+        if now == total {
+            continue;
+        }
+        total = now;
+        let rate = ctx.opt.per_plane_rate(total);
+        schedule.set_rate_per_plane(rate);
+        ctx.rate.set_rate_per_plane(1000.0 / crate::tick_millis(rate) as f64);
+        eprintln!("Rate schedule '{}': {} rows/s from now, {} rows/s per plane", schedule.name, thousands(total), rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::monday_at;
+
+    fn schedule(text: &str, fallback: Option<u64>) -> Result<RateSchedule, String> {
+        RateSchedule::new(&toml::from_str(text).unwrap(), "demo", fallback)
+    }
+
+    #[test]
+    fn busy_in_business_hours() {
+        let schedule = schedule(r#"
+            [rate_schedules.demo]
+            otherwise = "10k"
+            windows = [
+                { days = "mon-fri", hours = "9-17", total_rate = "100k" },
+                { days = "fri", hours = "22-02:30", total_rate = "50k" },
+                { days = "weekends", total_rate = "1k" },
+            ]
+        "#, None).unwrap();
+        assert_eq!(schedule.total_rate(monday_at(9.0)), 100_000);
+        assert_eq!(schedule.total_rate(monday_at(16.99)), 100_000);
+        assert_eq!(schedule.total_rate(monday_at(17.0)), 10_000);
+        assert_eq!(schedule.total_rate(monday_at(8.99)), 10_000);
+        // Friday night, then Saturday morning, when the first matching window wins
+        assert_eq!(schedule.total_rate(monday_at(4.0 * 24.0 + 23.0)), 50_000);
+        assert_eq!(schedule.total_rate(monday_at(5.0 * 24.0 + 2.0)), 50_000);
+        assert_eq!(schedule.total_rate(monday_at(5.0 * 24.0 + 3.0)), 1_000);
+        assert_eq!(schedule.too_low(5_000), Some(1_000));
+    }
+
+    #[test]
+    fn days_hours_and_offsets() {
+        assert_eq!(parse_days("mon-fri").unwrap(), 0x1f);
+        assert_eq!(parse_days("sat, sun").unwrap(), parse_days("weekends").unwrap());
+        assert_eq!(parse_days("fri-mon").unwrap(), 0b111_0001);
+        assert!(parse_days("mon-friday").is_err());
+        assert_eq!(parse_time("08:30").unwrap(), 510);
+        assert!(parse_time("25").is_err() && parse_time("9:60").is_err());
+        // 9 to 17 in New York is 13 to 21 UTC in summer time
+        let new_york = schedule("[rate_schedules.demo]\nutc_offset = -4.0\nwindows = [{ hours = \"9-17\", total_rate = \"2k\" }]\n", Some(500)).unwrap();
+        assert_eq!(new_york.total_rate(monday_at(13.5)), 2_000);
+        assert_eq!(new_york.total_rate(monday_at(10.0)), 500);
+        assert!(RateSchedule::new(&Config::default(), "demo", Some(1)).unwrap_err().contains("none"));
+        assert!(schedule("[rate_schedules.demo]\nwindows = [{ hours = \"9-17\", total_rate = \"2k\" }]\n", None).is_err());
+    }
+
+    #[test]
+    fn a_reloaded_schedule_replaces_the_rates() {
+        let running = schedule("[rate_schedules.demo]\notherwise = \"1k\"\n", None).unwrap();
+        let config = toml::from_str("[rate_schedules.demo]\notherwise = \"1k\"\nwindows = [{ hours = \"9-17\", total_rate = \"5k\" }]\n").unwrap();
+        running.replace(running.reloaded(&config, 10).unwrap());
+        assert_eq!(running.total_rate(monday_at(10.0)), 5_000);
+        assert_eq!(running.total_rate(monday_at(20.0)), 1_000);
+        let too_low = toml::from_str("[rate_schedules.demo]\notherwise = \"5\"\n").unwrap();
+        assert!(running.reloaded(&too_low, 10).unwrap_err().contains("less than 1 row/s"));
+        assert!(running.reloaded(&Config::default(), 10).unwrap_err().contains("no rate schedule 'demo'"));
+    }
+}
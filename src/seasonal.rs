@@ -0,0 +1,67 @@
+use std::f64::consts::PI;
+use chrono::{Datelike, Weekday};
+
+use crate::formation::mix;
+
+const HOUR_NANOS: f64 = 3.6e12;
+/// Half the difference between the warmest and coldest days of the year at the poles
+const ANNUAL_AMPLITUDE: f64 = 12.0;
+
+/// Daily, weekly and yearly cycles for --seasonality, from the timestamps of the rows so a
+/// backfill over months gets them: the outside air temperature peaks in the afternoon and
+/// in summer, and fewer planes report at night and at weekends. Local time is solar time
+/// at the plane's longitude.
+#[derive(Debug, Clone)]
+pub struct Seasonality {
+    /// Difference between the warmest and coldest hours of a day, in °C
+    day_swing: f64,
+    /// Share of the planes flying at night and at weekends, from 0 to 1
+    night_traffic: f64,
+    weekend_traffic: f64,
+}
+
+/// Local solar time, in nanoseconds since the epoch
+fn local(timestamp: i64, longitude: f64) -> i64 {
+    timestamp + (longitude / 15.0 * HOUR_NANOS) as i64
+}
+
+fn hour_of_day(local: i64) -> f64 {
+    (local as f64 / HOUR_NANOS).rem_euclid(24.0)
+}
+
+impl Seasonality {
+    pub fn new(day_swing: f64, night_traffic_pct: f64, weekend_traffic_pct: f64) -> Self {
+        Seasonality { day_swing, night_traffic: night_traffic_pct / 100.0, weekend_traffic: weekend_traffic_pct / 100.0 }
+    }
+
+    /// Added to the outside air temperature, in °C: warmest at 15:00 and coldest at 03:00,
+    /// warmest in late July up north and in late January down south, more so far from the
+    /// equator.
+    pub fn temperature(&self, timestamp: i64, latitude: f64, longitude: f64) -> f64 {
+        let local = local(timestamp, longitude);
+        let daily = self.day_swing / 2.0 * (2.0 * PI * (hour_of_day(local) - 15.0) / 24.0).cos();
+        let day_of_year = chrono::DateTime::from_timestamp_nanos(local).ordinal0() as f64;
+        let annual = ANNUAL_AMPLITUDE * (latitude / 90.0).clamp(-1.0, 1.0) * (2.0 * PI * (day_of_year - 200.0) / 365.25).cos();
+        daily + annual
+    }
+
+    /// Share of the planes flying: all of them at 14:00 on weekdays, the night share at
+    /// 02:00, and the weekend share of that on Saturdays and Sundays.
+    pub fn traffic(&self, timestamp: i64, longitude: f64) -> f64 {
+        let local = local(timestamp, longitude);
+        let daily = self.night_traffic + (1.0 - self.night_traffic) * (0.5 - 0.5 * (2.0 * PI * (hour_of_day(local) - 2.0) / 24.0).cos());
+        let weekly = match chrono::DateTime::from_timestamp_nanos(local).weekday() {
+            Weekday::Sat | Weekday::Sun => self.weekend_traffic,
+            _ => 1.0,
+        };
+        daily * weekly
+    }
+
+    /// Whether the plane is flying, and sends rows, at `timestamp`. The same planes fly for
+    /// a whole hour.
+    pub fn flies(&self, plane_id: &str, timestamp: i64, longitude: f64) -> bool {
+        let hour = (local(timestamp, longitude) as f64 / HOUR_NANOS).floor() as i64;
+        let hash = plane_id.bytes().fold(mix(hour as u64), |hash, byte| mix(hash ^ byte as u64));
+        ((hash >> 11) as f64 / (1u64 << 53) as f64) < self.traffic(timestamp, longitude)
+    }
+}
@@ -0,0 +1,152 @@
+use std::path::Path;
+
+/// Replaces each `${NAME}` in `text` with the environment variable NAME, so credentials can
+/// stay out of the command line and the config file. A `$` not followed by a `{` and a
+/// name is left as it is.
+pub fn expand(text: &str) -> Result<String, String> {
+    expand_into(text, &mut vec![])
+}
+
+/// `expand`, adding each variable it replaced and its value to `replaced`
+fn expand_into(text: &str, replaced: &mut Vec<(String, String)>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let name = after.find('}').map(|end| &after[..end])
+            .filter(|name| name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        match name {
+            Some(name) => {
+                let value = std::env::var(name).map_err(|e| match e {
+                    std::env::VarError::NotPresent => format!("environment variable {} is not set", name),
+                    std::env::VarError::NotUnicode(_) => format!("environment variable {} is not valid UTF-8", name),
+                })?;
+                expanded.push_str(&value);
+                replaced.push((name.to_string(), value));
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                expanded.push_str("${");
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Expands the `${NAME}` of every string of a TOML document, after it is parsed so a value
+/// with quotes or line breaks cannot change the document around it and comments are left
+/// alone. Returns the variables it replaced with their values, for `redact`.
+pub fn expand_toml(value: &mut toml::Value) -> Result<Vec<(String, String)>, String> {
+    fn walk(value: &mut toml::Value, replaced: &mut Vec<(String, String)>) -> Result<(), String> {
+        match value {
+            toml::Value::String(text) => *text = expand_into(text, replaced)?,
+            toml::Value::Array(values) => values.iter_mut().try_for_each(|value| walk(value, replaced))?,
+            toml::Value::Table(table) => table.iter_mut().try_for_each(|(_, value)| walk(value, replaced))?,
+            _ => {}
+        }
+        Ok(())
+    }
+    let mut replaced = vec![];
+    walk(value, &mut replaced)?;
+    Ok(replaced)
+}
+
+/// A message with the values of the variables given back as their `${NAME}`, so an error
+/// about an expanded value does not show it
+pub fn redact(message: &str, replaced: &[(String, String)]) -> String {
+    let mut replaced: Vec<&(String, String)> = replaced.iter().filter(|(_, value)| !value.is_empty()).collect();
+    // A value that is part of a longer one would leave the rest of it behind
+    replaced.sort_by_key(|(_, value)| std::cmp::Reverse(value.len()));
+    replaced.iter().fold(message.to_string(), |message, (name, value)| message.replace(value.as_str(), &format!("${{{}}}", name)))
+}
+
+/// The secret in a file, without the line break editors leave at its end
+pub fn read(path: &Path) -> Result<String, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let secret = text.trim_end_matches(['\n', '\r']);
+    if secret.is_empty() {
+        return Err(format!("{} is empty", path.display()));
+    }
+    if shared(path) {
+        eprintln!("Warning: {} can be read by other users", path.display());
+    }
+    Ok(secret.to_string())
+}
+
+#[cfg(unix)]
+fn shared(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).map(|metadata| metadata.permissions().mode() & 0o077 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn shared(_path: &Path) -> bool {
+    false
+}
+
+/// The connection string with its `${NAME}` expanded, and the token and password of the
+/// files added as its `token` and `password` keys.
+pub fn connection_string(conf: &str, token_file: Option<&Path>, password_file: Option<&Path>) -> Result<String, String> {
+    let mut conf = expand(conf).map_err(|e| format!("--connection-string: {}", e))?;
+    let given = |conf: &str, key: &str| questdb_confstr::parse_conf_str(conf).map(|parsed| parsed.get(key).is_some()).unwrap_or(false);
+    for (key, flag, path) in [("token", "--token-file", token_file), ("password", "--password-file", password_file)] {
+        let path = match path {
+            Some(path) => path,
+            None => continue,
+        };
+        if given(&conf, key) {
+            return Err(format!("the connection string already has a {}, leave it out to use {}", key, flag));
+        }
+        if !conf.ends_with(';') {
+            conf.push(';');
+        }
+        // A semicolon in a value of the connection string is written twice
+        let secret = read(path).map_err(|e| format!("{}: {}", flag, e))?;
+        conf.push_str(&format!("{}={};", key, secret.replace(';', ";;")));
+    }
+    Ok(conf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_the_environment() {
+        std::env::set_var("AIRPLANES_TEST_USER", "admin");
+        std::env::set_var("AIRPLANES_TEST_PASSWORD", "quest");
+        assert_eq!(expand("http::addr=db:9000;username=${AIRPLANES_TEST_USER};password=${AIRPLANES_TEST_PASSWORD};").unwrap(),
+                   "http::addr=db:9000;username=admin;password=quest;");
+        assert_eq!(expand("price $5, ${not a name} and ${").unwrap(), "price $5, ${not a name} and ${");
+        assert!(expand("token=${AIRPLANES_TEST_UNSET}").unwrap_err().contains("AIRPLANES_TEST_UNSET is not set"));
+    }
+
+    #[test]
+    fn expands_the_strings_of_toml() {
+        std::env::set_var("AIRPLANES_TEST_QUOTED", "a\"b\nc = 1");
+        let mut value: toml::Value = toml::from_str("# ${AIRPLANES_TEST_UNSET} in a comment\n[columns.altitude]\nunit = \"${AIRPLANES_TEST_QUOTED}\"\n").unwrap();
+        let replaced = expand_toml(&mut value).unwrap();
+        assert_eq!(value["columns"]["altitude"]["unit"].as_str(), Some("a\"b\nc = 1"));
+        assert_eq!(value["columns"]["altitude"].as_table().unwrap().len(), 1);
+        assert_eq!(redact("unknown unit `a\"b\nc = 1`", &replaced), "unknown unit `${AIRPLANES_TEST_QUOTED}`");
+    }
+
+    #[test]
+    fn adds_the_secrets_of_files() {
+        let dir = std::env::temp_dir().join(format!("airplanes-secrets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (token, password) = (dir.join("token"), dir.join("password"));
+        std::fs::write(&token, "abc123\n").unwrap();
+        std::fs::write(&password, "semi;colon\r\n").unwrap();
+        assert_eq!(connection_string("https::addr=db:9000;username=admin", Some(&token), Some(&password)).unwrap(),
+                   "https::addr=db:9000;username=admin;token=abc123;password=semi;;colon;");
+        assert!(connection_string("http::addr=db:9000;token=xyz;", Some(&token), None).unwrap_err().contains("already has a token"));
+        std::fs::write(&token, "\n").unwrap();
+        assert!(connection_string("http::addr=db:9000;", Some(&token), None).unwrap_err().starts_with("--token-file:"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+use std::io;
+use std::path::Path;
+use serde_json::{json, Value};
+
+use crate::formation::mix;
+use crate::plane::PlaneData;
+use crate::run_log;
+
+/// Seed of the random number generator of one plane, derived from the --seed of the run
+/// and the plane id only, so a plane gets the same seed whatever the other planes are.
+pub fn plane_seed(seed: u64, plane_id: &str) -> u64 {
+    plane_id.bytes().fold(mix(seed), |hash, byte| mix(hash ^ byte as u64))
+}
+
+/// Seed of a plane in slice `slice` of a --backfill-workers backfill. The first slice keeps
+/// the plane's seed, the others get seeds of their own so they do not replay its flights.
+pub fn slice_seed(plane_seed: u64, slice: usize) -> u64 {
+    match slice {
+        0 => plane_seed,
+        slice => mix(plane_seed ^ slice as u64),
+    }
+}
+
+/// Writes the seed of the run, and the seed and starting state of every plane, to the
+/// --seeds-file, to regenerate the stream of one plane with --replay-plane.
+pub fn export(path: &Path, seed: u64, planes: &[(u64, PlaneData)]) -> io::Result<()> {
+    let planes: Vec<Value> = planes.iter().map(|(plane_seed, plane)| json!({
+        "plane_id": plane.plane_id,
+        "icao24": plane.icao24,
+        "seed": plane_seed,
+        "timestamp": plane.timestamp,
+        "callsign": plane.callsign,
+        "origin": plane.origin,
+        "destination": plane.destination,
+        "arrival": plane.arrival(),
+        "phase": plane.phase.as_str(),
+        "latitude": plane.latitude,
+        "longitude": plane.longitude,
+        "altitude": plane.altitude,
+        "airspeed": plane.airspeed,
+        "fuel_kg": plane.fuel_kg,
+        "gross_weight_kg": plane.gross_weight_kg,
+    })).collect();
+    run_log::write_json(&json!({ "seed": seed, "planes": planes }), path)
+}
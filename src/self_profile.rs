@@ -0,0 +1,141 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::RunContext;
+use crate::estimate::size;
+
+/// The CPU time and resident memory of this process, as the system reports them
+struct Usage {
+    system: System,
+    pid: Pid,
+}
+
+impl Usage {
+    /// None where the platform does not report the use of a process
+    fn new() -> Option<Self> {
+        if !sysinfo::IS_SUPPORTED_SYSTEM {
+            return None;
+        }
+        Some(Usage { system: System::new(), pid: sysinfo::get_current_pid().ok()? })
+    }
+
+    /// CPU time the process used so far, in seconds, and its resident memory, in bytes
+    fn read(&mut self) -> Option<(f64, u64)> {
+        self.system.refresh_processes_specifics(ProcessesToUpdate::Some(&[self.pid]), false,
+                                                ProcessRefreshKind::nothing().with_cpu().with_memory());
+        let process = self.system.process(self.pid)?;
+        Some((process.accumulated_cpu_time() as f64 / 1000.0, process.memory()))
+    }
+}
+
+/// The generator's own use of the machine over one second.
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// Seconds since the start of the run
+    pub secs: f64,
+    /// CPU used in the second, 100 for a whole core
+    pub cpu_pct: f64,
+    pub rss_bytes: u64,
+}
+
+/// Averages and peaks of the samples of a run.
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileSummary {
+    pub cores: usize,
+    pub cpu_avg_pct: f64,
+    pub cpu_peak_pct: f64,
+    pub rss_avg_bytes: u64,
+    pub rss_peak_bytes: u64,
+}
+
+impl ProfileSummary {
+    /// Whether the generator kept most of the cores busy on average
+    pub fn is_cpu_bound(&self) -> bool {
+        self.cpu_avg_pct >= 90.0 * self.cores as f64
+    }
+}
+
+/// The CPU usage and resident memory of the generator, sampled every second, so a run can
+/// tell whether the client machine or the server held it back. On a platform that does not
+/// report them there are no samples.
+#[derive(Debug)]
+pub struct SelfProfile {
+    started: Instant,
+    stop: AtomicBool,
+    samples: Mutex<Vec<Sample>>,
+}
+
+impl SelfProfile {
+    pub fn new() -> Self {
+        SelfProfile { started: Instant::now(), stop: AtomicBool::new(false), samples: Mutex::new(vec![]) }
+    }
+
+    /// Samples every second until stopped, on a thread of its own so a busy runtime does not
+    /// delay the samples
+    pub fn start(ctx: Arc<RunContext>) -> Option<JoinHandle<()>> {
+        let mut usage = Usage::new()?;
+        let (mut cpu, _) = usage.read()?;
+        let mut at = Instant::now();
+        thread::Builder::new().name("self-profile".to_string()).spawn(move || {
+            let profile = &ctx.profile;
+            while !profile.stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_secs(1));
+                let (now, (now_cpu, rss_bytes)) = match usage.read() {
+                    Some(read) => (Instant::now(), read),
+                    None => break,
+                };
+                let sample = Sample {
+                    secs: profile.started.elapsed().as_secs_f64(),
+                    cpu_pct: 100.0 * (now_cpu - cpu) / (now - at).as_secs_f64(),
+                    rss_bytes,
+                };
+                profile.samples.lock().unwrap().push(sample);
+                ctx.guard.check(&sample, &ctx.quota);
+                cpu = now_cpu;
+                at = now;
+            }
+        }).ok()
+    }
+
+    pub fn stop(&self, thread: Option<JoinHandle<()>>) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn samples(&self) -> Vec<Sample> {
+        self.samples.lock().unwrap().clone()
+    }
+
+    pub fn summary(&self) -> Option<ProfileSummary> {
+        let samples = self.samples.lock().unwrap();
+        if samples.is_empty() {
+            return None;
+        }
+        let count = samples.len();
+        Some(ProfileSummary {
+            cores: thread::available_parallelism().map(|cores| cores.get()).unwrap_or(1),
+            cpu_avg_pct: samples.iter().map(|s| s.cpu_pct).sum::<f64>() / count as f64,
+            cpu_peak_pct: samples.iter().map(|s| s.cpu_pct).fold(0.0, f64::max),
+            rss_avg_bytes: samples.iter().map(|s| s.rss_bytes).sum::<u64>() / count as u64,
+            rss_peak_bytes: samples.iter().map(|s| s.rss_bytes).max().unwrap_or(0),
+        })
+    }
+
+    pub fn print_summary(&self) {
+        let summary = match self.summary() {
+            Some(summary) => summary,
+            None => return,
+        };
+        println!("Generator process: {:.0}% CPU on average and {:.0}% at peak, where 100% is a whole core and {} are available, {} resident on average and {} at peak",
+                 summary.cpu_avg_pct, summary.cpu_peak_pct, summary.cores,
+                 size(summary.rss_avg_bytes as f64), size(summary.rss_peak_bytes as f64));
+        if summary.is_cpu_bound() {
+            println!("The generator kept the {} cores of the client machine busy, the rates it reached say more about it than about the server", summary.cores);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use futures::future::BoxFuture;
+use questdb::{Error, ErrorCode, ingress::{Buffer, ProtocolVersion, Sender}};
+use tokio::sync::{Mutex, MutexGuard};
+use tokio::time::sleep;
+
+use crate::error_summary::thousands;
+use crate::estimate::size;
+use crate::network::Network;
+use crate::rate_limit::{RateLimit, RateLimits};
+use crate::sink::{self, Sink, SinkStats};
+
+/// One connection of the pool. `sender` is none once it had to be closed, until it is
+/// opened again; `retry_at` holds off reconnecting after a failed attempt.
+struct Slot {
+    sender: Option<Sender>,
+    last_used: Instant,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+    /// --max-rate-per-conn, kept when the connection is opened again
+    limit: Option<RateLimit>,
+}
+
+/// The connections the async generators and live sources flush through, --senders of
+/// them. A flush takes the next free one, or waits for one in turn when all are busy.
+/// A connection the client had to close is opened again before its next flush, waiting
+/// --reconnect-backoff-ms after a failed attempt and twice as long after each further
+/// one, up to --reconnect-max-backoff-ms. A new connection reads the credentials from
+/// the connection string again, so it authenticates again too. With
+/// --sender-keepalive-secs, connections idle for that long are replaced in the
+/// background, before a load balancer or firewall drops them silently. --auto-batch can
+/// have flushes use only the first few connections. It is the `questdb` sink, and the
+/// mirror of --mirror-connection-string.
+pub struct SenderPool {
+    conf: String,
+    /// --protocol-version, kept when a connection is opened again
+    pinned: Option<ProtocolVersion>,
+    /// What the first connection agreed on with the server
+    protocol_version: ProtocolVersion,
+    slots: Vec<Mutex<Slot>>,
+    /// Connections flushes take turns on, the first of `slots`
+    active: AtomicUsize,
+    next: AtomicUsize,
+    keepalive: Option<Duration>,
+    backoff: Duration,
+    max_backoff: Duration,
+    reconnects: AtomicU64,
+    refreshes: AtomicU64,
+    failures: AtomicU64,
+    /// Batches flushed and failed, with the rows and bytes of those flushed
+    batches: AtomicU64,
+    rows: AtomicU64,
+    bytes: AtomicU64,
+    failed_batches: AtomicU64,
+}
+
+impl SenderPool {
+    /// Opens all the connections, failing on the first that cannot be opened.
+    pub fn connect(conf: &str, pinned: Option<ProtocolVersion>, size: usize, keepalive: Option<Duration>, backoff: Duration, max_backoff: Duration,
+                   limits: &RateLimits) -> Result<Self, Error> {
+        let slots = (0..size.max(1)).map(|_| {
+            let sender = Some(sink::connect(conf, pinned)?);
+            Ok(Mutex::new(Slot { sender, last_used: Instant::now(), backoff, retry_at: None, limit: limits.for_connection() }))
+        }).collect::<Result<Vec<_>, Error>>()?;
+        let protocol_version = slots[0].try_lock().ok().and_then(|slot| slot.sender.as_ref().map(Sender::protocol_version)).unwrap_or(ProtocolVersion::V1);
+        Ok(SenderPool {
+            conf: conf.to_string(),
+            pinned,
+            protocol_version,
+            active: AtomicUsize::new(slots.len()),
+            slots,
+            next: AtomicUsize::new(0),
+            keepalive,
+            backoff,
+            max_backoff,
+            reconnects: AtomicU64::new(0),
+            refreshes: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            batches: AtomicU64::new(0),
+            rows: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            failed_batches: AtomicU64::new(0),
+        })
+    }
+
+    /// The next free connection, going round the pool so they are all used
+    async fn acquire(&self) -> (usize, MutexGuard<'_, Slot>) {
+        let active = self.active.load(Ordering::Relaxed);
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % active;
+        for offset in 0..active {
+            let index = (start + offset) % active;
+            if let Ok(slot) = self.slots[index].try_lock() {
+                return (index, slot);
+            }
+        }
+        (start, self.slots[start].lock().await)
+    }
+
+    /// Opens connection `index` again. `event` says why, for the log.
+    fn reopen(&self, index: usize, slot: &mut Slot, event: &str) -> Result<(), Error> {
+        match sink::connect(&self.conf, self.pinned) {
+            Ok(sender) => {
+                eprintln!("Sender {} {}", index, event);
+                slot.sender = Some(sender);
+                slot.last_used = Instant::now();
+                slot.backoff = self.backoff;
+                slot.retry_at = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                eprintln!("Sender {} cannot connect: {}, retrying in {} ms", index, e.msg(), slot.backoff.as_millis());
+                slot.sender = None;
+                slot.retry_at = Some(Instant::now() + slot.backoff);
+                slot.backoff = (slot.backoff * 2).min(self.max_backoff);
+                Err(e)
+            }
+        }
+    }
+
+    /// The sender of a connection, opened again first if it was closed.
+    fn usable<'a>(&self, index: usize, slot: &'a mut Slot) -> Result<&'a mut Sender, Error> {
+        if slot.sender.as_ref().is_some_and(Sender::must_close) {
+            slot.sender = None;
+        }
+        if slot.sender.is_none() {
+            if let Some(wait) = slot.retry_at.and_then(|at| at.checked_duration_since(Instant::now())) {
+                return Err(Error::new(ErrorCode::SocketError, format!("sender {} reconnects in {} ms", index, wait.as_millis())));
+            }
+            self.reopen(index, slot, "reconnected")?;
+            self.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(slot.sender.as_mut().unwrap())
+    }
+
+    /// Flushes the buffer on the next free connection, once its --max-rate-per-conn allows,
+    /// through the simulated `network`. A connection the client has to close after the
+    /// flush is opened again before it is used next.
+    pub async fn flush(&self, buffer: &mut Buffer, limits: &RateLimits, network: &Network) -> Result<(), Error> {
+        let (rows, bytes) = (buffer.row_count() as u64, buffer.len() as u64);
+        let result = self.send(buffer, limits, network).await;
+        match &result {
+            Ok(()) => {
+                self.batches.fetch_add(1, Ordering::Relaxed);
+                self.rows.fetch_add(rows, Ordering::Relaxed);
+                self.bytes.fetch_add(bytes, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.failed_batches.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+
+    async fn send(&self, buffer: &mut Buffer, limits: &RateLimits, network: &Network) -> Result<(), Error> {
+        let (index, mut slot) = self.acquire().await;
+        sleep(limits.reserve_conn(slot.limit.as_ref(), buffer.row_count())).await;
+        sleep(network.delay(buffer.len())).await;
+        let sender = self.usable(index, &mut slot)?;
+        let result = sender.flush(buffer);
+        if sender.must_close() {
+            eprintln!("Sender {} closed after: {}", index, result.as_ref().err().map(Error::msg).unwrap_or("an error"));
+            slot.sender = None;
+        }
+        slot.last_used = Instant::now();
+        result
+    }
+
+    /// Flushes a copy of the buffer on the next free connection, leaving the buffer as it
+    /// is, for the --mirror-connection-string. Neither rate limits nor the simulated
+    /// network apply, they are already in the flush of the rows to the primary.
+    pub async fn flush_and_keep(&self, buffer: &Buffer) -> Result<(), Error> {
+        let (index, mut slot) = self.acquire().await;
+        let sender = self.usable(index, &mut slot)?;
+        let result = sender.flush_and_keep(buffer);
+        if sender.must_close() {
+            eprintln!("Sender {} closed after: {}", index, result.as_ref().err().map(Error::msg).unwrap_or("an error"));
+            slot.sender = None;
+        }
+        slot.last_used = Instant::now();
+        result
+    }
+
+    /// Opens the closed connections again without waiting for their backoff, once the
+    /// health watcher sees the server answering. Fails when one still cannot connect.
+    pub async fn reconnect_closed(&self) -> Result<(), Error> {
+        for (index, slot) in self.slots.iter().enumerate() {
+            let mut slot = slot.lock().await;
+            if slot.sender.as_ref().map(Sender::must_close).unwrap_or(true) {
+                slot.retry_at = None;
+                self.reopen(index, &mut slot, "reconnected")?;
+                self.reconnects.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the connections idle for longer than --sender-keepalive-secs.
+    fn refresh_idle(&self, keepalive: Duration) {
+        for (index, slot) in self.slots.iter().enumerate() {
+            // Busy connections are not idle
+            let Ok(mut slot) = slot.try_lock() else { continue };
+            if slot.sender.is_some() && slot.last_used.elapsed() >= keepalive {
+                let idle = slot.last_used.elapsed().as_secs();
+                if self.reopen(index, &mut slot, &format!("refreshed after {}s idle", idle)).is_ok() {
+                    self.refreshes.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Closes every connection, once the run has flushed its last batch
+    pub async fn close(&self) {
+        for slot in &self.slots {
+            slot.lock().await.sender = None;
+        }
+    }
+
+    pub fn print_summary(&self) {
+        println!("Senders: {}, {} reconnects, {} refreshed when idle, {} failed connection attempts", self.slots.len(),
+                 thousands(self.reconnects.load(Ordering::Relaxed)), thousands(self.refreshes.load(Ordering::Relaxed)),
+                 thousands(self.failures.load(Ordering::Relaxed)));
+    }
+}
+
+impl Sink for SenderPool {
+    fn write_batch<'a>(&'a self, buffer: &'a mut Buffer, limits: &'a RateLimits, network: &'a Network) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(self.flush(buffer, limits, network))
+    }
+
+    fn close(&self) -> BoxFuture<'_, ()> {
+        Box::pin(SenderPool::close(self))
+    }
+
+    fn stats(&self) -> SinkStats {
+        SinkStats {
+            batches: self.batches.load(Ordering::Relaxed),
+            rows: self.rows.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            failed_batches: self.failed_batches.load(Ordering::Relaxed),
+        }
+    }
+
+    fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    fn reconnect(&self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(self.reconnect_closed())
+    }
+
+    /// A few times per --sender-keepalive-secs, so no connection stays idle much longer
+    fn keep_alive(&self) -> Option<Duration> {
+        self.keepalive.map(|keepalive| (keepalive / 4).max(Duration::from_millis(100)))
+    }
+
+    fn refresh(&self) {
+        if let Some(keepalive) = self.keepalive {
+            self.refresh_idle(keepalive);
+        }
+    }
+
+    fn connections(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn use_connections(&self, connections: usize) {
+        self.active.store(connections.clamp(1, self.slots.len()), Ordering::Relaxed);
+    }
+
+    fn print_summary(&self) {
+        let stats = self.stats();
+        println!("Sink questdb: {} batches with {} rows and {} sent, {} failed", thousands(stats.batches), thousands(stats.rows),
+                 size(stats.bytes as f64), thousands(stats.failed_batches));
+        SenderPool::print_summary(self);
+    }
+}
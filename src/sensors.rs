@@ -0,0 +1,37 @@
+/// When the sensor groups of one plane have a new reading.
+#[derive(Debug, Clone)]
+pub struct SensorSchedule {
+    periods: Vec<i64>,
+    next_due: Vec<i64>,
+}
+
+impl SensorSchedule {
+    pub fn new(rates: &[f64], now: i64) -> Self {
+        let periods = rates.iter().map(|rate| (1e9 / rate) as i64).collect();
+        SensorSchedule { periods, next_due: vec![now; rates.len()] }
+    }
+
+    /// Takes the rates of a reloaded config, for the same groups. Readings already
+    /// scheduled keep their time.
+    pub fn set_rates(&mut self, rates: &[f64]) {
+        for (period, rate) in self.periods.iter_mut().zip(rates.iter()) {
+            *period = (1e9 / rate) as i64;
+        }
+    }
+
+    /// Bitmask of the groups with a reading at `timestamp`. A group that fell more than
+    /// a period behind skips the missed readings.
+    pub fn due(&mut self, timestamp: i64) -> u64 {
+        let mut due = 0;
+        for (group, (next_due, period)) in self.next_due.iter_mut().zip(self.periods.iter()).enumerate() {
+            if timestamp >= *next_due {
+                due |= 1 << group;
+                *next_due += period;
+                if *next_due <= timestamp {
+                    *next_due = timestamp + period;
+                }
+            }
+        }
+        due
+    }
+}
@@ -0,0 +1,63 @@
+use std::str::FromStr;
+
+use crate::units;
+
+const DAY_NANOS: i64 = 86_400_000_000_000;
+
+/// When a window of --silence-at starts: a time of day in UTC, every day, or a time into
+/// the run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SilenceAt {
+    /// Seconds since midnight UTC, for "00:10" or "23:59:30"
+    TimeOfDay(u32),
+    /// Seconds after the start of the run, for "10m" or "90"
+    Offset(f64),
+}
+
+impl FromStr for SilenceAt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains(':') {
+            return units::secs_f64(s).map(SilenceAt::Offset);
+        }
+        let invalid = || format!("invalid time of day '{}', expected HH:MM or HH:MM:SS", s);
+        let parts: Vec<u32> = s.split(':').map(|part| part.trim().parse::<u32>()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+        let (hours, minutes, seconds) = match parts[..] {
+            [hours, minutes] => (hours, minutes, 0),
+            [hours, minutes, seconds] => (hours, minutes, seconds),
+            _ => return Err(invalid()),
+        };
+        if hours >= 24 || minutes >= 60 || seconds >= 60 {
+            return Err(invalid());
+        }
+        Ok(SilenceAt::TimeOfDay(hours * 3600 + minutes * 60 + seconds))
+    }
+}
+
+/// Windows of --silence-at where no plane sends anything, to test no-data alerts and how
+/// dashboards show gaps. They follow the clock of the rows, the wall clock unless
+/// backfilling.
+#[derive(Debug)]
+pub struct Silence {
+    windows: Vec<SilenceAt>,
+    length_nanos: i64,
+    /// Timestamp the offsets count from
+    start: i64,
+}
+
+impl Silence {
+    pub fn new(windows: Vec<SilenceAt>, length_secs: f64, start: i64) -> Self {
+        Silence { windows, length_nanos: (length_secs * 1e9) as i64, start }
+    }
+
+    pub fn is_silent(&self, timestamp: i64) -> bool {
+        self.windows.iter().any(|window| {
+            let since = match *window {
+                SilenceAt::TimeOfDay(secs) => (timestamp - secs as i64 * 1_000_000_000).rem_euclid(DAY_NANOS),
+                SilenceAt::Offset(secs) => timestamp - self.start - (secs * 1e9) as i64,
+            };
+            (0..self.length_nanos).contains(&since)
+        })
+    }
+}
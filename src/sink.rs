@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::BoxFuture;
+use questdb::{Result, ingress::{Buffer, ProtocolVersion, Sender, SenderBuilder}};
+use tokio::time::sleep;
+
+use crate::{Opt, RunContext};
+use crate::error::SimError;
+use crate::error_summary::thousands;
+use crate::estimate::size;
+#[cfg(feature = "object-store")]
+use crate::bucket::{self, Bucket};
+use crate::file_sink::{FileSink, Rotation, Target};
+use crate::network::Network;
+use crate::rate_limit::RateLimits;
+use crate::sender_pool::SenderPool;
+
+/// What a sink sent so far: the batches it accepted with their rows and bytes, and the
+/// batches it could not take.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SinkStats {
+    pub batches: u64,
+    pub rows: u64,
+    pub bytes: u64,
+    pub failed_batches: u64,
+}
+
+/// Where the generated rows go. The generators and live sources fill ILP buffers and hand
+/// them to the sink of the run, which sends them however it likes, so another output only
+/// needs an implementation and an entry in `SINKS`. A sink that is not a QuestDB server
+/// reports its failures as `questdb::Error`s too, a `ServerFlushError` for a batch it
+/// refuses for good and any other code for one worth retrying.
+pub trait Sink: Send + Sync {
+    /// Sends the rows of the buffer, once --max-rate-per-conn allows and through the
+    /// simulated `network`, and empties the buffer when they are accepted
+    fn write_batch<'a>(&'a self, buffer: &'a mut Buffer, limits: &'a RateLimits, network: &'a Network) -> BoxFuture<'a, Result<()>>;
+
+    /// Sends whatever the sink still holds back. Sinks that send each batch as it comes
+    /// have nothing to do.
+    fn flush(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Ends the run, after the last batch
+    fn close(&self) -> BoxFuture<'_, ()>;
+
+    fn stats(&self) -> SinkStats;
+
+    /// The ILP protocol version the buffers of the run are written in. Only version 2 has
+    /// binary doubles and arrays.
+    fn protocol_version(&self) -> ProtocolVersion {
+        ProtocolVersion::V1
+    }
+
+    /// Opens again what the sink had to close, once the health watcher sees the server
+    /// answering
+    fn reconnect(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// How often `refresh` is called in the background while the server is up, if at all
+    fn keep_alive(&self) -> Option<Duration> {
+        None
+    }
+
+    fn refresh(&self) {}
+
+    /// Connections the sink can flush through at the same time
+    fn connections(&self) -> usize {
+        1
+    }
+
+    /// Flushes through the first `connections` only, for --auto-batch. A sink with a single
+    /// output has nothing to limit.
+    fn use_connections(&self, _connections: usize) {}
+
+    fn print_summary(&self) {
+        let stats = self.stats();
+        println!("Sink: {} batches with {} rows and {} sent, {} failed", thousands(stats.batches), thousands(stats.rows),
+                 size(stats.bytes as f64), thousands(stats.failed_batches));
+    }
+}
+
+/// How the run opens a sink, from the options
+pub type OpenSink = fn(&Opt, &RateLimits) -> std::result::Result<Box<dyn Sink>, SimError>;
+
+/// A sink --sink, or the `[sink]` of the --config file, can choose: its name, what it is,
+/// and how the run opens it.
+pub struct SinkKind {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub open: OpenSink,
+}
+
+/// The sink of --sink by default, and the only one the blocking engine of --sync and
+/// --backfill-workers sends to
+pub const QUESTDB: &str = "questdb";
+
+/// The sinks built in. One with heavy dependencies of its own goes behind a cargo feature,
+/// off by default, with `#[cfg(feature = "...")]` on its entry, so a build only has the
+/// sinks it was asked for and --sink lists those.
+pub const SINKS: &[SinkKind] = &[
+    SinkKind { name: QUESTDB, about: "ILP over HTTP or TCP to the --connection-string, through the --senders pool", open: open_questdb },
+    SinkKind { name: CSV, about: "a CSV file for each table in the --sink-path directory or bucket", open: open_csv },
+];
+
+pub const CSV: &str = "csv";
+
+fn open_questdb(opt: &Opt, limits: &RateLimits) -> std::result::Result<Box<dyn Sink>, SimError> {
+    Ok(Box::new(SenderPool::connect(&opt.connection_string, opt.protocol_version, opt.senders, opt.sender_keepalive_secs.map(Duration::from_secs),
+                                    Duration::from_millis(opt.reconnect_backoff_ms), Duration::from_millis(opt.reconnect_max_backoff_ms),
+                                    limits)?))
+}
+
+fn open_csv(opt: &Opt, limits: &RateLimits) -> std::result::Result<Box<dyn Sink>, SimError> {
+    let path = opt.sink_path.clone().unwrap_or_default();
+    let target = match opt.sink_url() {
+        #[cfg(feature = "object-store")]
+        Some(url) => Target::Bucket(Bucket::open(url, opt.sink_part_size.unwrap_or(bucket::DEFAULT_PART_SIZE)).map_err(SimError::Setup)?),
+        // Without the feature, validation refuses a URL
+        _ => {
+            std::fs::create_dir_all(&path).map_err(SimError::io("create the sink directory", path.clone()))?;
+            Target::Dir(path)
+        }
+    };
+    let rotation = Rotation { size: opt.sink_rotate_size, every_secs: opt.sink_rotate_every };
+    Ok(Box::new(FileSink::new(target, opt.sink_compression.unwrap_or_default(), rotation, limits)))
+}
+
+/// Smallest part S3 takes, but for the last one of a file
+pub const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Opens a sender on `conf`, on the ILP protocol `version` when it is pinned, otherwise on the
+/// one the connection string asks for or the server offers
+pub fn connect(conf: &str, version: Option<ProtocolVersion>) -> Result<Sender> {
+    let builder = SenderBuilder::from_conf(conf)?;
+    match version {
+        Some(version) => builder.protocol_version(version)?.build(),
+        None => builder.build(),
+    }
+}
+
+/// The ILP protocol version of --protocol-version, 1 or 2
+pub fn protocol_version(version: &str) -> std::result::Result<ProtocolVersion, String> {
+    match version {
+        "1" => Ok(ProtocolVersion::V1),
+        "2" => Ok(ProtocolVersion::V2),
+        _ => Err(format!("unknown protocol version '{}', expected 1 or 2", version)),
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static SinkKind> {
+    SINKS.iter().find(|kind| kind.name == name)
+}
+
+/// Opens the --sink of the run
+pub fn open(opt: &Opt, limits: &RateLimits) -> std::result::Result<Box<dyn Sink>, SimError> {
+    let kind = find(opt.sink()).ok_or_else(|| SimError::Setup(format!("unknown --sink '{}', expected one of: {}", opt.sink(), describe())))?;
+    (kind.open)(opt, limits)
+}
+
+/// "questdb (ILP over ...), ...", for the error of an unknown --sink
+pub fn describe() -> String {
+    SINKS.iter().map(|kind| format!("{} ({})", kind.name, kind.about)).collect::<Vec<_>>().join(", ")
+}
+
+/// Refreshes the sink as often as it asks while the server is up.
+pub async fn keep_alive(ctx: Arc<RunContext>) {
+    let Some(every) = ctx.sink.keep_alive() else { return };
+    loop {
+        sleep(every).await;
+        if !ctx.health.is_down() {
+            ctx.sink.refresh();
+        }
+    }
+}
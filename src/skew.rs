@@ -0,0 +1,56 @@
+use rand::Rng;
+
+/// Share of --rate-per-plane of the plane of rank `n`, from 1, with --plane-skew
+pub fn weight(exponent: f64, n: u64) -> f64 {
+    (n as f64).powf(-exponent)
+}
+
+/// Sum of the shares of `planes` planes, the total rate in units of --rate-per-plane
+pub fn total_weight(exponent: f64, planes: u64) -> f64 {
+    (1..=planes).map(|n| weight(exponent, n)).sum()
+}
+
+/// The share of the ticks of one plane with --plane-skew. The rows follow a Zipf law over
+/// the planes in the order of their ids: the first plane sends a row at every tick, the
+/// others skip ticks, so a few hot planes send most of the rows.
+#[derive(Debug, Clone)]
+pub struct SkewShare {
+    weight: f64,
+    /// Ticks owed to the plane, it sends a row once it reaches 1
+    credit: f64,
+    due: bool,
+}
+
+impl SkewShare {
+    pub fn new(weight: f64) -> Self {
+        // Planes of the same weight do not all tick at the same time
+        SkewShare { weight, credit: rand::thread_rng().gen_range(0.0..1.0), due: true }
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// Moves on to the next tick of the plane
+    pub fn tick(&mut self) {
+        self.credit += self.weight;
+        self.due = self.credit >= 1.0;
+        if self.due {
+            self.credit -= 1.0;
+        }
+    }
+
+    /// Whether the plane sends a row at the current tick
+    pub fn is_due(&self) -> bool {
+        self.due
+    }
+}
+
+/// The line printed at the start of the run about how skewed the rows are
+pub fn describe(exponent: f64, planes: u64, rate_per_plane: u64) -> String {
+    let total = total_weight(exponent, planes);
+    let hot = (planes / 10).max(1);
+    format!("Plane skew {}: {} rows/s in total, the first plane sends {} rows/s and the last {:.2}, the hottest {} planes send {:.0}% of the rows",
+            exponent, (rate_per_plane as f64 * total).round(), rate_per_plane, rate_per_plane as f64 * weight(exponent, planes), hot,
+            total_weight(exponent, hot) / total * 100.0)
+}
@@ -0,0 +1,73 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand_distr::StandardNormal;
+
+use crate::plane::FlightPhase;
+
+/// Harmonics of the fan speed that show as peaks
+const HARMONICS: usize = 3;
+/// Amplitude of the bins away from the peaks, in g
+const NOISE_FLOOR: f64 = 0.002;
+/// Most bins --spectrum-bins takes
+pub const MAX_BINS: usize = 4096;
+/// Amplitude of the fan speed peak at 250 kt, in g; each harmonic gets a fraction of it
+const PEAK: f64 = 0.05;
+
+/// Vibration spectrum of an engine, for the --spectrum-bins array column: the amplitude in g
+/// of each frequency bin, with peaks at the fan speed and its harmonics over a noise floor.
+#[derive(Debug, Clone)]
+pub struct Spectrum {
+    pub bins: Vec<f64>,
+    /// Fan speed as a fraction of the frequency of the top bin, easing towards the one of
+    /// the flight phase
+    fan_speed: f64,
+    /// A generator of its own, so a plane flies the same with and without the spectrum
+    rng: StdRng,
+}
+
+impl Spectrum {
+    pub fn new(bins: usize, seed: u64) -> Self {
+        Spectrum { bins: vec![0.0; bins], fan_speed: fan_speed(FlightPhase::Climb), rng: StdRng::seed_from_u64(seed) }
+    }
+
+    /// Takes the next reading, for a plane at `airspeed` knots in `phase`.
+    pub fn update(&mut self, phase: FlightPhase, airspeed: f64) {
+        self.fan_speed += (fan_speed(phase) - self.fan_speed) * 0.05 + self.rng.gen_range(-0.002..0.002);
+        let (fan_speed, top, load) = (self.fan_speed, self.bins.len() as f64, airspeed / 250.0);
+        for (bin, amplitude) in self.bins.iter_mut().enumerate() {
+            let frequency = (bin as f64 + 0.5) / top;
+            let peaks: f64 = (1..=HARMONICS).map(|harmonic| {
+                // In bins, so the peaks are as wide whatever the resolution
+                let distance = (frequency - harmonic as f64 * fan_speed) * top;
+                PEAK * load / harmonic as f64 * (-distance * distance / 2.0).exp()
+            }).sum();
+            let noise: f64 = self.rng.sample(StandardNormal);
+            *amplitude = peaks + NOISE_FLOOR * (1.0 + noise.abs());
+        }
+    }
+}
+
+/// Fan speed of a flight phase, as a fraction of the frequency of the top bin
+fn fan_speed(phase: FlightPhase) -> f64 {
+    match phase {
+        FlightPhase::Climb => 0.3,
+        FlightPhase::Cruise => 0.27,
+        FlightPhase::Descent => 0.15,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_fan_speed_peak_follows_the_phase() {
+        let mut spectrum = Spectrum::new(16, 7);
+        for _ in 0..200 {
+            spectrum.update(FlightPhase::Descent, 250.0);
+        }
+        let peak = (0..16).max_by(|a, b| spectrum.bins[*a].total_cmp(&spectrum.bins[*b])).unwrap();
+        assert_eq!(peak, 2);
+        assert!(spectrum.bins.iter().all(|amplitude| *amplitude >= NOISE_FLOOR));
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// What a single plane did during the run.
+#[derive(Debug, Clone)]
+pub struct PlaneStats {
+    pub plane_id: String,
+    pub rows_generated: u64,
+    pub rows_flushed: u64,
+    pub flush_failures: u64,
+    pub row_errors: u64,
+    /// Rows in batches the server refused
+    pub rows_rejected: u64,
+    /// Rows discarded because the buffer was full while the server was down
+    pub rows_dropped: u64,
+    /// Panics the plane was restarted after
+    pub restarts: u64,
+    /// Rows generated after the warm-up, the ones the achieved rate is computed from
+    pub measured_rows: u64,
+    /// Rows per second the plane was asked to produce
+    pub target_rate: f64,
+    measured_from: Instant,
+    elapsed: Duration,
+}
+
+impl PlaneStats {
+    /// Rows generated during the first `warmup` are sent but left out of the achieved rate.
+    pub fn new(plane_id: String, target_rate: f64, warmup: Duration) -> Self {
+        PlaneStats {
+            plane_id,
+            rows_generated: 0,
+            rows_flushed: 0,
+            flush_failures: 0,
+            row_errors: 0,
+            rows_rejected: 0,
+            rows_dropped: 0,
+            restarts: 0,
+            measured_rows: 0,
+            target_rate,
+            measured_from: Instant::now() + warmup,
+            elapsed: Duration::default(),
+        }
+    }
+
+    pub fn record_row(&mut self) {
+        self.rows_generated += 1;
+        if Instant::now() >= self.measured_from {
+            self.measured_rows += 1;
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.elapsed = Instant::now().saturating_duration_since(self.measured_from);
+    }
+
+    pub fn achieved_rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.measured_rows as f64 / secs } else { 0.0 }
+    }
+
+    /// Achieved rate as a percentage of the target
+    pub fn rate_pct(&self) -> f64 {
+        if self.target_rate > 0.0 { 100.0 * self.achieved_rate() / self.target_rate } else { 0.0 }
+    }
+}
+
+/// Prints one line per plane, followed by the slowest and fastest planes so skew between
+/// tasks is easy to spot.
+pub fn print_table(stats: &[PlaneStats], per_plane: bool) {
+    if per_plane {
+        println!("{:<12} {:>12} {:>12} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>7}",
+                 "plane", "generated", "flushed", "failures", "errors", "rejected", "dropped", "rate/s", "target/s", "rate%");
+        for s in stats.iter() {
+            println!("{:<12} {:>12} {:>12} {:>10} {:>10} {:>10} {:>10} {:>10.1} {:>10.1} {:>6.1}%",
+                     s.plane_id, s.rows_generated, s.rows_flushed, s.flush_failures, s.row_errors, s.rows_rejected, s.rows_dropped,
+                     s.achieved_rate(), s.target_rate, s.rate_pct());
+        }
+    }
+    let by_rate = |a: &&PlaneStats, b: &&PlaneStats| a.achieved_rate().total_cmp(&b.achieved_rate());
+    if let (Some(slowest), Some(fastest)) = (stats.iter().min_by(by_rate), stats.iter().max_by(by_rate)) {
+        println!("Slowest plane {} at {:.1} rows/s ({:.1}% of target), fastest plane {} at {:.1} rows/s ({:.1}% of target)",
+                 slowest.plane_id, slowest.achieved_rate(), slowest.rate_pct(),
+                 fastest.plane_id, fastest.achieved_rate(), fastest.rate_pct());
+    }
+}
+
+pub fn write_csv(stats: &[PlaneStats], path: &Path) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    writeln!(file, "plane_id,rows_generated,rows_flushed,flush_failures,row_errors,rows_rejected,rows_dropped,measured_rows,measured_secs,achieved_rate,target_rate,restarts")?;
+    for s in stats.iter() {
+        writeln!(file, "{},{},{},{},{},{},{},{},{:.3},{:.3},{:.3},{}",
+                 s.plane_id, s.rows_generated, s.rows_flushed, s.flush_failures, s.row_errors, s.rows_rejected, s.rows_dropped,
+                 s.measured_rows, s.elapsed.as_secs_f64(), s.achieved_rate(), s.target_rate, s.restarts)?;
+    }
+    file.flush()
+}
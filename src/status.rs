@@ -0,0 +1,50 @@
+use questdb::ingress::{Buffer, TimestampMicros};
+
+use crate::config::Columns;
+use crate::plane::{FlightPhase, PlaneData};
+
+/// Decides when a plane writes a row to the status table: whenever its phase or flight
+/// leg changes, and at least every `--status-every-secs` otherwise. The table stays small,
+/// one slow stream per plane, so `LATEST ON` gives the current state of the fleet cheaply.
+#[derive(Debug, Clone)]
+pub struct StatusStream {
+    every_nanos: i64,
+    last: Option<(i64, FlightPhase, String)>,
+}
+
+impl StatusStream {
+    pub fn new(every_secs: f64) -> Self {
+        StatusStream { every_nanos: (every_secs * 1e9) as i64, last: None }
+    }
+
+    pub fn due(&mut self, plane: &PlaneData) -> bool {
+        let due = match &self.last {
+            Some((written_at, phase, callsign)) => {
+                *phase != plane.phase || *callsign != plane.callsign || plane.timestamp - written_at >= self.every_nanos
+            }
+            None => true,
+        };
+        if due {
+            self.last = Some((plane.timestamp, plane.phase, plane.callsign.clone()));
+        }
+        due
+    }
+}
+
+pub fn write(plane: &PlaneData, buffer: &mut Buffer, table_name: &str, columns: &Columns) -> questdb::Result<()> {
+    buffer.table(table_name)?
+        .symbol(columns.name("plane_id"), &plane.plane_id)?
+        .symbol(columns.name("callsign"), &plane.callsign)?
+        .symbol(columns.name("phase"), plane.phase.as_str())?
+        .symbol("origin", plane.origin)?
+        .symbol("destination", plane.destination)?;
+    if let Some((name, label)) = columns.run_label() {
+        buffer.symbol(name, label)?;
+    }
+    buffer.column_f64(columns.name("altitude"), columns.value("altitude", plane.altitude))?
+        .column_f64(columns.name("latitude"), columns.value("latitude", plane.latitude))?
+        .column_f64(columns.name("longitude"), columns.value("longitude", plane.longitude))?
+        .column_f64(columns.name("fuel_kg"), columns.value("fuel_kg", plane.fuel_kg))?
+        .column_ts("eta", TimestampMicros::new(plane.arrival() / 1000))?;
+    plane.at(buffer)
+}
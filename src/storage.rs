@@ -0,0 +1,122 @@
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::Value;
+
+use crate::RunContext;
+use crate::error_summary::thousands;
+use crate::questdb_http::{QueryClient, QueryResult};
+
+/// How long to wait for the WAL of the tables to be applied before measuring them
+const APPLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Clone, Copy)]
+struct TableSize {
+    rows: u64,
+    disk_bytes: u64,
+    partitions: u64,
+}
+
+fn number(value: &Value) -> u64 {
+    value.as_u64().or_else(|| value.as_f64().map(|v| v as u64)).unwrap_or(0)
+}
+
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn column<'a>(result: &'a QueryResult, row: &'a [Value], name: &str) -> &'a Value {
+    result.column_index(name).and_then(|index| row.get(index)).unwrap_or(&Value::Null)
+}
+
+/// Waits until the server has applied every committed transaction of the tables, so
+/// the partitions hold all the rows sent. Non-WAL tables are applied on commit.
+fn wait_applied(client: &QueryClient, tables: &[&str]) -> Result<(), String> {
+    let started = Instant::now();
+    loop {
+        let result = client.exec("SELECT name, writerTxn, sequencerTxn, suspended FROM wal_tables()")?;
+        let mut pending = vec![];
+        for row in result.dataset.iter() {
+            let name = column(&result, row, "name").as_str().unwrap_or_default();
+            if !tables.contains(&name) {
+                continue;
+            }
+            if column(&result, row, "suspended").as_bool().unwrap_or(false) {
+                return Err(format!("table '{}' is suspended", name));
+            }
+            if number(column(&result, row, "writerTxn")) < number(column(&result, row, "sequencerTxn")) {
+                pending.push(name.to_string());
+            }
+        }
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if started.elapsed() > APPLY_TIMEOUT {
+            return Err(format!("WAL of {} not applied after {}s", pending.join(", "), APPLY_TIMEOUT.as_secs()));
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+fn table_size(client: &QueryClient, table: &str) -> Result<TableSize, String> {
+    let result = client.exec(&format!("SELECT numRows, diskSize FROM table_partitions({})", literal(table)))?;
+    Ok(result.dataset.iter().fold(TableSize::default(), |size, row| TableSize {
+        rows: size.rows + number(column(&result, row, "numRows")),
+        disk_bytes: size.disk_bytes + number(column(&result, row, "diskSize")),
+        partitions: size.partitions + 1,
+    }))
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// Prints the on-disk size of the tables the run wrote to, per row, next to the ILP bytes
+/// sent per row. Sizes are for the whole tables, rows from earlier runs included, so
+/// comparing per-row figures keeps the ratio meaningful for a table that already existed.
+pub fn report(ctx: &RunContext) {
+    let opt = &ctx.opt;
+    let columns = ctx.columns();
+    let mut tables: Vec<&str> = vec![];
+    let written = std::iter::once(opt.table_name.as_str()).chain(columns.fleet_tables())
+        .chain(opt.companion_table.as_deref()).chain(opt.status_table.as_deref());
+    for table in written {
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+
+    let client = match QueryClient::from_conf(&opt.connection_string) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Cannot report storage: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = wait_applied(&client, &tables) {
+        eprintln!("Storage sizes may be incomplete: {}", e);
+    }
+
+    let mut total = TableSize::default();
+    for table in tables.iter() {
+        match table_size(&client, table) {
+            Ok(size) => {
+                println!("Storage of {}: {:.1} MiB in {} partitions for {} rows, {:.1} bytes per row",
+                         table, mib(size.disk_bytes), size.partitions, thousands(size.rows),
+                         size.disk_bytes as f64 / size.rows.max(1) as f64);
+                total.rows += size.rows;
+                total.disk_bytes += size.disk_bytes;
+            }
+            Err(e) => eprintln!("Cannot read the partitions of {}: {}", table, e),
+        }
+    }
+
+    let (rows_sent, bytes_sent) = (ctx.rows_sent.load(Ordering::SeqCst), ctx.bytes_sent.load(Ordering::SeqCst));
+    if rows_sent == 0 || total.rows == 0 || total.disk_bytes == 0 {
+        return;
+    }
+    let sent_per_row = bytes_sent as f64 / rows_sent as f64;
+    let stored_per_row = total.disk_bytes as f64 / total.rows as f64;
+    println!("Sent {:.1} MiB over ILP for {} rows, {:.1} bytes per row; stored in {:.1} bytes per row, {:.2}x compression",
+             mib(bytes_sent), thousands(rows_sent), sent_per_row, stored_per_row, sent_per_row / stored_per_row);
+}
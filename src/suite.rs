@@ -0,0 +1,225 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use serde::Deserialize;
+use serde_json::Value;
+use clap::{Parser, Subcommand};
+
+use crate::Opt;
+use crate::error_summary::thousands;
+use crate::runs;
+
+/// A benchmark suite: the arguments every run gets, and the runs, each with the
+/// arguments that set it apart.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Suite {
+    #[serde(default)]
+    args: Vec<String>,
+    /// Seconds to wait between two runs, for the server to settle
+    #[serde(default)]
+    pause_secs: f64,
+    runs: Vec<SuiteRun>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SuiteRun {
+    name: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// Commands of `airplanes suite`.
+#[derive(Subcommand, Debug)]
+pub enum SuiteCommand {
+    /// Run the benchmarks of a suite file one after the other and compare them
+    Run(SuiteRunOpt),
+}
+
+/// Options of `airplanes suite run`.
+#[derive(Parser, Debug)]
+pub struct SuiteRunOpt {
+    /// TOML or YAML file, by its extension, with the `args` of every run and the `runs`,
+    /// each with its `name` and own `args`
+    file: PathBuf,
+
+    /// Directory to write the JSON report of each run to
+    #[arg(long, default_value = "suite-reports")]
+    output_dir: PathBuf,
+}
+
+/// Arguments the suite sets itself, or that would keep a run from ending on its own
+const SUITE_ARGS: &[&str] = &["--report-json", "--daemon", "--tui"];
+
+/// The full arguments of each run, checked as the simulator would before the first one starts
+fn plan(suite: &Suite) -> Result<Vec<Vec<String>>, String> {
+    if suite.runs.is_empty() {
+        return Err("the suite has no [[runs]]".to_string());
+    }
+    if suite.pause_secs < 0.0 || suite.pause_secs.is_nan() {
+        return Err(format!("pause_secs must be 0 or more, got {}", suite.pause_secs));
+    }
+    suite.runs.iter().map(|run| {
+        let args: Vec<String> = suite.args.iter().chain(run.args.iter()).cloned().collect();
+        check(&args).map_err(|e| format!("run '{}': {}", run.name, e))?;
+        Ok(args)
+    }).collect()
+}
+
+/// Checks the arguments of a run as the simulator would, without starting it
+pub fn check(args: &[String]) -> Result<(), String> {
+    if let Some(arg) = args.iter().find(|arg| SUITE_ARGS.contains(&arg.split('=').next().unwrap_or(arg))) {
+        return Err(format!("{} cannot be used in a suite", arg));
+    }
+    let mut opt = Opt::try_parse_from(std::iter::once("airplanes".to_string()).chain(args.iter().cloned()))
+        .map_err(|e| e.to_string().lines().next().unwrap_or_default().trim_start_matches("error: ").to_string())?;
+    opt.read_secrets()?;
+    opt.resolve();
+    opt.load_config()?;
+    match opt.validate().0 {
+        errors if errors.is_empty() => Ok(()),
+        errors => Err(errors.join(", ")),
+    }
+}
+
+/// Runs the simulator with `args` and waits for it to end. Returns its exit code, none
+/// when it was killed, and the JSON report it wrote to `path`.
+pub fn run_one(args: &[String], path: &Path) -> Result<(Option<i32>, Option<Value>), String> {
+    let exe = std::env::current_exe().map_err(|e| format!("cannot find the simulator: {}", e))?;
+    // A report left by an earlier run would pass for this one's
+    let _ = std::fs::remove_file(path);
+    let status = Command::new(&exe).args(args).arg("--report-json").arg(path).status().map_err(|e| e.to_string())?;
+    let report = std::fs::read_to_string(path).ok().and_then(|text| serde_json::from_str::<Value>(&text).ok());
+    if report.is_some() {
+        eprintln!("Report written to {}", path.display());
+    }
+    Ok((status.code(), report))
+}
+
+/// Report file of the n-th run, from its name
+pub fn report_path(dir: &Path, n: usize, name: &str) -> PathBuf {
+    let name = name.to_lowercase();
+    let words: Vec<&str> = name.split(|c: char| !c.is_ascii_alphanumeric()).filter(|word| !word.is_empty()).collect();
+    dir.join(format!("{:02}-{}.json", n + 1, words.join("-")))
+}
+
+/// The suite of a `.yaml` or `.yml` file, or of a TOML one
+fn parse(file: &Path, text: &str) -> Result<Suite, String> {
+    match file.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+        _ => toml::from_str(text).map_err(|e| e.to_string()),
+    }
+}
+
+/// Runs the simulator once for each run of the suite, waiting for each one to end, then
+/// prints their key metrics side by side. A run that fails does not stop the others.
+pub fn run(opt: &SuiteRunOpt) -> Result<(), String> {
+    let text = std::fs::read_to_string(&opt.file).map_err(|e| format!("cannot read suite file {}: {}", opt.file.display(), e))?;
+    let suite = parse(&opt.file, &text).map_err(|e| format!("invalid suite file {}: {}", opt.file.display(), e))?;
+    let plan = plan(&suite)?;
+    std::fs::create_dir_all(&opt.output_dir).map_err(|e| format!("cannot create {}: {}", opt.output_dir.display(), e))?;
+    let mut results = vec![];
+    for (n, (run, args)) in suite.runs.iter().zip(plan.iter()).enumerate() {
+        if n > 0 && suite.pause_secs > 0.0 {
+            std::thread::sleep(Duration::from_secs_f64(suite.pause_secs));
+        }
+        eprintln!("=== Run {}/{}: {}", n + 1, plan.len(), run.name);
+        let (code, report) = run_one(args, &report_path(&opt.output_dir, n, &run.name))
+            .map_err(|e| format!("cannot start run '{}': {}", run.name, e))?;
+        results.push((run.name.clone(), code, report));
+    }
+    print_comparison(&results);
+    failures(&results)
+}
+
+/// An error telling how many of the runs failed, if any did
+pub fn failures(results: &[(String, Option<i32>, Option<Value>)]) -> Result<(), String> {
+    match results.iter().filter(|(_, code, _)| *code != Some(0)).count() {
+        0 => Ok(()),
+        failed => Err(format!("{} of the {} runs failed", failed, results.len())),
+    }
+}
+
+/// The key metrics of each run, from its report, with its rate against the first run's
+pub fn print_comparison(results: &[(String, Option<i32>, Option<Value>)]) {
+    let results: Vec<(&str, Option<i32>, Option<Value>)> = results.iter()
+        .map(|(name, code, report)| (name.as_str(), *code, report.as_ref().map(|report| runs::index_entry(report, ""))))
+        .collect();
+    let width = results.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0).max(3);
+    let number = |value: &Value, decimals: usize| value.as_f64().map(|n| format!("{:.*}", decimals, n)).unwrap_or_else(|| "-".to_string());
+    let baseline = results.first().and_then(|(_, _, entry)| entry.as_ref()).and_then(|entry| entry["achieved_rate"].as_f64());
+    println!();
+    println!("{:<width$} {:>6} {:>12} {:>10} {:>8} {:>10} {:>10} {:>9} {:>8}",
+             "run", "exit", "rows sent", "rows/s", "vs first", "target/s", "steady/s", "flush p99", "errors", width = width);
+    for (name, code, entry) in results.iter() {
+        let exit = code.map(|code| code.to_string()).unwrap_or_else(|| "killed".to_string());
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                println!("{:<width$} {:>6} {:>12}", name, exit, "no report", width = width);
+                continue;
+            }
+        };
+        let versus = match (entry["achieved_rate"].as_f64(), baseline) {
+            (Some(rate), Some(baseline)) if baseline > 0.0 => format!("{:+.1}%", (rate / baseline - 1.0) * 100.0),
+            _ => "-".to_string(),
+        };
+        println!("{:<width$} {:>6} {:>12} {:>10} {:>8} {:>10} {:>10} {:>9} {:>8}",
+                 name, exit, thousands(entry["rows_sent"].as_u64().unwrap_or(0)), number(&entry["achieved_rate"], 1), versus,
+                 number(&entry["target_rate"], 1), number(&entry["steady_rate"], 1),
+                 entry["flush_p99_ms"].as_f64().map(|ms| format!("{:.1} ms", ms)).unwrap_or_else(|| "-".to_string()),
+                 thousands(entry["errors"].as_u64().unwrap_or(0)), width = width);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_get_the_suite_args_and_are_checked_first() {
+        let suite: Suite = toml::from_str(r#"
+            args = ["--connection-string", "http::addr=localhost:9000;", "--table-name", "bench", "--plane-count", "10", "--rate-per-plane", "10", "--duration", "5"]
+            [[runs]]
+            name = "batch 100"
+            args = ["--batch-size", "100"]
+            [[runs]]
+            name = "4 senders"
+            args = ["--senders", "4"]
+        "#).unwrap();
+        let args = plan(&suite).unwrap();
+        assert_eq!(&args[0][10..], ["--batch-size", "100"]);
+        assert_eq!(args[1][..10], args[0][..10]);
+        assert_eq!(report_path(Path::new("out"), 1, "Batch 1000, 4 senders"), Path::new("out/02-batch-1000-4-senders.json"));
+
+        let mut failing = suite;
+        failing.runs[1].args = vec!["--senders".to_string(), "0".to_string()];
+        assert!(plan(&failing).unwrap_err().starts_with("run '4 senders': --senders must be at least 1"));
+        failing.runs[1].args = vec!["--report-json=x.json".to_string()];
+        assert!(plan(&failing).unwrap_err().contains("cannot be used in a suite"));
+        failing.runs[1].args = vec!["--batch-sise".to_string(), "4".to_string()];
+        assert!(plan(&failing).unwrap_err().starts_with("run '4 senders'"));
+    }
+
+    #[test]
+    fn yaml_suites_read_like_toml_ones() {
+        let suite = parse(Path::new("suite.yaml"), r#"
+args: ["--table-name", "bench"]
+pause_secs: 5
+runs:
+  - name: batch 100
+    args: ["--batch-size", "100"]
+  - name: defaults
+"#).unwrap();
+        assert_eq!(suite.args, ["--table-name", "bench"]);
+        assert_eq!(suite.pause_secs, 5.0);
+        assert_eq!(suite.runs[0].args, ["--batch-size", "100"]);
+        assert!(suite.runs[1].args.is_empty());
+        assert!(parse(Path::new("suite.yml"), "runs: []
+batch: 1
+").unwrap_err().contains("unknown field"));
+        assert!(parse(Path::new("suite.toml"), "args: []
+").is_err());
+    }
+}
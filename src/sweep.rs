@@ -0,0 +1,173 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use serde_json::Value;
+
+use crate::suite;
+
+/// A parameter of --sweep and the values it takes, one run each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    /// Name of the option, as given, e.g. batch_size
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl FromStr for Sweep {
+    type Err = String;
+
+    /// "batch_size=100,1000,10000"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, values) = s.split_once('=')
+            .ok_or_else(|| format!("invalid sweep '{}', expected NAME=VALUES such as batch_size=100,1000,10000", s))?;
+        let name = name.trim().trim_start_matches("--");
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("invalid sweep parameter '{}', use the name of an option such as batch_size", name));
+        }
+        let values: Vec<String> = values.split(',').map(|value| value.trim().to_string()).collect();
+        if values.iter().any(String::is_empty) {
+            return Err(format!("sweep '{}' has an empty value", s));
+        }
+        Ok(Sweep { name: name.to_string(), values })
+    }
+}
+
+impl Sweep {
+    /// The command line option the sweep sets
+    fn flag(&self) -> String {
+        format!("--{}", self.name.replace('_', "-"))
+    }
+}
+
+/// The arguments of the simulator, without the --sweep ones
+fn base_args(args: &[String]) -> Vec<String> {
+    let mut base = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sweep" | "--sweep-csv" => {
+                args.next();
+            }
+            _ if arg.starts_with("--sweep=") || arg.starts_with("--sweep-csv=") => {}
+            _ => base.push(arg.clone()),
+        }
+    }
+    base
+}
+
+/// Every combination of the values of the sweeps, the first one changing slowest
+fn combinations(sweeps: &[Sweep]) -> Vec<Vec<&str>> {
+    sweeps.iter().fold(vec![vec![]], |combinations, sweep| {
+        combinations.iter().flat_map(|combination| sweep.values.iter().map(move |value| {
+            let mut combination = combination.clone();
+            combination.push(value.as_str());
+            combination
+        })).collect()
+    })
+}
+
+/// The arguments of each combination, checked before the first one runs
+fn plan(sweeps: &[Sweep], args: &[String]) -> Result<Vec<(String, Vec<String>)>, String> {
+    let base = base_args(args);
+    for sweep in sweeps.iter() {
+        let flag = sweep.flag();
+        if base.iter().any(|arg| *arg == flag || arg.starts_with(&format!("{}=", flag))) {
+            return Err(format!("--sweep {} also sets {}, leave it out of the other arguments", sweep.name, flag));
+        }
+    }
+    combinations(sweeps).into_iter().map(|values| {
+        let name = sweeps.iter().zip(values.iter()).map(|(sweep, value)| format!("{}={}", sweep.name, value)).collect::<Vec<_>>().join(" ");
+        let mut args = base.clone();
+        for (sweep, value) in sweeps.iter().zip(values.iter()) {
+            args.extend([sweep.flag(), value.to_string()]);
+        }
+        suite::check(&args).map_err(|e| format!("--sweep {}: {}", name, e))?;
+        Ok((name, args))
+    }).collect()
+}
+
+/// The latency percentile of a report, in ms
+fn latency(report: &Value, name: &str, percentile: &str) -> String {
+    report["latencies"].as_array()
+        .and_then(|latencies| latencies.iter().find(|latency| latency["name"] == name))
+        .and_then(|latency| latency[percentile].as_f64())
+        .map(|ms| format!("{:.3}", ms))
+        .unwrap_or_default()
+}
+
+/// One line per combination: the values of the sweeps, then the throughput and latencies
+fn csv(sweeps: &[Sweep], results: &[(String, Option<i32>, Option<Value>)]) -> String {
+    let mut text = sweeps.iter().map(|sweep| sweep.name.clone()).collect::<Vec<_>>().join(",");
+    text.push_str(",exit_code,rows_sent,achieved_rate,target_rate,steady_rate,flush_p50_ms,flush_p99_ms,end_to_end_p50_ms,end_to_end_p99_ms,errors\n");
+    let values = combinations(sweeps);
+    for ((_, code, report), values) in results.iter().zip(values.iter()) {
+        let code = code.map(|code| code.to_string()).unwrap_or_default();
+        let _ = write!(text, "{},{}", values.join(","), code);
+        match report {
+            Some(report) => {
+                let entry = crate::runs::index_entry(report, "");
+                let number = |value: &Value| value.as_f64().map(|n| format!("{:.1}", n)).unwrap_or_default();
+                let _ = writeln!(text, ",{},{},{},{},{},{},{},{},{}",
+                                 entry["rows_sent"], number(&entry["achieved_rate"]), number(&entry["target_rate"]), number(&entry["steady_rate"]),
+                                 latency(report, "flush", "p50_ms"), latency(report, "flush", "p99_ms"),
+                                 latency(report, "end-to-end", "p50_ms"), latency(report, "end-to-end", "p99_ms"), entry["errors"]);
+            }
+            None => text.push_str(",,,,,,,,,\n"),
+        }
+    }
+    text
+}
+
+/// Directory for the report of each combination, next to the CSV: `sweep-reports` for `sweep.csv`
+fn reports_dir(csv_path: &Path) -> PathBuf {
+    let stem = csv_path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_else(|| "sweep".to_string());
+    csv_path.with_file_name(format!("{}-reports", stem))
+}
+
+/// Runs the simulator once for each combination of the --sweep values, each for
+/// --duration, and writes their throughput and latencies to `csv_path`.
+pub fn run(sweeps: &[Sweep], csv_path: &Path) -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let plan = plan(sweeps, &args)?;
+    let dir = reports_dir(csv_path);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    let mut results = vec![];
+    for (n, (name, args)) in plan.iter().enumerate() {
+        eprintln!("=== Sweep {}/{}: {}", n + 1, plan.len(), name);
+        let (code, report) = suite::run_one(args, &suite::report_path(&dir, n, name)).map_err(|e| format!("cannot start {}: {}", name, e))?;
+        results.push((name.clone(), code, report));
+    }
+    suite::print_comparison(&results);
+    std::fs::write(csv_path, csv(sweeps, &results)).map_err(|e| format!("cannot write {}: {}", csv_path.display(), e))?;
+    println!("Sweep results written to {}", csv_path.display());
+    suite::failures(&results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn every_combination_runs_with_the_other_arguments() {
+        let sweeps: Vec<Sweep> = ["batch_size=100,1000", "--senders=1, 4 ,8"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(sweeps[1], Sweep { name: "senders".to_string(), values: vec!["1".to_string(), "4".to_string(), "8".to_string()] });
+        let given = args("--connection-string http::addr=localhost:9000; --table-name bench --plane-count 10 --rate-per-plane 10 \
+                          --duration 5 --sweep batch_size=100,1000 --sweep=senders=1,4,8 --sweep-csv out.csv");
+        let runs = plan(&sweeps, &given).unwrap();
+        assert_eq!(runs.len(), 6);
+        assert_eq!(runs[1].0, "batch_size=100 senders=4");
+        assert_eq!(&runs[1].1[10..], args("--batch-size 100 --senders 4"));
+        assert_eq!(runs[5].0, "batch_size=1000 senders=8");
+
+        let twice = args("--connection-string http::addr=localhost:9000; --table-name bench --plane-count 10 --rate-per-plane 10 --duration 5 --batch-size 5");
+        assert!(plan(&sweeps, &twice).unwrap_err().contains("also sets --batch-size"));
+        let unknown: Vec<Sweep> = vec!["batch_sise=1,2".parse().unwrap()];
+        assert!(plan(&unknown, &given[..10]).unwrap_err().starts_with("--sweep batch_sise=1"));
+        assert!("batch_size".parse::<Sweep>().is_err() && "batch_size=1,,2".parse::<Sweep>().is_err());
+        assert_eq!(reports_dir(Path::new("results/sweep.csv")), Path::new("results/sweep-reports"));
+    }
+}
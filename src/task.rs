@@ -0,0 +1,153 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use questdb::ingress::Buffer;
+
+use crate::{Plane, RunContext};
+use crate::auto_flush::Batch;
+use crate::blocking::{self, Connection};
+use crate::memory::BufferFull;
+use crate::type_flip::FlipType;
+
+/// How a generation loop flushes: through the sink on the async runtime, or on a
+/// connection of the thread's own for --sync and --backfill-workers.
+pub enum Engine<'a> {
+    Async,
+    Blocking(&'a mut Connection),
+}
+
+impl Engine<'_> {
+    /// Whether the server is known to be down. Only the async engine has the health
+    /// watcher, the blocking threads keep the rows buffered and retry them on the next flush.
+    fn server_down(&self, ctx: &RunContext) -> bool {
+        matches!(self, Engine::Async) && ctx.health.is_down()
+    }
+
+    async fn wait_until_up(&self, ctx: &RunContext) {
+        if let Engine::Async = self {
+            ctx.health.wait_until_up(Duration::from_secs(ctx.opt.restart_timeout_secs)).await;
+        }
+    }
+
+    /// Flushes the buffer and counts the outcome against `planes`. Tells whether the server
+    /// accepted the rows.
+    async fn flush(&mut self, ctx: &RunContext, buffer: &mut Buffer, name: &str, planes: &mut [Plane], flip: Option<FlipType>) -> bool {
+        match self {
+            Engine::Async => crate::flush(ctx, buffer, name, planes, flip).await,
+            Engine::Blocking(connection) => blocking::flush(ctx, connection, buffer, name, planes, flip),
+        }
+    }
+}
+
+/// What a task writes the rows of: the planes, or the devices of a --model.
+pub trait Producers {
+    /// The planes whose rows the flushes count, none for devices, which count their own
+    /// with `flushed`
+    fn planes(&mut self) -> &mut [Plane];
+
+    /// Writes the next row of the `i`th into the buffer, and tells whether it is in
+    fn write_row(&mut self, ctx: &RunContext, i: usize, buffer: &mut Buffer, flip: Option<FlipType>) -> bool;
+
+    /// Counts a row of the `i`th that did not fit while the server is down
+    fn drop_row(&mut self, ctx: &RunContext, i: usize);
+
+    /// Counts a flush that was `accepted`, or that failed and `emptied` the buffer or not
+    fn flushed(&mut self, _accepted: bool, _emptied: bool) {}
+}
+
+impl Producers for [Plane] {
+    fn planes(&mut self) -> &mut [Plane] {
+        self
+    }
+
+    fn write_row(&mut self, ctx: &RunContext, i: usize, buffer: &mut Buffer, flip: Option<FlipType>) -> bool {
+        let written = self[i].generate_row(ctx, buffer, flip);
+        if written {
+            self[i].buffered += 1;
+        }
+        written
+    }
+
+    fn drop_row(&mut self, ctx: &RunContext, i: usize) {
+        self[i].stats.rows_dropped += 1;
+        ctx.rows_dropped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// The buffer of a generation task and its batch. Every loop, async, blocking, backfill
+/// and --model alike, writes each row and flushes with `write`, and the rows left at the
+/// end with `finish`.
+pub struct TaskBuffer {
+    buffer: Buffer,
+    /// Bytes of the buffer counted against the memory limits
+    accounted: usize,
+    batch: Batch,
+    rows_written: u64,
+    /// --type-flip-every flip of the batch being written
+    flip: Option<FlipType>,
+}
+
+impl TaskBuffer {
+    pub fn new(ctx: &RunContext) -> Self {
+        TaskBuffer { buffer: ctx.pool.empty(), accounted: 0, batch: Batch::new(), rows_written: 0, flip: None }
+    }
+
+    /// Rows written into the buffer so far
+    pub fn rows_written(&self) -> u64 {
+        self.rows_written
+    }
+
+    /// Writes the next row of the `i`th of `producers` and flushes the buffer when the batch
+    /// is due, when the memory limits are hit, or when it is the `last_row` of the run. Over
+    /// the memory limits with the server down, flushing would not help: the loop waits for
+    /// the server with --on-buffer-full block, otherwise the row is dropped.
+    pub async fn write<P: Producers + ?Sized>(&mut self, ctx: &RunContext, engine: &mut Engine<'_>, name: &str, producers: &mut P, i: usize, last_row: bool) {
+        if engine.server_down(ctx) && ctx.memory.exceeded(self.buffer.len()) && ctx.opt.on_buffer_full == BufferFull::Block {
+            engine.wait_until_up(ctx).await;
+            self.flush(ctx, engine, name, producers).await;
+        }
+        if engine.server_down(ctx) && ctx.memory.exceeded(self.buffer.len()) {
+            producers.drop_row(ctx, i);
+            return;
+        }
+        if self.buffer.capacity() == 0 {
+            self.buffer = ctx.pool.take();
+        }
+        self.flip = ctx.type_flips.as_ref().and_then(|flips| flips.flip_for(self.rows_written / ctx.opt.batch_size as u64));
+        if !producers.write_row(ctx, i, &mut self.buffer, self.flip) {
+            return;
+        }
+        self.rows_written += 1;
+        ctx.memory.track(&mut self.accounted, self.buffer.len());
+        let batch_started = self.batch.started();
+        if ctx.auto_flush.due(&mut self.batch, last_row || ctx.memory.exceeded(self.buffer.len())) {
+            if self.flush(ctx, engine, name, producers).await {
+                ctx.latencies.record_end_to_end(batch_started.elapsed());
+            }
+            if self.buffer.is_empty() {
+                ctx.pool.give(std::mem::replace(&mut self.buffer, ctx.pool.empty()));
+            }
+        }
+    }
+
+    /// Flushes the rows left once the loop is over, waiting for the server if it is
+    /// restarting, and gives the buffer back to the pool.
+    pub async fn finish<P: Producers + ?Sized>(&mut self, ctx: &RunContext, engine: &mut Engine<'_>, name: &str, producers: &mut P) {
+        if !self.buffer.is_empty() {
+            engine.wait_until_up(ctx).await;
+            self.flush(ctx, engine, name, producers).await;
+            if !self.buffer.is_empty() {
+                eprintln!("{} could not send its last {} rows", name, self.buffer.row_count());
+            }
+        }
+        if self.buffer.is_empty() && self.buffer.capacity() > 0 {
+            ctx.pool.give(std::mem::replace(&mut self.buffer, ctx.pool.empty()));
+        }
+    }
+
+    async fn flush<P: Producers + ?Sized>(&mut self, ctx: &RunContext, engine: &mut Engine<'_>, name: &str, producers: &mut P) -> bool {
+        let accepted = engine.flush(ctx, &mut self.buffer, name, producers.planes(), self.flip).await;
+        producers.flushed(accepted, self.buffer.is_empty());
+        ctx.memory.track(&mut self.accounted, self.buffer.len());
+        accepted
+    }
+}
@@ -0,0 +1,6 @@
+//! Helpers shared by the unit tests of several modules
+
+/// 2024-06-03, a Monday, at `hours` UTC
+pub fn monday_at(hours: f64) -> i64 {
+    1_717_372_800_000_000_000 + (hours * 3.6e12) as i64
+}
@@ -0,0 +1,227 @@
+use chrono::{Datelike, Timelike, Weekday};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand_distr::{LogNormal, StandardNormal};
+
+use questdb::ingress::{Buffer, TimestampNanos};
+
+use crate::model::{Device, DeviceModel};
+use crate::scaffold::Bundle;
+
+/// Where an instrument trades, with its session in minutes of the day in UTC, none for
+/// the markets that trade around the clock.
+#[derive(Debug, Clone, Copy)]
+struct Exchange {
+    name: &'static str,
+    session: Option<(f64, f64)>,
+}
+
+const NASDAQ: Exchange = Exchange { name: "NASDAQ", session: Some((14.5 * 60.0, 21.0 * 60.0)) };
+const NYSE: Exchange = Exchange { name: "NYSE", session: Some((14.5 * 60.0, 21.0 * 60.0)) };
+const LSE: Exchange = Exchange { name: "LSE", session: Some((8.0 * 60.0, 16.5 * 60.0)) };
+const CRYPTO: Exchange = Exchange { name: "CRYPTO", session: None };
+const FX: Exchange = Exchange { name: "FX", session: None };
+
+/// An instrument: its symbol and exchange, the price it starts near, its tick size and
+/// lot size, and its daily volatility.
+struct Instrument {
+    symbol: &'static str,
+    exchange: Exchange,
+    price: f64,
+    tick: f64,
+    lot: f64,
+    daily_vol: f64,
+}
+
+const INSTRUMENTS: &[Instrument] = &[
+    Instrument { symbol: "AAPL", exchange: NASDAQ, price: 190.0, tick: 0.01, lot: 1.0, daily_vol: 0.015 },
+    Instrument { symbol: "MSFT", exchange: NASDAQ, price: 410.0, tick: 0.01, lot: 1.0, daily_vol: 0.014 },
+    Instrument { symbol: "NVDA", exchange: NASDAQ, price: 120.0, tick: 0.01, lot: 1.0, daily_vol: 0.03 },
+    Instrument { symbol: "TSLA", exchange: NASDAQ, price: 250.0, tick: 0.01, lot: 1.0, daily_vol: 0.035 },
+    Instrument { symbol: "JPM", exchange: NYSE, price: 200.0, tick: 0.01, lot: 1.0, daily_vol: 0.012 },
+    Instrument { symbol: "XOM", exchange: NYSE, price: 115.0, tick: 0.01, lot: 1.0, daily_vol: 0.013 },
+    Instrument { symbol: "VOD.L", exchange: LSE, price: 70.0, tick: 0.02, lot: 1.0, daily_vol: 0.015 },
+    Instrument { symbol: "HSBA.L", exchange: LSE, price: 650.0, tick: 0.1, lot: 1.0, daily_vol: 0.012 },
+    Instrument { symbol: "BTC-USD", exchange: CRYPTO, price: 65000.0, tick: 0.01, lot: 0.0001, daily_vol: 0.03 },
+    Instrument { symbol: "ETH-USD", exchange: CRYPTO, price: 3200.0, tick: 0.01, lot: 0.001, daily_vol: 0.04 },
+    Instrument { symbol: "SOL-USD", exchange: CRYPTO, price: 150.0, tick: 0.001, lot: 0.01, daily_vol: 0.06 },
+    Instrument { symbol: "EUR-USD", exchange: FX, price: 1.085, tick: 0.00001, lot: 1000.0, daily_vol: 0.005 },
+    Instrument { symbol: "GBP-USD", exchange: FX, price: 1.27, tick: 0.00001, lot: 1000.0, daily_vol: 0.006 },
+    Instrument { symbol: "USD-JPY", exchange: FX, price: 150.0, tick: 0.001, lot: 1000.0, daily_vol: 0.006 },
+];
+
+/// Half-life of the bursts after the open and before the close, in minutes
+const BURST_MINUTES: f64 = 15.0;
+
+/// Share of the peak rate an exchange trades at, at a time: bursts after the open and
+/// before the close over a quieter middle of the day, a trickle of extended hours
+/// trading when it is closed and at weekends, and an even flow on the markets that never
+/// close.
+fn activity(exchange: &Exchange, timestamp: i64) -> f64 {
+    let Some((open, close)) = exchange.session else { return 0.5 };
+    let time = chrono::DateTime::from_timestamp_nanos(timestamp);
+    let minute = time.hour() as f64 * 60.0 + time.minute() as f64 + time.second() as f64 / 60.0;
+    if matches!(time.weekday(), Weekday::Sat | Weekday::Sun) || minute < open || minute >= close {
+        return 0.03;
+    }
+    let burst = |minutes: f64| 0.5f64.powf(minutes / BURST_MINUTES);
+    (0.25 + 0.75 * (burst(minute - open) + burst(close - minute))).min(1.0)
+}
+
+/// `value` to the nearest multiple of `step`, dividing rather than multiplying so that
+/// prices print as the decimals they are
+fn round_to(value: f64, step: f64) -> f64 {
+    let per_unit = (1.0 / step).round();
+    (value * per_unit).round() / per_unit
+}
+
+/// Table, queries and dashboard of `airplanes scaffold --model ticks`
+pub const BUNDLE: Bundle = Bundle {
+    key: "symbol",
+    symbols: &["symbol", "exchange", "side"],
+    columns: &[("bid", "DOUBLE"), ("ask", "DOUBLE"), ("price", "DOUBLE"), ("size", "DOUBLE")],
+    queries: &[
+        ("Latest quote of every instrument",
+         "SELECT symbol, exchange, bid, ask, price, timestamp\nFROM {table}\nLATEST ON timestamp PARTITION BY symbol;"),
+        ("One minute OHLC bars with volume for BTC-USD",
+         "SELECT timestamp, first(price) open, max(price) high, min(price) low, last(price) close, sum(size) volume\nFROM {table}\nWHERE symbol = 'BTC-USD'\nSAMPLE BY 1m;"),
+        ("VWAP of each instrument, per minute",
+         "SELECT timestamp, symbol, sum(price * size) / sum(size) vwap\nFROM {table}\nSAMPLE BY 1m;"),
+        ("Average spread of each instrument, in basis points of the mid price",
+         "SELECT symbol, avg((ask - bid) / ((ask + bid) / 2) * 10000) spread_bps, count() trades\nFROM {table}\nORDER BY spread_bps DESC;"),
+        ("Trades per exchange and hour, the bursts at the open and close",
+         "SELECT timestamp, exchange, count() trades\nFROM {table}\nSAMPLE BY 1h;"),
+    ],
+    panels: &[
+        ("BTC-USD", "SELECT timestamp, last(price) price, min(bid) bid, max(ask) ask FROM {table} WHERE $__timeFilter(timestamp) AND symbol = 'BTC-USD' SAMPLE BY $__interval"),
+        ("Volume", "SELECT timestamp, sum(size * price) notional FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+    ],
+    map: None,
+};
+
+/// Market data, `--model ticks`: trades of equities on NASDAQ, NYSE and the LSE, crypto
+/// pairs and FX rates, each a row with the quote at the time. The mid price follows a
+/// random walk at the volatility of the instrument, the spread is a few ticks and wider
+/// when the market is busy, and trades go through at the bid or the ask, so prices
+/// bounce between the two like real prints. Equities trade in bursts after the open and
+/// before the close of their exchange.
+pub struct Ticks;
+
+impl DeviceModel for Ticks {
+    /// The symbols of the instruments, then the same ones again with a suffix, AAPL_1...
+    fn device_id(&self, n: u64) -> String {
+        let instrument = &INSTRUMENTS[n as usize % INSTRUMENTS.len()];
+        match n as usize / INSTRUMENTS.len() {
+            0 => instrument.symbol.to_string(),
+            copy => format!("{}_{}", instrument.symbol, copy),
+        }
+    }
+
+    fn device(&self, id: &str, seed: u64, timestamp: i64) -> Box<dyn Device> {
+        Box::new(Ticker::new(id, seed, timestamp))
+    }
+}
+
+struct Ticker {
+    symbol: String,
+    instrument: &'static Instrument,
+    rng: StdRng,
+    timestamp: i64,
+    mid: f64,
+    bid: f64,
+    ask: f64,
+    /// The last trade: at the ask for a buyer, at the bid for a seller
+    buy: bool,
+    price: f64,
+    size: f64,
+}
+
+impl Ticker {
+    fn new(id: &str, seed: u64, timestamp: i64) -> Self {
+        let base = id.split('_').next().unwrap_or(id);
+        let instrument = INSTRUMENTS.iter().find(|instrument| instrument.symbol == base).unwrap_or(&INSTRUMENTS[0]);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mid = instrument.price * rng.gen_range(0.9..1.1);
+        let mut ticker = Ticker { symbol: id.to_string(), instrument, timestamp, mid, bid: mid, ask: mid, buy: true, price: mid, size: 0.0, rng };
+        ticker.update(timestamp);
+        ticker
+    }
+}
+
+impl Device for Ticker {
+    fn has_row(&mut self, timestamp: i64) -> bool {
+        let activity = activity(&self.instrument.exchange, timestamp);
+        self.rng.gen_bool(activity)
+    }
+
+    fn update(&mut self, timestamp: i64) {
+        let dt = ((timestamp - self.timestamp) as f64 / 1e9).clamp(0.0, 60.0);
+        self.timestamp = timestamp;
+        let instrument = self.instrument;
+        let busy = activity(&instrument.exchange, timestamp);
+        // Busier markets move more, the volatility of the day spread over its seconds
+        let z: f64 = self.rng.sample(StandardNormal);
+        let sigma = instrument.daily_vol * (dt / 86400.0).sqrt() * (0.5 + busy);
+        self.mid = (self.mid * (sigma * z).exp()).max(instrument.tick * 10.0);
+        let spread_ticks = (1.0 + self.rng.gen_range(0.0..2.0) * (1.0 + 2.0 * busy)).round();
+        let spread = spread_ticks * instrument.tick;
+        self.bid = round_to(self.mid - spread / 2.0, instrument.tick);
+        self.ask = round_to(self.bid + spread, instrument.tick);
+        self.buy = self.rng.gen_bool(0.5);
+        self.price = if self.buy { self.ask } else { self.bid };
+        // Mostly small orders, now and then a block
+        let lots: f64 = self.rng.sample(LogNormal::new(3.0, 1.2).unwrap());
+        self.size = round_to(lots.max(1.0), 1.0) * instrument.lot;
+    }
+
+    fn write(&self, buffer: &mut Buffer, table_name: &str) -> questdb::Result<()> {
+        buffer.table(table_name)?
+            .symbol("symbol", &self.symbol)?
+            .symbol("exchange", self.instrument.exchange.name)?
+            .symbol("side", if self.buy { "buy" } else { "sell" })?
+            .column_f64("bid", self.bid)?
+            .column_f64("ask", self.ask)?
+            .column_f64("price", self.price)?
+            .column_f64("size", self.size)?
+            .at(TimestampNanos::new(self.timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::monday_at;
+
+    #[test]
+    fn equities_trade_in_bursts_at_the_open_and_close() {
+        let open = activity(&NYSE, monday_at(14.5));
+        let midday = activity(&NYSE, monday_at(17.75));
+        let close = activity(&NYSE, monday_at(20.99));
+        assert!(open > 0.9 && close > 0.9, "open {}, close {}", open, close);
+        assert!(midday < 0.4, "midday {}", midday);
+        assert!(activity(&NYSE, monday_at(3.0)) < 0.05);
+        // Saturday
+        assert!(activity(&NYSE, monday_at(5.0 * 24.0 + 15.0)) < 0.05);
+        assert_eq!(activity(&CRYPTO, monday_at(5.0 * 24.0 + 15.0)), 0.5);
+    }
+
+    /// Over a trading day of every instrument, the quotes stay on the tick grid with the
+    /// bid below the ask, and trades go through at one of the two
+    #[test]
+    fn trades_go_through_at_the_quote() {
+        for n in 0..INSTRUMENTS.len() as u64 * 2 {
+            let mut ticker = Ticker::new(&Ticks.device_id(n), n, monday_at(14.5));
+            let tick = ticker.instrument.tick;
+            for second in (5..=23_400).step_by(5) {
+                ticker.update(monday_at(14.5) + second * 1_000_000_000);
+                assert!(ticker.bid < ticker.ask, "{}: bid {} ask {}", ticker.symbol, ticker.bid, ticker.ask);
+                assert!(((ticker.ask - ticker.bid) / tick).round() <= 7.0, "{}: spread {}", ticker.symbol, ticker.ask - ticker.bid);
+                assert!(ticker.price == ticker.bid || ticker.price == ticker.ask);
+                assert!(((ticker.bid / tick) - (ticker.bid / tick).round()).abs() < 1e-6, "{}: bid {} off the grid", ticker.symbol, ticker.bid);
+                assert!(ticker.size > 0.0);
+            }
+            let moved = ticker.mid / ticker.instrument.price;
+            assert!((0.5..2.0).contains(&moved), "{} moved {}x in a day", ticker.symbol, moved);
+        }
+    }
+}
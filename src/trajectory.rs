@@ -0,0 +1,167 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+
+use crate::plane::PlaneData;
+
+const FEET_TO_METERS: f64 = 0.3048;
+
+/// File format of the trajectory export, from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrajectoryFormat {
+    GeoJson,
+    Kml,
+}
+
+impl TrajectoryFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|extension| extension.to_str()).map(str::to_lowercase).as_deref() {
+            Some("geojson") | Some("json") => Ok(TrajectoryFormat::GeoJson),
+            Some("kml") => Ok(TrajectoryFormat::Kml),
+            _ => Err(format!("cannot tell the format of {}, use a .geojson or .kml file", path.display())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    longitude: f64,
+    latitude: f64,
+    altitude_m: f64,
+    timestamp: i64,
+}
+
+/// Positions of one plane during one flight leg.
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    plane_id: String,
+    callsign: String,
+    points: Vec<Point>,
+}
+
+/// Samples the position of a plane every `--trajectory-every-secs`, starting a new
+/// trajectory on every flight leg.
+#[derive(Debug)]
+pub struct TrackRecorder {
+    every_nanos: i64,
+    last_sample: Option<i64>,
+    legs: Vec<Trajectory>,
+}
+
+impl TrackRecorder {
+    pub fn new(every_secs: f64) -> Self {
+        TrackRecorder { every_nanos: (every_secs * 1e9) as i64, last_sample: None, legs: vec![] }
+    }
+
+    pub fn record(&mut self, plane: &PlaneData) {
+        let new_leg = self.legs.last().map(|leg| leg.callsign != plane.callsign).unwrap_or(true);
+        if !new_leg && self.last_sample.map(|last| plane.timestamp - last < self.every_nanos).unwrap_or(false) {
+            return;
+        }
+        if new_leg {
+            self.legs.push(Trajectory { plane_id: plane.plane_id.clone(), callsign: plane.callsign.clone(), points: vec![] });
+        }
+        self.last_sample = Some(plane.timestamp);
+        if let Some(leg) = self.legs.last_mut() {
+            leg.points.push(Point {
+                longitude: plane.longitude,
+                latitude: plane.latitude,
+                altitude_m: plane.altitude * FEET_TO_METERS,
+                timestamp: plane.timestamp,
+            });
+        }
+    }
+}
+
+/// Trajectories of all planes, collected as they finish and written at the end of the run.
+#[derive(Debug)]
+pub struct Trajectories {
+    path: PathBuf,
+    format: TrajectoryFormat,
+    every_secs: f64,
+    collected: Mutex<Vec<Trajectory>>,
+}
+
+impl Trajectories {
+    pub fn new(path: PathBuf, format: TrajectoryFormat, every_secs: f64) -> Self {
+        Trajectories { path, format, every_secs, collected: Mutex::new(vec![]) }
+    }
+
+    pub fn recorder(&self) -> TrackRecorder {
+        TrackRecorder::new(self.every_secs)
+    }
+
+    pub fn collect(&self, recorder: TrackRecorder) {
+        self.collected.lock().unwrap().extend(recorder.legs);
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        let mut trajectories = self.collected.lock().unwrap();
+        trajectories.sort_by(|a, b| a.plane_id.cmp(&b.plane_id));
+        let mut out = BufWriter::new(File::create(&self.path)?);
+        match self.format {
+            TrajectoryFormat::GeoJson => write_geojson(&mut out, &trajectories)?,
+            TrajectoryFormat::Kml => write_kml(&mut out, &trajectories)?,
+        }
+        out.flush()
+    }
+}
+
+fn iso_time(timestamp: i64) -> String {
+    Utc.timestamp_nanos(timestamp).format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// One LineString feature per flight leg, with the time of each point in the properties.
+fn write_geojson(out: &mut impl Write, trajectories: &[Trajectory]) -> io::Result<()> {
+    let features: Vec<_> = trajectories.iter().filter(|leg| !leg.points.is_empty()).map(|leg| {
+        let coordinates: Vec<_> = leg.points.iter().map(|p| json!([p.longitude, p.latitude, p.altitude_m])).collect();
+        let times: Vec<_> = leg.points.iter().map(|p| iso_time(p.timestamp)).collect();
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "LineString", "coordinates": coordinates },
+            "properties": {
+                "plane_id": leg.plane_id,
+                "callsign": leg.callsign,
+                "start": times.first(),
+                "end": times.last(),
+                "times": times,
+            },
+        })
+    }).collect();
+    serde_json::to_writer(&mut *out, &json!({ "type": "FeatureCollection", "features": features }))?;
+    writeln!(out)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// One placemark per flight leg, with absolute altitudes so the paths are drawn in 3D.
+fn write_kml(out: &mut impl Write, trajectories: &[Trajectory]) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+    writeln!(out, "<Document>")?;
+    writeln!(out, "<name>airplanes trajectories</name>")?;
+    for leg in trajectories.iter().filter(|leg| !leg.points.is_empty()) {
+        writeln!(out, "<Placemark>")?;
+        writeln!(out, "<name>{} {}</name>", escape_xml(&leg.plane_id), escape_xml(&leg.callsign))?;
+        if let (Some(first), Some(last)) = (leg.points.first(), leg.points.last()) {
+            writeln!(out, "<TimeSpan><begin>{}</begin><end>{}</end></TimeSpan>", iso_time(first.timestamp), iso_time(last.timestamp))?;
+        }
+        writeln!(out, "<LineString><altitudeMode>absolute</altitudeMode><coordinates>")?;
+        for p in leg.points.iter() {
+            writeln!(out, "{:.6},{:.6},{:.1}", p.longitude, p.latitude, p.altitude_m)?;
+        }
+        writeln!(out, "</coordinates></LineString>")?;
+        writeln!(out, "</Placemark>")?;
+    }
+    writeln!(out, "</Document>")?;
+    writeln!(out, "</kml>")
+}
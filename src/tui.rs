@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+#[cfg(feature = "tui")]
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+#[cfg(feature = "tui")]
+use std::{thread, time::Instant};
+#[cfg(feature = "tui")]
+use ratatui::{
+    DefaultTerminal,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Paragraph, Row, Table, TableState},
+};
+
+use crate::{Plane, RunContext};
+
+/// What the planes publish for the terminal UI, and the controls it sets for them. Built
+/// without the `tui` feature, --tui is refused and nothing reads the counters.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub struct Dashboard {
+    paused: AtomicBool,
+    /// Set when the user asks to stop, every plane then lands
+    quit: AtomicBool,
+    rate_per_plane: AtomicU64,
+    planes: Vec<PlaneSlot>,
+    slots: HashMap<String, usize>,
+    flush_nanos: AtomicU64,
+    flushes: AtomicU64,
+    closed: AtomicBool,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+struct PlaneSlot {
+    plane_id: String,
+    rows_generated: AtomicU64,
+    rows_flushed: AtomicU64,
+    buffered: AtomicU64,
+    /// Flush failures, rejected rows and row errors
+    errors: AtomicU64,
+    killed: AtomicBool,
+    stopped: AtomicBool,
+}
+
+impl Dashboard {
+    pub fn new(plane_ids: Vec<String>, rate_per_plane: u64) -> Self {
+        let slots = plane_ids.iter().enumerate().map(|(slot, plane_id)| (plane_id.clone(), slot)).collect();
+        Dashboard {
+            paused: AtomicBool::new(false),
+            quit: AtomicBool::new(false),
+            rate_per_plane: AtomicU64::new(rate_per_plane),
+            planes: plane_ids.into_iter().map(|plane_id| PlaneSlot { plane_id, ..PlaneSlot::default() }).collect(),
+            slots,
+            flush_nanos: AtomicU64::new(0),
+            flushes: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            thread: Mutex::new(None),
+        }
+    }
+
+    pub fn slot(&self, plane_id: &str) -> Option<usize> {
+        self.slots.get(plane_id).copied()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn tick_millis(&self) -> u64 {
+        crate::tick_millis(self.rate_per_plane.load(Ordering::Relaxed))
+    }
+
+    pub fn is_killed(&self, plane: &Plane) -> bool {
+        self.quit.load(Ordering::Relaxed) || plane.slot.map(|slot| self.planes[slot].killed.load(Ordering::Relaxed)).unwrap_or(false)
+    }
+
+    /// Copies the counters of a plane, for the next redraw.
+    pub fn publish(&self, plane: &Plane) {
+        if let Some(slot) = plane.slot.map(|slot| &self.planes[slot]) {
+            let stats = &plane.stats;
+            slot.rows_generated.store(stats.rows_generated, Ordering::Relaxed);
+            slot.rows_flushed.store(stats.rows_flushed, Ordering::Relaxed);
+            slot.buffered.store(plane.buffered + plane.backlog.row_count() as u64, Ordering::Relaxed);
+            slot.errors.store(stats.flush_failures + stats.rows_rejected + stats.row_errors, Ordering::Relaxed);
+            slot.stopped.store(plane.stopped, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_flush(&self, elapsed: Duration) {
+        self.flush_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Starts drawing on a thread of its own, so it works with both engines.
+    #[cfg(feature = "tui")]
+    pub fn open(ctx: Arc<RunContext>) {
+        let terminal = match ratatui::try_init() {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                eprintln!("Cannot start the terminal UI: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let thread_ctx = ctx.clone();
+        let thread = thread::Builder::new().name("tui".to_string()).spawn(move || {
+            if let Err(e) = run(&thread_ctx, terminal) {
+                ratatui::restore();
+                eprintln!("Terminal UI failed: {}", e);
+            }
+        });
+        match thread {
+            Ok(thread) => {
+                if let Some(dashboard) = &ctx.dashboard {
+                    *dashboard.thread.lock().unwrap() = Some(thread);
+                }
+            }
+            Err(e) => {
+                ratatui::restore();
+                eprintln!("Cannot start the terminal UI: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "tui"))]
+    pub fn open(_ctx: Arc<RunContext>) {
+        unreachable!("--tui is refused without the tui feature");
+    }
+
+    /// Stops drawing and gives the terminal back, before the final report is printed.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// State of the drawing thread between two frames.
+#[cfg(feature = "tui")]
+struct View {
+    table: TableState,
+    /// Rows generated by each plane at the last rate sample
+    last_rows: Vec<u64>,
+    last_total: u64,
+    last_flushes: (u64, u64),
+    sampled: Instant,
+    rates: Vec<f64>,
+    total_rate: f64,
+    latency_ms: Option<f64>,
+}
+
+#[cfg(feature = "tui")]
+const FRAME: Duration = Duration::from_millis(250);
+/// How often the whole screen is redrawn, to wipe messages printed over it
+#[cfg(feature = "tui")]
+const FULL_REDRAW_FRAMES: u32 = 4;
+
+#[cfg(feature = "tui")]
+fn run(ctx: &RunContext, mut terminal: DefaultTerminal) -> io::Result<()> {
+    let dashboard = match &ctx.dashboard {
+        Some(dashboard) => dashboard,
+        None => return Ok(()),
+    };
+    let mut view = View {
+        table: TableState::default().with_selected(Some(0)),
+        last_rows: vec![0; dashboard.planes.len()],
+        last_total: 0,
+        last_flushes: (0, 0),
+        sampled: Instant::now(),
+        rates: vec![0.0; dashboard.planes.len()],
+        total_rate: 0.0,
+        latency_ms: None,
+    };
+    let mut frames = 0u32;
+    let mut drawn: Option<Instant> = None;
+    while !dashboard.closed.load(Ordering::Relaxed) {
+        if view.sampled.elapsed() >= Duration::from_secs(1) {
+            sample(ctx, dashboard, &mut view);
+        }
+        if drawn.map(|drawn| drawn.elapsed() >= FRAME).unwrap_or(true) {
+            if frames.is_multiple_of(FULL_REDRAW_FRAMES) {
+                terminal.clear()?;
+            }
+            frames = frames.wrapping_add(1);
+            terminal.draw(|frame| draw(frame, ctx, dashboard, &mut view))?;
+            drawn = Some(Instant::now());
+        }
+
+        // Short, so closing at the end of the run does not wait for a whole frame
+        if !event::poll(Duration::from_millis(20))? {
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        let rate = dashboard.rate_per_plane.load(Ordering::Relaxed);
+        let step = (rate / 10).max(1);
+        match key.code {
+            // Lands every plane, the run then ends as if it was done
+            KeyCode::Char('q') | KeyCode::Esc => dashboard.quit.store(true, Ordering::Relaxed),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => dashboard.quit.store(true, Ordering::Relaxed),
+            KeyCode::Char('p') | KeyCode::Char(' ') => {
+                dashboard.paused.fetch_xor(true, Ordering::Relaxed);
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => set_rate(ctx, dashboard, rate + step),
+            KeyCode::Char('-') => set_rate(ctx, dashboard, rate.saturating_sub(step).max(1)),
+            KeyCode::Char('k') => {
+                if let Some(slot) = view.table.selected().and_then(|slot| dashboard.planes.get(slot)) {
+                    if !slot.killed.swap(true, Ordering::Relaxed) && !slot.stopped.load(Ordering::Relaxed) {
+                        ctx.rate.remove_plane();
+                    }
+                }
+            }
+            KeyCode::Down => view.table.select_next(),
+            KeyCode::Up => view.table.select_previous(),
+            KeyCode::PageDown => view.table.select(view.table.selected().map(|slot| slot + 20)),
+            KeyCode::PageUp => view.table.select(view.table.selected().map(|slot| slot.saturating_sub(20))),
+            _ => {}
+        }
+    }
+    ratatui::restore();
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn set_rate(ctx: &RunContext, dashboard: &Dashboard, rate: u64) {
+    dashboard.rate_per_plane.store(rate, Ordering::Relaxed);
+    ctx.rate.set_rate_per_plane(1000.0 / crate::tick_millis(rate) as f64);
+}
+
+/// Updates the rates and the flush latency, once a second.
+#[cfg(feature = "tui")]
+fn sample(ctx: &RunContext, dashboard: &Dashboard, view: &mut View) {
+    let secs = view.sampled.elapsed().as_secs_f64();
+    view.sampled = Instant::now();
+    for (i, slot) in dashboard.planes.iter().enumerate() {
+        let rows = slot.rows_generated.load(Ordering::Relaxed);
+        view.rates[i] = rows.saturating_sub(view.last_rows[i]) as f64 / secs;
+        view.last_rows[i] = rows;
+    }
+    let total = ctx.rows_generated();
+    view.total_rate = total.saturating_sub(view.last_total) as f64 / secs;
+    view.last_total = total;
+    let flushes = (dashboard.flush_nanos.load(Ordering::Relaxed), dashboard.flushes.load(Ordering::Relaxed));
+    if flushes.1 > view.last_flushes.1 {
+        view.latency_ms = Some((flushes.0 - view.last_flushes.0) as f64 / 1e6 / (flushes.1 - view.last_flushes.1) as f64);
+    }
+    view.last_flushes = flushes;
+}
+
+#[cfg(feature = "tui")]
+fn draw(frame: &mut ratatui::Frame, ctx: &RunContext, dashboard: &Dashboard, view: &mut View) {
+    let [header, table, footer] = Layout::vertical([Constraint::Length(5), Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+    let opt = &ctx.opt;
+    let rate = dashboard.rate_per_plane.load(Ordering::Relaxed);
+    let state = if dashboard.is_paused() { " [paused]" } else { "" };
+    let server = match (ctx.health.is_down(), view.latency_ms) {
+        (true, _) => "down, buffering".to_string(),
+        (false, Some(latency)) => format!("up, flush latency {:.1} ms", latency),
+        (false, None) => "up".to_string(),
+    };
+    let lines = vec![
+        Line::from(format!("{} planes at {} rows/s into {}{}", dashboard.planes.len(), rate, opt.table_name, state)),
+        Line::from(format!("Rate: {:.1} rows/s of {:.1} target   Generated: {}   Remaining: {}",
+                           view.total_rate, dashboard.planes.len() as f64 * 1000.0 / crate::tick_millis(rate) as f64,
+                           ctx.rows_generated(), match opt.total_rows_arg {
+                               Some(_) => ctx.quota.remaining().to_string(),
+                               None => "until --duration".to_string(),
+                           })),
+        Line::from(format!("Server: {}   Rejected: {}   Dropped: {}   Row errors: {}", server,
+                           ctx.rows_rejected.load(Ordering::Relaxed), ctx.rows_dropped.load(Ordering::Relaxed),
+                           ctx.row_errors.load(Ordering::Relaxed))),
+    ];
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" airplanes ")), header);
+
+    // Only the visible rows are built, there may be a hundred thousand planes
+    let visible = table.height.saturating_sub(3) as usize;
+    let selected = view.table.selected().unwrap_or(0).min(dashboard.planes.len().saturating_sub(1));
+    let first = selected.saturating_sub(visible.saturating_sub(1));
+    let rows = dashboard.planes.iter().enumerate().skip(first).take(visible.max(1)).map(|(i, slot)| {
+        let status = if slot.killed.load(Ordering::Relaxed) || dashboard.quit.load(Ordering::Relaxed) {
+            "killed"
+        } else if slot.stopped.load(Ordering::Relaxed) {
+            "stopped"
+        } else if dashboard.is_paused() {
+            "paused"
+        } else {
+            "flying"
+        };
+        Row::new(vec![
+            slot.plane_id.clone(),
+            status.to_string(),
+            slot.rows_generated.load(Ordering::Relaxed).to_string(),
+            slot.rows_flushed.load(Ordering::Relaxed).to_string(),
+            format!("{:.1}", view.rates[i]),
+            slot.buffered.load(Ordering::Relaxed).to_string(),
+            slot.errors.load(Ordering::Relaxed).to_string(),
+        ])
+    });
+    let widths = [
+        Constraint::Length(12), Constraint::Length(8), Constraint::Length(12), Constraint::Length(12),
+        Constraint::Length(10), Constraint::Length(10), Constraint::Length(8),
+    ];
+    let header_row = Row::new(vec!["plane", "status", "generated", "flushed", "rate/s", "buffered", "errors"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let planes = Table::new(rows, widths)
+        .header(header_row)
+        .block(Block::bordered().title(" planes "))
+        .row_highlight_style(Style::default().bg(Color::DarkGray));
+    let mut window = TableState::default().with_selected(Some(selected - first));
+    frame.render_stateful_widget(planes, table, &mut window);
+    view.table.select(Some(selected));
+
+    frame.render_widget(Paragraph::new("p pause/resume   +/- rate   k kill plane   up/down select   q stop and report"), footer);
+}
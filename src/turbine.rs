@@ -0,0 +1,236 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use questdb::ingress::{Buffer, TimestampNanos};
+
+use crate::model::{Device, DeviceModel};
+use crate::scaffold::Bundle;
+use crate::turbulence::OrnsteinUhlenbeck;
+
+/// Turbines per wind farm
+const FARM_SIZE: u64 = 20;
+
+/// Power curve of a 3 MW turbine: no power below the cut-in wind speed, growing with the
+/// cube of the wind up to the rated speed, rated power up to the cut-out speed, where it
+/// stops and feathers its blades until the wind drops below the restart speed.
+const CUT_IN: f64 = 3.0;
+const RATED_SPEED: f64 = 12.0;
+const CUT_OUT: f64 = 25.0;
+const RESTART: f64 = 20.0;
+const RATED_KW: f64 = 3000.0;
+const RATED_RPM: f64 = 15.0;
+
+/// What a turbine is doing, the `status` symbol.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    /// Wind below the cut-in speed
+    Idle,
+    Producing,
+    /// Stopped in a storm
+    CutOut,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Status::Idle => "idle",
+            Status::Producing => "producing",
+            Status::CutOut => "cut_out",
+        }
+    }
+}
+
+/// Output of the power curve for a wind speed, in kW
+fn power_kw(wind_speed: f64) -> f64 {
+    if wind_speed < CUT_IN {
+        0.0
+    } else if wind_speed < RATED_SPEED {
+        RATED_KW * (wind_speed.powi(3) - CUT_IN.powi(3)) / (RATED_SPEED.powi(3) - CUT_IN.powi(3))
+    } else {
+        RATED_KW
+    }
+}
+
+/// Table, queries and dashboard of `airplanes scaffold --model turbines`
+pub const BUNDLE: Bundle = Bundle {
+    key: "turbine_id",
+    symbols: &["turbine_id", "farm", "status"],
+    columns: &[
+        ("wind_speed", "DOUBLE"), ("wind_direction", "DOUBLE"), ("rotor_rpm", "DOUBLE"), ("pitch_angle", "DOUBLE"),
+        ("power_kw", "DOUBLE"), ("nacelle_temp", "DOUBLE"), ("vibration", "DOUBLE"),
+    ],
+    queries: &[
+        ("Latest reading of every turbine",
+         "SELECT turbine_id, farm, status, wind_speed, power_kw, timestamp\nFROM {table}\nLATEST ON timestamp PARTITION BY turbine_id;"),
+        ("Power of each farm, per minute",
+         "SELECT timestamp, farm, avg(power_kw) avg_kw, avg(wind_speed) wind_speed\nFROM {table}\nSAMPLE BY 1m;"),
+        ("The power curve, average power per m/s of wind",
+         "SELECT round(wind_speed) wind_bin, avg(power_kw) power_kw, count() readings\nFROM {table}\nORDER BY wind_bin;"),
+        ("Readings per status, storms included",
+         "SELECT status, count() readings\nFROM {table}\nORDER BY readings DESC;"),
+    ],
+    panels: &[
+        ("Power (kW)", "SELECT timestamp, avg(power_kw) avg_kw, max(power_kw) max_kw FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+        ("Wind speed (m/s)", "SELECT timestamp, avg(wind_speed) avg_wind, max(wind_speed) max_wind FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+    ],
+    map: None,
+};
+
+/// Wind turbines, `--model turbines`: the wind of each farm gusts around a mean of its
+/// own, and each turbine turns it into rotor speed, blade pitch and power.
+pub struct Turbines;
+
+impl DeviceModel for Turbines {
+    /// WT0000, in farms farm_00, farm_01...
+    fn device_id(&self, n: u64) -> String {
+        format!("WT{:04}", n)
+    }
+
+    fn device(&self, id: &str, seed: u64, timestamp: i64) -> Box<dyn Device> {
+        let number: u64 = id.trim_start_matches("WT").parse().unwrap_or(0);
+        let farm = number / FARM_SIZE;
+        // The turbines of a farm share its mean wind, somewhere between a calm and a windy site
+        let mean_wind = 5.0 + (farm * 7919 % 60) as f64 / 10.0;
+        let mut rng = StdRng::seed_from_u64(seed);
+        let wind = OrnsteinUhlenbeck::new(mean_wind, 0.05, 1.2, &mut rng);
+        let direction = rng.gen_range(0.0..360.0);
+        let mut turbine = Turbine {
+            turbine_id: id.to_string(),
+            farm: format!("farm_{:02}", farm),
+            rng,
+            timestamp,
+            wind,
+            wind_speed: 0.0,
+            wind_direction: direction,
+            status: Status::Idle,
+            rotor_rpm: 0.0,
+            pitch_angle: 0.0,
+            power_kw: 0.0,
+            nacelle_temp: 20.0,
+            vibration: 0.0,
+        };
+        turbine.update(timestamp);
+        Box::new(turbine)
+    }
+}
+
+struct Turbine {
+    turbine_id: String,
+    farm: String,
+    rng: StdRng,
+    timestamp: i64,
+    wind: OrnsteinUhlenbeck,
+    /// m/s
+    wind_speed: f64,
+    /// Degrees from north the wind blows from
+    wind_direction: f64,
+    status: Status,
+    rotor_rpm: f64,
+    /// Degrees, 0 facing the wind fully, 90 feathered
+    pitch_angle: f64,
+    power_kw: f64,
+    /// °C, heating up with the power produced
+    nacelle_temp: f64,
+    /// mm/s RMS at the main bearing
+    vibration: f64,
+}
+
+impl Device for Turbine {
+    fn update(&mut self, timestamp: i64) {
+        let dt = ((timestamp - self.timestamp) as f64 / 1e9).clamp(0.0, 10.0);
+        self.timestamp = timestamp;
+        self.wind_speed = self.wind.step(dt, &mut self.rng).clamp(0.0, 40.0);
+        self.wind_direction = (self.wind_direction + self.rng.gen_range(-0.5..0.5) * dt.sqrt() + 360.0) % 360.0;
+        self.status = match self.status {
+            Status::CutOut if self.wind_speed >= RESTART => Status::CutOut,
+            _ if self.wind_speed >= CUT_OUT => Status::CutOut,
+            _ if self.wind_speed < CUT_IN => Status::Idle,
+            _ => Status::Producing,
+        };
+        let (rpm, pitch, power) = match self.status {
+            Status::Idle => (self.wind_speed / CUT_IN * 2.0, 0.0, 0.0),
+            Status::CutOut => (0.0, 90.0, 0.0),
+            // Above the rated speed the blades pitch out of the wind to hold the rated power
+            Status::Producing => (
+                RATED_RPM * (self.wind_speed / RATED_SPEED).min(1.0),
+                (self.wind_speed - RATED_SPEED).max(0.0) * 1.8,
+                power_kw(self.wind_speed),
+            ),
+        };
+        self.rotor_rpm = (rpm + self.rng.gen_range(-0.1..0.1)).max(0.0);
+        self.pitch_angle = pitch;
+        self.power_kw = (power * (1.0 + self.rng.gen_range(-0.01..0.01))).clamp(0.0, RATED_KW);
+        let heated = 20.0 + 25.0 * self.power_kw / RATED_KW;
+        self.nacelle_temp += (heated - self.nacelle_temp) * (dt / 600.0).min(1.0) + self.rng.gen_range(-0.05..0.05);
+        self.vibration = 0.3 + 0.12 * self.rotor_rpm + self.rng.gen_range(0.0..0.2);
+    }
+
+    fn write(&self, buffer: &mut Buffer, table_name: &str) -> questdb::Result<()> {
+        buffer.table(table_name)?
+            .symbol("turbine_id", &self.turbine_id)?
+            .symbol("farm", &self.farm)?
+            .symbol("status", self.status.as_str())?
+            .column_f64("wind_speed", self.wind_speed)?
+            .column_f64("wind_direction", self.wind_direction)?
+            .column_f64("rotor_rpm", self.rotor_rpm)?
+            .column_f64("pitch_angle", self.pitch_angle)?
+            .column_f64("power_kw", self.power_kw)?
+            .column_f64("nacelle_temp", self.nacelle_temp)?
+            .column_f64("vibration", self.vibration)?
+            .at(TimestampNanos::new(self.timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_follows_the_curve() {
+        assert_eq!(power_kw(2.9), 0.0);
+        assert_eq!(power_kw(CUT_IN), 0.0);
+        assert_eq!(power_kw(RATED_SPEED), RATED_KW);
+        assert_eq!(power_kw(CUT_OUT - 1.0), RATED_KW);
+        let speeds: Vec<f64> = (30..=120).map(|tenths| tenths as f64 / 10.0).collect();
+        assert!(speeds.windows(2).all(|pair| power_kw(pair[0]) < power_kw(pair[1])));
+    }
+
+    /// Readings stay physical over 200 seeds, and a turbine in a storm stays stopped until
+    /// the wind drops below the restart speed
+    #[test]
+    fn turbines_stay_within_their_limits() {
+        for seed in 0..200 {
+            // Windy, so that storms stop it now and then
+            let mut wind = OrnsteinUhlenbeck::new(18.0, 0.05, 4.0, &mut StdRng::seed_from_u64(seed));
+            wind.value = 18.0;
+            let mut turbine = Turbine {
+                turbine_id: "WT0000".to_string(),
+                farm: "farm_00".to_string(),
+                rng: StdRng::seed_from_u64(seed),
+                timestamp: 0,
+                wind,
+                wind_speed: 0.0,
+                wind_direction: 0.0,
+                status: Status::Idle,
+                rotor_rpm: 0.0,
+                pitch_angle: 0.0,
+                power_kw: 0.0,
+                nacelle_temp: 20.0,
+                vibration: 0.0,
+            };
+            let mut previous = Status::Idle;
+            for tick in 1..2000 {
+                turbine.update(tick * 1_000_000_000);
+                assert!((0.0..=RATED_KW).contains(&turbine.power_kw), "seed {}: {} kW", seed, turbine.power_kw);
+                assert!((0.0..360.0).contains(&turbine.wind_direction), "seed {}: wind from {}", seed, turbine.wind_direction);
+                if turbine.status == Status::CutOut {
+                    assert_eq!(turbine.power_kw, 0.0, "seed {}", seed);
+                }
+                if previous == Status::CutOut && turbine.status != Status::CutOut {
+                    assert!(turbine.wind_speed < RESTART, "seed {}: restarted at {} m/s", seed, turbine.wind_speed);
+                }
+                previous = turbine.status;
+            }
+        }
+    }
+}
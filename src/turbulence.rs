@@ -0,0 +1,34 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+/// Ornstein-Uhlenbeck process: a random walk pulled back towards its mean, giving
+/// autocorrelated noise that looks like real attitude data instead of white noise.
+#[derive(Debug, Clone)]
+pub struct OrnsteinUhlenbeck {
+    pub value: f64,
+    mean: f64,
+    /// Mean reversion speed, per second
+    theta: f64,
+    /// Volatility, in units per square root of a second
+    sigma: f64,
+}
+
+impl OrnsteinUhlenbeck {
+    pub fn new(mean: f64, theta: f64, sigma: f64, rng: &mut impl Rng) -> Self {
+        // Start from the stationary distribution so there is no initial transient
+        let stationary_std_dev = sigma / (2.0 * theta).sqrt();
+        let z: f64 = rng.sample(StandardNormal);
+        OrnsteinUhlenbeck { value: mean + z * stationary_std_dev, mean, theta, sigma }
+    }
+
+    pub fn set_mean(&mut self, mean: f64) {
+        self.mean = mean;
+    }
+
+    /// Advances the process by `dt` seconds.
+    pub fn step(&mut self, dt: f64, rng: &mut impl Rng) -> f64 {
+        let z: f64 = rng.sample(StandardNormal);
+        self.value += self.theta * (self.mean - self.value) * dt + self.sigma * dt.sqrt() * z;
+        self.value
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::Mutex;
+
+/// Type the flipped column is sent as, instead of a double.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlipType {
+    Long,
+    Text,
+}
+
+impl FlipType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlipType::Long => "LONG",
+            FlipType::Text => "STRING",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Outcome {
+    accepted: u64,
+    rejected: u64,
+    first_error: Option<String>,
+}
+
+/// Schedules the batches where a column changes type, and collects how the server
+/// responded to each of them.
+#[derive(Debug)]
+pub struct TypeFlips {
+    every: u64,
+    /// Outcomes for LONG and STRING batches
+    outcomes: Mutex<[Outcome; 2]>,
+}
+
+impl TypeFlips {
+    pub fn new(every: u64) -> Self {
+        TypeFlips { every, outcomes: Mutex::new(Default::default()) }
+    }
+
+    /// Every `every`th batch is flipped, alternating between LONG and STRING.
+    pub fn flip_for(&self, batch: u64) -> Option<FlipType> {
+        let n = batch + 1;
+        if !n.is_multiple_of(self.every) {
+            return None;
+        }
+        Some(if (n / self.every).is_multiple_of(2) { FlipType::Text } else { FlipType::Long })
+    }
+
+    /// Records the server's answer to a flipped batch. `error` is None if it was accepted.
+    pub fn record(&self, flip: FlipType, error: Option<String>) {
+        let mut outcomes = self.outcomes.lock().unwrap();
+        let outcome = &mut outcomes[flip as usize];
+        match error {
+            None => outcome.accepted += 1,
+            Some(error) => {
+                outcome.rejected += 1;
+                outcome.first_error.get_or_insert(error);
+            }
+        }
+    }
+
+    pub fn print_summary(&self, column: &str) {
+        let outcomes = self.outcomes.lock().unwrap();
+        for flip in [FlipType::Long, FlipType::Text].iter() {
+            let outcome = &outcomes[*flip as usize];
+            if outcome.accepted + outcome.rejected == 0 {
+                continue;
+            }
+            println!("Column {} sent as {}: {} batches accepted, {} rejected", column, flip.as_str(), outcome.accepted, outcome.rejected);
+            if let Some(error) = &outcome.first_error {
+                println!("  first error: {}", error);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+/// Splits "1.5M rows" into 1.5 and "M rows". Underscores between digits are allowed.
+///
+/// Values of the options can have units: durations ("90", "10m", "2h30m", "500ms"), counts
+/// and rates ("1000", "1.5M rows", "20k/s") and sizes ("4096", "64KiB", "1.5GB"). A bare
+/// number keeps the unit the option is named after.
+fn split(value: &str) -> Result<(f64, String), String> {
+    let value = value.trim().replace('_', "");
+    let end = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let (number, unit) = value.split_at(end);
+    let number: f64 = number.parse().map_err(|_| format!("invalid number in '{}'", value))?;
+    Ok((number, unit.trim().to_string()))
+}
+
+fn whole(number: f64, value: &str) -> Result<u64, String> {
+    if number.fract() != 0.0 || number > u64::MAX as f64 {
+        return Err(format!("'{}' is not a whole number", value));
+    }
+    Ok(number as u64)
+}
+
+/// Seconds in a duration unit
+fn unit_secs(unit: &str, value: &str) -> Result<f64, String> {
+    match unit {
+        "" | "s" | "sec" | "secs" => Ok(1.0),
+        "ms" => Ok(0.001),
+        "m" | "min" | "mins" => Ok(60.0),
+        "h" => Ok(3600.0),
+        "d" => Ok(86_400.0),
+        _ => Err(format!("invalid duration '{}', expected a number with ms, s, m, h or d", value)),
+    }
+}
+
+/// A duration in seconds, possibly fractional, of one or more parts such as "2h30m"
+pub fn secs_f64(value: &str) -> Result<f64, String> {
+    let value = value.trim().replace('_', "");
+    let is_number = |c: char| c.is_ascii_digit() || c == '.';
+    let mut rest = value.as_str();
+    let mut secs = 0.0;
+    loop {
+        let digits = rest.find(|c: char| !is_number(c)).unwrap_or(rest.len());
+        let unit = rest[digits..].find(is_number).map(|end| digits + end).unwrap_or(rest.len());
+        let number: f64 = rest[..digits].parse().map_err(|_| format!("invalid number in '{}'", value))?;
+        secs += number * unit_secs(rest[digits..unit].trim(), &value)?;
+        rest = &rest[unit..];
+        if rest.is_empty() {
+            return Ok(secs);
+        }
+    }
+}
+
+/// A duration in whole seconds
+pub fn secs(value: &str) -> Result<u64, String> {
+    whole(secs_f64(value)?, value)
+}
+
+/// A duration in whole milliseconds; a bare number is milliseconds
+pub fn millis(value: &str) -> Result<u64, String> {
+    match split(value)? {
+        (number, unit) if unit.is_empty() => whole(number, value),
+        _ => whole((secs_f64(value)? * 1000.0).round(), value),
+    }
+}
+
+/// A number of rows, or rows per second, with an optional k, M or G multiplier
+pub fn count(value: &str) -> Result<u64, String> {
+    let (number, unit) = split(value)?;
+    let unit = unit.trim_end_matches("/s").trim_end_matches("rows").trim_end_matches("row").trim();
+    let multiplier = match unit {
+        "" => 1.0,
+        "k" | "K" => 1e3,
+        "M" => 1e6,
+        "G" | "B" => 1e9,
+        _ => return Err(format!("invalid count '{}', expected a number with k, M or G", value)),
+    };
+    whole((number * multiplier).round(), value)
+}
+
+pub fn count_usize(value: &str) -> Result<usize, String> {
+    count(value).map(|count| count as usize)
+}
+
+/// A size in bytes; KB, MB and GB are powers of 1000, KiB, MiB and GiB powers of 1024
+pub fn bytes(value: &str) -> Result<u64, String> {
+    let (number, unit) = split(value)?;
+    let multiplier = match unit.as_str() {
+        "" | "B" => 1.0,
+        "k" | "K" | "KB" | "kB" => 1e3,
+        "M" | "MB" => 1e6,
+        "G" | "GB" => 1e9,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(format!("invalid size '{}', expected a number with KB, MB, GB, KiB, MiB or GiB", value)),
+    };
+    whole((number * multiplier).round(), value)
+}
@@ -0,0 +1,325 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use questdb::ingress::{Buffer, TimestampNanos};
+
+use crate::model::{Device, DeviceModel};
+use crate::scaffold::Bundle;
+
+/// Cities the fleets drive in, with the position of their centre in degrees.
+const CITIES: &[(&str, f64, f64)] = &[
+    ("berlin", 52.5200, 13.4050),
+    ("madrid", 40.4168, -3.7038),
+    ("chicago", 41.8781, -87.6298),
+    ("sao_paulo", -23.5505, -46.6333),
+    ("tokyo", 35.6762, 139.6503),
+];
+
+/// The streets are a grid with a crossing every BLOCK metres, every ARTERIAL_EVERY one an
+/// arterial road, within RADIUS of the centre
+const BLOCK: f64 = 250.0;
+const ARTERIAL_EVERY: i64 = 8;
+const RADIUS: f64 = 8000.0;
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// Speed limits in m/s, 50 and 80 km/h
+const STREET_LIMIT: f64 = 50.0 / 3.6;
+const ARTERIAL_LIMIT: f64 = 80.0 / 3.6;
+
+/// Accelerations in m/s², braking harder than HARSH_BRAKE being a harsh-brake event
+const ACCELERATION: f64 = 2.0;
+const BRAKING: f64 = 2.5;
+const HARSH_BRAKE: f64 = 6.0;
+
+const TANK_LITRES: f64 = 60.0;
+const LITRES_PER_KM: f64 = 0.09;
+const IDLE_LITRES_PER_HOUR: f64 = 0.8;
+
+/// Lower speed of each gear, in km/h
+const GEARS: [f64; 6] = [0.0, 20.0, 35.0, 50.0, 65.0, 85.0];
+
+/// What a vehicle is doing, the `state` symbol.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Driving,
+    /// At a red light
+    Stopped,
+    Refueling,
+}
+
+impl State {
+    fn as_str(&self) -> &'static str {
+        match self {
+            State::Driving => "driving",
+            State::Stopped => "stopped",
+            State::Refueling => "refueling",
+        }
+    }
+}
+
+/// Engine speed for a road speed, in the gear a driver would be in
+fn engine_rpm(speed_kmh: f64) -> f64 {
+    if speed_kmh < 2.0 {
+        return 750.0;
+    }
+    let gear = GEARS.iter().rposition(|low| speed_kmh >= *low).unwrap_or(0);
+    let high = GEARS.get(gear + 1).copied().unwrap_or(130.0);
+    1300.0 + 2200.0 * ((speed_kmh - GEARS[gear]) / (high - GEARS[gear])).min(1.0)
+}
+
+/// Table, queries and dashboard of `airplanes scaffold --model vehicles`
+pub const BUNDLE: Bundle = Bundle {
+    key: "vehicle_id",
+    symbols: &["vehicle_id", "city", "state"],
+    columns: &[
+        ("latitude", "DOUBLE"), ("longitude", "DOUBLE"), ("speed_kmh", "DOUBLE"), ("rpm", "LONG"), ("fuel_level_pct", "DOUBLE"),
+        ("odometer_km", "DOUBLE"), ("coolant_temp", "DOUBLE"), ("harsh_brake", "BOOLEAN"),
+    ],
+    queries: &[
+        ("Latest position and state of every vehicle",
+         "SELECT vehicle_id, city, state, latitude, longitude, speed_kmh, fuel_level_pct, timestamp\nFROM {table}\nLATEST ON timestamp PARTITION BY vehicle_id;"),
+        ("Distance driven by each vehicle, per day, from the odometer",
+         "SELECT timestamp, vehicle_id, last(odometer_km) - first(odometer_km) km\nFROM {table}\nSAMPLE BY 1d;"),
+        ("Vehicles braking harshly the most",
+         "SELECT vehicle_id, city, count() harsh_brakes\nFROM {table}\nWHERE harsh_brake\nORDER BY harsh_brakes DESC\nLIMIT 20;"),
+        ("Average speed of the fleet in each city, per minute, stops included",
+         "SELECT timestamp, city, avg(speed_kmh) speed_kmh\nFROM {table}\nSAMPLE BY 1m;"),
+    ],
+    panels: &[
+        ("Speed (km/h)", "SELECT timestamp, avg(speed_kmh) avg_speed, max(speed_kmh) max_speed FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+        ("Harsh brakes", "SELECT timestamp, count() harsh_brakes FROM {table} WHERE $__timeFilter(timestamp) AND harsh_brake SAMPLE BY $__interval"),
+    ],
+    map: Some(("Vehicles", "latitude", "longitude")),
+};
+
+/// Telematics of delivery and service vehicles, `--model vehicles`: fleets in five cities
+/// drive a grid of streets and arterial roads, stop at red lights, brake harshly now and
+/// then, and refuel when the tank runs low.
+pub struct Vehicles;
+
+impl DeviceModel for Vehicles {
+    /// VH00000 onwards, the city going round the list
+    fn device_id(&self, n: u64) -> String {
+        format!("VH{:05}", n)
+    }
+
+    fn device(&self, id: &str, seed: u64, timestamp: i64) -> Box<dyn Device> {
+        Box::new(Vehicle::new(id, seed, timestamp))
+    }
+}
+
+struct Vehicle {
+    vehicle_id: String,
+    /// Index in CITIES
+    city: usize,
+    rng: StdRng,
+    timestamp: i64,
+    state: State,
+    /// Metres east and north of the centre, always on a street
+    x: f64,
+    y: f64,
+    /// Unit steps along the street it drives: (1, 0) is east, (0, -1) south
+    direction: (i8, i8),
+    /// m/s
+    speed: f64,
+    rpm: i64,
+    /// Share of the speed limit the driver keeps to
+    pace: f64,
+    /// Whether the light at the next crossing is red
+    red_ahead: bool,
+    /// When a stop at a light or a refuel ends
+    stopped_until: i64,
+    fuel_litres: f64,
+    odometer_km: f64,
+    /// Warms up from the air temperature to about 90 °C after the start
+    coolant_temp: f64,
+    /// Braked harder than HARSH_BRAKE since the last row
+    harsh_brake: bool,
+}
+
+impl Vehicle {
+    fn new(id: &str, seed: u64, timestamp: i64) -> Self {
+        let number: u64 = id.trim_start_matches("VH").parse().unwrap_or(0);
+        let mut rng = StdRng::seed_from_u64(seed);
+        // On a random street, somewhere along its block
+        let crossing = |rng: &mut StdRng| (rng.gen_range(-20..=20) as f64) * BLOCK;
+        let (x, y, direction) = match rng.gen_range(0..4) {
+            0 => (crossing(&mut rng) + rng.gen_range(0.0..BLOCK), crossing(&mut rng), (1, 0)),
+            1 => (crossing(&mut rng) + rng.gen_range(0.0..BLOCK), crossing(&mut rng), (-1, 0)),
+            2 => (crossing(&mut rng), crossing(&mut rng) + rng.gen_range(0.0..BLOCK), (0, 1)),
+            _ => (crossing(&mut rng), crossing(&mut rng) + rng.gen_range(0.0..BLOCK), (0, -1)),
+        };
+        Vehicle {
+            vehicle_id: id.to_string(),
+            city: number as usize % CITIES.len(),
+            timestamp,
+            state: State::Driving,
+            x,
+            y,
+            direction,
+            speed: 0.0,
+            rpm: 750,
+            pace: rng.gen_range(0.85..1.1),
+            red_ahead: rng.gen_bool(0.3),
+            stopped_until: timestamp,
+            fuel_litres: rng.gen_range(0.2..1.0) * TANK_LITRES,
+            odometer_km: rng.gen_range(5_000.0..250_000.0),
+            coolant_temp: rng.gen_range(15.0..90.0),
+            harsh_brake: false,
+            rng,
+        }
+    }
+
+    /// Speed limit of the street it is on
+    fn limit(&self) -> f64 {
+        let street = if self.direction.0 != 0 { self.y } else { self.x };
+        match (street / BLOCK).round() as i64 % ARTERIAL_EVERY {
+            0 => ARTERIAL_LIMIT,
+            _ => STREET_LIMIT,
+        }
+    }
+
+    /// Metres to the next crossing ahead
+    fn to_crossing(&self) -> f64 {
+        let (along, step) = if self.direction.0 != 0 { (self.x, self.direction.0) } else { (self.y, self.direction.1) };
+        let next = match step > 0 {
+            true => ((along / BLOCK).floor() + 1.0) * BLOCK,
+            false => ((along / BLOCK).ceil() - 1.0) * BLOCK,
+        };
+        (next - along).abs()
+    }
+
+    /// At a crossing: stops if the light is red, then goes straight on or turns, back
+    /// towards the centre when near the edge of the city
+    fn cross(&mut self) {
+        // Snap to the crossing so rounding does not take the vehicle off the grid
+        self.x = (self.x / BLOCK).round() * BLOCK;
+        self.y = (self.y / BLOCK).round() * BLOCK;
+        if self.red_ahead {
+            self.speed = 0.0;
+            self.state = State::Stopped;
+            self.stopped_until = self.timestamp + (self.rng.gen_range(10.0..45.0) * 1e9) as i64;
+        }
+        if self.fuel_litres < 0.1 * TANK_LITRES {
+            self.speed = 0.0;
+            self.state = State::Refueling;
+            self.stopped_until = self.timestamp + (self.rng.gen_range(240.0..480.0) * 1e9) as i64;
+        }
+        let (dx, dy) = (self.direction.0 as f64, self.direction.1 as f64);
+        let heading_out = (self.x + dx * BLOCK).hypot(self.y + dy * BLOCK) > RADIUS;
+        if heading_out || self.rng.gen_bool(0.3) {
+            let (left, right) = ((-self.direction.1, self.direction.0), (self.direction.1, -self.direction.0));
+            let away = |(dx, dy): (i8, i8)| self.x * dx as f64 + self.y * dy as f64;
+            self.direction = match heading_out {
+                // The turn that heads more towards the centre, or back the way it came
+                true if away(left).min(away(right)) >= 0.0 => (-self.direction.0, -self.direction.1),
+                true if away(left) < away(right) => left,
+                true => right,
+                false if self.rng.gen_bool(0.5) => left,
+                false => right,
+            };
+        }
+        self.red_ahead = self.rng.gen_bool(0.3);
+    }
+}
+
+impl Device for Vehicle {
+    fn update(&mut self, timestamp: i64) {
+        let dt = ((timestamp - self.timestamp) as f64 / 1e9).clamp(0.0, 10.0);
+        self.timestamp = timestamp;
+        self.harsh_brake = false;
+        self.coolant_temp += (90.0 - self.coolant_temp) * (dt / 300.0).min(1.0) + self.rng.gen_range(-0.1..0.1);
+        if self.state != State::Driving {
+            self.fuel_litres -= IDLE_LITRES_PER_HOUR * dt / 3600.0;
+            if timestamp < self.stopped_until {
+                self.rpm = engine_rpm(0.0) as i64 + self.rng.gen_range(-20..20);
+                return;
+            }
+            if self.state == State::Refueling {
+                self.fuel_litres = TANK_LITRES;
+            }
+            self.state = State::Driving;
+        }
+
+        let to_crossing = self.to_crossing();
+        if self.speed > 8.0 && self.rng.gen_bool((dt / 900.0).min(1.0)) {
+            // Something in the road
+            self.speed = (self.speed - HARSH_BRAKE * dt.min(1.5)).max(0.0);
+            self.harsh_brake = true;
+        } else if self.red_ahead && to_crossing <= self.speed * self.speed / (2.0 * BRAKING) + self.speed * dt {
+            self.speed = (self.speed - BRAKING * dt).max(1.0);
+        } else {
+            let target = self.limit() * self.pace;
+            self.speed += (target - self.speed).clamp(-BRAKING * dt, ACCELERATION * dt);
+        }
+
+        let travelled = (self.speed * dt).min(to_crossing);
+        self.x += self.direction.0 as f64 * travelled;
+        self.y += self.direction.1 as f64 * travelled;
+        self.odometer_km += travelled / 1000.0;
+        self.fuel_litres -= LITRES_PER_KM * travelled / 1000.0 + IDLE_LITRES_PER_HOUR * dt / 3600.0;
+        if travelled >= to_crossing {
+            self.cross();
+        }
+        self.rpm = engine_rpm(self.speed * 3.6).round() as i64 + self.rng.gen_range(-20..20);
+    }
+
+    fn write(&self, buffer: &mut Buffer, table_name: &str) -> questdb::Result<()> {
+        let (city, latitude, longitude) = CITIES[self.city];
+        let latitude = latitude + self.y / METRES_PER_DEGREE;
+        let longitude = longitude + self.x / (METRES_PER_DEGREE * latitude.to_radians().cos());
+        buffer.table(table_name)?
+            .symbol("vehicle_id", &self.vehicle_id)?
+            .symbol("city", city)?
+            .symbol("state", self.state.as_str())?
+            .column_f64("latitude", latitude)?
+            .column_f64("longitude", longitude)?
+            .column_f64("speed_kmh", self.speed * 3.6)?
+            .column_i64("rpm", self.rpm)?
+            .column_f64("fuel_level_pct", 100.0 * self.fuel_litres.max(0.0) / TANK_LITRES)?
+            .column_f64("odometer_km", self.odometer_km)?
+            .column_f64("coolant_temp", self.coolant_temp)?
+            .column_bool("harsh_brake", self.harsh_brake)?
+            .at(TimestampNanos::new(self.timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpm_stays_in_the_band_of_each_gear() {
+        assert_eq!(engine_rpm(0.0), 750.0);
+        for kmh in 2..130 {
+            let rpm = engine_rpm(kmh as f64);
+            assert!((1300.0..=3500.0).contains(&rpm), "{} rpm at {} km/h", rpm, kmh);
+        }
+    }
+
+    /// Over a simulated day, vehicles keep to the streets of their city and to the
+    /// limits, the odometer only goes up and the tank is refilled before it runs dry
+    #[test]
+    fn vehicles_drive_the_streets() {
+        let mut harsh_brakes = 0;
+        let mut refuels = 0;
+        for n in 0..40 {
+            let mut vehicle = Vehicle::new(&Vehicles.device_id(n), n, 0);
+            for tick in 1..=8640 {
+                let odometer = vehicle.odometer_km;
+                let state = vehicle.state;
+                vehicle.update(tick * 10_000_000_000);
+                let on_street = (vehicle.x / BLOCK).fract().abs() < 1e-6 || (vehicle.y / BLOCK).fract().abs() < 1e-6;
+                assert!(on_street, "vehicle {} off the streets at {}, {}", n, vehicle.x, vehicle.y);
+                assert!(vehicle.x.hypot(vehicle.y) < RADIUS * 1.5, "vehicle {} left the city", n);
+                assert!(vehicle.speed >= 0.0 && vehicle.speed <= ARTERIAL_LIMIT * 1.11, "vehicle {} at {} m/s", n, vehicle.speed);
+                assert!(vehicle.odometer_km >= odometer);
+                assert!(vehicle.fuel_litres > 0.0 && vehicle.fuel_litres <= TANK_LITRES, "vehicle {} has {} l", n, vehicle.fuel_litres);
+                harsh_brakes += vehicle.harsh_brake as u32;
+                refuels += (state == State::Refueling && vehicle.state == State::Driving) as u32;
+            }
+        }
+        assert!(harsh_brakes > 0, "no harsh brakes");
+        assert!(refuels > 0, "no vehicle refueled");
+    }
+}
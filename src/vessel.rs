@@ -0,0 +1,346 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use questdb::ingress::{Buffer, TimestampNanos};
+
+use crate::model::{Device, DeviceModel};
+use crate::scaffold::Bundle;
+
+/// Ports a vessel sails between, with their position in degrees.
+struct Port {
+    name: &'static str,
+    latitude: f64,
+    longitude: f64,
+}
+
+/// A sea area: the Maritime Identification Digits its vessels' MMSIs start with, and its
+/// ports, which the vessels of the area sail between so their routes stay plausible.
+struct Area {
+    mid: u32,
+    ports: &'static [Port],
+}
+
+const AREAS: &[Area] = &[
+    Area { mid: 244, ports: &[
+        Port { name: "NLRTM", latitude: 51.95, longitude: 4.05 },
+        Port { name: "BEANR", latitude: 51.35, longitude: 3.95 },
+        Port { name: "DEHAM", latitude: 53.90, longitude: 8.60 },
+        Port { name: "GBFXT", latitude: 51.93, longitude: 1.35 },
+        Port { name: "NOOSL", latitude: 59.85, longitude: 10.60 },
+    ] },
+    Area { mid: 563, ports: &[
+        Port { name: "SGSIN", latitude: 1.22, longitude: 103.85 },
+        Port { name: "MYPKG", latitude: 2.95, longitude: 101.30 },
+        Port { name: "HKHKG", latitude: 22.25, longitude: 114.15 },
+        Port { name: "VNSGN", latitude: 10.35, longitude: 107.05 },
+        Port { name: "THLCH", latitude: 13.05, longitude: 100.85 },
+    ] },
+    Area { mid: 366, ports: &[
+        Port { name: "USLAX", latitude: 33.70, longitude: -118.25 },
+        Port { name: "USOAK", latitude: 37.80, longitude: -122.55 },
+        Port { name: "USSEA", latitude: 48.20, longitude: -123.00 },
+        Port { name: "CAVAN", latitude: 49.25, longitude: -123.35 },
+        Port { name: "MXZLO", latitude: 19.05, longitude: -104.35 },
+    ] },
+];
+
+/// Kinds of vessel, with their cruising speed in knots and their draught in metres light
+/// and fully laden.
+#[derive(Debug, Clone, Copy)]
+enum ShipType {
+    Cargo,
+    Tanker,
+    Passenger,
+    Fishing,
+    Tug,
+}
+
+impl ShipType {
+    const ALL: [ShipType; 5] = [ShipType::Cargo, ShipType::Tanker, ShipType::Passenger, ShipType::Fishing, ShipType::Tug];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ShipType::Cargo => "cargo",
+            ShipType::Tanker => "tanker",
+            ShipType::Passenger => "passenger",
+            ShipType::Fishing => "fishing",
+            ShipType::Tug => "tug",
+        }
+    }
+
+    fn cruise_knots(&self) -> f64 {
+        match self {
+            ShipType::Cargo => 16.0,
+            ShipType::Tanker => 13.0,
+            ShipType::Passenger => 20.0,
+            ShipType::Fishing => 10.0,
+            ShipType::Tug => 11.0,
+        }
+    }
+
+    fn draught(&self) -> (f64, f64) {
+        match self {
+            ShipType::Cargo => (8.0, 14.5),
+            ShipType::Tanker => (9.0, 20.0),
+            ShipType::Passenger => (7.5, 8.5),
+            ShipType::Fishing => (4.0, 6.0),
+            ShipType::Tug => (4.5, 5.0),
+        }
+    }
+}
+
+/// The AIS navigational status, the `nav_status` symbol.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NavStatus {
+    UnderWay,
+    AtAnchor,
+    Moored,
+    Fishing,
+}
+
+impl NavStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NavStatus::UnderWay => "under_way_using_engine",
+            NavStatus::AtAnchor => "at_anchor",
+            NavStatus::Moored => "moored",
+            NavStatus::Fishing => "engaged_in_fishing",
+        }
+    }
+}
+
+/// Distance from `latitude`, `longitude` to a port in nautical miles and the bearing to
+/// it in degrees, on a flat chart, close enough between the ports of an area
+fn course_to(latitude: f64, longitude: f64, port: &Port) -> (f64, f64) {
+    let north = (port.latitude - latitude) * 60.0;
+    let east = (port.longitude - longitude) * 60.0 * latitude.to_radians().cos();
+    (north.hypot(east), (east.atan2(north).to_degrees() + 360.0) % 360.0)
+}
+
+/// The offset from `from` to `to`, in degrees between -180 and 180
+fn turn(from: f64, to: f64) -> f64 {
+    (to - from + 540.0) % 360.0 - 180.0
+}
+
+/// Table, queries and dashboard of `airplanes scaffold --model vessels`
+pub const BUNDLE: Bundle = Bundle {
+    key: "mmsi",
+    symbols: &["mmsi", "ship_type", "nav_status", "destination"],
+    columns: &[
+        ("latitude", "DOUBLE"), ("longitude", "DOUBLE"), ("sog", "DOUBLE"), ("cog", "DOUBLE"), ("heading", "DOUBLE"),
+        ("draught", "DOUBLE"),
+    ],
+    queries: &[
+        ("Latest position of every vessel",
+         "SELECT mmsi, ship_type, nav_status, destination, latitude, longitude, sog, timestamp\nFROM {table}\nLATEST ON timestamp PARTITION BY mmsi;"),
+        ("Vessels per navigational status right now",
+         "SELECT nav_status, count() vessels\nFROM (SELECT * FROM {table} LATEST ON timestamp PARTITION BY mmsi);"),
+        ("Average speed of each ship type, per hour",
+         "SELECT timestamp, ship_type, avg(sog) sog\nFROM {table}\nWHERE nav_status = 'under_way_using_engine'\nSAMPLE BY 1h;"),
+        ("Vessels heading to each port",
+         "SELECT destination, count_distinct(mmsi) vessels\nFROM {table}\nORDER BY vessels DESC;"),
+    ],
+    panels: &[
+        ("Speed over ground (kn)", "SELECT timestamp, avg(sog) avg_sog, max(sog) max_sog FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+    ],
+    map: Some(("Vessels", "latitude", "longitude")),
+};
+
+/// AIS position reports of vessels, `--model vessels`: cargo ships, tankers, ferries,
+/// fishing boats and tugs sailing between the ports of one of three sea areas, slowing
+/// down on the approach, now and then anchoring off the port before mooring, and loading
+/// or unloading there, which changes their draught.
+pub struct Vessels;
+
+impl DeviceModel for Vessels {
+    /// A nine digit MMSI: the MID of the area, then the vessel's number
+    fn device_id(&self, n: u64) -> String {
+        let area = &AREAS[n as usize % AREAS.len()];
+        format!("{}{:06}", area.mid, n / AREAS.len() as u64)
+    }
+
+    fn device(&self, id: &str, seed: u64, timestamp: i64) -> Box<dyn Device> {
+        Box::new(Vessel::new(id, seed, timestamp))
+    }
+}
+
+struct Vessel {
+    mmsi: String,
+    ship_type: ShipType,
+    /// Index in AREAS, and of the port it is bound for
+    area: usize,
+    destination: usize,
+    rng: StdRng,
+    timestamp: i64,
+    nav_status: NavStatus,
+    latitude: f64,
+    longitude: f64,
+    /// Speed and course over ground, in knots and degrees
+    sog: f64,
+    cog: f64,
+    /// Where the bow points, off the course by the drift
+    heading: f64,
+    /// Metres
+    draught: f64,
+    /// When an anchorage, a stay in port or a fishing trip ends
+    status_until: i64,
+}
+
+impl Vessel {
+    fn new(id: &str, seed: u64, timestamp: i64) -> Self {
+        let mid: u32 = id.get(..3).and_then(|mid| mid.parse().ok()).unwrap_or(0);
+        let area = AREAS.iter().position(|area| area.mid == mid).unwrap_or(0);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let ship_type = ShipType::ALL[rng.gen_range(0..ShipType::ALL.len())];
+        let ports = AREAS[area].ports;
+        // Somewhere at sea between two ports of the area, on the way to the second
+        let (from, to) = (rng.gen_range(0..ports.len()), rng.gen_range(1..ports.len()));
+        let destination = (from + to) % ports.len();
+        let along = rng.gen_range(0.1..0.9);
+        let latitude = ports[from].latitude + (ports[destination].latitude - ports[from].latitude) * along;
+        let longitude = ports[from].longitude + (ports[destination].longitude - ports[from].longitude) * along;
+        let (light, laden) = ship_type.draught();
+        let cog = course_to(latitude, longitude, &ports[destination]).1;
+        let mut vessel = Vessel {
+            mmsi: id.to_string(),
+            ship_type,
+            area,
+            destination,
+            timestamp,
+            nav_status: NavStatus::UnderWay,
+            latitude,
+            longitude,
+            sog: ship_type.cruise_knots(),
+            cog,
+            heading: cog,
+            draught: rng.gen_range(light..laden),
+            status_until: timestamp,
+            rng,
+        };
+        vessel.update(timestamp);
+        vessel
+    }
+
+    fn port(&self) -> &'static Port {
+        &AREAS[self.area].ports[self.destination]
+    }
+
+    /// Stays in `status` for between `min` and `max` minutes
+    fn stay(&mut self, status: NavStatus, min: f64, max: f64) {
+        self.nav_status = status;
+        self.status_until = self.timestamp + (self.rng.gen_range(min..max) * 60e9) as i64;
+    }
+
+    /// Sets off from the port it is in for another one of the area, loaded or unloaded
+    fn depart(&mut self) {
+        let ports = AREAS[self.area].ports.len();
+        self.destination = (self.destination + self.rng.gen_range(1..ports)) % ports;
+        let (light, laden) = self.ship_type.draught();
+        self.draught = if self.draught > (light + laden) / 2.0 { light } else { laden } + self.rng.gen_range(-0.3..0.3);
+        self.nav_status = NavStatus::UnderWay;
+    }
+}
+
+impl Device for Vessel {
+    fn update(&mut self, timestamp: i64) {
+        let dt = ((timestamp - self.timestamp) as f64 / 1e9).clamp(0.0, 10.0);
+        self.timestamp = timestamp;
+        let (distance, bearing) = course_to(self.latitude, self.longitude, self.port());
+        let cruise = self.ship_type.cruise_knots();
+        let (target_speed, target_course) = match self.nav_status {
+            NavStatus::Moored | NavStatus::AtAnchor if timestamp < self.status_until => (0.0, self.cog),
+            NavStatus::Moored => {
+                self.depart();
+                (cruise * 0.3, self.cog)
+            }
+            NavStatus::AtAnchor => {
+                self.nav_status = NavStatus::UnderWay;
+                (4.0, bearing)
+            }
+            NavStatus::Fishing if timestamp < self.status_until => (3.0, self.cog + self.rng.gen_range(-20.0..20.0)),
+            NavStatus::Fishing => {
+                self.nav_status = NavStatus::UnderWay;
+                (cruise, bearing)
+            }
+            NavStatus::UnderWay if distance < 0.3 => {
+                self.stay(NavStatus::Moored, 20.0, 90.0);
+                (0.0, bearing)
+            }
+            NavStatus::UnderWay if distance < 8.0 && distance > 6.0 && self.rng.gen_bool((dt / 600.0).min(1.0)) => {
+                // Waiting for a berth
+                self.stay(NavStatus::AtAnchor, 10.0, 60.0);
+                (0.0, self.cog)
+            }
+            NavStatus::UnderWay if matches!(self.ship_type, ShipType::Fishing) && distance > 20.0 && self.rng.gen_bool((dt / 1800.0).min(1.0)) => {
+                self.stay(NavStatus::Fishing, 30.0, 120.0);
+                (3.0, self.cog)
+            }
+            // Slowing down on the approach
+            NavStatus::UnderWay => (cruise.min(2.0 + distance * 1.5), bearing),
+        };
+        // Speed and course follow the targets over a minute or so rather than in a tick
+        let ease = (dt / 60.0).min(1.0);
+        self.sog = (self.sog + (target_speed - self.sog) * ease + self.rng.gen_range(-0.1..0.1) * ease.sqrt()).max(0.0);
+        if self.sog < 0.1 {
+            self.sog = 0.0;
+        }
+        self.cog = (self.cog + turn(self.cog, target_course) * ease + 360.0) % 360.0;
+        self.heading = (self.cog + self.rng.gen_range(-3.0..3.0) + 360.0) % 360.0;
+        let distance = self.sog * dt / 3600.0;
+        self.latitude += distance * self.cog.to_radians().cos() / 60.0;
+        self.longitude += distance * self.cog.to_radians().sin() / (60.0 * self.latitude.to_radians().cos());
+    }
+
+    fn write(&self, buffer: &mut Buffer, table_name: &str) -> questdb::Result<()> {
+        buffer.table(table_name)?
+            .symbol("mmsi", &self.mmsi)?
+            .symbol("ship_type", self.ship_type.as_str())?
+            .symbol("nav_status", self.nav_status.as_str())?
+            .symbol("destination", self.port().name)?
+            .column_f64("latitude", self.latitude)?
+            .column_f64("longitude", self.longitude)?
+            .column_f64("sog", self.sog)?
+            .column_f64("cog", self.cog)?
+            .column_f64("heading", self.heading)?
+            .column_f64("draught", self.draught)?
+            .at(TimestampNanos::new(self.timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mmsis_start_with_the_mid_of_their_area() {
+        for n in 0..100 {
+            let mmsi = Vessels.device_id(n);
+            assert_eq!(mmsi.len(), 9, "{}", mmsi);
+            assert_eq!(mmsi[..3].parse::<u32>().unwrap(), AREAS[n as usize % AREAS.len()].mid);
+        }
+    }
+
+    /// Over two simulated days, vessels reach their ports and sail on, within the sea
+    /// area of their ports and with a draught between light and fully laden
+    #[test]
+    fn vessels_sail_between_their_ports() {
+        let mut moored = 0;
+        for n in 0..60 {
+            let mut vessel = Vessel::new(&Vessels.device_id(n), n, 0);
+            let ports = AREAS[vessel.area].ports;
+            let (light, laden) = vessel.ship_type.draught();
+            let mut was_moored = false;
+            for tick in 1..=17280 {
+                vessel.update(tick * 10_000_000_000);
+                let near = ports.iter().any(|port| (port.latitude - vessel.latitude).abs() < 12.0 && (port.longitude - vessel.longitude).abs() < 12.0);
+                assert!(near, "vessel {} left its area at {}, {}", n, vessel.latitude, vessel.longitude);
+                assert!(vessel.sog >= 0.0 && vessel.sog <= vessel.ship_type.cruise_knots() + 1.0, "vessel {} at {} knots", n, vessel.sog);
+                assert!((0.0..360.0).contains(&vessel.cog) && (0.0..360.0).contains(&vessel.heading));
+                assert!(vessel.draught >= light - 0.3 && vessel.draught <= laden + 0.3, "vessel {} draught {}", n, vessel.draught);
+                was_moored |= vessel.nav_status == NavStatus::Moored;
+            }
+            moored += was_moored as u32;
+        }
+        assert!(moored > 0, "no vessel reached a port");
+    }
+}
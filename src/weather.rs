@@ -0,0 +1,277 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use questdb::ingress::{Buffer, TimestampNanos};
+
+use crate::model::{Device, DeviceModel};
+use crate::scaffold::Bundle;
+use crate::turbulence::OrnsteinUhlenbeck;
+
+/// Default --sample-interval-ms, a sample a minute
+pub const SAMPLE_INTERVAL_MS: u64 = 60_000;
+
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+
+/// Chance per second for dry weather to turn to rain, and for rain to stop
+const RAIN_STARTS: f64 = 1.0 / 14_400.0;
+const RAIN_STOPS: f64 = 1.0 / 2_400.0;
+
+/// Table, queries and dashboard of `airplanes scaffold --model weather`
+pub const BUNDLE: Bundle = Bundle {
+    key: "station_id",
+    symbols: &["station_id"],
+    columns: &[
+        ("latitude", "DOUBLE"), ("longitude", "DOUBLE"), ("temperature", "DOUBLE"), ("humidity", "DOUBLE"), ("pressure", "DOUBLE"),
+        ("wind_speed", "DOUBLE"), ("wind_direction", "DOUBLE"), ("precipitation", "DOUBLE"), ("solar_radiation", "DOUBLE"),
+    ],
+    queries: &[
+        ("Latest sample of every station",
+         "SELECT station_id, temperature, humidity, pressure, wind_speed, timestamp\nFROM {table}\nLATEST ON timestamp PARTITION BY station_id;"),
+        ("Hourly averages of one station, gaps filled with the previous value",
+         "SELECT timestamp, avg(temperature) temperature, avg(humidity) humidity, sum(precipitation) precipitation\nFROM {table}\nWHERE station_id = 'WS00000'\nSAMPLE BY 1h FILL(PREV);"),
+        ("Wettest stations over the last day",
+         "SELECT station_id, sum(precipitation) precipitation\nFROM {table}\nWHERE timestamp > dateadd('d', -1, now())\nORDER BY precipitation DESC\nLIMIT 20;"),
+        ("Stations that have not sent a sample for 5 minutes",
+         "SELECT *\nFROM (SELECT station_id, max(timestamp) last_sample FROM {table})\nWHERE last_sample < dateadd('m', -5, now());"),
+    ],
+    panels: &[
+        ("Temperature (°C)", "SELECT timestamp, avg(temperature) avg_temperature, min(temperature) min_temperature, max(temperature) max_temperature FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+        ("Stations reporting", "SELECT timestamp, count_distinct(station_id) stations FROM {table} WHERE $__timeFilter(timestamp) SAMPLE BY $__interval"),
+    ],
+    map: Some(("Stations", "latitude", "longitude")),
+};
+
+/// Weather stations, `--model weather`: many stations each sending a sample every
+/// --sample-interval-ms, a minute by default, spread over the interval so that they do not
+/// all report at once. Each has a climate of its own from its latitude and elevation, a
+/// daily cycle of temperature, humidity and sunshine in its local solar time, gusting
+/// wind, slowly moving pressure and spells of rain.
+pub struct Stations {
+    pub interval_nanos: i64,
+}
+
+impl DeviceModel for Stations {
+    /// WS00000 onwards
+    fn device_id(&self, n: u64) -> String {
+        format!("WS{:05}", n)
+    }
+
+    fn device(&self, id: &str, seed: u64, timestamp: i64) -> Box<dyn Device> {
+        Box::new(Station::new(id, seed, timestamp, self.interval_nanos))
+    }
+
+    fn rows_per_tick(&self, tick_millis: u64) -> f64 {
+        (tick_millis as f64 * 1e6 / self.interval_nanos as f64).min(1.0)
+    }
+}
+
+struct Station {
+    station_id: String,
+    rng: StdRng,
+    timestamp: i64,
+    interval_nanos: i64,
+    next_sample: i64,
+    latitude: f64,
+    longitude: f64,
+    /// Metres above sea level
+    elevation: f64,
+    /// Daily mean temperature, °C
+    mean_temperature: f64,
+    /// Weather moving through, as °C off the daily cycle
+    weather: OrnsteinUhlenbeck,
+    wind: OrnsteinUhlenbeck,
+    /// Pressure at sea level, hPa
+    sea_level_pressure: OrnsteinUhlenbeck,
+    raining: bool,
+    temperature: f64,
+    /// %
+    humidity: f64,
+    /// hPa at the station
+    pressure: f64,
+    /// m/s
+    wind_speed: f64,
+    /// Degrees from north the wind blows from
+    wind_direction: f64,
+    /// mm since the last sample
+    precipitation: f64,
+    /// W/m²
+    solar_radiation: f64,
+}
+
+impl Station {
+    fn new(id: &str, seed: u64, timestamp: i64, interval_nanos: i64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let latitude: f64 = rng.gen_range(-55.0..70.0);
+        let longitude = rng.gen_range(-180.0..180.0);
+        // Most stations are low, some up in the mountains
+        let elevation = rng.gen_range(0.0f64..1.0).powi(3) * 3000.0;
+        let mean_temperature = 28.0 - 0.45 * latitude.abs() - 6.5 * elevation / 1000.0;
+        // Weather systems of a few degrees and 8 hPa passing over days, gusts over minutes
+        let weather = OrnsteinUhlenbeck::new(0.0, 1.0 / 86_400.0, 3.0 * (2.0 / 86_400.0f64).sqrt(), &mut rng);
+        let sea_level_pressure = OrnsteinUhlenbeck::new(1013.0, 1.0 / 172_800.0, 8.0 * (2.0 / 172_800.0f64).sqrt(), &mut rng);
+        let wind = OrnsteinUhlenbeck::new(rng.gen_range(2.0..6.0), 1.0 / 600.0, 0.1, &mut rng);
+        // The first sample somewhere in the first interval, after that one every interval
+        let next_sample = timestamp + rng.gen_range(0..interval_nanos.max(1));
+        let mut station = Station {
+            station_id: id.to_string(),
+            wind_direction: rng.gen_range(0.0..360.0),
+            raining: rng.gen_bool(RAIN_STARTS / (RAIN_STARTS + RAIN_STOPS)),
+            rng,
+            timestamp,
+            interval_nanos,
+            next_sample,
+            latitude,
+            longitude,
+            elevation,
+            mean_temperature,
+            weather,
+            wind,
+            sea_level_pressure,
+            temperature: mean_temperature,
+            humidity: 60.0,
+            pressure: 1013.0,
+            wind_speed: 0.0,
+            precipitation: 0.0,
+            solar_radiation: 0.0,
+        };
+        station.update(timestamp);
+        station.precipitation = 0.0;
+        station
+    }
+
+    /// Hours of the local solar day at `timestamp`, from the longitude
+    fn solar_hour(&self, timestamp: i64) -> f64 {
+        let utc_hours = timestamp.rem_euclid(NANOS_PER_DAY) as f64 / 3.6e12;
+        (utc_hours + self.longitude / 15.0).rem_euclid(24.0)
+    }
+}
+
+impl Device for Station {
+    fn has_row(&mut self, timestamp: i64) -> bool {
+        if timestamp < self.next_sample {
+            return false;
+        }
+        // A station that fell behind sends one sample and takes up its rhythm again
+        let missed = (timestamp - self.next_sample) / self.interval_nanos.max(1);
+        self.next_sample += (missed + 1) * self.interval_nanos.max(1);
+        true
+    }
+
+    fn update(&mut self, timestamp: i64) {
+        let dt = ((timestamp - self.timestamp) as f64 / 1e9).clamp(0.0, 3600.0);
+        self.timestamp = timestamp;
+        let hour = self.solar_hour(timestamp);
+
+        if self.rng.gen_bool((if self.raining { RAIN_STOPS } else { RAIN_STARTS } * dt).min(1.0)) {
+            self.raining = !self.raining;
+        }
+        // Between a drizzle and a downpour, in mm an hour
+        self.precipitation = if self.raining { self.rng.gen_range(0.2..8.0) * dt / 3600.0 } else { 0.0 };
+
+        // Sunshine from six to eighteen, peaking at noon, little of it through rain clouds
+        let sun = ((hour - 6.0) / 12.0 * std::f64::consts::PI).sin().max(0.0);
+        let clouds = if self.raining { 0.2 } else { self.rng.gen_range(0.7..1.0) };
+        self.solar_radiation = 1000.0 * sun * clouds * (1.0 - self.latitude.abs() / 120.0);
+
+        // Coolest at dawn and warmest mid afternoon, with less of a swing when it rains
+        let swing = if self.raining { 2.0 } else { 5.0 };
+        let cycle = -swing * ((hour - 3.0) / 24.0 * 2.0 * std::f64::consts::PI).cos();
+        self.temperature = self.mean_temperature + cycle + self.weather.step(dt, &mut self.rng);
+
+        // Humidity rises as the air cools, and is near saturation in the rain
+        let dry = (self.temperature - self.mean_temperature + 8.0) * 3.0;
+        self.humidity = if self.raining { self.rng.gen_range(90.0..100.0) } else { (85.0 - dry + self.rng.gen_range(-2.0..2.0)).clamp(10.0, 100.0) };
+
+        let sea_level = self.sea_level_pressure.step(dt, &mut self.rng).clamp(960.0, 1050.0);
+        self.pressure = sea_level * (-self.elevation / 8400.0).exp();
+
+        self.wind_speed = self.wind.step(dt, &mut self.rng).max(0.0) * if self.raining { 1.5 } else { 1.0 };
+        self.wind_direction = (self.wind_direction + self.rng.gen_range(-2.0..2.0) * dt.sqrt() / 8.0).rem_euclid(360.0);
+    }
+
+    fn write(&self, buffer: &mut Buffer, table_name: &str) -> questdb::Result<()> {
+        buffer.table(table_name)?
+            .symbol("station_id", &self.station_id)?
+            .column_f64("latitude", self.latitude)?
+            .column_f64("longitude", self.longitude)?
+            .column_f64("temperature", self.temperature)?
+            .column_f64("humidity", self.humidity)?
+            .column_f64("pressure", self.pressure)?
+            .column_f64("wind_speed", self.wind_speed)?
+            .column_f64("wind_direction", self.wind_direction)?
+            .column_f64("precipitation", self.precipitation)?
+            .column_f64("solar_radiation", self.solar_radiation)?
+            .at(TimestampNanos::new(self.timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::monday_at;
+
+    const MINUTE: i64 = 60_000_000_000;
+
+    /// Checked every second for an hour, a station samples once a minute on a phase of its
+    /// own, and once again when it fell behind
+    #[test]
+    fn stations_sample_once_an_interval() {
+        let mut phases = vec![];
+        for seed in 0..20 {
+            let mut station = Station::new("WS00000", seed, 0, MINUTE);
+            let phase = station.next_sample;
+            let samples: Vec<i64> = (0..3600).map(|second| second * 1_000_000_000)
+                .filter(|timestamp| station.has_row(*timestamp))
+                .collect();
+            assert!((59..=60).contains(&samples.len()), "seed {}: {} samples", seed, samples.len());
+            assert!(samples.windows(2).all(|pair| pair[1] - pair[0] == MINUTE), "seed {}", seed);
+            phases.push(phase);
+            // Twenty minutes late, it samples once and keeps to its phase
+            assert!(station.has_row(80 * MINUTE));
+            assert!(station.next_sample > 80 * MINUTE && station.next_sample <= 81 * MINUTE, "seed {}", seed);
+            assert_eq!((station.next_sample - phase) % MINUTE, 0, "seed {}", seed);
+        }
+        phases.sort();
+        phases.dedup();
+        assert_eq!(phases.len(), 20, "the stations report together: {:?}", phases);
+    }
+
+    /// On the 10 ms ticks of --rate-per-plane 100, a station sampling every 100 ms sends a
+    /// row every tenth tick, whatever its phase
+    #[test]
+    fn stations_sample_every_tenth_tick() {
+        const TICK: i64 = 10_000_000;
+        let start = monday_at(9.0);
+        for seed in 0..20 {
+            let mut station = Station::new("WS00000", seed, start, 10 * TICK);
+            let ticks: Vec<i64> = (1..=1000).filter(|tick| station.has_row(start + tick * TICK)).collect();
+            assert_eq!(ticks.len(), 100, "seed {}", seed);
+            assert!(ticks.windows(2).all(|pair| pair[1] - pair[0] == 10), "seed {}: {:?}", seed, ticks);
+        }
+    }
+
+    /// Over a week of every 10 minutes, the readings of 50 stations stay physical and it is
+    /// warmer in the afternoon than before dawn
+    #[test]
+    fn readings_stay_physical() {
+        for seed in 0..50 {
+            let mut station = Station::new("WS00000", seed, 0, MINUTE);
+            let (mut afternoons, mut dawns) = (0.0, 0.0);
+            for step in 1..=7 * 144 {
+                let timestamp = step * 10 * MINUTE;
+                station.update(timestamp);
+                assert!((0.0..=100.0).contains(&station.humidity), "seed {}: {}%", seed, station.humidity);
+                assert!(station.pressure > 600.0 && station.pressure < 1050.0, "seed {}: {} hPa", seed, station.pressure);
+                assert!(station.solar_radiation >= 0.0 && station.precipitation >= 0.0 && station.wind_speed >= 0.0);
+                assert!((-60.0..60.0).contains(&station.temperature), "seed {}: {} °C", seed, station.temperature);
+                let hour = station.solar_hour(timestamp).floor();
+                if hour == 15.0 {
+                    afternoons += station.temperature;
+                } else if hour == 3.0 {
+                    dawns += station.temperature;
+                }
+            }
+            assert!(afternoons > dawns, "seed {}", seed);
+        }
+    }
+}
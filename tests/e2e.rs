@@ -0,0 +1,76 @@
+//! End to end against a real QuestDB in a Docker container, with `cargo test --features e2e`:
+//! the rows of a small simulation must all be in the table, counted over PG wire. Needs a
+//! running Docker daemon; the container is started and removed with testcontainers.
+#![cfg(feature = "e2e")]
+
+mod testing;
+
+use std::thread;
+use std::time::{Duration, Instant};
+use testcontainers::{Container, GenericImage};
+use testcontainers::core::IntoContainerPort;
+use testcontainers::runners::SyncRunner;
+
+/// A QuestDB container, removed when dropped
+struct QuestDb {
+    _container: Container<GenericImage>,
+    http_port: u16,
+    pg_port: u16,
+}
+
+impl QuestDb {
+    fn start() -> Self {
+        let container = GenericImage::new("questdb/questdb", "latest")
+            .with_exposed_port(9000.tcp())
+            .with_exposed_port(8812.tcp())
+            .start()
+            .expect("the e2e tests need a running Docker daemon");
+        let port = |port: u16| container.get_host_port_ipv4(port.tcp()).expect("the container port is published");
+        QuestDb { http_port: port(9000), pg_port: port(8812), _container: container }
+    }
+
+    fn pg(&self) -> postgres::Config {
+        let mut config = postgres::Config::new();
+        config.host("127.0.0.1").port(self.pg_port).user("admin").password("quest").dbname("qdb")
+            .connect_timeout(Duration::from_secs(5));
+        config
+    }
+
+    /// Connects over PG wire once the server is up
+    fn connect(&self) -> postgres::Client {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        loop {
+            match self.pg().connect(postgres::NoTls) {
+                Ok(client) => return client,
+                Err(e) if Instant::now() >= deadline => panic!("QuestDB did not start: {}", e),
+                Err(_) => thread::sleep(Duration::from_millis(500)),
+            }
+        }
+    }
+}
+
+/// Rows in the table, once the server applied them all or after a while; the WAL is
+/// applied after the rows are acknowledged
+fn count_rows(client: &mut postgres::Client, table: &str, expected: i64) -> i64 {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    loop {
+        let count = client.query_one(format!("SELECT count() FROM '{}'", table).as_str(), &[]).map(|row| row.get::<_, i64>(0)).unwrap_or(0);
+        if count >= expected || Instant::now() >= deadline {
+            return count;
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+#[test]
+fn every_row_lands_in_questdb() {
+    let container = QuestDb::start();
+    let mut client = container.connect();
+    let (planes, rows) = (5, 1000);
+    testing::simulate(&format!("http::addr=127.0.0.1:{};", container.http_port),
+                      &["--table-name", "flights_e2e", "--plane-count", &planes.to_string(), "--rate-per-plane", "200",
+                        "--total-rows", &rows.to_string(), "--batch-size", "100"]);
+    assert_eq!(count_rows(&mut client, "flights_e2e", rows), rows);
+    let plane_ids = client.query_one("SELECT count_distinct(plane_id) FROM 'flights_e2e'", &[]).unwrap().get::<_, i64>(0);
+    assert_eq!(plane_ids, planes);
+}
@@ -0,0 +1,213 @@
+mod testing;
+
+use testing::{Line, MockQuestDb, Protocol};
+
+const PLANES: usize = 4;
+const ROWS_PER_PLANE: usize = 25;
+
+/// The sensor columns of the wide schema, in the order they are sent
+const SENSORS: &[&str] = &[
+    "airspeed", "altitude", "pitch", "roll", "yaw", "aoa", "oat", "fuel_kg", "gross_weight_kg",
+    "latitude", "longitude", "ground_speed", "track", "vertical_speed",
+];
+
+fn run(server: &MockQuestDb, schema: &str) -> Vec<Line> {
+    let total = (PLANES * ROWS_PER_PLANE).to_string();
+    testing::simulate(&server.conf(), &["--table-name", "flights", "--plane-count", &PLANES.to_string(), "--rate-per-plane", "100",
+                                        "--total-rows", &total, "--batch-size", "10", "--schema", schema]);
+    server.lines()
+}
+
+/// Every plane sent rows, in increasing time
+fn check_planes(lines: &[Line]) {
+    for n in 0..PLANES {
+        let plane_id = format!("AA{:02}", n);
+        let timestamps: Vec<i64> = lines.iter().filter(|line| line.symbol("plane_id") == Some(&plane_id))
+            .map(|line| line.timestamp.expect("rows have a timestamp")).collect();
+        assert!(!timestamps.is_empty(), "no rows from plane {}", plane_id);
+        assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]), "rows of plane {} out of order", plane_id);
+    }
+}
+
+fn check_wide(lines: &[Line]) {
+    assert_eq!(lines.len(), PLANES * ROWS_PER_PLANE);
+    for line in lines {
+        assert_eq!(line.table, "flights");
+        let symbols: Vec<&str> = line.symbols.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(symbols, ["plane_id", "icao24", "callsign", "phase"], "symbols of {:?}", line);
+        assert_eq!(line.column_names(), SENSORS, "columns of {:?}", line);
+        for sensor in SENSORS {
+            let value = line.column(sensor).unwrap();
+            assert!(value.parse::<f64>().is_ok(), "{} is not a double in {:?}", sensor, line);
+        }
+    }
+    check_planes(lines);
+}
+
+#[test]
+fn every_row_arrives_over_http() {
+    let server = MockQuestDb::start(Protocol::Http);
+    check_wide(&run(&server, "wide"));
+}
+
+#[test]
+fn every_row_arrives_over_tcp() {
+    let server = MockQuestDb::start(Protocol::Tcp);
+    check_wide(&run(&server, "wide"));
+}
+
+/// Over HTTP the client takes version 2 the server offers, unless a version is pinned;
+/// over TCP it only speaks version 2 when pinned
+#[test]
+fn the_protocol_version_can_be_pinned() {
+    for (protocol, pinned, binary) in [(Protocol::Http, None, true), (Protocol::Http, Some("1"), false), (Protocol::Tcp, Some("2"), true)] {
+        let server = MockQuestDb::start(protocol);
+        let total = (PLANES * ROWS_PER_PLANE).to_string();
+        let mut args = vec!["--table-name", "flights", "--plane-count", "4", "--rate-per-plane", "100", "--total-rows", &total, "--batch-size", "10"];
+        args.extend(pinned.iter().flat_map(|version| ["--protocol-version", version]));
+        testing::simulate(&server.conf(), &args);
+        check_wide(&server.lines());
+        assert_eq!(server.saw_binary(), binary, "binary doubles over {:?} with --protocol-version {:?}", protocol, pinned);
+    }
+}
+
+/// The spectrum goes as an array after the sensors, and is left out when the connection
+/// cannot take arrays
+#[test]
+fn spectra_are_sent_as_arrays() {
+    for (pinned, bins) in [(None, Some(16)), (Some("1"), None)] {
+        let server = MockQuestDb::start(Protocol::Http);
+        let total = (PLANES * ROWS_PER_PLANE).to_string();
+        let mut args = vec!["--table-name", "flights", "--plane-count", "4", "--rate-per-plane", "100", "--total-rows", &total, "--batch-size", "10",
+                            "--spectrum-bins", "16"];
+        args.extend(pinned.iter().flat_map(|version| ["--protocol-version", version]));
+        testing::simulate(&server.conf(), &args);
+        let lines = server.lines();
+        assert_eq!(lines.len(), PLANES * ROWS_PER_PLANE);
+        for line in &lines {
+            let spectrum = line.column("vibration_spectrum").map(|array| {
+                let amplitudes: Vec<f64> = array.strip_prefix('[').and_then(|array| array.strip_suffix(']'))
+                    .unwrap_or_else(|| panic!("{} is not an array in {:?}", array, line))
+                    .split(';').map(|amplitude| amplitude.parse().unwrap()).collect();
+                assert!(amplitudes.iter().all(|amplitude| *amplitude > 0.0), "amplitudes of {:?}", line);
+                amplitudes.len()
+            });
+            assert_eq!(spectrum, bins, "spectrum with --protocol-version {:?} in {:?}", pinned, line);
+            assert_eq!(&line.column_names()[..SENSORS.len()], SENSORS, "columns of {:?}", line);
+        }
+    }
+}
+
+/// The `[sink]` of the config sends the rows to a CSV file for each table instead
+#[test]
+fn the_config_can_send_the_rows_to_csv_files() {
+    let dir = std::env::temp_dir().join(format!("airplanes-csv-{}", std::process::id()));
+    let config = dir.join("sink.toml");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&config, format!("[sink]\nkind = \"csv\"\npath = {:?}\n", dir.join("out"))).unwrap();
+    let total = (PLANES * ROWS_PER_PLANE).to_string();
+    testing::simulate("", &["--table-name", "flights", "--plane-count", &PLANES.to_string(), "--rate-per-plane", "100", "--total-rows", &total,
+                            "--batch-size", "10", "--config", config.to_str().unwrap()]);
+    let text = std::fs::read_to_string(dir.join("out").join("flights.csv")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let mut lines = text.lines();
+    let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+    assert_eq!(header[..4], ["plane_id", "icao24", "callsign", "phase"]);
+    assert_eq!(header[4..header.len() - 1], *SENSORS);
+    assert_eq!(header.last(), Some(&"timestamp"));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows.len(), PLANES * ROWS_PER_PLANE);
+    for row in &rows {
+        assert_eq!(row.len(), header.len(), "fields of {:?}", row);
+        assert!(row[4..row.len() - 1].iter().all(|value| value.parse::<f64>().is_ok()), "readings of {:?}", row);
+        assert!(chrono::DateTime::parse_from_rfc3339(row[row.len() - 1]).is_ok(), "timestamp of {:?}", row);
+    }
+}
+
+#[test]
+fn narrow_rows_have_one_line_per_sensor() {
+    let server = MockQuestDb::start(Protocol::Http);
+    let lines = run(&server, "narrow");
+    assert_eq!(lines.len(), PLANES * ROWS_PER_PLANE * SENSORS.len());
+    for line in &lines {
+        assert_eq!(line.table, "flights");
+        assert!(SENSORS.contains(&line.symbol("sensor").unwrap()), "unknown sensor in {:?}", line);
+        assert_eq!(line.column_names(), ["value"], "columns of {:?}", line);
+    }
+    check_planes(&lines);
+}
+
+#[test]
+fn turbines_send_their_own_rows() {
+    let lines = testing::simulate_model("turbines", PLANES, PLANES * ROWS_PER_PLANE,
+                                        &["wind_speed", "wind_direction", "rotor_rpm", "pitch_angle", "power_kw", "nacelle_temp", "vibration"], &[]);
+    for line in &lines {
+        let symbols: Vec<&str> = line.symbols.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(symbols, ["turbine_id", "farm", "status"], "symbols of {:?}", line);
+    }
+    for n in 0..PLANES {
+        let turbine_id = format!("WT{:04}", n);
+        assert!(lines.iter().any(|line| line.symbol("turbine_id") == Some(&turbine_id)), "no rows from turbine {}", turbine_id);
+    }
+}
+
+#[test]
+fn vessels_go_to_their_own_table() {
+    let lines = testing::simulate_model("vessels", PLANES, PLANES * ROWS_PER_PLANE, &["latitude", "longitude", "sog", "cog", "heading", "draught"], &[]);
+    for line in &lines {
+        let mmsi = line.symbol("mmsi").unwrap();
+        assert!(mmsi.len() == 9 && mmsi.chars().all(|c| c.is_ascii_digit()), "MMSI {} in {:?}", mmsi, line);
+        assert!(line.symbol("nav_status").is_some() && line.symbol("destination").is_some(), "AIS symbols of {:?}", line);
+    }
+}
+
+#[test]
+fn vehicles_report_their_telematics() {
+    let lines = testing::simulate_model("vehicles", PLANES, PLANES * ROWS_PER_PLANE,
+                                        &["latitude", "longitude", "speed_kmh", "rpm", "fuel_level_pct", "odometer_km", "coolant_temp", "harsh_brake"], &[]);
+    for line in &lines {
+        assert!(line.column("rpm").unwrap().ends_with('i'), "rpm is not a long in {:?}", line);
+        assert!(["t", "f"].contains(&line.column("harsh_brake").unwrap()), "harsh_brake is not a boolean in {:?}", line);
+    }
+}
+
+#[test]
+fn ticks_quote_and_trade_at_the_quote() {
+    // Every instrument once, so that the markets that never close keep rows coming at any time of the day
+    let lines = testing::simulate_model("ticks", 14, 140, &["bid", "ask", "price", "size"], &[]);
+    for line in &lines {
+        let value = |name| line.column(name).unwrap().parse::<f64>().unwrap();
+        assert!(value("bid") < value("ask"), "crossed quote in {:?}", line);
+        let at = if line.symbol("side") == Some("buy") { "ask" } else { "bid" };
+        assert_eq!(value("price"), value(at), "trade away from the quote in {:?}", line);
+    }
+}
+
+#[test]
+fn meter_registers_only_go_up() {
+    let lines = testing::simulate_model("meters", PLANES, PLANES * ROWS_PER_PLANE, &["energy_kwh", "power_kw", "voltage", "current", "power_factor"], &[]);
+    let mut registers = std::collections::HashMap::new();
+    for line in &lines {
+        let energy: f64 = line.column("energy_kwh").unwrap().parse().unwrap();
+        let previous = registers.insert(line.symbol("meter_id").unwrap().to_string(), energy);
+        assert!(previous.map(|previous| energy >= previous).unwrap_or(true), "register went back in {:?}", line);
+    }
+    assert_eq!(registers.len(), PLANES);
+}
+
+#[test]
+fn weather_stations_send_their_samples_in_order() {
+    let lines = testing::simulate_model("weather", PLANES, PLANES * ROWS_PER_PLANE,
+                                        &["latitude", "longitude", "temperature", "humidity", "pressure", "wind_speed", "wind_direction", "precipitation", "solar_radiation"],
+                                        &["--sample-interval-ms", "100"]);
+    let mut samples: std::collections::HashMap<&str, Vec<i64>> = std::collections::HashMap::new();
+    for line in &lines {
+        samples.entry(line.symbol("station_id").unwrap()).or_default().push(line.timestamp.unwrap());
+    }
+    // When a station samples is checked against a fixed clock in the unit tests of the
+    // model, here the rows come on the wall clock, in order
+    assert_eq!(samples.len(), PLANES);
+    for (station, timestamps) in &samples {
+        assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]), "{} out of order: {:?}", station, timestamps);
+    }
+}
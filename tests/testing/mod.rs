@@ -0,0 +1,285 @@
+//! A stand-in for QuestDB that records the ILP lines it receives, over HTTP or TCP, so the
+//! simulator can be run end to end without a server. The binary doubles and arrays of ILP
+//! protocol version 2 are recorded as text.
+// Each test crate uses its own part of it
+#![allow(dead_code)]
+
+use std::convert::TryInto;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Answer to `/settings`, the ILP protocol versions of a recent QuestDB
+const SETTINGS: &str = r#"{"config":{"release.type":"OSS","release.version":"8.2.0","line.proto.support.versions":[1,2]}}"#;
+
+/// Answer to any query sent to `/exec`
+const EXEC: &str = r#"{"query":"","columns":[{"name":"count","type":"LONG"}],"timestamp":-1,"dataset":[[0]],"count":1}"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Http,
+    Tcp,
+}
+
+/// A mock server listening on a port of its own, for the rest of the tests.
+pub struct MockQuestDb {
+    protocol: Protocol,
+    port: u16,
+    lines: Arc<Mutex<Vec<String>>>,
+    /// Whether a binary value of protocol version 2 arrived
+    binary: Arc<AtomicBool>,
+    /// Connections not closed yet, whose last lines may still be on their way
+    open: Arc<AtomicUsize>,
+}
+
+impl MockQuestDb {
+    pub fn start(protocol: Protocol) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let lines = Arc::new(Mutex::new(vec![]));
+        let (binary, open) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicUsize::new(0)));
+        let (received, connections) = (Received { lines: lines.clone(), binary: binary.clone() }, open.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (received, connections) = (received.clone(), connections.clone());
+                connections.fetch_add(1, Ordering::SeqCst);
+                thread::spawn(move || {
+                    // A broken connection only ends that connection, the test looks at what arrived
+                    let _ = match protocol {
+                        Protocol::Http => serve_http(stream, &received),
+                        Protocol::Tcp => serve_tcp(stream, &received),
+                    };
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        MockQuestDb { protocol, port, lines, binary, open }
+    }
+
+    /// The --connection-string of the server
+    pub fn conf(&self) -> String {
+        match self.protocol {
+            Protocol::Http => format!("http::addr=127.0.0.1:{};", self.port),
+            Protocol::Tcp => format!("tcp::addr=127.0.0.1:{};", self.port),
+        }
+    }
+
+    /// Every line received, parsed, once the connections are closed or after a few seconds
+    pub fn lines(&self) -> Vec<Line> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while self.open.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.lines.lock().unwrap().iter().map(|line| Line::parse(line)).collect()
+    }
+
+    /// Whether the rows came in ILP protocol version 2, with binary doubles
+    pub fn saw_binary(&self) -> bool {
+        self.binary.load(Ordering::SeqCst)
+    }
+}
+
+/// Where the connections of the server put what they receive
+#[derive(Clone)]
+struct Received {
+    lines: Arc<Mutex<Vec<String>>>,
+    binary: Arc<AtomicBool>,
+}
+
+/// Type of a binary double and of a binary array, after the `==` of a column
+const BINARY_DOUBLE: u8 = 16;
+const BINARY_ARRAY: u8 = 14;
+
+/// The lines of an ILP body, with the binary values of protocol version 2 as text: a double
+/// as its value, an array of doubles as `[a;b;c]` with the values of all its dimensions
+fn decode(body: &[u8], binary: &AtomicBool) -> String {
+    let f64_at = |at: usize| f64::from_le_bytes(body[at..at + 8].try_into().unwrap());
+    let (mut text, mut at, mut quoted, mut escaped) = (vec![], 0, false, false);
+    while at < body.len() {
+        let byte = body[at];
+        if byte == b'=' && !quoted && !escaped && body.get(at + 1) == Some(&b'=') {
+            binary.store(true, Ordering::SeqCst);
+            match body.get(at + 2) {
+                Some(&BINARY_DOUBLE) => {
+                    text.extend(format!("={}", f64_at(at + 3)).bytes());
+                    at += 11;
+                    continue;
+                }
+                Some(&BINARY_ARRAY) => {
+                    let dims = body[at + 4] as usize;
+                    let shape = (0..dims).map(|dim| u32::from_le_bytes(body[at + 5 + 4 * dim..at + 9 + 4 * dim].try_into().unwrap()) as usize);
+                    let (count, data) = (shape.product::<usize>(), at + 5 + 4 * dims);
+                    let values: Vec<String> = (0..count).map(|n| f64_at(data + 8 * n).to_string()).collect();
+                    text.extend(format!("=[{}]", values.join(";")).bytes());
+                    at = data + 8 * count;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        match byte {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => quoted = !quoted,
+            _ => {}
+        }
+        text.push(byte);
+        at += 1;
+    }
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+fn record(received: &Received, body: &[u8]) {
+    let text = decode(body, &received.binary);
+    received.lines.lock().unwrap().extend(text.lines().filter(|line| !line.is_empty()).map(str::to_string));
+}
+
+/// Records what arrived once the client closes the connection, since a binary value can
+/// hold a line break
+fn serve_tcp(mut stream: TcpStream, received: &Received) -> std::io::Result<()> {
+    let mut body = vec![];
+    stream.read_to_end(&mut body)?;
+    record(received, &body);
+    Ok(())
+}
+
+/// Serves the requests of a keep-alive HTTP/1.1 connection: the ILP rows posted to
+/// `/write` are recorded, `/settings`, `/ping` and `/exec` get a plausible answer.
+fn serve_http(stream: TcpStream, received: &Received) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut request = String::new();
+        if reader.read_line(&mut request)? == 0 {
+            return Ok(());
+        }
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header)?;
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        let path = request.split_whitespace().nth(1).unwrap_or("/");
+        let (status, answer) = if request.starts_with("POST") {
+            record(received, &body);
+            ("204 No Content", "")
+        } else if path.starts_with("/settings") {
+            ("200 OK", SETTINGS)
+        } else if path.starts_with("/ping") {
+            ("204 No Content", "")
+        } else {
+            ("200 OK", EXEC)
+        };
+        write!(writer, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", status, answer.len(), answer)?;
+    }
+}
+
+/// An ILP line: the table, the symbols and the other columns in order, and the timestamp.
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub table: String,
+    pub symbols: Vec<(String, String)>,
+    pub columns: Vec<(String, String)>,
+    pub timestamp: Option<i64>,
+}
+
+/// Splits at the unescaped `separator`s outside of double quotes
+fn split(text: &str, separator: char) -> Vec<String> {
+    let (mut parts, mut part, mut quoted, mut escaped) = (vec![], String::new(), false, false);
+    for c in text.chars() {
+        match c {
+            _ if escaped => {
+                part.push(c);
+                escaped = false;
+            }
+            '\\' => {
+                part.push(c);
+                escaped = true;
+            }
+            '"' => {
+                part.push(c);
+                quoted = !quoted;
+            }
+            _ if c == separator && !quoted => parts.push(std::mem::take(&mut part)),
+            _ => part.push(c),
+        }
+    }
+    parts.push(part);
+    parts
+}
+
+fn pairs(parts: &[String]) -> Vec<(String, String)> {
+    parts.iter().map(|part| match part.split_once('=') {
+        Some((name, value)) => (name.to_string(), value.to_string()),
+        None => (part.clone(), String::new()),
+    }).collect()
+}
+
+impl Line {
+    pub fn parse(line: &str) -> Self {
+        let sections = split(line, ' ');
+        let series = split(&sections[0], ',');
+        Line {
+            table: series[0].clone(),
+            symbols: pairs(&series[1..]),
+            columns: sections.get(1).map(|columns| pairs(&split(columns, ','))).unwrap_or_default(),
+            timestamp: sections.get(2).and_then(|timestamp| timestamp.parse().ok()),
+        }
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&str> {
+        self.symbols.iter().find(|(symbol, _)| symbol == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn column(&self, name: &str) -> Option<&str> {
+        self.columns.iter().find(|(column, _)| column == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns.iter().map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+/// Runs the simulator against the server of `conf` with these options, and checks it succeeded
+pub fn simulate(conf: &str, args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_airplanes"))
+        .args(["--connection-string", conf, "--no-run-metadata", "--quiet"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "the simulator failed: {}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+/// Runs `devices` devices of a --model on a server of its own, `rows` rows in all and `args`
+/// on top, and checks every row arrived in the table of the model, with `columns` in that
+/// order. Returns the lines, for the checks of each model.
+pub fn simulate_model(model: &str, devices: usize, rows: usize, columns: &[&str], args: &[&str]) -> Vec<Line> {
+    let server = MockQuestDb::start(Protocol::Http);
+    let (devices_arg, rows_arg) = (devices.to_string(), rows.to_string());
+    // No --table-name, the rows go to the table of the model
+    let mut options = vec!["--model", model, "--plane-count", &devices_arg, "--rate-per-plane", "100", "--total-rows", &rows_arg, "--batch-size", "10"];
+    options.extend_from_slice(args);
+    simulate(&server.conf(), &options);
+    let lines = server.lines();
+    assert_eq!(lines.len(), rows);
+    for line in &lines {
+        assert_eq!(line.table, model);
+        assert_eq!(line.column_names(), columns, "columns of {:?}", line);
+    }
+    lines
+}